@@ -3,7 +3,7 @@
 //! Use the main `ergokv` crate
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
-use quote::{format_ident, quote};
+use quote::{format_ident, quote, quote_spanned};
 use syn::{
     parse_macro_input, punctuated::Punctuated, token::Comma,
     Data, DeriveInput, Field, Fields, Ident,
@@ -13,15 +13,259 @@ use syn::{
 ///
 /// This macro will generate the following methods:
 /// - `load`: Loads an instance from TiKV.
-/// - `save`: Saves the instance to TiKV.
+/// - `load_many`: Loads several instances with a single batch read. `by_<field>` uses it for
+///   `#[index]` and `#[index(hashed)]` fields.
+/// - `count`: Counts the stored instances without loading them.
+/// - `compare_keys`: Compares two keys in their stored order, the order `all` yields them in.
+/// - `load_at`, `all_at`: Like `load` and `all`, but read from a snapshot at a given TiKV
+///   timestamp.
+/// - `load_snapshot`: Like `load`, but reads from a given read-only `Snapshot`.
+/// - `save`: Saves the instance to TiKV, returning whether it was `ergokv::SaveOutcome::Created`
+///   or `Updated`.
 /// - `delete`: Deletes the instance from TiKV.
+/// - `delete_many`: Deletes the instances with the given keys, batching shared index updates.
 /// - `by_<field>`: For each indexed field, generates a method to find an instance by that field.
-/// - `set_<field>`: For each field, generates a method to update that field.
+///   For `#[index]` and `#[index(hashed)]` fields, keys of instances that are no longer stored
+///   are skipped and pruned from the index. For a field of type `Option<T>`, it takes a `T` and
+///   finds the instances whose field is `Some` of it.
+/// - `by_<field>_ref`: For each `#[unique_index]`, `#[index]` and `#[index(hashed)]` field, like
+///   `by_<field>`, but takes a borrowed value, e.g. a `&str` for a `String` field. Low-cardinality
+///   fields, whose `by_<field>` streams, get none.
+/// - `by_<field>_concurrent`: For each `#[index]` and `#[index(hashed)]` field, like `by_<field>`,
+///   but loads the instances concurrently from snapshots. `load_many_at` does the same for a
+///   list of keys.
+/// - `by_<field>_one`: For each `#[index]` and `#[index(hashed)]` field, like `by_<field>`, but
+///   only loads the first instance with the value, or returns `None` if there is none.
+/// - `by_<field>_in`: For each indexed field but range-indexed ones, finds all instances whose
+///   field has any of the given values. Like `by_<field>`, it takes values of `T` for an
+///   `Option<T>` field.
+/// - `group_by_<field>`: For each indexed field, loads all instances with an indexed value,
+///   grouped by the value, finding them through the index.
+/// - `by_<field>_exists`: For each indexed field, checks whether any instance has a given value
+///   without loading it.
+/// - `by_<field>_range`: For each range-indexed field, generates a method to find all instances
+///   whose field value lies in a given range.
+/// - `search_<field>`: For each `#[index(search)]` field, streams all instances whose field value
+///   starts with a given prefix.
+/// - `set_<field>`: For each field, generates a method to update that field. Setting the value
+///   the field already has, compared by its serialization, writes nothing.
+/// - `with_<field>`: For each field, returns the instance with the field replaced, without writing
+///   anything, so that an instance can be assembled in memory and written once with `save`.
+/// - `load_auto`, `save_auto`, `delete_auto`: Like `load`, `save` and `delete`, but take a
+///   `TransactionClient` and manage a transaction of their own.
+/// - `load_field_<field>`: For each non-key field, loads only that field of an instance.
+/// - `set_<field>_buffered`: For each non-indexed field, like `set_<field>`, but queues the write
+///   in an `ergokv::WriteBuffer`, which writes each key once when flushed. Not generated for
+///   flattened fields, or for models with `strict`, `audit_log`, `timestamps`, `cache_ttl` or
+///   `checksum`.
+/// - `cas_<field>`: For each non-key field, sets the field only if its stored value equals an
+///   expected one.
+/// - `lock_and_set_<field>`: For each non-key field, updates the field from its stored value
+///   under a pessimistic lock, so that concurrent updates are serialized instead of conflicting.
+/// - `schema_version_of`: Returns the migration an instance was last saved under.
+/// - `reindex_all`: Rebuilds the master trie and all indexes of the model from the stored field
+///   values, removing orphaned entries.
+/// - `prune_indexes`: Removes index entries pointing at instances that are no longer stored.
+/// - `verify_integrity`: Reports inconsistencies between the master trie, the indexes and the
+///   stored field values, without modifying anything.
+/// - `rekey`: Moves the stored instance, including its index entries, to a new primary key,
+///   without running hooks or writing audit and outbox entries.
+/// - `apply_patch` / `upsert_patch`: Update only the fields set in a generated `<Name>Patch`
+///   struct, optionally creating the instance if it does not exist.
+/// - `update`: Returns a generated `<Name>Update` builder, whose `set_<field>` calls are written
+///   together by `apply`, with a single migration check.
+/// - `backup`, `restore`, `restore_mapping`: Write all instances to a JSON lines file and read
+///   them back. Models with `backup` are also registered for `ergokv::backup_all`.
+/// - `restore_with`: Like `restore`, but an `ergokv::ConflictPolicy` decides what happens to
+///   instances whose key is already stored.
+/// - `backup_framed`, `restore_framed`: Like `backup` and `restore`, but with every instance
+///   prefixed by its length instead of ending in a newline.
+/// - `all_in_partition`: With `#[store(partition_by = "field")]`, streams the instances with
+///   the given value of the field.
+/// - `rewrite_all`: With fields in `#[store(format = "bincode")]` or `"rkyv"`, writes every
+///   instance again, moving values stored as CBOR to the field's format.
+/// - `load_field_<field>_archived`: For each `#[store(format = "rkyv")]` field, loads the field's
+///   `ergokv::ArchivedValue`, which is read in place instead of being deserialized.
+/// - `export_csv`: Writes all instances as CSV, with a header row of the field names.
+/// - `poll_outbox`, `ack_outbox`: With `#[store(outbox)]`, read the events recorded for saves and
+///   deletes, and remove those that were handled.
+/// - `drain`: Streams all instances, deleting each one before yielding it.
+/// - `as_map`: Returns the fields of an instance as JSON values by name, as they are backed up.
+/// - `load_stale`, `load_many_stale`, `all_stale`: With `#[store(stale_snapshot(...))]`, like
+///   `load_at`, `load_many_at` and `all_at`, at a timestamp the configured staleness in the past.
+/// - `reserve_key`, `complete`, `is_pending`: Claim a key before its instance is saved, save the
+///   instance of a claimed key, and check whether a key is claimed but not yet saved.
+/// - `new_with_generated_key`, `save_new`: With `#[key(auto)]`, create an instance with a newly
+///   generated key, and give an instance a new key and save it.
+///
+/// It also implements `ergokv::Store`, whose `FIELD_NAMES`, `INDEX_FIELDS` and
+/// `UNIQUE_INDEX_FIELDS` constants list the names of the fields, of the indexed fields and of the
+/// `#[unique_index]` fields.
 ///
 /// # Attributes
 ///
-/// - `#[key]`: Marks a field as the primary key. Required on exactly one field.
+/// - `#[key]`: Marks a field as the primary key. Required on exactly one field, except on
+///   singletons.
+/// - `#[key(auto)]`: Like `#[key]`, but generates `new_with_generated_key`, which takes the other
+///   fields and fills in a newly generated key, and `save_new`, which gives an instance a new key
+///   and saves it. The key type must implement `ergokv::GenerateKey`, e.g. `uuid::Uuid` (`uuid`
+///   feature). Can be combined with `as_str`.
+/// - `#[key(as_str)]`: Like `#[key]`, but stores the key using its `Display` and `FromStr`
+///   implementations instead of JSON.
+/// - `#[unique_index]`: Marks a field as uniquely indexed. Writing a value that another instance
+///   already has fails. `None` values of `Option` fields are not indexed, so any number of
+///   instances can have them.
 /// - `#[index]`: Marks a field as indexed, allowing efficient lookups.
+///   Index keys use the JSON form of the value, so enum fields are indexed by their serde
+///   representation, e.g. `by_status(Status::Active, txn)`.
+/// - `#[index(low_cardinality)]`: Like `#[index]`, but for fields with few distinct values, whose
+///   index entries each list a large part of the table. Indexing such a field is usually not
+///   worth it, as a lookup reads about as much as a scan. Their `by_<field>` returns a `Stream`
+///   loading the instances one at a time, so that callers can stop early or paginate. Plain
+///   `#[index]` fields of type `bool` are treated the same way.
+/// - `#[index(range)]`: Marks a field as range-indexed, allowing efficient range queries.
+///   The field type must implement `ergokv::RangeKey`.
+/// - `#[index(hashed)]`: Like `#[index]`, but stores the index under a hash of the value,
+///   keeping index keys short for long values. The field type must implement `PartialEq`.
+/// - `#[index(sparse)]`: Only indexes the field when its value differs from `Default::default()`
+///   (e.g. `None` or `""`). The field type must implement `Default` and `PartialEq`.
+///   Can be combined with `range`.
+/// - `#[index(search)]`: Like `#[index]`, but also keeps the values in a prefix trie,
+///   `ergokv:{MODEL}:fti:{field}`, for `search_<field>`. The field type must implement
+///   `AsRef<str>` and `From<String>`, e.g. `String`. Cannot be combined with `range` or `hashed`.
+/// - `#[store(raw_bytes)]`: On a `Vec<u8>` or `bytes::Bytes` field, stores the bytes as they are,
+///   without CBOR framing. The field type must implement `AsRef<[u8]>` and `From<Vec<u8>>`.
+/// - `#[store(encrypt)]`: Encrypts the stored value of the field with the `ergokv::Encryptor`
+///   installed at runtime by `ergokv::set_encryptor`, e.g. an `ergokv::AesGcmEncryptor`
+///   (`encryption` feature). Loading and saving fail while no encryptor is installed.
+///   Encrypted fields cannot be the key or indexed. Backups hold the decrypted values.
+/// - `#[store(compression_threshold = 256)]`: On the struct or a field, compresses the stored
+///   value of a field with LZ4 when it is larger than the given number of bytes, and stores it
+///   as it is otherwise. Each value starts with a byte telling which of the two it is, which is
+///   checked on load, so values stored before the threshold was set cannot be read until they are
+///   written again. On the struct, it applies to every field but the key and flattened fields,
+///   which cannot be compressed. Compression happens before encryption.
+/// - `#[store(max_value_size = 1_048_576)]`: On the struct or a field, caps the size in bytes of
+///   the stored value of a field, as written after compression and encryption. `save`,
+///   `set_<field>`, `set_<field>_buffered` and patches encode the new values first and fail with
+///   an error recognized by `ergokv::is_value_too_large` before writing anything if one is over
+///   the cap. On the struct, it applies to every field but flattened fields, whose sub-fields
+///   are stored under keys of their own and are not checked.
+/// - `#[store(format = "bincode")]`: On the struct or a field, stores field values with
+///   `bincode` (re-exported as `ergokv::bincode`), a compact binary format, instead of CBOR.
+///   A field can return to CBOR with `format = "cbor"`. The format is not self-describing, so
+///   adding, removing or reordering fields of a stored value's type (or variants of an enum)
+///   requires a migration. `raw_bytes` and flattened fields keep their own encodings.
+///   Bincode values are stored behind a tag byte that no CBOR value starts with (see
+///   `ergokv::split_format_tag`), and untagged values are read as CBOR, so a field can switch to
+///   bincode without a migration. `rewrite_all`, or `load` with `rewrite_on_load`, writes such
+///   values again in bincode.
+/// - `#[store(format = "rkyv")]`: Like `format = "bincode"`, but stores field values as archived
+///   `rkyv` bytes (`rkyv` feature, re-exported as `ergokv::rkyv`), whose loading skips most of the
+///   decoding work, and which `load_field_<field>_archived` reads in place. The field type must
+///   implement `rkyv::Archive`, `rkyv::Serialize` and `rkyv::Deserialize` (with
+///   `#[rkyv(crate = ergokv::rkyv)]` when deriving them), besides serde's traits, which backups
+///   still use. Fields with `#[serde(with)]` or its variants cannot be stored with rkyv. Values
+///   are tagged like bincode ones, and values stored as CBOR or bincode are read in their format.
+/// - `#[store(rewrite_on_load)]`: On the struct, with fields in `format = "bincode"` or `"rkyv"`,
+///   makes `load` write an instance again, like `rewrite_all`, if one of its values is stored in
+///   another format than its field's, e.g. as CBOR from before the field moved to bincode.
+/// - `#[store(load_or_default)]`: On the struct, generates `load_or_default`, which returns
+///   `Default::default()` with the given key set when no instance is stored under it. The struct
+///   must implement `Default`.
+/// - `#[store(flatten)]`: On a field whose type serializes to a map, e.g. a struct, stores each
+///   of its entries under a key of its own, `ergokv:{MODEL}:{key}:{field}.{subfield}`. Saving the
+///   field only writes the sub-fields whose stored value changed. Flattened fields cannot be the
+///   key, indexed, `raw_bytes` or encrypted, and get no `lock_and_set_` method.
+/// - `#[store(immutable)]`: The field keeps the value the instance was created with. It gets no
+///   `set_`, `cas_` or `lock_and_set_` method, and `save`, `apply_patch` and `update` fail if it
+///   differs from the stored value. The field type must implement `PartialEq`.
+/// - `#[store(compute = "path::to::fn")]`: The field is not stored, but set by calling the given
+///   `fn(&Self) -> T` whenever an instance is loaded. The function sees the stored fields, while
+///   the computed field itself still holds `Default::default()`. Computed fields get no methods or
+///   patch entries of their own, and `set_<field>` does not recompute them. They cannot be the key,
+///   indexed, or combined with other `#[store]` options. The field type must implement `Default`.
+/// - `#[store(audit_log)]`: On the struct, appends an `ergokv::AuditEntry` for every `save`,
+///   `set_<field>` and `delete` within the same transaction, readable with `audit_log`.
+/// - `#[store(outbox)]`: On the struct, appends an `ergokv::OutboxEvent` to the model's outbox,
+///   `ergokv:{MODEL}:__outbox`, for every `save` and `delete` within the same transaction, so the
+///   event is committed exactly when the change is. The events are read with `poll_outbox` and
+///   removed with `ack_outbox`. Changes made with `set_<field>` and the other single-field
+///   updates are not recorded. Every event bumps a sequence number shared by all instances, so
+///   concurrent transactions writing the same model conflict.
+/// - `#[store(hooks)]`: On the struct, calls the methods of its `ergokv::StoreHooks`
+///   implementation before and after `save` and `delete` write the instance, within the same
+///   transaction. The struct must implement `ergokv::StoreHooks`, whose methods default to doing
+///   nothing.
+/// - `#[store(timeline)]`: On the struct, appends an `ergokv::TimelineEntry` for every `save` to
+///   a timeline shared by all models, so that `ergokv::recent_activity` lists the latest saves
+///   across model types, newest first.
+/// - `#[store(stale_snapshot(max_staleness = "5s"))]`: On the struct, generates `load_stale`,
+///   `load_many_stale` and `all_stale`, which read a snapshot from the given duration ago instead
+///   of the latest state. They trade freshness for reads that take no locks and rarely meet those
+///   of transactions still committing: writes made within the duration, including new instances,
+///   are not seen. These are stale-snapshot reads only, not follower reads: tikv-client 0.3
+///   cannot route reads to follower replicas, so region leaders serve them like any other read,
+///   and `load`, `by_<field>` and `all` are unchanged. Index lookups have no stale variant.
+/// - `#[store(cache_ttl = "30s")]`: On the struct, caches loaded instances in-process for the
+///   given duration (`ms`, `s`, `m` or `h`). Mutations invalidate the cache, and a key written by
+///   a transaction is never read from or put in the cache by it. `clear_cache` empties it. The struct must implement `Clone`. `cache_capacity = N` bounds the number of
+///   cached instances (1024 by default).
+/// - `#[store(on_conflict = "...")]`: On the struct, sets what `save` does when the key is
+///   already stored: `"overwrite"` it (the default), fail with an `"error"`, or `"skip"` the
+///   write, in which case `save` returns whether it wrote the instance.
+/// - `#[store(key_prefix_shards = 16)]`: On the struct, spreads instances across the given
+///   number of key prefixes, `ergokv:{MODEL}:shard{n}:{key}`, picked by a hash of the key.
+///   Changing the number of shards of a stored model requires a migration.
+/// - `#[store(partition_by = "field")]`: On the struct, lists the keys of the instances sharing
+///   a value of the field in a trie of their own, `ergokv:{MODEL}:partition:{value}`, so that
+///   `all_in_partition` iterates one partition without scanning the others. The field is
+///   immutable, like with `#[store(immutable)]`, so instances never move between partitions. It
+///   cannot be the key, encrypted, flattened or computed, and its type must implement `PartialEq`.
+/// - `#[store(timestamps)]`: On the struct, manages its `created_at` and `updated_at` fields,
+///   whose type must implement `ergokv::AutoTimestamp`. `save` sets `created_at` only for a new
+///   instance and `updated_at` every time, and `set_<field>` bumps `updated_at`. The values
+///   given to `save` are ignored, and the fields get no `set_`/`cas_` methods or patch entries.
+///   The struct must implement `Clone`.
+/// - `#[store(no_trie)]`: On the struct, skips registering instances in the master trie, saving
+///   its reads and writes on every `save` and `delete`. Such models have no `all`, `count` or
+///   `backup` (but still have `all_at`), and cannot be migrated from.
+/// - `#[store(key_range_scan)]`: On the struct, skips the master trie like `no_trie`, but keeps
+///   `all`, `count` and `backup`, which find the instances by scanning the keys of their key
+///   fields under `ergokv:{MODEL}:` instead, like `all_at` does. This saves the trie's reads and
+///   writes on every `save` and `delete`, and there is no trie entry to get out of sync, at the
+///   cost of `all` and `count` reading one key per instance. The instances are not counted by
+///   `ergokv::model_stats`.
+/// - `#[store(singleton)]`: On a struct without a `#[key]` field, stores its one instance under
+///   the fixed path `{MODEL}:__singleton` instead. Only `get`, `set` and `clear` are generated,
+///   reading, replacing and removing that instance; none of the other methods above are. Fields
+///   cannot be indexed, but keep their own `#[store]` options, like `format`, `encrypt` or
+///   `compute`.
+/// - `#[store(checksum)]`: On the struct, stores a BLAKE3 hash of the stored bytes of all fields
+///   of every instance under `ergokv:{MODEL}:{key}:__checksum`, written along with the fields by
+///   `save`, `set_<field>` and patches. `load` and `load_many` hash the fields they read again, and
+///   fail with an error recognized by `ergokv::is_checksum_mismatch` if the hashes differ, e.g.
+///   because a field was corrupted or changed outside of ergokv, or if no checksum is stored.
+///   `checksum = "warn"` passes the error to the handler installed with
+///   `ergokv::set_checksum_mismatch_handler` instead and returns the instance. Snapshot reads,
+///   like `load_at`, and `load_field_<field>` do not verify checksums, and such models get no
+///   `set_<field>_buffered`.
+/// - `#[store(model_name = "User_v2")]`: On the struct, stores the model under the given name
+///   instead of the name of the struct, in every key, trie entry, index, migration record and
+///   backup file name of the model. Models with different names never see each other's
+///   instances, so this can keep the instances of a schema version apart from those of another,
+///   or let a renamed struct keep reading the instances of its old name. The name cannot
+///   contain `:` or `;`. `#[model_name = "..."]` is the same option.
+/// - `#[store(lazy_migration)]`: On a struct with `#[migrate_from(Prev)]`, migrates instances as
+///   they are loaded instead of all at once in `ensure_migrations`. `load` (and `load_many`, so
+///   `by_<field>` too) converts an instance that is not stamped with the current migration with
+///   `from_<prev>`, replaces it with the result within the transaction, and returns it. Snapshot
+///   reads, e.g. `load_at`, cannot write and do not migrate. `Prev` must be readable by its own
+///   `load`, i.e. fully migrated or lazy itself. Strict models still need `ensure_migrations` to
+///   record the migration before they can be written.
+/// - `#[store(strict)]`: On the struct, rejects writes to an outdated model version (or to a
+///   version whose migration has not run yet), like the `strict-migrations` feature does for
+///   every model.
 ///
 /// # Example
 ///
@@ -61,30 +305,195 @@ pub fn derive_store(input: TokenStream) -> TokenStream {
         .map(|attr| attr.parse_args::<syn::Path>())
         .transpose()
         .unwrap_or(None);
+    let options = StoreOptions::from_attrs(&input.attrs);
 
-    let fields = match &input.data {
+    let declared_fields = match &input.data {
         Data::Struct(data) => match &data.fields {
             Fields::Named(fields) => &fields.named,
             _ => panic!("Only named fields are supported"),
         },
         _ => panic!("Only structs are supported"),
     };
-    let key_field = fields
+    let all_fields =
+        &apply_model_options(declared_fields, &options);
+    if options.rewrite_on_load
+        && (options.singleton
+            || !all_fields.iter().any(is_tagged_format))
+    {
+        panic!("#[store(rewrite_on_load)] needs a field with format = \"bincode\" or \"rkyv\", and cannot be combined with singleton");
+    }
+    if options.singleton {
+        return generate_singleton(name, all_fields, &options)
+            .into();
+    }
+    let key_field = all_fields
         .iter()
         .find(|f| {
             f.attrs.iter().any(|a| a.path().is_ident("key"))
         })
         .expect("A field with #[key] attribute is required");
 
-    let load_method = generate_load_method(fields);
-    let save_method =
-        generate_save_method(name, fields, prev_type.as_ref());
-    let delete_method =
-        generate_delete_method(name, fields, prev_type.as_ref());
+    if options.timestamps
+        && (field_type(all_fields, "created_at").is_none()
+            || field_type(all_fields, "updated_at").is_none())
+    {
+        panic!("#[store(timestamps)] requires `created_at` and `updated_at` fields");
+    }
+
+    if let Some(partition) = &options.partition_by {
+        let field = all_fields
+            .iter()
+            .find(|f| is_partition_field(f, &options))
+            .unwrap_or_else(|| {
+                panic!(
+                    "#[store(partition_by)] names no field `{}`",
+                    partition
+                )
+            });
+        let field_options = FieldOptions::from_field(field);
+        if field.ident == key_field.ident
+            || field_options.encrypt
+            || field_options.flatten
+            || field_options.compute.is_some()
+        {
+            panic!("#[store(partition_by)] cannot name the key or an encrypted, flattened or computed field");
+        }
+    }
+
+    for field in all_fields {
+        let field_options = FieldOptions::from_field(field);
+        let keyed_or_indexed = index_kind(field).is_some()
+            || field.ident == key_field.ident;
+
+        // Index keys would reveal encrypted values, and so would the key itself
+        if field_options.encrypt && keyed_or_indexed {
+            panic!("#[store(encrypt)] fields cannot be the key or indexed");
+        }
+        if field_options.flatten
+            && (keyed_or_indexed
+                || field_options.raw_bytes
+                || field_options.encrypt
+                || field_options.compression_threshold.is_some())
+        {
+            panic!("#[store(flatten)] fields cannot be the key, indexed, raw_bytes, encrypted or compressed");
+        }
+        if field_options.flatten
+            && field_options.max_value_size.is_some()
+        {
+            panic!("#[store(max_value_size)] cannot be set on #[store(flatten)] fields");
+        }
+        if field_options.compression_threshold.is_some()
+            && field.ident == key_field.ident
+        {
+            panic!("#[store(compression_threshold)] cannot be set on the key");
+        }
+        if field_options.format != Format::Cbor
+            && (field_options.raw_bytes || field_options.flatten)
+            && options.format == Format::Cbor
+        {
+            panic!("#[store(format = \"...\")] fields cannot be raw_bytes or flattened");
+        }
+        // rkyv does not go through serde, so serde's field attributes would be ignored
+        if field_options.format == Format::Rkyv
+            && !field_options.raw_bytes
+            && !field_options.flatten
+            && field_options.compute.is_none()
+            && !matches!(
+                serde_field_paths(field),
+                (None, None, None)
+            )
+        {
+            panic!("#[store(format = \"rkyv\")] fields cannot use #[serde(with)], #[serde(serialize_with)] or #[serde(deserialize_with)], give them format = \"cbor\"");
+        }
+        if field_options.compute.is_some()
+            && (keyed_or_indexed
+                || field_options.raw_bytes
+                || field_options.encrypt
+                || field_options.flatten
+                || field_options.immutable
+                || is_managed_timestamp(field, &options))
+        {
+            panic!("#[store(compute)] fields cannot be the key, indexed, managed timestamps or have other store options");
+        }
+    }
+
+    // Computed fields are never stored, so only loading and creating instances see them
+    let stored_fields: Punctuated<Field, Comma> = all_fields
+        .iter()
+        .filter(|f| !is_computed(f))
+        .cloned()
+        .collect();
+    let fields = &stored_fields;
+
+    let key_bounds = generate_key_bounds_check(key_field);
+    if options.key_range_scan && options.no_trie {
+        panic!("#[store(key_range_scan)] cannot be combined with #[store(no_trie)]");
+    }
+    if options.lazy_migration && prev_type.is_none() {
+        panic!("#[store(lazy_migration)] requires #[migrate_from(...)]");
+    }
+    let lazy_migration = prev_type
+        .as_ref()
+        .filter(|_| options.lazy_migration)
+        .map(|prev| generate_lazy_migration(name, prev));
+    let load_method = generate_load_method(
+        all_fields,
+        &options,
+        lazy_migration.as_ref(),
+    );
+    let schema_version = prev_type
+        .as_ref()
+        .map(|prev| migration_name(name, prev));
+    let save_method = generate_save_method(
+        fields,
+        &options,
+        schema_version.as_deref(),
+    );
+    let delete_method = generate_delete_method(fields, &options);
+    let rekey_method =
+        generate_rekey_method(key_field, &options);
+    let reservation_methods =
+        generate_reservation_methods(key_field, &options);
+    let checksum_methods =
+        generate_checksum_methods(fields, key_field, &options);
+    let key_generation_methods = generate_key_generation_methods(
+        all_fields, key_field, &options,
+    );
+    let reindex_method =
+        generate_reindex_method(key_field, &options);
+    let auto_methods =
+        generate_auto_methods(key_field, &options);
+    let audit_methods =
+        generate_audit_methods(key_field, &options);
+    let outbox_methods =
+        generate_outbox_methods(key_field, &options);
+    let cache_methods = generate_cache_methods(name, &options);
     let index_methods = generate_index_methods(name, fields);
-    let set_methods =
-        generate_set_methods(name, fields, prev_type.as_ref());
-    let all_method = generate_all_method(key_field);
+    let exists_methods =
+        generate_exists_methods(fields, key_field);
+    let search_methods = generate_search_methods(name, fields);
+    let partition_methods =
+        generate_partition_methods(name, fields, &options);
+    let rewrite_method =
+        generate_rewrite_method(fields, &options);
+    let set_methods = generate_set_methods(fields, &options);
+    let with_methods = generate_with_methods(fields, &options);
+    let cas_methods =
+        generate_cas_methods(fields, key_field, &options);
+    let lock_methods =
+        generate_lock_methods(fields, key_field, &options);
+    let flatten_methods = generate_flatten_methods(fields);
+    let projection_methods =
+        generate_projection_methods(fields, key_field);
+    let check_migrations = generate_check_migrations_method(
+        name,
+        prev_type.as_ref(),
+        &options,
+    );
+    let all_method = generate_all_method(key_field, &options);
+    let stale_snapshot_methods =
+        generate_stale_snapshot_methods(key_field, &options);
+    let key_codec = generate_key_codec(key_field, &options);
     let migration_trait = prev_type
         .as_ref()
         .map(|prev| generate_migration_trait(name, prev));
@@ -98,284 +507,1106 @@ pub fn derive_store(input: TokenStream) -> TokenStream {
             },
             |prev| generate_ensure_migrations(name, prev)
         );
-    let backup_restore = generate_backup_restore_methods();
+    let backup_restore =
+        generate_backup_restore_methods(&options);
+    let model_name = options.model_name(name);
+    let restore_with =
+        generate_restore_with(key_field, &options);
+    let registration = generate_registration(
+        name,
+        prev_type.as_ref(),
+        &options,
+    );
+    let (patch_struct, patch_methods) = generate_patch(
+        name, &input.vis, all_fields, key_field, &options,
+    );
+    let reflection = generate_reflection(name, all_fields);
 
     // TODO: Add unique_index, which is a field_value->ID mapping (this is currently index) and index, which is a field_value->Vec<ID> mapping
     // TODO: Add search function, which queries a field by predicate -- think about if we can make this fast
     quote! {
+        #key_bounds
         #migration_trait
+        #patch_struct
+        #registration
+        #reflection
 
         impl #name {
-            const MODEL_NAME: &'static str = stringify!(#name);
+            const MODEL_NAME: &'static str = #model_name;
 
             #load_method
             #save_method
             #delete_method
+            #rekey_method
+            #reservation_methods
+            #checksum_methods
+            #key_generation_methods
+            #reindex_method
+            #auto_methods
+            #audit_methods
+            #outbox_methods
+            #cache_methods
             #ensure_migrations
+            #check_migrations
             #all_method
+            #stale_snapshot_methods
+            #key_codec
             #backup_restore
+            #restore_with
             #(#index_methods)*
+            #(#exists_methods)*
+            #(#search_methods)*
+            #partition_methods
+            #rewrite_method
             #(#set_methods)*
+            #(#with_methods)*
+            #(#cas_methods)*
+            #(#lock_methods)*
+            #flatten_methods
+            #(#projection_methods)*
+            #patch_methods
         }
     }
     .into()
 }
 
-fn generate_load_method(
-    fields: &Punctuated<Field, Comma>,
-) -> TokenStream2 {
-    let key_field = fields
-        .iter()
-        .find(|f| {
-            f.attrs.iter().any(|a| a.path().is_ident("key"))
-        })
-        .expect("A field with #[key] attribute is required");
-    let key_type = &key_field.ty;
+/// Options given to `#[store(...)]` on the struct itself.
+#[derive(Default)]
+struct StoreOptions {
+    /// `#[store(strict)]`, generate migration checks even without the `strict-migrations` feature
+    strict: bool,
+    /// `#[store(audit_log)]`, record every mutation in an append-only log
+    audit_log: bool,
+    /// `#[store(timeline)]`, record every save in the global timeline
+    timeline: bool,
+    /// `#[store(outbox)]`, record every save and delete in the model's outbox
+    outbox: bool,
+    /// `#[store(checksum)]`, store a checksum of every instance and verify it on `load`
+    checksum: Option<ChecksumMode>,
+    /// `#[store(cache_ttl = "...")]`, cache loaded instances in-process for this many milliseconds
+    cache_ttl: Option<u64>,
+    /// `#[store(stale_snapshot(max_staleness = "..."))]`, how many milliseconds behind the `_stale` reads are
+    stale_snapshot: Option<u64>,
+    /// `#[store(cache_capacity = ...)]`, maximum number of cached instances
+    cache_capacity: Option<usize>,
+    /// `#[store(compression_threshold = N)]`, compress stored field values larger than this many bytes
+    compression_threshold: Option<usize>,
+    /// `#[store(max_value_size = N)]`, reject stored field values larger than this many bytes
+    max_value_size: Option<usize>,
+    /// `#[store(on_conflict = "...")]`, what `save` does when the key is already stored
+    on_conflict: OnConflict,
+    /// `#[store(key_prefix_shards = N)]`, spread instances across this many key prefixes
+    shards: Option<u64>,
+    /// `#[store(partition_by = "...")]`, the field whose values partition the instances
+    partition_by: Option<String>,
+    /// `#[store(model_name = "...")]` or `#[model_name = "..."]`, the name the model is stored under
+    model_name: Option<String>,
+    /// `#[store(no_trie)]`, don't register instances in the master trie
+    no_trie: bool,
+    /// `#[store(singleton)]`, store the one instance of the model under a fixed path
+    singleton: bool,
+    /// `#[store(key_range_scan)]`, find instances by scanning their keys instead of the master trie
+    key_range_scan: bool,
+    /// `#[store(lazy_migration)]`, migrate instances from the previous model as they are loaded
+    lazy_migration: bool,
+    /// `#[store(timestamps)]`, manage the `created_at` and `updated_at` fields
+    timestamps: bool,
+    /// `#[store(load_or_default)]`, generate `load_or_default`
+    load_or_default: bool,
+    /// `#[store(hooks)]`, call the model's `ergokv::StoreHooks` around `save` and `delete`
+    hooks: bool,
+    /// `#[store(format = "...")]`, the format every field is stored in by default
+    format: Format,
+    /// `#[store(rewrite_on_load)]`, have `load` write values stored in another format than
+    /// their field's again
+    rewrite_on_load: bool,
+}
 
-    let field_loads = fields.iter().map(|f| {
-        let field_name = &f.ident;
-        let field_type = &f.ty;
-        quote! {
-            let #field_name: #field_type = {
-                let key = format!(
-                    "ergokv:{}:{}:{}",
-                    Self::MODEL_NAME,
-                    ::ergokv::serde_json::to_string(&key)
-                        .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode struct key: {e}")))?,
-                    stringify!(#field_name)
-                );
-                let value = txn.get(key.clone()).await?
-                    .ok_or_else(|| tikv_client::Error::StringError(key.clone()))?;
-                ::ergokv::ciborium::de::from_reader(value.as_slice())
-                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {}: {}", stringify!(#field_name), e)))?
+/// What `load` does with an instance whose checksum does not match, picked with
+/// `#[store(checksum = "...")]`.
+#[derive(Clone, Copy, PartialEq)]
+enum ChecksumMode {
+    /// Fail with an error
+    Error,
+    /// Report the mismatch to the handler installed with
+    /// `ergokv::set_checksum_mismatch_handler` and return the instance
+    Warn,
+}
+
+/// What `save` does when an instance with the same key is already stored.
+#[derive(Default, Clone, Copy, PartialEq)]
+enum OnConflict {
+    /// Replace the stored instance
+    #[default]
+    Overwrite,
+    /// Fail with an error
+    Error,
+    /// Leave the stored instance untouched, `save` returns `false`
+    Skip,
+}
+
+impl StoreOptions {
+    /// Whether instances are registered in the master trie.
+    fn uses_trie(&self) -> bool {
+        !self.no_trie && !self.key_range_scan
+    }
+
+    fn from_attrs(attrs: &[syn::Attribute]) -> Self {
+        let mut options = Self::default();
+
+        for attr in
+            attrs.iter().filter(|a| a.path().is_ident("store"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("strict") {
+                    options.strict = true;
+                    Ok(())
+                } else if meta.path.is_ident("timestamps") {
+                    options.timestamps = true;
+                    Ok(())
+                } else if meta.path.is_ident("load_or_default") {
+                    options.load_or_default = true;
+                    Ok(())
+                } else if meta.path.is_ident("rewrite_on_load") {
+                    options.rewrite_on_load = true;
+                    Ok(())
+                } else if meta.path.is_ident("no_trie") {
+                    options.no_trie = true;
+                    Ok(())
+                } else if meta.path.is_ident("key_range_scan") {
+                    options.key_range_scan = true;
+                    Ok(())
+                } else if meta.path.is_ident("singleton") {
+                    options.singleton = true;
+                    Ok(())
+                } else if meta.path.is_ident("model_name") {
+                    let model_name: syn::LitStr = meta.value()?.parse()?;
+                    options.set_model_name(model_name.value());
+                    Ok(())
+                } else if meta.path.is_ident("lazy_migration") {
+                    options.lazy_migration = true;
+                    Ok(())
+                } else if meta.path.is_ident("format") {
+                    options.format = parse_format(&meta)?;
+                    Ok(())
+                } else if meta.path.is_ident("hooks") {
+                    options.hooks = true;
+                    Ok(())
+                } else if meta.path.is_ident("outbox") {
+                    options.outbox = true;
+                    Ok(())
+                } else if meta.path.is_ident("checksum") {
+                    options.checksum = Some(ChecksumMode::Error);
+                    if meta.input.peek(syn::Token![=]) {
+                        let mode: syn::LitStr = meta.value()?.parse()?;
+                        options.checksum = Some(match mode.value().as_str() {
+                            "error" => ChecksumMode::Error,
+                            "warn" => ChecksumMode::Warn,
+                            _ => return Err(meta.error("expected \"error\" or \"warn\"")),
+                        });
+                    }
+                    Ok(())
+                } else if meta.path.is_ident("audit_log") {
+                    options.audit_log = true;
+                    Ok(())
+                } else if meta.path.is_ident("timeline") {
+                    options.timeline = true;
+                    Ok(())
+                } else if meta.path.is_ident("cache_ttl") {
+                    let ttl: syn::LitStr = meta.value()?.parse()?;
+                    options.cache_ttl =
+                        Some(parse_duration_ms(&ttl.value()).ok_or_else(
+                            || meta.error("expected a duration like \"500ms\", \"30s\", \"5m\" or \"1h\""),
+                        )?);
+                    Ok(())
+                } else if meta.path.is_ident("stale_snapshot") {
+                    meta.parse_nested_meta(|inner| {
+                        if inner.path.is_ident("max_staleness") {
+                            let staleness: syn::LitStr = inner.value()?.parse()?;
+                            options.stale_snapshot =
+                                Some(parse_duration_ms(&staleness.value()).ok_or_else(
+                                    || inner.error("expected a duration like \"500ms\", \"30s\", \"5m\" or \"1h\""),
+                                )?);
+                            Ok(())
+                        } else {
+                            Err(inner.error("expected max_staleness"))
+                        }
+                    })?;
+                    if options.stale_snapshot.is_none() {
+                        return Err(meta.error("expected stale_snapshot(max_staleness = \"...\")"));
+                    }
+                    Ok(())
+                } else if meta.path.is_ident("cache_capacity") {
+                    let capacity: syn::LitInt = meta.value()?.parse()?;
+                    options.cache_capacity = Some(capacity.base10_parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("compression_threshold") {
+                    let threshold: syn::LitInt = meta.value()?.parse()?;
+                    options.compression_threshold = Some(threshold.base10_parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("max_value_size") {
+                    let max: syn::LitInt = meta.value()?.parse()?;
+                    options.max_value_size = Some(max.base10_parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("key_prefix_shards") {
+                    let shards: syn::LitInt = meta.value()?.parse()?;
+                    let shards: u64 = shards.base10_parse()?;
+                    if shards == 0 {
+                        return Err(meta.error("expected at least one shard"));
+                    }
+                    options.shards = Some(shards);
+                    Ok(())
+                } else if meta.path.is_ident("partition_by") {
+                    let field: syn::LitStr = meta.value()?.parse()?;
+                    options.partition_by = Some(field.value());
+                    Ok(())
+                } else if meta.path.is_ident("on_conflict") {
+                    let policy: syn::LitStr = meta.value()?.parse()?;
+                    options.on_conflict = match policy.value().as_str() {
+                        "overwrite" => OnConflict::Overwrite,
+                        "error" => OnConflict::Error,
+                        "skip" => OnConflict::Skip,
+                        _ => {
+                            return Err(meta.error(
+                                "expected \"overwrite\", \"error\" or \"skip\"",
+                            ))
+                        }
+                    };
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown store option"))
+                }
+            })
+            .unwrap_or_else(|e| {
+                panic!("Invalid #[store] attribute: {e}")
+            });
+        }
+
+        for attr in attrs
+            .iter()
+            .filter(|a| a.path().is_ident("model_name"))
+        {
+            let model_name = match &attr.meta {
+                syn::Meta::NameValue(syn::MetaNameValue {
+                    value:
+                        syn::Expr::Lit(syn::ExprLit {
+                            lit: syn::Lit::Str(name),
+                            ..
+                        }),
+                    ..
+                }) => name.value(),
+                _ => panic!("Expected #[model_name = \"...\"]"),
             };
+            options.set_model_name(model_name);
         }
-    });
 
-    let struct_init = fields.iter().map(|f| {
-        let field_name = &f.ident;
-        quote! { #field_name: #field_name }
-    });
+        options
+    }
 
-    quote! {
-        pub async fn load(key: &#key_type, txn: &mut tikv_client::Transaction) -> Result<Self, tikv_client::Error> {
-            #(#field_loads)*
-            Ok(Self {
-                #(#struct_init,)*
-            })
+    /// Sets the stored model name, which must be usable in keys and given only once.
+    fn set_model_name(&mut self, model_name: String) {
+        if model_name.is_empty()
+            || model_name.contains([':', ';'])
+        {
+            panic!("Model name {model_name:?} must be non-empty and contain no ':' or ';'");
+        }
+        if let Some(name) = &self.model_name {
+            if *name != model_name {
+                panic!("Conflicting model names {name:?} and {model_name:?}");
+            }
         }
+        self.model_name = Some(model_name);
+    }
+
+    /// The name the model is stored under, its `#[store(model_name)]` or the name of the struct.
+    fn model_name(&self, name: &Ident) -> String {
+        self.model_name
+            .clone()
+            .unwrap_or_else(|| name.to_string())
     }
 }
 
-fn generate_save_method(
-    name: &Ident,
-    fields: &Punctuated<Field, Comma>,
-    prev_type: Option<&syn::Path>,
-) -> TokenStream2 {
-    let key_field = fields
-        .iter()
-        .find(|f| {
-            f.attrs.iter().any(|a| a.path().is_ident("key"))
-        })
-        .expect("A field with #[key] attribute is required");
-    let key_ident = &key_field.ident;
-    let checks = generate_mutation_checks(name, prev_type);
+/// The format field values are stored in, picked with `#[store(format = "...")]`.
+#[derive(Default, Clone, Copy, PartialEq)]
+enum Format {
+    /// CBOR, through `ergokv::ciborium`
+    #[default]
+    Cbor,
+    /// `ergokv::bincode`
+    Bincode,
+    /// Archived `ergokv::rkyv` bytes, which can be read without deserializing them
+    Rkyv,
+}
 
-    let field_saves = fields.iter().map(|f| {
-        let field_name = &f.ident;
-        quote! {
-            let key = format!(
-                "ergokv:{}:{}:{}",
-                Self::MODEL_NAME,
-                ::ergokv::serde_json::to_string(&self.#key_ident)
-                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode struct key: {}", e)))?,
-                stringify!(#field_name)
-            );
-            let mut value = Vec::new();
-            ::ergokv::ciborium::ser::into_writer(&self.#field_name, &mut value)
-                .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {}: {}", stringify!(#field_name), e)))?;
-            txn.put(key, value).await?;
+impl Format {
+    fn name(self) -> &'static str {
+        match self {
+            Format::Cbor => "cbor",
+            Format::Bincode => "bincode",
+            Format::Rkyv => "rkyv",
         }
-    });
-
-    let index_saves = fields.iter()
-        .filter(|f| f.attrs.iter().any(|a| a.path().is_ident("unique_index") || a.path().is_ident("index")))
-        .map(|f| {
-            let field_name = &f.ident;
-            let is_unique = f.attrs.iter().any(|a| a.path().is_ident("unique_index"));
+    }
+}
 
-            if is_unique {
-                quote! {
-                    let index_key = format!(
-                        "ergokv:{}:unique_index:{}:{}",
-                        Self::MODEL_NAME,
-                        stringify!(#field_name),
-                        ::ergokv::serde_json::to_string(&self.#field_name)
-                            .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode struct field: {}", e)))?,
-                    );
-                    let mut value = Vec::new();
-                    ::ergokv::ciborium::ser::into_writer(&self.#key_ident, &mut value)
-                        .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {}: {}", stringify!(#field_name), e)))?;
-                    txn.put(index_key, value).await?;
-                }
-            } else {
-                quote! {
-                    let index_key = format!(
-                        "ergokv:{}:index:{}:{}",
-                        Self::MODEL_NAME,
-                        stringify!(#field_name),
-                        ::ergokv::serde_json::to_string(&self.#field_name)
-                            .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode struct field: {}", e)))?,
-                    );
+/// Parses the value of `format = "..."`.
+fn parse_format(
+    meta: &syn::meta::ParseNestedMeta,
+) -> syn::Result<Format> {
+    let format: syn::LitStr = meta.value()?.parse()?;
+    match format.value().as_str() {
+        "cbor" => Ok(Format::Cbor),
+        "bincode" => Ok(Format::Bincode),
+        "rkyv" => Ok(Format::Rkyv),
+        _ => Err(meta.error(
+            "expected \"cbor\", \"bincode\" or \"rkyv\"",
+        )),
+    }
+}
 
-                    // Read existing keys
-                    let mut keys = if let Some(existing_keys_bytes) = txn.get(index_key.clone()).await? {
-                        ::ergokv::ciborium::de::from_reader(existing_keys_bytes.as_slice())
-                            .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode keys: {}", e)))?
-                    } else {
-                        Vec::new()
-                    };
+/// Parses a duration like `500ms`, `30s`, `5m` or `1h` into milliseconds.
+fn parse_duration_ms(value: &str) -> Option<u64> {
+    let split = value.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount.parse().ok()?;
 
-                    // Add current key if not already present
-                    if !keys.contains(&self.#key_ident) {
-                        keys.push(self.#key_ident);
-                    }
+    let factor = match unit {
+        "ms" => 1,
+        "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        _ => return None,
+    };
+    amount.checked_mul(factor)
+}
 
-                    // Write updated keys
-                    let mut value = Vec::new();
-                    ::ergokv::ciborium::ser::into_writer(&keys, &mut value)
-                        .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode keys: {}", e)))?;
-                    txn.put(index_key, value).await?;
-                }
-            }
-        });
+/// Returns the `with`, `serialize_with` and `deserialize_with` paths given in
+/// `#[serde(...)]` on a field, in this order.
+///
+/// Other serde attributes, like `rename`, only affect the name of the field in the
+/// serialized struct, and so make no difference for the per-field storage.
+fn serde_field_paths(
+    field: &Field,
+) -> (Option<syn::Path>, Option<syn::Path>, Option<syn::Path>) {
+    let mut paths = (None, None, None);
 
-    quote! {
-        pub async fn save(&self, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
-            #checks
+    for attr in
+        field.attrs.iter().filter(|a| a.path().is_ident("serde"))
+    {
+        let Ok(metas) = attr.parse_args_with(
+            Punctuated::<syn::Meta, Comma>::parse_terminated,
+        ) else {
+            continue;
+        };
 
-            // Add to master trie
-            let trie = ::ergokv::PrefixTrie::new("ergokv:__trie");
-            trie.insert(
-                txn,
-                &format!(
-                    "{}:{}",
-                    Self::MODEL_NAME,
-                    ::ergokv::serde_json::to_string(&self.#key_ident)
-                        .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode struct key: {}", e)))?
-                )
-            ).await?;
+        for meta in metas {
+            let syn::Meta::NameValue(nv) = meta else {
+                continue;
+            };
+            let syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(value),
+                ..
+            }) = &nv.value
+            else {
+                continue;
+            };
+            let path: syn::Path = value
+                .parse()
+                .expect("Expected a path in #[serde(...)]");
 
-            #(#field_saves)*
-            #(#index_saves)*
-            Ok(())
+            if nv.path.is_ident("with") {
+                paths.0 = Some(path);
+            } else if nv.path.is_ident("serialize_with") {
+                paths.1 = Some(path);
+            } else if nv.path.is_ident("deserialize_with") {
+                paths.2 = Some(path);
+            }
         }
     }
+
+    paths
 }
 
-fn generate_delete_method(
-    name: &Ident,
+/// Returns the fields with the model's `#[store(format = "...")]` applied to each of them,
+/// its `#[store(compression_threshold = N)]` applied to all but the key and flattened fields,
+/// its `#[store(max_value_size = N)]` applied to all but flattened fields, and the field named
+/// by `#[store(partition_by = "...")]` made immutable.
+///
+/// The model's format, threshold and size cap go before the attributes of the field, so that
+/// a field can still pick its own. `raw_bytes` and flattened fields keep their own encodings.
+fn apply_model_options(
     fields: &Punctuated<Field, Comma>,
-    prev_type: Option<&syn::Path>,
-) -> TokenStream2 {
-    let key_field = fields
-        .iter()
-        .find(|f| {
-            f.attrs.iter().any(|a| a.path().is_ident("key"))
-        })
-        .expect("A field with #[key] attribute is required");
-    let key_ident = &key_field.ident;
-    let key_type = &key_field.ty;
-    let checks = generate_mutation_checks(name, prev_type);
-
-    let field_deletes = fields.iter().map(|f| {
-        let field_name = &f.ident;
-        quote! {
-            let key = format!(
-                "ergokv:{}:{}:{}",
-                Self::MODEL_NAME,
-                ::ergokv::serde_json::to_string(&self.#key_ident)
-                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode struct key: {}", e)))?,
-                stringify!(#field_name)
+    options: &StoreOptions,
+) -> Punctuated<Field, Comma> {
+    let mut fields = fields.clone();
+    for field in fields.iter_mut() {
+        if options.format != Format::Cbor {
+            let format = options.format.name();
+            field.attrs.insert(
+                0,
+                syn::parse_quote!(#[store(format = #format)]),
             );
-            txn.delete(key).await?;
         }
-    });
+        if let Some(threshold) = options.compression_threshold {
+            let field_options = FieldOptions::from_field(field);
+            let is_key = field
+                .attrs
+                .iter()
+                .any(|a| a.path().is_ident("key"));
+            if !is_key && !field_options.flatten {
+                field.attrs.insert(
+                    0,
+                    syn::parse_quote!(#[store(compression_threshold = #threshold)]),
+                );
+            }
+        }
+        if let Some(max) = options.max_value_size {
+            if !FieldOptions::from_field(field).flatten {
+                field.attrs.insert(
+                    0,
+                    syn::parse_quote!(#[store(max_value_size = #max)]),
+                );
+            }
+        }
+        if is_partition_field(field, options) {
+            field
+                .attrs
+                .push(syn::parse_quote!(#[store(immutable)]));
+        }
+    }
+    fields
+}
 
-    let index_deletes = fields.iter()
-        .filter(|f| f.attrs.iter().any(|a| a.path().is_ident("unique_index") || a.path().is_ident("index")))
-        .map(|f| {
-            let field_name = &f.ident;
-            let is_unique = f.attrs.iter().any(|a| a.path().is_ident("unique_index"));
+/// Whether the field is the one named by `#[store(partition_by = "...")]`.
+fn is_partition_field(
+    field: &Field,
+    options: &StoreOptions,
+) -> bool {
+    options.partition_by.as_ref().is_some_and(|partition| {
+        field.ident.as_ref().is_some_and(|i| i == partition)
+    })
+}
 
-            if is_unique {
-                quote! {
-                    let index_key = format!(
-                        "ergokv:{}:unique_index:{}:{}",
-                        Self::MODEL_NAME,
-                        stringify!(#field_name),
-                        ::ergokv::serde_json::to_string(&self.#field_name)
-                            .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode struct field: {}", e)))?,
-                    );
-                    txn.delete(index_key).await?;
-                }
-            } else {
-                quote! {
-                    let index_key = format!(
-                        "ergokv:{}:index:{}:{}",
-                        Self::MODEL_NAME,
-                        stringify!(#field_name),
-                        ::ergokv::serde_json::to_string(&self.#field_name)
-                            .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode struct field: {}", e)))?,
-                    );
+/// Whether the field is a `created_at` or `updated_at` field managed by `#[store(timestamps)]`.
+fn is_managed_timestamp(
+    field: &Field,
+    options: &StoreOptions,
+) -> bool {
+    options.timestamps
+        && field.ident.as_ref().is_some_and(|i| {
+            i == "created_at" || i == "updated_at"
+        })
+}
 
-                    // Read existing keys
-                    if let Some(existing_keys_bytes) = txn.get(index_key.clone()).await? {
-                        let mut keys: Vec<#key_type> = ::ergokv::ciborium::de::from_reader(existing_keys_bytes.as_slice())
-                            .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode keys: {}", e)))?;
+/// Whether the values of the field are stored behind a format tag, i.e. in bincode or rkyv.
+fn is_tagged_format(field: &Field) -> bool {
+    let field_options = FieldOptions::from_field(field);
+    field_options.format != Format::Cbor
+        && !field_options.raw_bytes
+        && !field_options.flatten
+        && field_options.compute.is_none()
+}
 
-                        // Remove current key
-                        keys.retain(|k| k != &self.#key_ident);
+/// Whether the field is marked `#[store(compute = "...")]`, i.e. computed on load instead of stored.
+fn is_computed(field: &Field) -> bool {
+    FieldOptions::from_field(field).compute.is_some()
+}
 
-                        // If keys is empty, delete the index entry
-                        if keys.is_empty() {
-                            txn.delete(index_key).await?;
-                        } else {
-                            // Otherwise, update the keys
-                            let mut value = Vec::new();
-                            ::ergokv::ciborium::ser::into_writer(&keys, &mut value)
-                                .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode keys: {}", e)))?;
-                            txn.put(index_key, value).await?;
-                        }
-                    }
-                }
-            }
-        });
+/// Returns the type of the named field, if the struct has one.
+fn field_type<'a>(
+    fields: &'a Punctuated<Field, Comma>,
+    name: &str,
+) -> Option<&'a syn::Type> {
+    fields
+        .iter()
+        .find(|f| f.ident.as_ref().is_some_and(|i| i == name))
+        .map(|f| &f.ty)
+}
 
-    quote! {
-        pub async fn delete(&self, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
-            #checks
+/// Options given to `#[store(...)]` on a field.
+#[derive(Default)]
+struct FieldOptions {
+    /// `#[store(raw_bytes)]`, store the field as its bytes, without CBOR
+    raw_bytes: bool,
+    /// `#[store(encrypt)]`, encrypt the stored bytes with the installed `ergokv::Encryptor`
+    encrypt: bool,
+    /// `#[store(flatten)]`, store each sub-field of the value under a key of its own
+    flatten: bool,
+    /// `#[store(immutable)]`, the field keeps the value it was created with
+    immutable: bool,
+    /// `#[store(compute = "...")]`, the function computing the field on load instead of storing it
+    compute: Option<syn::Path>,
+    /// `#[store(format = "...")]`, the format the field is stored in
+    format: Format,
+    /// `#[store(compression_threshold = N)]`, compress stored values larger than this many bytes
+    compression_threshold: Option<usize>,
+    /// `#[store(max_value_size = N)]`, reject stored values larger than this many bytes
+    max_value_size: Option<usize>,
+}
 
-            // Remove from master trie
-            let trie = ::ergokv::PrefixTrie::new("ergokv:__trie");
-            trie.remove(txn, &format!(
-                "{}:{}",
-                Self::MODEL_NAME,
-                ::ergokv::serde_json::to_string(&self.#key_ident)
-                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode struct key: {}", e)))?,
-            )).await?;
+impl FieldOptions {
+    fn from_field(field: &Field) -> Self {
+        let mut options = Self::default();
 
-            #(#field_deletes)*
-            #(#index_deletes)*
-            Ok(())
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|a| a.path().is_ident("store"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("raw_bytes") {
+                    options.raw_bytes = true;
+                    Ok(())
+                } else if meta.path.is_ident("encrypt") {
+                    options.encrypt = true;
+                    Ok(())
+                } else if meta.path.is_ident("flatten") {
+                    options.flatten = true;
+                    Ok(())
+                } else if meta.path.is_ident("immutable") {
+                    options.immutable = true;
+                    Ok(())
+                } else if meta.path.is_ident("compute") {
+                    let path: syn::LitStr =
+                        meta.value()?.parse()?;
+                    options.compute = Some(path.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("format") {
+                    options.format = parse_format(&meta)?;
+                    Ok(())
+                } else if meta
+                    .path
+                    .is_ident("compression_threshold")
+                {
+                    let threshold: syn::LitInt =
+                        meta.value()?.parse()?;
+                    options.compression_threshold =
+                        Some(threshold.base10_parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("max_value_size") {
+                    let max: syn::LitInt =
+                        meta.value()?.parse()?;
+                    options.max_value_size =
+                        Some(max.base10_parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error(
+                        "unknown store option for a field",
+                    ))
+                }
+            })
+            .unwrap_or_else(|e| {
+                panic!("Invalid #[store] attribute: {e}")
+            });
         }
+
+        options
     }
 }
 
-fn generate_index_methods(
+/// Generates an expression writing the CBOR (or bincode) encoding of `value` (a reference
+/// to the field's value) into `value`, honoring `#[serde(with)]` and `#[serde(serialize_with)]`,
+/// so that the stored value matches the struct's own serialization.
+fn encode_field_value(
+    field: &Field,
+    value: TokenStream2,
+) -> TokenStream2 {
+    let field_options = FieldOptions::from_field(field);
+    let mut encode = encode_plain_field_value(field, value);
+    if let Some(threshold) = field_options.compression_threshold
+    {
+        encode = quote! {
+            (#encode).map(|()| {
+                value = ::ergokv::compress_field(&value, #threshold);
+            })
+        };
+    }
+    if !field_options.encrypt {
+        return encode;
+    }
+
+    quote! {
+        (match #encode {
+            Ok(()) => match ::ergokv::encrypt_field(&value) {
+                Ok(ciphertext) => {
+                    value = ciphertext;
+                    Ok(())
+                }
+                Err(e) => Err(e.to_string()),
+            },
+            Err(e) => Err(e.to_string()),
+        })
+    }
+}
+
+/// Generates an expression encoding `value` (a reference to a new value of `field`) into the
+/// key and bytes written for it in the record at `record_path`, which fails if the bytes are
+/// more than the field's `#[store(max_value_size = N)]`. Flattened fields, whose sub-fields
+/// `write_flattened` writes, get none. Callers encode every new value before writing anything,
+/// so that an oversized one leaves the transaction untouched.
+fn encode_field_write(
+    field: &Field,
+    record_path: TokenStream2,
+    value: TokenStream2,
+) -> Option<TokenStream2> {
+    let field_options = FieldOptions::from_field(field);
+    if field_options.flatten {
+        return None;
+    }
+
+    let field_name = &field.ident;
+    let encode = encode_field_value(field, value);
+    let size_check = field_options.max_value_size.map(|max| {
+        quote! {
+            if value.len() > #max {
+                return Err(::ergokv::value_too_large(Self::MODEL_NAME, stringify!(#field_name), value.len(), #max));
+            }
+        }
+    });
+
+    Some(quote! {
+        {
+            let key = format!(
+                "ergokv:{}:{}",
+                #record_path,
+                stringify!(#field_name)
+            );
+            let mut value = Vec::new();
+            #encode
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {}.{} at {}: {}", Self::MODEL_NAME, stringify!(#field_name), key, e)))?;
+            #size_check
+            (key, value)
+        }
+    })
+}
+
+/// Like [`encode_field_value`], but without compression and encryption.
+fn encode_plain_field_value(
+    field: &Field,
+    value: TokenStream2,
+) -> TokenStream2 {
+    let field_type = &field.ty;
+    if FieldOptions::from_field(field).raw_bytes {
+        return quote! {
+            {
+                value.extend_from_slice(::std::convert::AsRef::<[u8]>::as_ref(#value));
+                Ok::<(), ::std::convert::Infallible>(())
+            }
+        };
+    }
+
+    let serialize = match serde_field_paths(field) {
+        (_, Some(path), _) => path,
+        (Some(mut path), _, _) => {
+            path.segments
+                .push(format_ident!("serialize").into());
+            path
+        }
+        _ => return write_value(field, value),
+    };
+    let write = write_value(field, quote! { &Wrapper(#value) });
+
+    quote! {
+        {
+            struct Wrapper<'a>(&'a #field_type);
+
+            impl ::ergokv::serde::Serialize for Wrapper<'_> {
+                fn serialize<S: ::ergokv::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    #serialize(self.0, serializer)
+                }
+            }
+
+            #write
+        }
+    }
+}
+
+/// Generates an expression decoding the field's value from the CBOR (or bincode) bytes in
+/// `value`, honoring `#[serde(with)]` and `#[serde(deserialize_with)]`.
+fn decode_field_value(field: &Field) -> TokenStream2 {
+    let field_options = FieldOptions::from_field(field);
+    let mut decode = decode_plain_field_value(field);
+    if field_options.compression_threshold.is_some() {
+        decode = quote! {
+            (match ::ergokv::decompress_field(&value) {
+                Ok(value) => (#decode).map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            })
+        };
+    }
+    if !field_options.encrypt {
+        return decode;
+    }
+
+    quote! {
+        (match ::ergokv::decrypt_field(&value) {
+            Ok(value) => (#decode).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        })
+    }
+}
+
+/// Like [`decode_field_value`], but without decryption and decompression.
+fn decode_plain_field_value(field: &Field) -> TokenStream2 {
+    let field_type = &field.ty;
+    if FieldOptions::from_field(field).raw_bytes {
+        return quote! {
+            Ok::<#field_type, ::std::convert::Infallible>(<#field_type>::from(value.to_vec()))
+        };
+    }
+
+    let deserialize = match serde_field_paths(field) {
+        (_, _, Some(path)) => path,
+        (Some(mut path), _, _) => {
+            path.segments
+                .push(format_ident!("deserialize").into());
+            path
+        }
+        _ => return read_value(field, quote! { #field_type }),
+    };
+    let read = read_value(field, quote! { Wrapper });
+
+    quote! {
+        {
+            struct Wrapper(#field_type);
+
+            impl<'de> ::ergokv::serde::Deserialize<'de> for Wrapper {
+                fn deserialize<D: ::ergokv::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    #deserialize(deserializer).map(Wrapper)
+                }
+            }
+
+            #read.map(|w| w.0)
+        }
+    }
+}
+
+/// Generates an expression serializing `value` into the bytes in `value`, in the format
+/// of `field`.
+fn write_value(
+    field: &Field,
+    value: TokenStream2,
+) -> TokenStream2 {
+    match FieldOptions::from_field(field).format {
+        Format::Cbor => {
+            quote! { ::ergokv::ciborium::ser::into_writer(#value, &mut value) }
+        }
+        Format::Bincode => quote! {
+            {
+                value.push(::ergokv::BINCODE_TAG);
+                ::ergokv::bincode::serialize_into(&mut value, #value)
+            }
+        },
+        Format::Rkyv => quote! {
+            ::ergokv::rkyv::to_bytes::<::ergokv::rkyv::rancor::Error>(#value).map(|bytes| {
+                value.push(::ergokv::RKYV_TAG);
+                value.extend_from_slice(&bytes);
+            })
+        },
+    }
+}
+
+/// Generates an expression deserializing a `ty` from the bytes in `value`, in the format
+/// of `field`.
+///
+/// Bincode and rkyv fields read every value in the format its tag names, so that values
+/// stored as CBOR before the field moved to its format can still be read, and rkyv fields
+/// also read values stored as bincode.
+fn read_value(field: &Field, ty: TokenStream2) -> TokenStream2 {
+    let format = FieldOptions::from_field(field).format;
+    let format_name = format.name();
+    if format == Format::Cbor {
+        return quote! { ::ergokv::ciborium::de::from_reader::<#ty, _>(value.as_slice()) };
+    }
+
+    let bincode = quote! {
+        (::ergokv::StoredFormat::Bincode, bytes) => {
+            use ::ergokv::bincode::Options as _;
+            ::ergokv::bincode::DefaultOptions::new()
+                .with_fixint_encoding()
+                .reject_trailing_bytes()
+                .deserialize::<#ty>(bytes)
+                .map_err(|e| e.to_string())
+        }
+    };
+    // Archived values have to be aligned, which the bytes read from TiKV are not
+    let rkyv = match format {
+        Format::Rkyv => quote! {
+            (::ergokv::StoredFormat::Rkyv, bytes) => {
+                let mut aligned = ::ergokv::rkyv::util::AlignedVec::<16>::new();
+                aligned.extend_from_slice(bytes);
+                ::ergokv::rkyv::from_bytes::<#ty, ::ergokv::rkyv::rancor::Error>(&aligned)
+                    .map_err(|e| e.to_string())
+            }
+        },
+        _ => quote! {
+            (::ergokv::StoredFormat::Rkyv, _) => Err(format!("Stored as rkyv, which a {} field cannot read", #format_name)),
+        },
+    };
+
+    quote! {
+        (match ::ergokv::split_format_tag(value.as_slice()) {
+            #bincode
+            #rkyv
+            (::ergokv::StoredFormat::Cbor, bytes) => {
+                ::ergokv::ciborium::de::from_reader::<#ty, _>(bytes).map_err(|e| e.to_string())
+            }
+        })
+    }
+}
+
+/// Generates a statement reading the stored value of `field`, for the record at
+/// `record_path`, from `txn` into a new variable named `target`.
+fn read_field_value(
+    field: &Field,
+    record_path: TokenStream2,
+    target: TokenStream2,
+) -> TokenStream2 {
+    let field_name = &field.ident;
+    let field_type = &field.ty;
+
+    if FieldOptions::from_field(field).flatten {
+        return quote! {
+            let #target: #field_type = {
+                let prefix = format!("ergokv:{}:{}.", #record_path, stringify!(#field_name));
+                let end = format!("ergokv:{}:{}/", #record_path, stringify!(#field_name));
+
+                let mut entries = Vec::new();
+                for pair in txn.scan(prefix.clone()..end, u32::MAX).await? {
+                    let (key, value): (tikv_client::Key, tikv_client::Value) = pair.into();
+                    let key: Vec<u8> = key.into();
+                    entries.push((String::from_utf8_lossy(&key[prefix.len()..]).into_owned(), value));
+                }
+                ::ergokv::unflatten_fields(entries)
+                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {}.{} at {}: {}", Self::MODEL_NAME, stringify!(#field_name), prefix, e)))?
+            };
+        };
+    }
+
+    let decode = decode_field_value(field);
+    quote! {
+        let #target: #field_type = {
+            let key = format!(
+                "ergokv:{}:{}",
+                #record_path,
+                stringify!(#field_name)
+            );
+            let value = txn.get(key.clone()).await?
+                .ok_or_else(|| tikv_client::Error::StringError(format!("No {}.{} stored at {}", Self::MODEL_NAME, stringify!(#field_name), key)))?;
+            #decode
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {}.{} at {}: {}", Self::MODEL_NAME, stringify!(#field_name), key, e)))?
+        };
+    }
+}
+
+/// Generates statements decrypting and decompressing the stored bytes of `field`, read
+/// from the TiKV key `key` into `value`, into the bytes of its encoded value.
+fn plaintext_field_value(field: &Field) -> TokenStream2 {
+    let field_name = &field.ident;
+    let field_options = FieldOptions::from_field(field);
+
+    let decrypt = field_options.encrypt.then(|| quote! {
+        let value = ::ergokv::decrypt_field(&value)
+            .map_err(|e| tikv_client::Error::StringError(format!("Failed to decrypt {}.{} at {}: {}", Self::MODEL_NAME, stringify!(#field_name), key, e)))?;
+    });
+    let decompress = field_options.compression_threshold.is_some().then(|| quote! {
+        let value = ::ergokv::decompress_field(&value)
+            .map_err(|e| tikv_client::Error::StringError(format!("Failed to decompress {}.{} at {}: {}", Self::MODEL_NAME, stringify!(#field_name), key, e)))?;
+    });
+
+    quote! {
+        #decrypt
+        #decompress
+    }
+}
+
+/// Generates an expression reading the stored value of `field`, for the record at
+/// `record_path`, from `txn` as an `Option`, which is `None` if the value is missing
+/// or cannot be decoded. Flattened fields are not supported.
+fn read_stored_field_value(
+    field: &Field,
+    record_path: TokenStream2,
+) -> TokenStream2 {
+    let field_name = &field.ident;
+    let decode = decode_field_value(field);
+
+    quote! {
+        txn.get(format!("ergokv:{}:{}", #record_path, stringify!(#field_name)))
+            .await?
+            .and_then(|value| (#decode).ok())
+    }
+}
+
+/// Generates statements writing the value of `field` in `self` to `txn`, for the
+/// record at `record_path`.
+fn write_field_value(
+    field: &Field,
+    record_path: TokenStream2,
+) -> TokenStream2 {
+    let field_name = &field.ident;
+
+    match encode_field_write(
+        field,
+        record_path.clone(),
+        quote! { &self.#field_name },
+    ) {
+        Some(encoded) => quote! {
+            let (key, value) = #encoded;
+            txn.put(key, value).await?;
+        },
+        None => quote! {
+            Self::write_flattened(
+                format!("ergokv:{}:{}.", #record_path, stringify!(#field_name)),
+                ::ergokv::flatten_fields(&self.#field_name)?,
+                txn,
+            ).await?;
+        },
+    }
+}
+
+/// Generates `write_flattened` for models with `#[store(flatten)]` fields.
+fn generate_flatten_methods(
+    fields: &Punctuated<Field, Comma>,
+) -> Option<TokenStream2> {
+    if !fields
+        .iter()
+        .any(|f| FieldOptions::from_field(f).flatten)
+    {
+        return None;
+    }
+
+    Some(quote! {
+        /// Writes the sub-fields of a `#[store(flatten)]` field under `prefix`.
+        ///
+        /// Sub-fields whose stored value is unchanged are not written again, and stored
+        /// sub-fields missing from `entries` are deleted.
+        async fn write_flattened(prefix: String, entries: Vec<(String, Vec<u8>)>, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            let mut end = prefix.clone();
+            end.pop();
+            end.push('/');
+
+            let mut stored: std::collections::HashMap<Vec<u8>, Vec<u8>> = txn
+                .scan(prefix.clone()..end, u32::MAX)
+                .await?
+                .map(|pair| {
+                    let (key, value): (tikv_client::Key, tikv_client::Value) = pair.into();
+                    (key.into(), value)
+                })
+                .collect();
+
+            for (sub_field, value) in entries {
+                let key = format!("{}{}", prefix, sub_field);
+                if stored.remove(key.as_bytes()).as_ref() != Some(&value) {
+                    txn.put(key, value).await?;
+                }
+            }
+            for key in stored.into_keys() {
+                txn.delete(key).await?;
+            }
+
+            Ok(())
+        }
+    })
+}
+
+/// Generates the statements `load` and `load_many` start with for models with
+/// `#[store(lazy_migration)]`, which migrate instances from `prev_type` when they are read.
+fn generate_lazy_migration(
     name: &Ident,
+    prev_type: &syn::Path,
+) -> (TokenStream2, TokenStream2) {
+    let migration_name = migration_name(name, prev_type);
+    let prev_name = &prev_type.segments.last().unwrap().ident;
+    let trait_name = format_ident!("{}To{}", prev_name, name);
+    let method_name = format_ident!(
+        "from_{}",
+        prev_name.to_string().to_lowercase()
+    );
+
+    let load = quote! {
+        // Instances not stamped with this version are migrated as they are loaded
+        if Self::schema_version_of(key, txn).await?.as_deref() != Some(#migration_name) {
+            let prev = <#prev_type>::load(key, txn).await?;
+            let migrated = <Self as #trait_name>::#method_name(&prev)?;
+            prev.remove_index_entries(txn).await?;
+            migrated.save_unchecked(txn).await?;
+            return Ok(migrated);
+        }
+    };
+    let load_many = quote! {
+        // A single instance that needs migrating makes every instance load on its own
+        let mut schema_keys = Vec::with_capacity(keys.len());
+        for key in keys {
+            schema_keys.push(format!("ergokv:{}:__schema", Self::record_path(key)?));
+        }
+        let current = txn
+            .batch_get(schema_keys)
+            .await?
+            .filter(|pair| {
+                ::ergokv::ciborium::de::from_reader::<String, _>(pair.value().as_slice())
+                    .is_ok_and(|version| version == #migration_name)
+            })
+            .count();
+        if current < keys.len() {
+            let mut records = Vec::with_capacity(keys.len());
+            for key in keys {
+                records.push(Self::load(key, txn).await?);
+            }
+            return Ok(records);
+        }
+    };
+
+    (load, load_many)
+}
+
+/// Generates code writing `record`, as just loaded from the record at `key`, again if
+/// one of its values is stored in another format than its field's, for models with
+/// `#[store(rewrite_on_load)]`.
+fn generate_rewrite_on_load(
     fields: &Punctuated<Field, Comma>,
-) -> Vec<TokenStream2> {
+    options: &StoreOptions,
+) -> Option<TokenStream2> {
+    if !options.rewrite_on_load {
+        return None;
+    }
+
+    let format_checks = fields.iter().filter(|f| is_tagged_format(f)).map(|f| {
+        let field_name = &f.ident;
+        let plaintext = plaintext_field_value(f);
+        let format = match FieldOptions::from_field(f).format {
+            Format::Bincode => quote! { Bincode },
+            _ => quote! { Rkyv },
+        };
+        quote! {
+            {
+                let key = format!("ergokv:{}:{}", Self::record_path(key)?, stringify!(#field_name));
+                if let Some(value) = txn.get(key.clone()).await? {
+                    #plaintext
+                    rewrite |= ::ergokv::split_format_tag(&value).0 != ::ergokv::StoredFormat::#format;
+                }
+            }
+        }
+    });
+
+    Some(quote! {
+        let mut rewrite = false;
+        #(#format_checks)*
+        if rewrite {
+            record.save_unchecked(txn).await?;
+        }
+    })
+}
+
+fn generate_load_method(
+    fields: &Punctuated<Field, Comma>,
+    options: &StoreOptions,
+    lazy_migration: Option<&(TokenStream2, TokenStream2)>,
+) -> TokenStream2 {
+    let (lazy_load, lazy_load_many) = match lazy_migration {
+        Some((load, load_many)) => {
+            (load.clone(), load_many.clone())
+        }
+        None => (quote! {}, quote! {}),
+    };
     let key_field = fields
         .iter()
         .find(|f| {
@@ -384,160 +1615,4269 @@ fn generate_index_methods(
         .expect("A field with #[key] attribute is required");
     let key_type = &key_field.ty;
 
-    fields.iter()
-        .filter(|f| f.attrs.iter().any(|a| a.path().is_ident("unique_index") || a.path().is_ident("index")))
+    let field_loads = fields
+        .iter()
+        .filter(|f| !is_computed(f))
         .map(|f| {
             let field_name = &f.ident;
-            let field_type = &f.ty;
-            let method_name = format_ident!("by_{}", field_name.clone().expect("Missing field name"));
-            let is_unique = f.attrs.iter().any(|a| a.path().is_ident("unique_index"));
+            read_field_value(
+                f,
+                quote! { Self::record_path(key)? },
+                quote! { #field_name },
+            )
+        })
+        .collect::<Vec<_>>();
 
-            if is_unique {
-                quote! {
-                    #[doc = concat!("Find a ", stringify!(#name), " by its ", stringify!(#field_name), " field.")]
-                    #[doc = ""]
-                    #[doc = concat!("This method uses the unique index on the ", stringify!(#field_name), " field to efficiently retrieve the object.")]
-                    pub async fn #method_name<T: Into<#field_type>>(value: T, client: &mut tikv_client::Transaction) -> Result<Option<Self>, tikv_client::Error> {
-                        let index_key = format!(
-                            "ergokv:{}:unique_index:{}:{}",
-                            Self::MODEL_NAME,
-                            stringify!(#field_name),
-                            ::ergokv::serde_json::to_string(&value.into())
-                                .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode struct value: {e}")))?
-                        );
-                        if let Some(key_bytes) = client.get(index_key).await? {
-                            let key = ::ergokv::ciborium::de::from_reader(key_bytes.as_slice())
-                                .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode key: {}", e)))?;
+    let construct = generate_construct(fields, |f| {
+        let field_name = &f.ident;
+        quote! { #field_name }
+    });
+    let checksum_verify =
+        options.checksum.is_some().then(|| {
+            quote! {
+                Self::verify_checksum(key, txn).await?;
+            }
+        });
+    let rewrite = generate_rewrite_on_load(fields, options);
+    // TODO: Add #[store(validate_on_load)], running the #[validate] checks on every loaded instance, once #[validate] exists
 
-                            Self::load(&key, client).await.map(Some)
-                        } else {
-                            Ok(None)
-                        }
+    let body = if options.cache_ttl.is_some() {
+        quote! {
+            let cache_key = Self::encode_key(key)?;
+            if let Some(cached) = Self::read_cache().get(&cache_key, tikv_client::TimestampExt::version(&txn.start_timestamp())) {
+                return Ok(cached);
+            }
+
+            #lazy_load
+            #(#field_loads)*
+            #checksum_verify
+            let record = #construct;
+            #rewrite
+            Self::read_cache().insert(cache_key, record.clone(), tikv_client::TimestampExt::version(&txn.start_timestamp()));
+            Ok(record)
+        }
+    } else {
+        quote! {
+            #lazy_load
+            #(#field_loads)*
+            #checksum_verify
+            let record = #construct;
+            #rewrite
+            Ok(record)
+        }
+    };
+    let body = instrument("load", quote! { Self }, body);
+
+    // Flattened fields span a range of keys, which a batch get cannot read
+    let batch_body = if fields
+        .iter()
+        .any(|f| FieldOptions::from_field(f).flatten)
+    {
+        quote! {
+            let mut records = Vec::with_capacity(keys.len());
+            for key in keys {
+                records.push(Self::load(key, txn).await?);
+            }
+            Ok(records)
+        }
+    } else {
+        let field_decodes = fields.iter().filter(|f| !is_computed(f)).map(|f| {
+            let field_name = &f.ident;
+            let field_type = &f.ty;
+            let decode = decode_field_value(f);
+            quote! {
+                let #field_name: #field_type = {
+                    let key = format!(
+                        "ergokv:{}:{}",
+                        Self::record_path(key)?,
+                        stringify!(#field_name)
+                    );
+                    let value = values.get(key.as_bytes())
+                        .ok_or_else(|| tikv_client::Error::StringError(format!("No {}.{} stored at {}", Self::MODEL_NAME, stringify!(#field_name), key)))?;
+                    #decode
+                        .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {}.{} at {}: {}", Self::MODEL_NAME, stringify!(#field_name), key, e)))?
+                };
+            }
+        });
+        let field_keys = fields.iter().filter(|f| !is_computed(f)).map(|f| {
+            let field_name = &f.ident;
+            quote! {
+                field_keys.push(format!("ergokv:{}:{}", Self::record_path(key)?, stringify!(#field_name)));
+            }
+        });
+        let (cache_lookup, cache_insert) = if options
+            .cache_ttl
+            .is_some()
+        {
+            (
+                quote! {
+                    for (key, record) in keys.iter().zip(records.iter_mut()) {
+                        *record = Self::read_cache().get(&Self::encode_key(key)?, tikv_client::TimestampExt::version(&txn.start_timestamp()));
                     }
+                },
+                quote! {
+                    Self::read_cache().insert(Self::encode_key(key)?, loaded.clone(), tikv_client::TimestampExt::version(&txn.start_timestamp()));
+                },
+            )
+        } else {
+            (quote! {}, quote! {})
+        };
+
+        quote! {
+            let mut records: Vec<Option<Self>> = keys.iter().map(|_| None).collect();
+
+            #cache_lookup
+
+            let mut field_keys = Vec::new();
+            for (key, record) in keys.iter().zip(&records) {
+                if record.is_none() {
+                    #(#field_keys)*
                 }
+            }
+
+            let values: ::std::collections::HashMap<Vec<u8>, Vec<u8>> = if field_keys.is_empty() {
+                ::std::collections::HashMap::new()
             } else {
-                quote! {
-                    #[doc = concat!("Find all ", stringify!(#name), " by its ", stringify!(#field_name), " field.")]
-                    #[doc = ""]
-                    #[doc = concat!("This method uses the index on the ", stringify!(#field_name), " field to efficiently retrieve multiple objects.")]
-                    pub async fn #method_name<T: Into<#field_type>>(value: T, client: &mut tikv_client::Transaction) -> Result<Vec<Self>, tikv_client::Error> {
-                        let index_key = format!(
-                            "ergokv:{}:index:{}:{}",
-                            Self::MODEL_NAME,
-                            stringify!(#field_name),
-                            ::ergokv::serde_json::to_string(&value.into())
-                                .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode struct value: {e}")))?
-                        );
-                        if let Some(keys_bytes) = client.get(index_key).await? {
-                            let keys: Vec<#key_type> = ::ergokv::ciborium::de::from_reader(keys_bytes.as_slice())
-                                .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode keys: {}", e)))?;
+                txn.batch_get(field_keys)
+                    .await?
+                    .map(|pair| {
+                        let (key, value): (tikv_client::Key, tikv_client::Value) = pair.into();
+                        (key.into(), value)
+                    })
+                    .collect()
+            };
 
-                            let mut results = Vec::new();
-                            for key in keys {
-                                results.push(Self::load(&key, client).await?);
+            for (key, record) in keys.iter().zip(records.iter_mut()) {
+                if record.is_some() {
+                    continue;
+                }
+                #(#field_decodes)*
+                #checksum_verify
+                let loaded = #construct;
+                #cache_insert
+                *record = Some(loaded);
+            }
+
+            Ok(records.into_iter().flatten().collect())
+        }
+    };
+    let batch_body = instrument(
+        "load_many",
+        quote! { Vec<Self> },
+        quote! {
+            #lazy_load_many
+            #batch_body
+        },
+    );
+
+    let key_ident = &key_field.ident;
+    let load_or_default = options.load_or_default.then(|| {
+        quote! {
+            /// Loads an instance, or returns `Default::default()` with the given key if
+            /// there is none.
+            ///
+            /// The default instance is not saved.
+            pub async fn load_or_default(key: &#key_type, txn: &mut tikv_client::Transaction) -> Result<Self, tikv_client::Error> {
+                if Self::key_exists(key, txn).await? {
+                    Self::load(key, txn).await
+                } else {
+                    Ok(Self {
+                        #key_ident: key.clone(),
+                        ..::core::default::Default::default()
+                    })
+                }
+            }
+        }
+    });
+
+    quote! {
+        #load_or_default
+
+        pub async fn load(key: &#key_type, txn: &mut tikv_client::Transaction) -> Result<Self, tikv_client::Error> {
+            let loaded: Result<Self, tikv_client::Error> = async { #body }.await;
+
+            // Reserved keys have nothing to load yet, which deserves a clearer error
+            match loaded {
+                Err(_) if Self::is_pending(key, txn).await? => Err(tikv_client::Error::StringError(format!(
+                    "{} is reserved, but its instance is not completed yet",
+                    Self::record_path(key)?
+                ))),
+                loaded => loaded,
+            }
+        }
+
+        /// Loads the instances with the given keys, in the order of `keys`.
+        ///
+        /// The fields of all instances are read with a single batch get instead of one
+        /// read per field and instance. Fails like [`load`](Self::load) if any instance
+        /// is missing.
+        pub async fn load_many(keys: &[#key_type], txn: &mut tikv_client::Transaction) -> Result<Vec<Self>, tikv_client::Error> {
+            #batch_body
+        }
+
+        /// Loads an instance as it was at the given TiKV timestamp.
+        ///
+        /// The read goes to a read-only snapshot, which sees exactly the writes committed
+        /// before `timestamp`. Reads sharing a timestamp therefore observe one consistent
+        /// state of the whole database, even across models and while writes continue.
+        /// Obtain a timestamp with `TransactionClient::current_timestamp`, or take the one
+        /// returned by `Transaction::commit`. Timestamps older than TiKV's garbage collection
+        /// safe point (10 minutes by default) can no longer be read.
+        ///
+        /// The read cache is never consulted.
+        pub async fn load_at(key: &#key_type, client: &tikv_client::TransactionClient, timestamp: tikv_client::Timestamp) -> Result<Self, tikv_client::Error> {
+            let mut snapshot = client.snapshot(timestamp, tikv_client::TransactionOptions::new_optimistic().read_only());
+            Self::load_snapshot(key, &mut snapshot).await
+        }
+
+        /// Loads the instances with the given keys as they were at the given TiKV timestamp,
+        /// with up to `concurrency` loads in flight at once.
+        ///
+        /// Every load reads from a snapshot of its own, so the loads do not wait for one
+        /// another, while all of them see the same state, see [`load_at`](Self::load_at).
+        /// Instances are returned in the order of `keys`.
+        pub async fn load_many_at(keys: &[#key_type], client: &tikv_client::TransactionClient, timestamp: tikv_client::Timestamp, concurrency: usize) -> Result<Vec<Self>, tikv_client::Error> {
+            use ::ergokv::futures::{StreamExt, TryStreamExt};
+
+            ::ergokv::futures::stream::iter(keys)
+                .map(|key| Self::load_at(key, client, timestamp.clone()))
+                .buffered(concurrency.max(1))
+                .try_collect()
+                .await
+        }
+
+        /// Loads an instance from a read-only snapshot, e.g. one created with
+        /// `TransactionClient::snapshot`.
+        ///
+        /// Snapshots take no locks and need no commit, which makes them cheaper than a
+        /// transaction for code that only reads. Reads see the state at the timestamp of
+        /// the snapshot, see [`load_at`](Self::load_at). The read cache is never consulted.
+        pub async fn load_snapshot(key: &#key_type, txn: &mut tikv_client::Snapshot) -> Result<Self, tikv_client::Error> {
+            #(#field_loads)*
+            Ok(#construct)
+        }
+    }
+}
+
+/// Generates an expression creating an instance from the stored fields, given by `init`,
+/// and filling in the `#[store(compute)]` fields afterwards.
+///
+/// Computed fields hold `Default::default()` while their functions run, which see the
+/// instance with all stored fields and the computed fields before them set.
+fn generate_construct(
+    fields: &Punctuated<Field, Comma>,
+    init: impl Fn(&Field) -> TokenStream2,
+) -> TokenStream2 {
+    let stored_inits =
+        fields.iter().filter(|f| !is_computed(f)).map(|f| {
+            let field_name = &f.ident;
+            let value = init(f);
+            quote! { #field_name: #value }
+        });
+    let computed = fields
+        .iter()
+        .filter_map(|f| {
+            Some((
+                &f.ident,
+                FieldOptions::from_field(f).compute?,
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    if computed.is_empty() {
+        return quote! {
+            Self {
+                #(#stored_inits,)*
+            }
+        };
+    }
+
+    let computed_inits = computed.iter().map(|(field_name, _)| {
+        quote! { #field_name: ::core::default::Default::default() }
+    });
+    let computations =
+        computed.iter().map(|(field_name, path)| {
+            quote! { record.#field_name = #path(&record); }
+        });
+
+    quote! {
+        {
+            let mut record = Self {
+                #(#stored_inits,)*
+                #(#computed_inits,)*
+            };
+            #(#computations)*
+            record
+        }
+    }
+}
+
+/// Generates the whole implementation of a `#[store(singleton)]` model, whose one instance
+/// is stored like any other, under the fixed record path `{MODEL}:__singleton`.
+fn generate_singleton(
+    name: &Ident,
+    fields: &Punctuated<Field, Comma>,
+    options: &StoreOptions,
+) -> TokenStream2 {
+    if fields.iter().any(|f| {
+        f.attrs.iter().any(|a| a.path().is_ident("key"))
+            || index_kind(f).is_some()
+    }) {
+        panic!("#[store(singleton)] models cannot have #[key] or indexed fields");
+    }
+    if options.checksum.is_some() {
+        panic!("#[store(singleton)] cannot be combined with checksum");
+    }
+
+    let stored_fields =
+        fields.iter().filter(|f| !is_computed(f));
+    let field_loads = stored_fields.clone().map(|f| {
+        let field_name = &f.ident;
+        read_field_value(
+            f,
+            quote! { path },
+            quote! { #field_name },
+        )
+    });
+    let field_encodings =
+        stored_fields.clone().filter_map(|f| {
+            let field_name = &f.ident;
+            encode_field_write(
+                f,
+                quote! { path },
+                quote! { &self.#field_name },
+            )
+        });
+    let flattened_writes = stored_fields
+        .filter(|f| FieldOptions::from_field(f).flatten)
+        .map(|f| write_field_value(f, quote! { path }));
+    let construct = generate_construct(fields, |f| {
+        let field_name = &f.ident;
+        quote! { #field_name }
+    });
+    let flatten_methods = generate_flatten_methods(fields);
+    let reflection = generate_reflection(name, fields);
+    let model_name = options.model_name(name);
+
+    quote! {
+        #reflection
+
+        impl #name {
+            const MODEL_NAME: &'static str = #model_name;
+
+            /// Returns the record path of the one instance of this model.
+            fn singleton_path() -> String {
+                format!("{}:__singleton", Self::MODEL_NAME)
+            }
+
+            /// Loads the instance of this model, or returns `None` if none was [`set`](Self::set).
+            pub async fn get(txn: &mut tikv_client::Transaction) -> Result<Option<Self>, tikv_client::Error> {
+                let path = Self::singleton_path();
+                if txn.get(format!("ergokv:{}", path)).await?.is_none() {
+                    return Ok(None);
+                }
+
+                #(#field_loads)*
+                Ok(Some(#construct))
+            }
+
+            /// Stores the instance as the one instance of this model, replacing the previous one.
+            ///
+            /// Next to its fields, the time it was set is stored under `ergokv:{MODEL}:__singleton`,
+            /// which marks the instance as present.
+            pub async fn set(&self, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+                let path = Self::singleton_path();
+                let encoded: Vec<(String, Vec<u8>)> = vec![#(#field_encodings),*];
+                for (key, value) in encoded {
+                    txn.put(key, value).await?;
+                }
+                #(#flattened_writes)*
+
+                let mut value = Vec::new();
+                ::ergokv::ciborium::ser::into_writer(&::std::time::SystemTime::now(), &mut value)
+                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {} marker: {}", Self::MODEL_NAME, e)))?;
+                txn.put(format!("ergokv:{}", path), value).await?;
+                Ok(())
+            }
+
+            /// Removes the instance of this model, after which [`get`](Self::get) returns `None`.
+            pub async fn clear(txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+                let path = Self::singleton_path();
+                let start = format!("ergokv:{}:", path);
+                let end = format!("ergokv:{};", path);
+
+                let keys: Vec<tikv_client::Key> = txn.scan_keys(start..end, u32::MAX).await?.collect();
+                for key in keys {
+                    txn.delete(key).await?;
+                }
+                txn.delete(format!("ergokv:{}", path)).await?;
+                Ok(())
+            }
+
+            #flatten_methods
+        }
+    }
+}
+
+fn generate_save_method(
+    fields: &Punctuated<Field, Comma>,
+    options: &StoreOptions,
+    schema_version: Option<&str>,
+) -> TokenStream2 {
+    let key_field = fields
+        .iter()
+        .find(|f| {
+            f.attrs.iter().any(|a| a.path().is_ident("key"))
+        })
+        .expect("A field with #[key] attribute is required");
+    let key_ident = &key_field.ident;
+    let checks = generate_mutation_checks(options);
+    let audit =
+        generate_audit_append(options, "Save", fields.iter());
+    let outbox = generate_outbox_append(options, "Save");
+    let timeline = options.timeline.then(|| {
+        quote! {
+            ::ergokv::append_timeline(txn, Self::MODEL_NAME, &Self::encode_key(&self.#key_ident)?).await?;
+        }
+    });
+    let invalidate =
+        generate_cache_invalidation(options, key_field);
+    let trie_insert = options.uses_trie().then(|| {
+        quote! {
+            // Add to master trie
+            let trie = ::ergokv::PrefixTrie::new("ergokv:__trie");
+            trie.insert(txn, &Self::record_path(&self.#key_ident)?).await?;
+        }
+    });
+    let partition_insert = partition_field(fields, options).map(|field| {
+        let field_name = &field.ident;
+        quote! {
+            Self::partition_trie(&self.#field_name)?.insert(txn, &Self::encode_key(&self.#key_ident)?).await?;
+        }
+    });
+    let checksum_write = generate_checksum_write(options);
+    let schema_stamp = schema_version.map(|version| {
+        quote! {
+            let key = format!(
+                "ergokv:{}:__schema",
+                Self::record_path(&self.#key_ident)?,
+            );
+            let mut value = Vec::new();
+            ::ergokv::ciborium::ser::into_writer(#version, &mut value)
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {} schema version: {}", Self::MODEL_NAME, e)))?;
+            txn.put(key, value).await?;
+        }
+    });
+
+    let field_encodings = fields.iter().filter_map(|f| {
+        let field_name = &f.ident;
+        encode_field_write(
+            f,
+            quote! { Self::record_path(&self.#key_ident)? },
+            quote! { &self.#field_name },
+        )
+    });
+
+    let flattened_saves = fields
+        .iter()
+        .filter(|f| FieldOptions::from_field(f).flatten)
+        .map(|f| {
+            write_field_value(
+                f,
+                quote! { Self::record_path(&self.#key_ident)? },
+            )
+        });
+
+    let index_saves = fields.iter().filter_map(|f| {
+        index_kind(f).map(|kind| {
+            generate_index_insert(f, kind, key_field)
+        })
+    });
+
+    let immutable_checks: Vec<_> = fields
+        .iter()
+        .filter(|f| FieldOptions::from_field(f).immutable)
+        .map(|f| {
+            let field_name = &f.ident;
+            let read = read_field_value(
+                f,
+                quote! { Self::record_path(&self.#key_ident)? },
+                quote! { stored },
+            );
+            quote! {
+                #read
+                if stored != self.#field_name {
+                    return Err(tikv_client::Error::StringError(format!(
+                        "Cannot save {}: {} is immutable",
+                        Self::MODEL_NAME,
+                        stringify!(#field_name),
+                    )));
+                }
+            }
+        })
+        .collect();
+    let immutable_check = (!immutable_checks.is_empty()).then(|| {
+        quote! {
+            // Immutable fields keep the value the stored instance was created with
+            if Self::key_exists(&self.#key_ident, txn).await? {
+                #(#immutable_checks)*
+            }
+        }
+    });
+
+    // With managed timestamps, a copy of the instance carrying the stored
+    // `created_at` and the current `updated_at` is written instead
+    let (prepare, target) = if options.timestamps {
+        let created_type = field_type(fields, "created_at");
+        let updated_type = field_type(fields, "updated_at");
+        let created_field = fields
+            .iter()
+            .find(|f| {
+                f.ident
+                    .as_ref()
+                    .is_some_and(|i| i == "created_at")
+            })
+            .expect("Missing created_at field");
+        let read_created_at = read_stored_field_value(
+            created_field,
+            quote! { Self::record_path(&self.#key_ident)? },
+        );
+        (
+            quote! {
+                let stored = Self::key_exists(&self.#key_ident, txn).await?;
+                let created_at: Option<#created_type> = if stored {
+                    #read_created_at
+                } else {
+                    None
+                };
+                let record = Self {
+                    created_at: created_at.unwrap_or_else(<#created_type as ::ergokv::AutoTimestamp>::now),
+                    updated_at: <#updated_type as ::ergokv::AutoTimestamp>::now(),
+                    ..::std::clone::Clone::clone(self)
+                };
+            },
+            quote! { record },
+        )
+    } else if options.on_conflict == OnConflict::Overwrite {
+        (
+            quote! {
+                let stored = matches!(outcome, ::ergokv::SaveOutcome::Updated);
+            },
+            quote! { self },
+        )
+    } else {
+        (quote! {}, quote! { self })
+    };
+    // The stored values (with managed timestamps, the old `updated_at` too) may differ
+    // from the new ones and be indexed, so their index entries are dropped
+    let drop_stale_entries = (options.timestamps
+        || options.on_conflict == OnConflict::Overwrite)
+        .then(|| {
+            quote! {
+                if stored {
+                    self.remove_stored_index_entries(txn).await?;
+                }
+            }
+        });
+
+    let before_save =
+        generate_hook(options, "before_save", quote! { self });
+    let after_save =
+        generate_hook(options, "after_save", quote! { #target });
+
+    let ret = save_return_type(options);
+    let (conflict_check, finish) = match options.on_conflict {
+        OnConflict::Overwrite => (
+            quote! {
+                let outcome = if Self::key_exists(&self.#key_ident, txn).await? {
+                    ::ergokv::SaveOutcome::Updated
+                } else {
+                    ::ergokv::SaveOutcome::Created
+                };
+            },
+            quote! {
+                #target.save_encoded(encoded, txn).await?;
+                #after_save
+                Ok(outcome)
+            },
+        ),
+        OnConflict::Error => (
+            quote! {
+                if Self::key_exists(&self.#key_ident, txn).await? {
+                    return Err(tikv_client::Error::StringError(format!(
+                        "Cannot save {}: key {} already exists",
+                        Self::MODEL_NAME,
+                        Self::encode_key(&self.#key_ident)?
+                    )));
+                }
+            },
+            quote! {
+                #target.save_encoded(encoded, txn).await?;
+                #after_save
+                Ok(::ergokv::SaveOutcome::Created)
+            },
+        ),
+        OnConflict::Skip => (
+            quote! {
+                if Self::key_exists(&self.#key_ident, txn).await? {
+                    return Ok(false);
+                }
+            },
+            quote! {
+                #target.save_encoded(encoded, txn).await?;
+                #after_save
+                Ok(true)
+            },
+        ),
+    };
+    let doc = match options.on_conflict {
+        OnConflict::Overwrite => quote! {
+            /// Saves the instance, replacing any stored instance with the same key.
+            ///
+            /// Returns whether the instance was `Created` or an existing one `Updated`.
+        },
+        OnConflict::Error => quote! {
+            /// Saves the instance, failing if an instance with the same key is already stored.
+            ///
+            /// Update stored instances with `set_<field>` or `apply_patch` instead. A
+            /// successful save always returns `SaveOutcome::Created`.
+        },
+        OnConflict::Skip => quote! {
+            /// Saves the instance, unless an instance with the same key is already stored.
+            ///
+            /// Returns whether the instance was written.
+        },
+    };
+    let timestamps_doc = options.timestamps.then(|| {
+        quote! {
+            ///
+            /// The `created_at` and `updated_at` fields of `self` are ignored. The stored
+            /// `created_at` is kept (or set to the current time for a new instance, or one
+            /// whose `created_at` cannot be read), and `updated_at` is set to the current time.
+        }
+    });
+
+    let body = instrument(
+        "save",
+        ret.clone(),
+        quote! {
+            #checks
+            #conflict_check
+            #immutable_check
+            #prepare
+            let encoded = #target.encode_fields()?;
+            #before_save
+            #audit
+            #outbox
+            #timeline
+            #drop_stale_entries
+            #finish
+        },
+    );
+
+    quote! {
+        #doc
+        #timestamps_doc
+        pub async fn save(&self, txn: &mut tikv_client::Transaction) -> Result<#ret, tikv_client::Error> {
+            #body
+        }
+
+        /// Saves the instance without running migration checks.
+        ///
+        /// Used by `ensure_migrations`, which has to write the new version
+        /// before the migration is recorded as applied.
+        async fn save_unchecked(&self, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            let encoded = self.encode_fields()?;
+            self.save_encoded(encoded, txn).await
+        }
+
+        /// Encodes the key and stored bytes of every field of the instance but flattened ones,
+        /// failing if any is over its `max_value_size`.
+        fn encode_fields(&self) -> Result<Vec<(String, Vec<u8>)>, tikv_client::Error> {
+            Ok(vec![#(#field_encodings),*])
+        }
+
+        /// Writes the instance, with the fields encoded by `encode_fields`.
+        async fn save_encoded(&self, encoded: Vec<(String, Vec<u8>)>, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            #invalidate
+
+            #trie_insert
+            #partition_insert
+
+            for (key, value) in encoded {
+                txn.put(key, value).await?;
+            }
+            #(#flattened_saves)*
+            #schema_stamp
+            #checksum_write
+            self.insert_index_entries(txn).await
+        }
+
+        /// Adds the instance to every index on its fields.
+        async fn insert_index_entries(&self, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            #(#index_saves)*
+            Ok(())
+        }
+    }
+}
+
+/// Generates a call of the `ergokv::StoreHooks` method `hook` on `receiver`, for models
+/// with `#[store(hooks)]`.
+fn generate_hook(
+    options: &StoreOptions,
+    hook: &str,
+    receiver: TokenStream2,
+) -> Option<TokenStream2> {
+    let hook = format_ident!("{}", hook);
+    options.hooks.then(|| {
+        quote! {
+            ::ergokv::StoreHooks::#hook(#receiver, txn).await?;
+        }
+    })
+}
+
+/// The type `save` returns: whether it wrote the instance for `on_conflict = "skip"`,
+/// and an `ergokv::SaveOutcome` otherwise.
+fn save_return_type(options: &StoreOptions) -> TokenStream2 {
+    match options.on_conflict {
+        OnConflict::Skip => quote! { bool },
+        _ => quote! { ::ergokv::SaveOutcome },
+    }
+}
+
+fn generate_delete_method(
+    fields: &Punctuated<Field, Comma>,
+    options: &StoreOptions,
+) -> TokenStream2 {
+    let key_field = fields
+        .iter()
+        .find(|f| {
+            f.attrs.iter().any(|a| a.path().is_ident("key"))
+        })
+        .expect("A field with #[key] attribute is required");
+    let key_ident = &key_field.ident;
+    let key_type = &key_field.ty;
+    let checks = generate_mutation_checks(options);
+    let audit =
+        generate_audit_append(options, "Delete", fields.iter());
+    let outbox = generate_outbox_append(options, "Delete");
+    let invalidate =
+        generate_cache_invalidation(options, key_field);
+    let trie_remove = options.uses_trie().then(|| {
+        quote! {
+            // Remove from master trie
+            let trie = ::ergokv::PrefixTrie::new("ergokv:__trie");
+            trie.remove(txn, &Self::record_path(&self.#key_ident)?).await?;
+        }
+    });
+
+    let partition_remove = partition_field(fields, options).map(|field| {
+        let field_name = &field.ident;
+        quote! {
+            Self::partition_trie(&self.#field_name)?.remove(txn, &Self::encode_key(&self.#key_ident)?).await?;
+        }
+    });
+    let checksum_delete = options.checksum.is_some().then(|| {
+        quote! {
+            txn.delete(Self::checksum_key(&self.#key_ident)?).await?;
+        }
+    });
+
+    let field_deletes = fields.iter().map(|f| {
+        let field_name = &f.ident;
+        if FieldOptions::from_field(f).flatten {
+            return quote! {
+                Self::write_flattened(
+                    format!("ergokv:{}:{}.", Self::record_path(&self.#key_ident)?, stringify!(#field_name)),
+                    Vec::new(),
+                    txn,
+                ).await?;
+            };
+        }
+        quote! {
+            let key = format!(
+                "ergokv:{}:{}",
+                Self::record_path(&self.#key_ident)?,
+                stringify!(#field_name)
+            );
+            txn.delete(key).await?;
+        }
+    });
+
+    // Unique and range index entries belong to a single record, while
+    // `#[index]` and `#[index(hashed)]` entries list the keys of many
+    let direct_index_deletes = fields.iter().filter_map(|f| {
+        index_kind(f)
+            .filter(|kind| {
+                matches!(
+                    kind,
+                    IndexKind::Unique | IndexKind::Range
+                )
+            })
+            .map(|kind| {
+                let field_name = &f.ident;
+                generate_index_remove(
+                    f,
+                    kind,
+                    key_field,
+                    quote! { &self.#field_name },
+                )
+            })
+    });
+
+    let direct_index_keys = fields.iter().filter_map(|f| {
+        let field_name = &f.ident;
+        let index_key = match index_kind(f)? {
+            IndexKind::Unique => quote! {
+                format!(
+                    "ergokv:{}:unique_index:{}:{}",
+                    Self::MODEL_NAME,
+                    stringify!(#field_name),
+                    ::ergokv::serde_json::to_string(&self.#field_name)
+                        .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {}.{} for its index: {}", Self::MODEL_NAME, stringify!(#field_name), e)))?,
+                )
+            },
+            IndexKind::Range => quote! {
+                format!(
+                    "ergokv:{}:range_index:{}:{}:{}",
+                    Self::MODEL_NAME,
+                    stringify!(#field_name),
+                    ::ergokv::RangeKey::range_key(&self.#field_name),
+                    Self::encode_key(&self.#key_ident)?,
+                )
+            },
+            IndexKind::NonUnique | IndexKind::Hashed => return None,
+        };
+        Some(sparse_guard(f, quote! { keys.push(#index_key); }))
+    });
+
+    let list_index_keys = fields.iter().filter_map(|f| {
+        index_kind(f)
+            .filter(|kind| {
+                matches!(
+                    kind,
+                    IndexKind::NonUnique | IndexKind::Hashed
+                )
+            })
+            .map(|kind| {
+                let field_name = &f.ident;
+                let index_key = list_index_key(
+                    f,
+                    kind,
+                    quote! { &self.#field_name },
+                );
+                sparse_guard(
+                    f,
+                    quote! { keys.push(#index_key); },
+                )
+            })
+    });
+
+    let stored_index_removes = fields.iter().filter_map(|f| {
+        let kind = index_kind(f)?;
+        let read = read_stored_field_value(
+            f,
+            quote! { Self::record_path(&self.#key_ident)? },
+        );
+        let remove = generate_index_remove(
+            f,
+            kind,
+            key_field,
+            quote! { stored },
+        );
+        Some(quote! {
+            if let Some(stored) = &#read {
+                #remove
+            }
+        })
+    });
+
+    let search_removes = fields.iter().filter(|f| is_search(f)).map(|f| {
+        let field_name = &f.ident;
+        let exists_method = format_ident!(
+            "by_{}_exists",
+            field_name.clone().expect("Missing field name")
+        );
+        let trie = search_trie(f);
+        sparse_guard(
+            f,
+            quote! {
+                if !Self::#exists_method(self.#field_name.clone(), txn).await? {
+                    #trie.remove(txn, ::std::convert::AsRef::<str>::as_ref(&self.#field_name)).await?;
+                }
+            },
+        )
+    });
+
+    let before_delete =
+        generate_hook(options, "before_delete", quote! { self });
+    let after_delete =
+        generate_hook(options, "after_delete", quote! { self });
+    let body = instrument(
+        "delete",
+        quote! { () },
+        quote! {
+            #checks
+            #before_delete
+            self.append_delete_events(txn).await?;
+            self.delete_fields(txn).await?;
+            self.remove_index_entries(txn).await?;
+            #after_delete
+            Ok(())
+        },
+    );
+
+    let before_delete_many = generate_hook(
+        options,
+        "before_delete",
+        quote! { &record },
+    );
+    let after_delete_many = generate_hook(
+        options,
+        "after_delete",
+        quote! { record },
+    );
+    let after_delete_many = after_delete_many.map(|hook| {
+        quote! {
+            for record in &records {
+                #hook
+            }
+        }
+    });
+    let many_body = instrument(
+        "delete",
+        quote! { () },
+        quote! {
+            #checks
+
+            let mut lists: ::std::collections::HashMap<String, Vec<#key_type>> = ::std::collections::HashMap::new();
+            let mut records = Vec::with_capacity(keys.len());
+            for key in keys {
+                let record = Self::load(key, txn).await?;
+                #before_delete_many
+                record.append_delete_events(txn).await?;
+                record.delete_fields(txn).await?;
+                record.remove_direct_index_entries(txn).await?;
+
+                for index_key in record.list_index_keys()? {
+                    lists.entry(index_key).or_default().push(record.#key_ident.clone());
+                }
+                records.push(record);
+            }
+
+            for (index_key, removed) in lists {
+                Self::remove_from_index_list(index_key, &removed, txn).await?;
+            }
+            for record in &records {
+                record.remove_search_entries(txn).await?;
+            }
+            #after_delete_many
+            Ok(())
+        },
+    );
+
+    quote! {
+        pub async fn delete(&self, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            #body
+        }
+
+        /// Deletes the instances with the given keys.
+        ///
+        /// Has the same effect as loading and deleting every instance, hooks included, but
+        /// each index entry shared by several of the instances is only read and written once.
+        pub async fn delete_many(keys: &[#key_type], txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            #many_body
+        }
+
+        /// Records the deletion of the instance in its audit log and the outbox.
+        async fn append_delete_events(&self, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            #audit
+            #outbox
+            Ok(())
+        }
+
+        /// Deletes the fields of the instance and its trie entry, but not its index entries.
+        async fn delete_fields(&self, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            #invalidate
+
+            #trie_remove
+            #partition_remove
+
+            #(#field_deletes)*
+            txn.delete(format!(
+                "ergokv:{}:__schema",
+                Self::record_path(&self.#key_ident)?,
+            )).await?;
+            #checksum_delete
+            Ok(())
+        }
+
+        /// Removes the instance from all of its indexes, leaving its fields in place.
+        ///
+        /// Used by the migration to the next version of the model, which may index
+        /// its fields differently (e.g. `#[index]` instead of `#[unique_index]`).
+        #[doc(hidden)]
+        pub async fn remove_index_entries(&self, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            self.remove_direct_index_entries(txn).await?;
+            for index_key in self.list_index_keys()? {
+                Self::remove_from_index_list(index_key, ::std::slice::from_ref(&self.#key_ident), txn).await?;
+            }
+            self.remove_search_entries(txn).await
+        }
+
+        /// Removes the index entries of the values stored for the instance, which may
+        /// differ from its own. Only the indexed fields are read, and a value that is
+        /// missing or cannot be decoded has no entries removed, so that a corrupt
+        /// instance can still be overwritten.
+        async fn remove_stored_index_entries(&self, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            #(#stored_index_removes)*
+            Ok(())
+        }
+
+        /// Removes the values of the instance from the search tries, unless other
+        /// instances still have them. Must run after the index lists were updated.
+        async fn remove_search_entries(&self, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            #(#search_removes)*
+            Ok(())
+        }
+
+        /// Removes the unique and range index entries of the instance.
+        async fn remove_direct_index_entries(&self, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            #(#direct_index_deletes)*
+            Ok(())
+        }
+
+        /// Returns the keys of the unique and range index entries of the instance.
+        fn direct_index_keys(&self) -> Result<Vec<String>, tikv_client::Error> {
+            let mut keys = Vec::new();
+            #(#direct_index_keys)*
+            Ok(keys)
+        }
+
+        /// Returns the keys of the index entries listing this instance among others.
+        fn list_index_keys(&self) -> Result<Vec<String>, tikv_client::Error> {
+            let mut keys = Vec::new();
+            #(#list_index_keys)*
+            Ok(keys)
+        }
+
+        /// Removes `removed` from the list of keys stored under `index_key`.
+        async fn remove_from_index_list(index_key: String, removed: &[#key_type], txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            if let Some(existing_keys_bytes) = txn.get(index_key.clone()).await? {
+                let mut keys: Vec<#key_type> = ::ergokv::ciborium::de::from_reader(existing_keys_bytes.as_slice())
+                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+
+                keys.retain(|k| !removed.contains(k));
+
+                // If keys is empty, delete the index entry
+                if keys.is_empty() {
+                    txn.delete(index_key).await?;
+                } else {
+                    let mut value = Vec::new();
+                    ::ergokv::ciborium::ser::into_writer(&keys, &mut value)
+                        .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+                    txn.put(index_key, value).await?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Wraps the body of a generated method returning `Result<#ret, tikv_client::Error>`
+/// so that it records `metrics` for `operation`.
+///
+/// Every call increments `ergokv_<operation>_total` and records its latency in
+/// `ergokv_operation_duration_seconds`, both labelled with the model name.
+#[cfg(feature = "metrics")]
+fn instrument(
+    operation: &str,
+    ret: TokenStream2,
+    body: TokenStream2,
+) -> TokenStream2 {
+    let counter = format!("ergokv_{operation}_total");
+
+    quote! {
+        let start = ::std::time::Instant::now();
+        let result: Result<#ret, tikv_client::Error> = async { #body }.await;
+
+        ::ergokv::metrics::counter!(#counter, "model" => Self::MODEL_NAME)
+            .increment(1);
+        ::ergokv::metrics::histogram!(
+            "ergokv_operation_duration_seconds",
+            "model" => Self::MODEL_NAME,
+            "operation" => #operation
+        )
+        .record(start.elapsed().as_secs_f64());
+
+        result
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+fn instrument(
+    _operation: &str,
+    _ret: TokenStream2,
+    body: TokenStream2,
+) -> TokenStream2 {
+    body
+}
+
+/// Generates code appending an entry for `operation` to the audit log, if the
+/// model has `#[store(audit_log)]`.
+fn generate_audit_append<'a>(
+    options: &StoreOptions,
+    operation: &str,
+    fields: impl Iterator<Item = &'a Field>,
+) -> TokenStream2 {
+    if !options.audit_log {
+        return quote! {};
+    }
+
+    let operation = format_ident!("{}", operation);
+    let field_names = fields.map(|f| &f.ident);
+    quote! {
+        self.append_audit(
+            ::ergokv::AuditOperation::#operation,
+            &[#(stringify!(#field_names)),*],
+            txn,
+        ).await?;
+    }
+}
+
+/// Generates `audit_log` and the private `append_audit` helper for models with
+/// `#[store(audit_log)]`.
+fn generate_audit_methods(
+    key_field: &Field,
+    options: &StoreOptions,
+) -> TokenStream2 {
+    if !options.audit_log {
+        return quote! {};
+    }
+
+    let key_ident = &key_field.ident;
+    let key_type = &key_field.ty;
+
+    quote! {
+        /// Appends an entry to the audit log of this instance.
+        ///
+        /// Entries are numbered by a per-record sequence counter, so that they keep
+        /// their order even when written within the same instant.
+        async fn append_audit(&self, operation: ::ergokv::AuditOperation, fields: &[&str], txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            let key = Self::encode_key(&self.#key_ident)?;
+
+            let seq_key = format!("ergokv:{}:__audit_seq:{}", Self::MODEL_NAME, key);
+            let seq: u64 = match txn.get(seq_key.clone()).await? {
+                Some(bytes) => ::ergokv::ciborium::de::from_reader(bytes.as_slice())
+                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} audit sequence at {}: {}", Self::MODEL_NAME, key, e)))?,
+                None => 0,
+            };
+
+            let entry = ::ergokv::AuditEntry {
+                timestamp: ::std::time::SystemTime::now(),
+                operation,
+                key: key.clone(),
+                changed_fields: fields.iter().map(|f| f.to_string()).collect(),
+            };
+
+            let mut value = Vec::new();
+            ::ergokv::ciborium::ser::into_writer(&entry, &mut value)
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {} audit entry at {}: {}", Self::MODEL_NAME, key, e)))?;
+            txn.put(format!("ergokv:{}:__audit:{}:{:016x}", Self::MODEL_NAME, key, seq), value).await?;
+
+            let mut value = Vec::new();
+            ::ergokv::ciborium::ser::into_writer(&(seq + 1), &mut value)
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {} audit sequence at {}: {}", Self::MODEL_NAME, key, e)))?;
+            txn.put(seq_key, value).await?;
+
+            Ok(())
+        }
+
+        /// Reads the audit log of the instance with the given key, oldest entry first.
+        ///
+        /// The log outlives the instance, so it can still be read after a `delete`.
+        #[allow(clippy::result_large_err)]
+        pub async fn audit_log(key: &#key_type, txn: &mut tikv_client::Transaction) -> Result<Vec<::ergokv::AuditEntry>, tikv_client::Error> {
+            let prefix = format!(
+                "ergokv:{}:__audit:{}:",
+                Self::MODEL_NAME,
+                Self::encode_key(key)?
+            );
+            // ';' directly follows ':', so this covers every key starting with `prefix`
+            let end = format!("{};", &prefix[..prefix.len() - 1]);
+
+            txn.scan(prefix..end, u32::MAX)
+                .await?
+                .map(|entry| {
+                    ::ergokv::ciborium::de::from_reader(entry.value().as_slice())
+                        .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} audit entry: {}", Self::MODEL_NAME, e)))
+                })
+                .collect()
+        }
+    }
+}
+
+/// Generates code appending an event for `operation` to the outbox, if the model has
+/// `#[store(outbox)]`.
+fn generate_outbox_append(
+    options: &StoreOptions,
+    operation: &str,
+) -> TokenStream2 {
+    if !options.outbox {
+        return quote! {};
+    }
+
+    let operation = format_ident!("{}", operation);
+    quote! {
+        self.append_outbox(::ergokv::OutboxOperation::#operation, txn).await?;
+    }
+}
+
+/// Generates `poll_outbox`, `ack_outbox` and the private `append_outbox` helper for models
+/// with `#[store(outbox)]`.
+fn generate_outbox_methods(
+    key_field: &Field,
+    options: &StoreOptions,
+) -> TokenStream2 {
+    if !options.outbox {
+        return quote! {};
+    }
+
+    let key_ident = &key_field.ident;
+
+    quote! {
+        /// Appends an event for a change of this instance to the outbox of this model.
+        async fn append_outbox(&self, operation: ::ergokv::OutboxOperation, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            let seq_key = format!("ergokv:{}:__outbox_seq", Self::MODEL_NAME);
+            let seq: u64 = match txn.get(seq_key.clone()).await? {
+                Some(bytes) => ::ergokv::ciborium::de::from_reader(bytes.as_slice())
+                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} outbox sequence: {}", Self::MODEL_NAME, e)))?,
+                None => 0,
+            } + 1;
+
+            let event = ::ergokv::OutboxEvent {
+                seq,
+                timestamp: ::std::time::SystemTime::now(),
+                operation,
+                key: Self::encode_key(&self.#key_ident)?,
+                value: match operation {
+                    ::ergokv::OutboxOperation::Save => Some(self.to_backup_json()?),
+                    ::ergokv::OutboxOperation::Delete => None,
+                },
+            };
+
+            let mut value = Vec::new();
+            ::ergokv::ciborium::ser::into_writer(&event, &mut value)
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {} outbox event {}: {}", Self::MODEL_NAME, seq, e)))?;
+            txn.put(Self::outbox_key(seq), value).await?;
+
+            let mut value = Vec::new();
+            ::ergokv::ciborium::ser::into_writer(&seq, &mut value)
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {} outbox sequence: {}", Self::MODEL_NAME, e)))?;
+            txn.put(seq_key, value).await?;
+
+            Ok(())
+        }
+
+        /// Returns the key of the outbox event with the given sequence number.
+        fn outbox_key(seq: u64) -> String {
+            format!("ergokv:{}:__outbox:{:016x}", Self::MODEL_NAME, seq)
+        }
+
+        /// Reads the events in the outbox of this model with a sequence number greater than
+        /// `after`, oldest first.
+        ///
+        /// Sequence numbers start at 1, so `poll_outbox(txn, 0)` returns every event not yet
+        /// acknowledged with [`ack_outbox`](Self::ack_outbox).
+        #[allow(clippy::result_large_err)]
+        pub async fn poll_outbox(txn: &mut tikv_client::Transaction, after: u64) -> Result<Vec<::ergokv::OutboxEvent>, tikv_client::Error> {
+            let start = Self::outbox_key(after.saturating_add(1));
+            // ';' directly follows ':', so this covers every event
+            let end = format!("ergokv:{}:__outbox;", Self::MODEL_NAME);
+
+            txn.scan(start..end, u32::MAX)
+                .await?
+                .map(|entry| {
+                    ::ergokv::ciborium::de::from_reader(entry.value().as_slice())
+                        .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} outbox event: {}", Self::MODEL_NAME, e)))
+                })
+                .collect()
+        }
+
+        /// Removes the events in the outbox of this model with a sequence number up to and
+        /// including `up_to`, returning how many were removed.
+        ///
+        /// The sequence numbers of the removed events are not reused.
+        pub async fn ack_outbox(txn: &mut tikv_client::Transaction, up_to: u64) -> Result<usize, tikv_client::Error> {
+            let start = Self::outbox_key(0);
+            let end = Self::outbox_key(up_to.saturating_add(1));
+
+            let keys: Vec<tikv_client::Key> = txn.scan_keys(start..end, u32::MAX).await?.collect();
+            for key in &keys {
+                txn.delete(key.clone()).await?;
+            }
+            Ok(keys.len())
+        }
+    }
+}
+
+/// Generates `read_cache` and `clear_cache` for models with `#[store(cache_ttl = "...")]`.
+fn generate_cache_methods(
+    name: &Ident,
+    options: &StoreOptions,
+) -> TokenStream2 {
+    let Some(ttl) = options.cache_ttl else {
+        return quote! {};
+    };
+    let capacity = options.cache_capacity.unwrap_or(1024);
+
+    quote! {
+        /// The in-process cache of loaded instances.
+        fn read_cache() -> &'static ::ergokv::ReadCache<#name> {
+            static CACHE: ::std::sync::OnceLock<::ergokv::ReadCache<#name>> = ::std::sync::OnceLock::new();
+            CACHE.get_or_init(|| ::ergokv::ReadCache::new(::std::time::Duration::from_millis(#ttl), #capacity))
+        }
+
+        /// Drops all instances from the in-process read cache.
+        pub fn clear_cache() {
+            Self::read_cache().clear();
+        }
+    }
+}
+
+/// Generates code invalidating the cached instance of `self`, if the model has a read cache.
+fn generate_cache_invalidation(
+    options: &StoreOptions,
+    key_field: &Field,
+) -> TokenStream2 {
+    if options.cache_ttl.is_none() {
+        return quote! {};
+    }
+
+    let key_ident = &key_field.ident;
+    quote! {
+        Self::read_cache().invalidate(
+            Self::encode_key(&self.#key_ident)?,
+            tikv_client::TimestampExt::version(&txn.start_timestamp()),
+        );
+    }
+}
+
+/// Generates `load_auto`, `save_auto` and `delete_auto`, which run in a transaction of their own.
+fn generate_auto_methods(
+    key_field: &Field,
+    options: &StoreOptions,
+) -> TokenStream2 {
+    let key_type = &key_field.ty;
+    let save_ret = save_return_type(options);
+
+    quote! {
+        /// Like [`load`](Self::load), but reads in a short transaction of its own.
+        pub async fn load_auto(key: &#key_type, client: &tikv_client::TransactionClient) -> Result<Self, tikv_client::Error> {
+            let mut txn = client.begin_optimistic().await?;
+            let result = Self::load(key, &mut txn).await;
+            txn.rollback().await?;
+            result
+        }
+
+        /// Like [`save`](Self::save), but runs in a transaction of its own, which is
+        /// committed on success and rolled back on failure.
+        pub async fn save_auto(&self, client: &tikv_client::TransactionClient) -> Result<#save_ret, tikv_client::Error> {
+            let mut txn = client.begin_optimistic().await?;
+            match self.save(&mut txn).await {
+                Ok(saved) => txn.commit().await.map(|_| saved),
+                Err(e) => {
+                    txn.rollback().await?;
+                    Err(e)
+                }
+            }
+        }
+
+        /// Like [`delete`](Self::delete), but runs in a transaction of its own, which is
+        /// committed on success and rolled back on failure.
+        pub async fn delete_auto(&self, client: &tikv_client::TransactionClient) -> Result<(), tikv_client::Error> {
+            let mut txn = client.begin_optimistic().await?;
+            match self.delete(&mut txn).await {
+                Ok(()) => txn.commit().await.map(|_| ()),
+                Err(e) => {
+                    txn.rollback().await?;
+                    Err(e)
+                }
+            }
+        }
+    }
+}
+
+fn generate_rekey_method(
+    key_field: &Field,
+    options: &StoreOptions,
+) -> TokenStream2 {
+    let key_ident = &key_field.ident;
+    let key_type = &key_field.ty;
+    let checks = generate_mutation_checks(options);
+
+    quote! {
+        /// Checks whether an instance with the given key is stored.
+        async fn key_exists(key: &#key_type, txn: &mut tikv_client::Transaction) -> Result<bool, tikv_client::Error> {
+            let key_field_key = format!(
+                "ergokv:{}:{}",
+                Self::record_path(key)?,
+                stringify!(#key_ident)
+            );
+
+            Ok(txn.get(key_field_key).await?.is_some())
+        }
+
+        /// Returns the migration the instance with the given key was last saved under.
+        ///
+        /// Models with `#[migrate_from(Prev)]` stamp every saved instance with the name of
+        /// their migration, e.g. `"UserV1->User"`. Returns `None` for instances saved by a
+        /// model without `#[migrate_from]`, i.e. ones that predate all migrations.
+        #[allow(clippy::result_large_err)]
+        pub async fn schema_version_of(key: &#key_type, txn: &mut tikv_client::Transaction) -> Result<Option<String>, tikv_client::Error> {
+            let schema_key = format!(
+                "ergokv:{}:__schema",
+                Self::record_path(key)?,
+            );
+
+            txn.get(schema_key)
+                .await?
+                .map(|value| {
+                    ::ergokv::ciborium::de::from_reader(value.as_slice())
+                        .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} schema version: {}", Self::MODEL_NAME, e)))
+                })
+                .transpose()
+        }
+
+        /// Changes the primary key of the instance.
+        ///
+        /// The stored field values, index entries and trie entry are moved from the old key
+        /// to `new_key` within the transaction, and `self` is replaced by the moved instance.
+        /// As nothing is saved or deleted, no hooks run, no audit or outbox entries are
+        /// written, and managed timestamps keep their values. Fails if an instance with
+        /// `new_key` already exists.
+        pub async fn rekey(&mut self, new_key: #key_type, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            #checks
+            if Self::key_exists(&new_key, txn).await? {
+                return Err(tikv_client::Error::StringError(format!(
+                    "Cannot rekey {}: key {} already exists",
+                    Self::MODEL_NAME,
+                    Self::encode_key(&new_key)?
+                )));
+            }
+
+            // The stored values are moved, not the ones in memory, which may be stale
+            let mut record = Self::load(&self.#key_ident, txn).await?;
+            record.remove_index_entries(txn).await?;
+            record.delete_fields(txn).await?;
+            record.#key_ident = new_key;
+            record.save_unchecked(txn).await?;
+            *self = record;
+            Ok(())
+        }
+    }
+}
+
+/// Generates `reindex_all`, which rebuilds the trie and all indexes from the field data.
+fn generate_reindex_method(
+    key_field: &Field,
+    options: &StoreOptions,
+) -> TokenStream2 {
+    let key_ident = &key_field.ident;
+    let key_type = &key_field.ty;
+    let decode = decode_field_value(key_field);
+    let existing_keys =
+        generate_existing_keys(key_field, quote! { txn });
+    let trie_check = options.uses_trie().then(|| {
+        quote! {
+            let trie_entries: std::collections::BTreeSet<String> = ::ergokv::PrefixTrie::new("ergokv:__trie")
+                .find_by_prefix(txn, &format!("{}:", Self::MODEL_NAME))
+                .await?
+                .into_iter()
+                .collect();
+            let record_paths = records
+                .iter()
+                .map(Self::record_path)
+                .collect::<Result<std::collections::BTreeSet<String>, _>>()?;
+
+            // Reserved keys are in the trie before they are stored
+            for path in trie_entries.difference(&record_paths) {
+                if txn.get(Self::pending_key(path)).await?.is_none() {
+                    report.orphaned_trie_entries.push(path.clone());
+                }
+            }
+            report.missing_trie_entries = record_paths.difference(&trie_entries).cloned().collect();
+        }
+    });
+    let trie_repair = options.uses_trie().then(|| {
+        quote! {
+            let trie = ::ergokv::PrefixTrie::new("ergokv:__trie");
+            let mut stale_trie_entries: std::collections::HashSet<String> = trie
+                .find_by_prefix(txn, &format!("{}:", Self::MODEL_NAME))
+                .await?
+                .into_iter()
+                .collect();
+
+            for key in &records {
+                let trie_key = Self::record_path(key)?;
+                if !stale_trie_entries.remove(&trie_key) {
+                    trie.insert(txn, &trie_key).await?;
+                    report.missing_trie_entries += 1;
+                }
+            }
+
+            // Reserved keys are in the trie before they are stored
+            for trie_key in &stale_trie_entries {
+                if txn.get(Self::pending_key(trie_key)).await?.is_none() {
+                    trie.remove(txn, trie_key).await?;
+                    report.orphaned_trie_entries += 1;
+                }
+            }
+        }
+    });
+
+    quote! {
+        /// Rebuilds the master trie entries and every index of this model from the stored
+        /// field values.
+        ///
+        /// Every key under `ergokv:{MODEL_NAME}:` is scanned. Each record found there is
+        /// added back to the trie and its indexes, while trie and index entries that no
+        /// record accounts for are removed, except for the trie entries of reserved keys.
+        /// The whole rebuild runs in a single transaction, which is rolled back if any
+        /// record fails to load.
+        pub async fn reindex_all(client: &tikv_client::TransactionClient) -> Result<::ergokv::ReindexReport, tikv_client::Error> {
+            let mut txn = client.begin_optimistic().await?;
+            match Self::reindex_all_in(&mut txn).await {
+                Ok(report) => {
+                    txn.commit().await?;
+                    Ok(report)
+                }
+                Err(e) => {
+                    txn.rollback().await?;
+                    Err(e)
+                }
+            }
+        }
+
+        /// Removes the keys of instances that are no longer stored from every index of this
+        /// model, deleting index entries left without any key.
+        ///
+        /// Unlike [`reindex_all`](Self::reindex_all), this only scans the index keyspace and
+        /// checks each listed key with a batch read, so it is cheap to run regularly. Returns
+        /// the number of keys removed, where an index entry with an empty list counts as one.
+        pub async fn prune_indexes(txn: &mut tikv_client::Transaction) -> Result<usize, tikv_client::Error> {
+            let mut pruned = 0;
+            for kind in ["unique_index", "range_index", "index", "hashed_index"] {
+                let prefix = format!("ergokv:{}:{}:", Self::MODEL_NAME, kind);
+                let end = format!("ergokv:{}:{};", Self::MODEL_NAME, kind);
+                let entries: Vec<(tikv_client::Key, tikv_client::Value)> = txn
+                    .scan(prefix..end, u32::MAX)
+                    .await?
+                    .map(Into::into)
+                    .collect();
+
+                for (index_key, value) in entries {
+                    let index_key: Vec<u8> = index_key.into();
+                    let index_key = String::from_utf8(index_key)
+                        .map_err(|e| tikv_client::Error::StringError(format!("Invalid {} index key {}: {}", Self::MODEL_NAME, String::from_utf8_lossy(e.as_bytes()), e)))?;
+
+                    // Unique and range entries point at a single instance
+                    let direct = kind == "unique_index" || kind == "range_index";
+                    let listed: Vec<#key_type> = if direct {
+                        ::ergokv::ciborium::de::from_reader(value.as_slice()).map(|key| vec![key])
+                    } else {
+                        ::ergokv::ciborium::de::from_reader(value.as_slice())
+                    }
+                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+                    let keys = #existing_keys;
+
+                    if keys.is_empty() {
+                        txn.delete(index_key).await?;
+                        pruned += listed.len().max(1);
+                    } else if keys.len() < listed.len() {
+                        let mut value = Vec::new();
+                        ::ergokv::ciborium::ser::into_writer(&keys, &mut value)
+                            .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+                        txn.put(index_key, value).await?;
+                        pruned += listed.len() - keys.len();
+                    }
+                }
+            }
+
+            Ok(pruned)
+        }
+
+        /// Scans the keyspace of this model, returning the keys of all index entries
+        /// and the keys of all records.
+        async fn scan_model_keyspace(txn: &mut tikv_client::Transaction) -> Result<(std::collections::HashSet<Vec<u8>>, Vec<#key_type>), tikv_client::Error> {
+            let prefix = format!("ergokv:{}:", Self::MODEL_NAME);
+            let end = format!("ergokv:{};", Self::MODEL_NAME);
+            let key_suffix = format!(":{}", stringify!(#key_ident));
+
+            let mut index_entries = std::collections::HashSet::new();
+            let mut records = Vec::new();
+            for pair in txn.scan(prefix.clone()..end, u32::MAX).await? {
+                let (raw_key, value): (tikv_client::Key, tikv_client::Value) = pair.into();
+                let raw_key: Vec<u8> = raw_key.into();
+                let Some(rest) = std::str::from_utf8(&raw_key).ok().and_then(|k| k.strip_prefix(&prefix)) else {
+                    continue;
+                };
+
+                if ["unique_index:", "index:", "hashed_index:", "range_index:"]
+                    .iter()
+                    .any(|p| rest.starts_with(p))
+                {
+                    index_entries.insert(raw_key.clone());
+                } else if !["__audit", "__outbox", "fti:", "partition:"].iter().any(|p| rest.starts_with(p)) && rest.ends_with(&key_suffix) {
+                    let key: #key_type = #decode
+                        .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {}.{} at {}{}: {}", Self::MODEL_NAME, stringify!(#key_ident), prefix, rest, e)))?;
+                    records.push(key);
+                }
+            }
+
+            Ok((index_entries, records))
+        }
+
+        /// Cross-checks the master trie, the indexes and the field data of this model,
+        /// without modifying anything.
+        ///
+        /// Every record found in the field data is loaded, and the trie entries and index
+        /// entries it should have are compared with the stored ones. Inconsistencies are
+        /// listed in the returned report; [`reindex_all`](Self::reindex_all) repairs them.
+        pub async fn verify_integrity(txn: &mut tikv_client::Transaction) -> Result<::ergokv::IntegrityReport, tikv_client::Error> {
+            use std::collections::{BTreeMap, BTreeSet};
+
+            let (index_entries, records) = Self::scan_model_keyspace(txn).await?;
+            let mut report = ::ergokv::IntegrityReport {
+                records: records.len(),
+                ..Default::default()
+            };
+
+            #trie_check
+
+            // The index entries every loadable record should have
+            let mut expected: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+            for key in &records {
+                let Ok(record) = Self::load(key, txn).await else {
+                    report.unloadable_records.push(Self::encode_key(key)?);
+                    continue;
+                };
+
+                let encoded_key = Self::encode_key(key)?;
+                for index_key in record.direct_index_keys()?.into_iter().chain(record.list_index_keys()?) {
+                    expected.entry(index_key).or_default().insert(encoded_key.clone());
+                }
+            }
+
+            // The index entries actually stored, with the keys they point to
+            let mut stored: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+            for raw_key in index_entries {
+                let index_key = String::from_utf8(raw_key)
+                    .map_err(|e| tikv_client::Error::StringError(format!("Invalid {} index key {}: {}", Self::MODEL_NAME, String::from_utf8_lossy(e.as_bytes()), e)))?;
+                let Some(value) = txn.get(index_key.clone()).await? else {
+                    continue;
+                };
+
+                let rest = &index_key[format!("ergokv:{}:", Self::MODEL_NAME).len()..];
+                let keys: Vec<#key_type> = if rest.starts_with("unique_index:") || rest.starts_with("range_index:") {
+                    ::ergokv::ciborium::de::from_reader(value.as_slice()).map(|key| vec![key])
+                } else {
+                    ::ergokv::ciborium::de::from_reader(value.as_slice())
+                }
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+
+                let pointed = stored.entry(index_key).or_default();
+                for key in &keys {
+                    pointed.insert(Self::encode_key(key)?);
+                }
+            }
+
+            for (index_key, keys) in &stored {
+                for key in keys {
+                    if !expected.get(index_key).is_some_and(|expected| expected.contains(key)) {
+                        report.dangling_index_entries.push(::ergokv::IndexPointer {
+                            index_key: index_key.clone(),
+                            key: key.clone(),
+                        });
+                    }
+                }
+            }
+            for (index_key, keys) in &expected {
+                for key in keys {
+                    if !stored.get(index_key).is_some_and(|stored| stored.contains(key)) {
+                        report.missing_index_entries.push(::ergokv::IndexPointer {
+                            index_key: index_key.clone(),
+                            key: key.clone(),
+                        });
+                    }
+                }
+            }
+
+            report.unloadable_records.sort();
+            Ok(report)
+        }
+
+        async fn reindex_all_in(txn: &mut tikv_client::Transaction) -> Result<::ergokv::ReindexReport, tikv_client::Error> {
+            let (old_index_entries, records) = Self::scan_model_keyspace(txn).await?;
+            let mut report = ::ergokv::ReindexReport {
+                records: records.len(),
+                ..Default::default()
+            };
+
+            for index_key in &old_index_entries {
+                txn.delete(index_key.clone()).await?;
+            }
+
+            // The search tries are rebuilt along with the indexes
+            let search_nodes: Vec<tikv_client::Key> = txn
+                .scan_keys(format!("ergokv:{}:fti:", Self::MODEL_NAME)..format!("ergokv:{}:fti;", Self::MODEL_NAME), u32::MAX)
+                .await?
+                .collect();
+            for node in search_nodes {
+                txn.delete(node).await?;
+            }
+
+            #trie_repair
+
+            for key in &records {
+                Self::load(key, txn).await?.insert_index_entries(txn).await?;
+            }
+
+            let (new_index_entries, _) = Self::scan_model_keyspace(txn).await?;
+            report.index_entries = new_index_entries.len();
+            report.orphaned_index_entries = old_index_entries.difference(&new_index_entries).count();
+
+            Ok(report)
+        }
+    }
+}
+
+/// The kind of index requested on a field.
+#[derive(Clone, Copy, PartialEq)]
+enum IndexKind {
+    /// `#[unique_index]`, maps a field value to a single key
+    Unique,
+    /// `#[index]`, maps a field value to a list of keys
+    NonUnique,
+    /// `#[index(range)]`, one sortable entry per record, allowing range scans
+    Range,
+    /// `#[index(hashed)]`, like `NonUnique`, but keyed by a hash of the field value
+    Hashed,
+}
+
+/// Returns the options given to `#[index(...)]` on a field, if any.
+fn index_options(field: &Field) -> Vec<String> {
+    field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("index"))
+        .filter(|attr| matches!(attr.meta, syn::Meta::List(_)))
+        .flat_map(|attr| {
+            attr.parse_args_with(
+                Punctuated::<Ident, Comma>::parse_terminated,
+            )
+            .expect("Expected #[index(option, ...)]")
+        })
+        .map(|option| option.to_string())
+        .collect()
+}
+
+fn index_kind(field: &Field) -> Option<IndexKind> {
+    field.attrs.iter().find_map(|attr| {
+        if attr.path().is_ident("unique_index") {
+            Some(IndexKind::Unique)
+        } else if attr.path().is_ident("index") {
+            let mut kind = IndexKind::NonUnique;
+            for option in index_options(field) {
+                match option.as_str() {
+                    "range" => kind = IndexKind::Range,
+                    "hashed" => kind = IndexKind::Hashed,
+                    "sparse" | "search" | "low_cardinality" => {}
+                    other => {
+                        panic!("Unknown index option: {other}")
+                    }
+                }
+            }
+            if kind != IndexKind::NonUnique && is_search(field) {
+                panic!("#[index(search)] cannot be combined with range or hashed");
+            }
+            if index_options(field).iter().any(|option| option == "low_cardinality")
+                && (kind != IndexKind::NonUnique || is_search(field))
+            {
+                panic!("#[index(low_cardinality)] cannot be combined with range, hashed or search");
+            }
+            Some(kind)
+        } else {
+            None
+        }
+    })
+}
+
+/// Whether `by_<field>` streams the instances instead of collecting them, for fields
+/// marked `#[index(low_cardinality)]` and plain `#[index]` fields of type `bool`.
+fn is_low_cardinality(field: &Field) -> bool {
+    let is_bool = matches!(&field.ty, syn::Type::Path(path) if path.path.is_ident("bool"));
+    index_kind(field) == Some(IndexKind::NonUnique)
+        && !is_search(field)
+        && (is_bool
+            || index_options(field)
+                .iter()
+                .any(|option| option == "low_cardinality"))
+}
+
+/// Whether the field is marked `#[index(search)]`, i.e. its values are also kept
+/// in a prefix trie for `search_<field>`.
+fn is_search(field: &Field) -> bool {
+    index_options(field).iter().any(|option| option == "search")
+}
+
+/// Returns the field named by `#[store(partition_by = "...")]`, if any.
+fn partition_field<'a>(
+    fields: &'a Punctuated<Field, Comma>,
+    options: &StoreOptions,
+) -> Option<&'a Field> {
+    fields.iter().find(|f| is_partition_field(f, options))
+}
+
+/// Generates `all_in_partition` and the `partition_trie` it reads, for models with
+/// `#[store(partition_by = "...")]`.
+fn generate_partition_methods(
+    name: &Ident,
+    fields: &Punctuated<Field, Comma>,
+    options: &StoreOptions,
+) -> Option<TokenStream2> {
+    let field = partition_field(fields, options)?;
+    let field_name = &field.ident;
+    let field_type = &field.ty;
+
+    Some(quote! {
+        /// Returns the trie holding the keys of the instances in the given partition.
+        fn partition_trie(partition: &#field_type) -> Result<::ergokv::PrefixTrie, tikv_client::Error> {
+            let encoded = ::ergokv::serde_json::to_string(partition)
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {}.{} for its partition: {}", Self::MODEL_NAME, stringify!(#field_name), e)))?;
+            Ok(::ergokv::PrefixTrie::new(format!(
+                "ergokv:{}:partition:{}",
+                Self::MODEL_NAME,
+                encoded,
+            )))
+        }
+
+        #[doc = concat!("Streams all ", stringify!(#name), " whose ", stringify!(#field_name), " field equals `partition`.")]
+        #[doc = ""]
+        #[doc = "Only the trie of that partition is read. Instances are yielded sorted by their serialized key."]
+        pub fn all_in_partition(partition: #field_type, txn: &mut tikv_client::Transaction) -> impl futures::Stream<Item = Result<Self, tikv_client::Error>> + '_ {
+            async_stream::try_stream! {
+                let keys = Self::partition_trie(&partition)?.all(txn).await?;
+                for key in keys {
+                    yield Self::load(&Self::decode_key(&key)?, txn).await?;
+                }
+            }
+        }
+    })
+}
+
+/// Generates the trie holding the values of a `#[index(search)]` field.
+fn search_trie(field: &Field) -> TokenStream2 {
+    let field_name = &field.ident;
+    quote! {
+        ::ergokv::PrefixTrie::new(format!(
+            "ergokv:{}:fti:{}",
+            Self::MODEL_NAME,
+            stringify!(#field_name),
+        ))
+    }
+}
+
+/// Generates the key of the entry listing all records with the given value of `field`,
+/// for `#[index]` and `#[index(hashed)]` fields.
+fn list_index_key(
+    field: &Field,
+    kind: IndexKind,
+    value: TokenStream2,
+) -> TokenStream2 {
+    let field_name = &field.ident;
+    let encoded = quote! {
+        ::ergokv::serde_json::to_string(#value)
+            .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {}.{} for its index: {}", Self::MODEL_NAME, stringify!(#field_name), e)))?
+    };
+
+    if kind == IndexKind::Hashed {
+        quote! {
+            format!(
+                "ergokv:{}:hashed_index:{}:{}",
+                Self::MODEL_NAME,
+                stringify!(#field_name),
+                ::ergokv::blake3::hash(#encoded.as_bytes()).to_hex(),
+            )
+        }
+    } else {
+        quote! {
+            format!(
+                "ergokv:{}:index:{}:{}",
+                Self::MODEL_NAME,
+                stringify!(#field_name),
+                #encoded,
+            )
+        }
+    }
+}
+
+/// Whether the field is marked `#[index(sparse)]`, i.e. default values are not indexed.
+fn is_sparse(field: &Field) -> bool {
+    index_options(field).iter().any(|option| option == "sparse")
+}
+
+/// Whether the type of the field is written as `Option<...>`.
+fn is_option(field: &Field) -> bool {
+    match &field.ty {
+        syn::Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+/// Returns `T` for a field whose type is written as `Option<T>`.
+fn option_inner_type(field: &Field) -> Option<&syn::Type> {
+    let syn::Type::Path(path) = &field.ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => {
+            args.args.iter().find_map(|arg| match arg {
+                syn::GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Returns the type the `by_<field>` lookups take values as, the type inside the `Option`
+/// for fields of an `Option` type, along with an expression converting such a `value` into
+/// the type of the field.
+fn index_query_type(
+    field: &Field,
+) -> (TokenStream2, TokenStream2) {
+    let field_type = &field.ty;
+    match option_inner_type(field) {
+        Some(inner) => (
+            quote! { #inner },
+            quote! { Some(Into::<#inner>::into(value)) },
+        ),
+        None => (
+            quote! { #field_type },
+            quote! { Into::<#field_type>::into(value) },
+        ),
+    }
+}
+
+/// Wraps index maintenance code so that it only runs for non-default values
+/// of `#[index(sparse)]` fields, and for `Some` values of `#[unique_index]`
+/// fields of an `Option` type, which `None` values would all collide on.
+fn sparse_guard(
+    field: &Field,
+    code: TokenStream2,
+) -> TokenStream2 {
+    let field_name = &field.ident;
+    sparse_guard_value(field, quote! { &self.#field_name }, code)
+}
+
+/// Like [`sparse_guard`], but for `value`, a reference to a value of `field` that need
+/// not be the one in `self`.
+fn sparse_guard_value(
+    field: &Field,
+    value: TokenStream2,
+    code: TokenStream2,
+) -> TokenStream2 {
+    let field_type = &field.ty;
+
+    if index_kind(field) == Some(IndexKind::Unique)
+        && is_option(field)
+    {
+        return quote! {
+            if (#value).is_some() {
+                #code
+            }
+        };
+    }
+    if !is_sparse(field) {
+        return code;
+    }
+
+    quote! {
+        if *(#value) != <#field_type as ::core::default::Default>::default() {
+            #code
+        }
+    }
+}
+
+/// Generates code adding `self` to the index on `field`, using the current field value.
+fn generate_index_insert(
+    field: &Field,
+    kind: IndexKind,
+    key_field: &Field,
+) -> TokenStream2 {
+    let field_name = &field.ident;
+    let key_ident = &key_field.ident;
+    let key_type = &key_field.ty;
+
+    let list_key = list_index_key(
+        field,
+        kind,
+        quote! { &self.#field_name },
+    );
+    // The trie does not store empty strings, which no prefix search could miss anyway
+    let search_insert = is_search(field).then(|| {
+        let trie = search_trie(field);
+        quote! {
+            let searched: &str = ::std::convert::AsRef::<str>::as_ref(&self.#field_name);
+            if !searched.is_empty() {
+                #trie.insert(txn, searched).await?;
+            }
+        }
+    });
+
+    let code = match kind {
+        IndexKind::Unique => quote! {
+            let encoded = ::ergokv::serde_json::to_string(&self.#field_name)
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {}.{} for its index: {}", Self::MODEL_NAME, stringify!(#field_name), e)))?;
+            let index_key = format!(
+                "ergokv:{}:unique_index:{}:{}",
+                Self::MODEL_NAME,
+                stringify!(#field_name),
+                encoded,
+            );
+
+            // A value held by another instance is only free again once that one lets go of it
+            if let Some(existing_key_bytes) = txn.get(index_key.clone()).await? {
+                let existing_key: #key_type = ::ergokv::ciborium::de::from_reader(existing_key_bytes.as_slice())
+                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+                if existing_key != self.#key_ident {
+                    return Err(tikv_client::Error::StringError(format!(
+                        "Unique index violation: {}.{} {} is already taken by key {}",
+                        Self::MODEL_NAME,
+                        stringify!(#field_name),
+                        encoded,
+                        Self::encode_key(&existing_key)?,
+                    )));
+                }
+            }
+
+            let mut value = Vec::new();
+            ::ergokv::ciborium::ser::into_writer(&self.#key_ident, &mut value)
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+            txn.put(index_key, value).await?;
+        },
+        IndexKind::NonUnique | IndexKind::Hashed => quote! {
+            let index_key = #list_key;
+
+            // Read existing keys
+            let mut keys: Vec<#key_type> = if let Some(existing_keys_bytes) = txn.get(index_key.clone()).await? {
+                ::ergokv::ciborium::de::from_reader(existing_keys_bytes.as_slice())
+                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?
+            } else {
+                Vec::new()
+            };
+
+            // Add current key if not already present
+            if !keys.contains(&self.#key_ident) {
+                keys.push(self.#key_ident.clone());
+            }
+
+            // Write updated keys
+            let mut value = Vec::new();
+            ::ergokv::ciborium::ser::into_writer(&keys, &mut value)
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+            txn.put(index_key, value).await?;
+
+            #search_insert
+        },
+        IndexKind::Range => quote! {
+            let index_key = format!(
+                "ergokv:{}:range_index:{}:{}:{}",
+                Self::MODEL_NAME,
+                stringify!(#field_name),
+                ::ergokv::RangeKey::range_key(&self.#field_name),
+                Self::encode_key(&self.#key_ident)?,
+            );
+            let mut value = Vec::new();
+            ::ergokv::ciborium::ser::into_writer(&self.#key_ident, &mut value)
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+            txn.put(index_key, value).await?;
+        },
+    };
+
+    sparse_guard(field, code)
+}
+
+/// Generates code removing `self` from the index on `field` for `value`, a reference to
+/// a value of the field, e.g. `&self.<field>`.
+fn generate_index_remove(
+    field: &Field,
+    kind: IndexKind,
+    key_field: &Field,
+    value: TokenStream2,
+) -> TokenStream2 {
+    let field_name = &field.ident;
+    let key_ident = &key_field.ident;
+    let key_type = &key_field.ty;
+
+    let list_key = list_index_key(field, kind, value.clone());
+    // The value leaves the trie along with the last record having it
+    let search_remove = is_search(field).then(|| {
+        let trie = search_trie(field);
+        quote! {
+            #trie.remove(txn, ::std::convert::AsRef::<str>::as_ref(#value)).await?;
+        }
+    });
+
+    let code = match kind {
+        IndexKind::Unique => quote! {
+            let index_key = format!(
+                "ergokv:{}:unique_index:{}:{}",
+                Self::MODEL_NAME,
+                stringify!(#field_name),
+                ::ergokv::serde_json::to_string(#value)
+                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {}.{} for its index: {}", Self::MODEL_NAME, stringify!(#field_name), e)))?,
+            );
+            txn.delete(index_key).await?;
+        },
+        IndexKind::NonUnique | IndexKind::Hashed => quote! {
+            let index_key = #list_key;
+
+            // Read existing keys
+            if let Some(existing_keys_bytes) = txn.get(index_key.clone()).await? {
+                let mut keys: Vec<#key_type> = ::ergokv::ciborium::de::from_reader(existing_keys_bytes.as_slice())
+                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+
+                // Remove current key
+                keys.retain(|k| k != &self.#key_ident);
+
+                // If keys is empty, delete the index entry
+                if keys.is_empty() {
+                    txn.delete(index_key).await?;
+                    #search_remove
+                } else {
+                    // Otherwise, update the keys
+                    let mut value = Vec::new();
+                    ::ergokv::ciborium::ser::into_writer(&keys, &mut value)
+                        .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+                    txn.put(index_key, value).await?;
+                }
+            }
+        },
+        IndexKind::Range => quote! {
+            let index_key = format!(
+                "ergokv:{}:range_index:{}:{}:{}",
+                Self::MODEL_NAME,
+                stringify!(#field_name),
+                ::ergokv::RangeKey::range_key(#value),
+                Self::encode_key(&self.#key_ident)?,
+            );
+            txn.delete(index_key).await?;
+        },
+    };
+
+    sparse_guard_value(field, value, code)
+}
+
+/// Generates an expression keeping the keys in `listed` whose instances are stored, as read
+/// through `receiver` with a single batch get.
+///
+/// Index lists can outlive their instances when a delete only partially succeeded, and
+/// `load_many` fails on any missing instance.
+fn generate_existing_keys(
+    key_field: &Field,
+    receiver: TokenStream2,
+) -> TokenStream2 {
+    let key_ident = &key_field.ident;
+    let key_type = &key_field.ty;
+
+    quote! {
+        {
+            let mut key_field_keys = Vec::with_capacity(listed.len());
+            for key in &listed {
+                key_field_keys.push(format!("ergokv:{}:{}", Self::record_path(key)?, stringify!(#key_ident)));
+            }
+            let stored: ::std::collections::HashSet<Vec<u8>> = #receiver
+                .batch_get(key_field_keys.clone())
+                .await?
+                .map(|pair| {
+                    let (key, _): (tikv_client::Key, tikv_client::Value) = pair.into();
+                    key.into()
+                })
+                .collect();
+
+            listed
+                .iter()
+                .zip(key_field_keys)
+                .filter(|(_, key_field_key)| stored.contains(key_field_key.as_bytes()))
+                .map(|(key, _)| key.clone())
+                .collect::<Vec<#key_type>>()
+        }
+    }
+}
+
+fn generate_index_methods(
+    name: &Ident,
+    fields: &Punctuated<Field, Comma>,
+) -> Vec<TokenStream2> {
+    let key_field = fields
+        .iter()
+        .find(|f| {
+            f.attrs.iter().any(|a| a.path().is_ident("key"))
+        })
+        .expect("A field with #[key] attribute is required");
+    let key_type = &key_field.ty;
+
+    fields.iter()
+        .filter_map(|f| index_kind(f).map(|kind| (f, kind)))
+        .map(|(f, kind)| {
+            let field_name = &f.ident;
+            let field_type = &f.ty;
+            let method_name = format_ident!("by_{}", field_name.clone().expect("Missing field name"));
+            let ref_method_name = format_ident!("by_{}_ref", field_name.clone().expect("Missing field name"));
+            let ref_doc = quote! {
+                #[doc = concat!("Like [`", stringify!(#method_name), "`](Self::", stringify!(#method_name), "), but takes the value by reference, e.g. a `&str` for a")]
+                #[doc = "`String` field, so that querying does not need an owned value."]
+                #[doc = ""]
+                #[doc = concat!("The value must serialize like the ", stringify!(#field_name), " field would, as it is looked up by its JSON form.")]
+            };
+
+            let in_method = (kind != IndexKind::Range)
+                .then(|| generate_in_method(name, f, kind, key_field));
+            let group_by_method = generate_group_by_method(name, f, kind, key_field);
+            let (query_type, into_field) = index_query_type(f);
+            let option_doc = option_inner_type(f).is_some().then(|| quote! {
+                #[doc = ""]
+                #[doc = concat!("The ", stringify!(#field_name), " field is an `Option`, so it takes the value inside and looks up `Some(value)`;")]
+                #[doc = "instances whose field is `None` are not found by any value."]
+            });
+
+            let methods = match kind {
+                IndexKind::Unique => quote! {
+                    #[doc = concat!("Find a ", stringify!(#name), " by its ", stringify!(#field_name), " field.")]
+                    #[doc = ""]
+                    #[doc = concat!("This method uses the unique index on the ", stringify!(#field_name), " field to efficiently retrieve the object.")]
+                    #option_doc
+                    pub async fn #method_name<T: Into<#query_type>>(value: T, client: &mut tikv_client::Transaction) -> Result<Option<Self>, tikv_client::Error> {
+                        Self::#ref_method_name(&#into_field, client).await
+                    }
+
+                    #ref_doc
+                    pub async fn #ref_method_name<Q: ::ergokv::serde::Serialize + ?Sized>(value: &Q, client: &mut tikv_client::Transaction) -> Result<Option<Self>, tikv_client::Error> {
+                        let index_key = format!(
+                            "ergokv:{}:unique_index:{}:{}",
+                            Self::MODEL_NAME,
+                            stringify!(#field_name),
+                            ::ergokv::serde_json::to_string(value)
+                                .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {}.{} for its index: {}", Self::MODEL_NAME, stringify!(#field_name), e)))?
+                        );
+                        if let Some(key_bytes) = client.get(index_key.clone()).await? {
+                            let key = ::ergokv::ciborium::de::from_reader(key_bytes.as_slice())
+                                .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+
+                            Self::load(&key, client).await.map(Some)
+                        } else {
+                            Ok(None)
+                        }
+                    }
+                },
+                IndexKind::NonUnique | IndexKind::Hashed => {
+                    let index_key = list_index_key(f, kind, quote! { &value });
+                    let ref_index_key = list_index_key(f, kind, quote! { value });
+                    let existing_in_txn = generate_existing_keys(key_field, quote! { client });
+                    let existing_in_snapshot = generate_existing_keys(key_field, quote! { snapshot });
+                    // Distinct values may share a hash, so candidates have to be checked
+                    let (doc, load, load_concurrently, ref_bound) = if kind == IndexKind::Hashed {
+                        (
+                            quote! { #[doc = concat!("This method uses the hashed index on the ", stringify!(#field_name), " field, and filters out hash collisions by comparing the loaded values.")] },
+                            quote! {
+                                let mut results = Self::load_many(&keys, client).await?;
+                                results.retain(|record| record.#field_name == *value);
+                                Ok(results)
+                            },
+                            quote! {
+                                let mut results = Self::load_many_at(&keys, client, timestamp, concurrency).await?;
+                                results.retain(|record| record.#field_name == value);
+                                Ok(results)
+                            },
+                            quote! { where #field_type: PartialEq<Q> },
+                        )
+                    } else {
+                        (
+                            quote! { #[doc = concat!("This method uses the index on the ", stringify!(#field_name), " field to efficiently retrieve multiple objects.")] },
+                            quote! { Self::load_many(&keys, client).await },
+                            quote! { Self::load_many_at(&keys, client, timestamp, concurrency).await },
+                            quote! {},
+                        )
+                    };
+                    let concurrent_method_name = format_ident!(
+                        "by_{}_concurrent",
+                        field_name.clone().expect("Missing field name")
+                    );
+                    let one_method_name = format_ident!(
+                        "by_{}_one",
+                        field_name.clone().expect("Missing field name")
+                    );
+                    let found = if kind == IndexKind::Hashed {
+                        quote! {
+                            if record.#field_name == value {
+                                return Ok(Some(record));
+                            }
+                        }
+                    } else {
+                        quote! { return Ok(Some(record)); }
+                    };
+
+                    // Buckets of low-cardinality fields may hold a large part of the table
+                    let lookup = if is_low_cardinality(f) {
+                        quote! {
+                            #[doc = concat!("Streams all ", stringify!(#name), " by its ", stringify!(#field_name), " field.")]
+                            #[doc = ""]
+                            #[doc = concat!("The ", stringify!(#field_name), " field has few distinct values, so the index entry of one may list")]
+                            #[doc = "a large part of the table. Instances are loaded one at a time as the stream is polled,"]
+                            #[doc = "so that callers can stop early or paginate with `skip` and `take`. Keys of instances"]
+                            #[doc = "that are no longer stored are skipped."]
+                            #option_doc
+                            #[allow(clippy::result_large_err)]
+                            pub fn #method_name<'a, T: Into<#query_type> + 'a>(value: T, client: &'a mut tikv_client::Transaction) -> impl futures::Stream<Item = Result<Self, tikv_client::Error>> + 'a {
+                                let value: #field_type = #into_field;
+                                // Built outside the stream, which cannot use `?` within `format!`
+                                let index_key = (|| -> Result<String, tikv_client::Error> { Ok(#index_key) })();
+
+                                async_stream::try_stream! {
+                                    let index_key = index_key?;
+                                    if let Some(keys_bytes) = client.get(index_key.clone()).await? {
+                                        let listed: Vec<#key_type> = ::ergokv::ciborium::de::from_reader(keys_bytes.as_slice())
+                                            .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+                                        for key in listed {
+                                            if Self::key_exists(&key, client).await? {
+                                                yield Self::load(&key, client).await?;
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    } else {
+                        quote! {
+                            #[doc = concat!("Find all ", stringify!(#name), " by its ", stringify!(#field_name), " field.")]
+                            #[doc = ""]
+                            #doc
+                            #option_doc
+                            pub async fn #method_name<T: Into<#query_type>>(value: T, client: &mut tikv_client::Transaction) -> Result<Vec<Self>, tikv_client::Error> {
+                                Self::#ref_method_name(&#into_field, client).await
+                            }
+
+                            #ref_doc
+                            pub async fn #ref_method_name<Q: ::ergokv::serde::Serialize + ?Sized>(value: &Q, client: &mut tikv_client::Transaction) -> Result<Vec<Self>, tikv_client::Error> #ref_bound {
+                                let index_key = #ref_index_key;
+                                if let Some(keys_bytes) = client.get(index_key.clone()).await? {
+                                    let listed: Vec<#key_type> = ::ergokv::ciborium::de::from_reader(keys_bytes.as_slice())
+                                        .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+                                    let keys = #existing_in_txn;
+
+                                    // Instances deleted without their index entries are pruned from the list
+                                    if keys.len() < listed.len() {
+                                        if keys.is_empty() {
+                                            client.delete(index_key).await?;
+                                        } else {
+                                            let mut value = Vec::new();
+                                            ::ergokv::ciborium::ser::into_writer(&keys, &mut value)
+                                                .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+                                            client.put(index_key, value).await?;
+                                        }
+                                    }
+
+                                    #load
+                                } else {
+                                    Ok(Vec::new())
+                                }
+                            }
+                        }
+                    };
+
+                    quote! {
+                        #lookup
+
+                        #[doc = concat!("Find one ", stringify!(#name), " by its ", stringify!(#field_name), " field.")]
+                        #[doc = ""]
+                        #[doc = concat!("Like [`", stringify!(#method_name), "`](Self::", stringify!(#method_name), "), but only loads the first instance listed in the")]
+                        #[doc = "index, which is cheaper for values that usually have a single match. Keys of instances that"]
+                        #[doc = "are no longer stored are skipped. Returns `None` if no instance has the value."]
+                        pub async fn #one_method_name<T: Into<#query_type>>(value: T, client: &mut tikv_client::Transaction) -> Result<Option<Self>, tikv_client::Error> {
+                            let value: #field_type = #into_field;
+                            let index_key = #index_key;
+                            let Some(keys_bytes) = client.get(index_key.clone()).await? else {
+                                return Ok(None);
+                            };
+                            let listed: Vec<#key_type> = ::ergokv::ciborium::de::from_reader(keys_bytes.as_slice())
+                                .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+
+                            for key in listed {
+                                if Self::key_exists(&key, client).await? {
+                                    let record = Self::load(&key, client).await?;
+                                    #found
+                                }
+                            }
+                            Ok(None)
+                        }
+
+                        #[doc = concat!("Like [`", stringify!(#method_name), "`](Self::", stringify!(#method_name), "), but loads up to `concurrency` instances at once.")]
+                        #[doc = ""]
+                        #[doc = "A transaction can only serve one request at a time, so the reads go to read-only"]
+                        #[doc = "snapshots at the current timestamp instead, see [`load_many_at`](Self::load_many_at)."]
+                        #[doc = "The result is the same as that of a transaction started now, but it does not see"]
+                        #[doc = "the uncommitted writes of any transaction."]
+                        pub async fn #concurrent_method_name<T: Into<#query_type>>(value: T, client: &tikv_client::TransactionClient, concurrency: usize) -> Result<Vec<Self>, tikv_client::Error> {
+                            let value: #field_type = #into_field;
+                            let timestamp = client.current_timestamp().await?;
+                            let mut snapshot = client.snapshot(timestamp.clone(), tikv_client::TransactionOptions::new_optimistic().read_only());
+
+                            let index_key = #index_key;
+                            let Some(keys_bytes) = snapshot.get(index_key.clone()).await? else {
+                                return Ok(Vec::new());
+                            };
+                            let listed: Vec<#key_type> = ::ergokv::ciborium::de::from_reader(keys_bytes.as_slice())
+                                .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+                            let keys = #existing_in_snapshot;
+
+                            #load_concurrently
+                        }
+                    }
+                },
+                IndexKind::Range => {
+                    let range_method_name = format_ident!("by_{}_range", field_name.clone().expect("Missing field name"));
+                    quote! {
+                        #[doc = concat!("Find all ", stringify!(#name), " whose ", stringify!(#field_name), " field lies in `from..to`.")]
+                        #[doc = ""]
+                        #[doc = concat!("This method scans the range index on the ", stringify!(#field_name), " field. Results are ordered by ", stringify!(#field_name), ", ascending.")]
+                        pub async fn #range_method_name<T: Into<#field_type>>(from: T, to: T, client: &mut tikv_client::Transaction) -> Result<Vec<Self>, tikv_client::Error> {
+                            let prefix = format!(
+                                "ergokv:{}:range_index:{}:",
+                                Self::MODEL_NAME,
+                                stringify!(#field_name),
+                            );
+                            let start = format!("{}{}", prefix, ::ergokv::RangeKey::range_key(&from.into()));
+                            let end = format!("{}{}", prefix, ::ergokv::RangeKey::range_key(&to.into()));
+
+                            let entries: Vec<_> = client.scan(start..end, u32::MAX).await?.collect();
+
+                            let mut results = Vec::new();
+                            for entry in entries {
+                                let key: #key_type = ::ergokv::ciborium::de::from_reader(entry.value().as_slice())
+                                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} range index entry for {}: {}", Self::MODEL_NAME, stringify!(#field_name), e)))?;
+                                results.push(Self::load(&key, client).await?);
                             }
                             Ok(results)
-                        } else {
-                            Ok(Vec::new())
                         }
                     }
                 }
+            };
+
+            quote! {
+                #methods
+                #in_method
+                #group_by_method
+            }
+        })
+        .collect()
+}
+
+/// Generates `group_by_<field>`, which groups all instances by the value of an indexed
+/// field, finding them through the field's index.
+fn generate_group_by_method(
+    name: &Ident,
+    field: &Field,
+    kind: IndexKind,
+    key_field: &Field,
+) -> TokenStream2 {
+    let field_name = &field.ident;
+    let field_type = &field.ty;
+    let key_type = &key_field.ty;
+    let method_name = format_ident!(
+        "group_by_{}",
+        field_name.clone().expect("Missing field name")
+    );
+    let existing_keys =
+        generate_existing_keys(key_field, quote! { client });
+
+    let namespace = match kind {
+        IndexKind::Unique => "unique_index",
+        IndexKind::NonUnique => "index",
+        IndexKind::Hashed => "hashed_index",
+        IndexKind::Range => "range_index",
+    };
+    // Lists of keys are only kept by `#[index]` and `#[index(hashed)]`, the other
+    // kinds have an entry per instance
+    let entry_keys = match kind {
+        IndexKind::NonUnique | IndexKind::Hashed => quote! {
+            ::ergokv::ciborium::de::from_reader::<Vec<#key_type>, _>(pair.value().as_slice())
+        },
+        IndexKind::Unique | IndexKind::Range => quote! {
+            ::ergokv::ciborium::de::from_reader::<#key_type, _>(pair.value().as_slice()).map(|key| vec![key])
+        },
+    };
+
+    quote! {
+        #[doc = concat!("Groups all ", stringify!(#name), " by their ", stringify!(#field_name), " field.")]
+        #[doc = ""]
+        #[doc = concat!("The instances are found by scanning the index on the ", stringify!(#field_name), " field, and loaded")]
+        #[doc = "with one batch get, instead of loading every instance and grouping them afterwards."]
+        #[doc = "Instances whose value is not indexed, like the default values of `#[index(sparse)]`"]
+        #[doc = "fields, are left out. The field's type must implement `Hash`, `Eq` and `Clone`."]
+        pub async fn #method_name(client: &mut tikv_client::Transaction) -> Result<::std::collections::HashMap<#field_type, Vec<Self>>, tikv_client::Error>
+        where
+            // Only checked where the method is used, so that fields of other types still compile
+            for<'a> #field_type: ::std::hash::Hash + Eq + Clone,
+        {
+            let prefix = format!("ergokv:{}:{}:{}:", Self::MODEL_NAME, #namespace, stringify!(#field_name));
+            let end = format!("{};", &prefix[..prefix.len() - 1]);
+
+            let mut seen = ::std::collections::HashSet::new();
+            let mut listed: Vec<#key_type> = Vec::new();
+            for pair in client.scan(prefix..end, u32::MAX).await? {
+                let entry_keys = #entry_keys
+                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} index entry for {}: {}", Self::MODEL_NAME, stringify!(#field_name), e)))?;
+                for key in entry_keys {
+                    if seen.insert(Self::encode_key(&key)?) {
+                        listed.push(key);
+                    }
+                }
+            }
+            if listed.is_empty() {
+                return Ok(::std::collections::HashMap::new());
+            }
+
+            let keys = #existing_keys;
+            let mut groups: ::std::collections::HashMap<#field_type, Vec<Self>> = ::std::collections::HashMap::new();
+            for record in Self::load_many(&keys, client).await? {
+                groups.entry(record.#field_name.clone()).or_default().push(record);
+            }
+            Ok(groups)
+        }
+    }
+}
+
+/// Generates `by_<field>_in`, which finds the instances whose indexed field has any of
+/// several values, reading all of their index entries with one batch get.
+fn generate_in_method(
+    name: &Ident,
+    field: &Field,
+    kind: IndexKind,
+    key_field: &Field,
+) -> TokenStream2 {
+    let field_name = &field.ident;
+    let key_type = &key_field.ty;
+    let method_name = format_ident!(
+        "by_{}_in",
+        field_name.clone().expect("Missing field name")
+    );
+    let existing_keys =
+        generate_existing_keys(key_field, quote! { client });
+    let (query_type, _) = index_query_type(field);
+    let is_option = option_inner_type(field).is_some();
+    // `Some(&value)` serializes like `Some(value)`, so values need not be cloned
+    let wrap =
+        is_option.then(|| quote! { let value = &Some(value); });
+    let option_doc = is_option.then(|| quote! {
+        #[doc = ""]
+        #[doc = concat!("The ", stringify!(#field_name), " field is an `Option`, so it takes the values inside and looks up `Some(value)`;")]
+        #[doc = "instances whose field is `None` are not found by any value."]
+    });
+
+    let (index_key, entry_keys) = if kind == IndexKind::Unique {
+        (
+            quote! {
+                format!(
+                    "ergokv:{}:unique_index:{}:{}",
+                    Self::MODEL_NAME,
+                    stringify!(#field_name),
+                    ::ergokv::serde_json::to_string(value)
+                        .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {}.{} for its index: {}", Self::MODEL_NAME, stringify!(#field_name), e)))?
+                )
+            },
+            quote! {
+                let key: #key_type = ::ergokv::ciborium::de::from_reader(bytes.as_slice())
+                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+                vec![key]
+            },
+        )
+    } else {
+        (
+            list_index_key(field, kind, quote! { value }),
+            quote! {
+                ::ergokv::ciborium::de::from_reader::<Vec<#key_type>, _>(bytes.as_slice())
+                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?
+            },
+        )
+    };
+    // Distinct values may share a hash, so the loaded values have to be checked
+    let load = if kind == IndexKind::Hashed {
+        let matches = if is_option {
+            quote! { record.#field_name.as_ref().is_some_and(|value| values.contains(value)) }
+        } else {
+            quote! { values.contains(&record.#field_name) }
+        };
+        quote! {
+            let mut results = Self::load_many(&keys, client).await?;
+            results.retain(|record| #matches);
+            Ok(results)
+        }
+    } else {
+        quote! { Self::load_many(&keys, client).await }
+    };
+
+    quote! {
+        #[doc = concat!("Find all ", stringify!(#name), " whose ", stringify!(#field_name), " field has any of the given values.")]
+        #[doc = ""]
+        #[doc = "The index entries of all values are read with one batch get, and every instance is"]
+        #[doc = "loaded once, even if it is listed more than once. Instances are returned in the order"]
+        #[doc = "of the values they were found by. Keys of instances that are no longer stored are skipped."]
+        #option_doc
+        pub async fn #method_name(values: &[#query_type], client: &mut tikv_client::Transaction) -> Result<Vec<Self>, tikv_client::Error> {
+            if values.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            let mut index_keys = Vec::with_capacity(values.len());
+            for value in values {
+                #wrap
+                index_keys.push(#index_key);
+            }
+            let entries: ::std::collections::HashMap<Vec<u8>, Vec<u8>> = client
+                .batch_get(index_keys.clone())
+                .await?
+                .map(|pair| {
+                    let (key, value): (tikv_client::Key, tikv_client::Value) = pair.into();
+                    (key.into(), value)
+                })
+                .collect();
+
+            let mut seen = ::std::collections::HashSet::new();
+            let mut listed: Vec<#key_type> = Vec::new();
+            for index_key in &index_keys {
+                let Some(bytes) = entries.get(index_key.as_bytes()) else {
+                    continue;
+                };
+                let entry_keys: Vec<#key_type> = { #entry_keys };
+                for key in entry_keys {
+                    if seen.insert(Self::encode_key(&key)?) {
+                        listed.push(key);
+                    }
+                }
+            }
+            if listed.is_empty() {
+                return Ok(Vec::new());
+            }
+            let keys = #existing_keys;
+
+            #load
+        }
+    }
+}
+
+/// Generates a `search_<field>` method for every `#[index(search)]` field, which streams
+/// the instances whose value starts with a prefix.
+fn generate_search_methods(
+    name: &Ident,
+    fields: &Punctuated<Field, Comma>,
+) -> Vec<TokenStream2> {
+    fields
+        .iter()
+        .filter(|f| is_search(f))
+        .map(|f| {
+            let field_name = &f.ident;
+            let method_name = format_ident!(
+                "search_{}",
+                field_name.clone().expect("Missing field name")
+            );
+            let index_method = format_ident!(
+                "by_{}",
+                field_name.clone().expect("Missing field name")
+            );
+            let trie = search_trie(f);
+
+            quote! {
+                #[doc = concat!("Streams all ", stringify!(#name), " whose ", stringify!(#field_name), " field starts with `prefix`.")]
+                #[doc = ""]
+                #[doc = concat!("Matching values are found in the search trie of the ", stringify!(#field_name), " field and")]
+                #[doc = "yielded in lexicographic order, instances sharing a value in the order of the index."]
+                pub fn #method_name<'a>(prefix: &'a str, txn: &'a mut tikv_client::Transaction) -> impl futures::Stream<Item = Result<Self, tikv_client::Error>> + 'a {
+                    async_stream::try_stream! {
+                        let values = #trie.find_by_prefix(txn, prefix).await?;
+                        for value in values {
+                            for record in Self::#index_method(value, txn).await? {
+                                yield record;
+                            }
+                        }
+                    }
+                }
+            }
+        })
+        .collect()
+}
+
+/// Generates a `by_<field>_exists` method for every indexed field, which checks the
+/// index without loading any instance.
+fn generate_exists_methods(
+    fields: &Punctuated<Field, Comma>,
+    key_field: &Field,
+) -> Vec<TokenStream2> {
+    let key_type = &key_field.ty;
+
+    fields
+        .iter()
+        .filter_map(|f| index_kind(f).map(|kind| (f, kind)))
+        .map(|(f, kind)| {
+            let field_name = &f.ident;
+            let field_type = &f.ty;
+            let method_name = format_ident!(
+                "by_{}_exists",
+                field_name.clone().expect("Missing field name")
+            );
+            let (query_type, into_field) = index_query_type(f);
+
+            let check = match kind {
+                IndexKind::Unique => quote! {
+                    let index_key = format!(
+                        "ergokv:{}:unique_index:{}:{}",
+                        Self::MODEL_NAME,
+                        stringify!(#field_name),
+                        ::ergokv::serde_json::to_string(&value)
+                            .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {}.{} for its index: {}", Self::MODEL_NAME, stringify!(#field_name), e)))?
+                    );
+                    Ok(txn.get(index_key.clone()).await?.is_some())
+                },
+                IndexKind::NonUnique => {
+                    let index_key = list_index_key(f, kind, quote! { &value });
+                    quote! {
+                        let index_key = #index_key;
+                        match txn.get(index_key.clone()).await? {
+                            Some(keys_bytes) => {
+                                let keys: Vec<#key_type> = ::ergokv::ciborium::de::from_reader(keys_bytes.as_slice())
+                                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+                                Ok(!keys.is_empty())
+                            }
+                            None => Ok(false),
+                        }
+                    }
+                }
+                IndexKind::Hashed => {
+                    let index_key = list_index_key(f, kind, quote! { &value });
+                    let decode = decode_field_value(f);
+                    quote! {
+                        let index_key = #index_key;
+                        let Some(keys_bytes) = txn.get(index_key.clone()).await? else {
+                            return Ok(false);
+                        };
+                        let keys: Vec<#key_type> = ::ergokv::ciborium::de::from_reader(keys_bytes.as_slice())
+                            .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} index entry {}: {}", Self::MODEL_NAME, index_key, e)))?;
+
+                        // Distinct values may share a hash, so only the field itself is compared
+                        for key in keys {
+                            let field_key = format!(
+                                "ergokv:{}:{}",
+                                Self::record_path(&key)?,
+                                stringify!(#field_name)
+                            );
+                            if let Some(stored_bytes) = txn.get(field_key.clone()).await? {
+                                let stored: #field_type = {
+                                    let value = stored_bytes;
+                                    #decode
+                                        .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {}.{} at {}: {}", Self::MODEL_NAME, stringify!(#field_name), field_key, e)))?
+                                };
+                                if stored == value {
+                                    return Ok(true);
+                                }
+                            }
+                        }
+                        Ok(false)
+                    }
+                }
+                IndexKind::Range => quote! {
+                    let prefix = format!(
+                        "ergokv:{}:range_index:{}:{}:",
+                        Self::MODEL_NAME,
+                        stringify!(#field_name),
+                        ::ergokv::RangeKey::range_key(&value),
+                    );
+                    // ';' directly follows ':', so this covers every key starting with `prefix`
+                    let end = format!("{};", &prefix[..prefix.len() - 1]);
+                    Ok(txn.scan(prefix..end, 1).await?.next().is_some())
+                },
+            };
+
+            quote! {
+                #[doc = concat!("Checks whether any instance has the given ", stringify!(#field_name), " value.")]
+                #[doc = ""]
+                #[doc = "Only the index is consulted, no instance is loaded."]
+                pub async fn #method_name<T: Into<#query_type>>(value: T, txn: &mut tikv_client::Transaction) -> Result<bool, tikv_client::Error> {
+                    let value: #field_type = #into_field;
+                    #check
+                }
+            }
+        })
+        .collect()
+}
+
+/// Generates the in-memory `with_<field>` builder methods, which write nothing to TiKV.
+fn generate_with_methods(
+    fields: &Punctuated<Field, Comma>,
+    options: &StoreOptions,
+) -> Vec<TokenStream2> {
+    fields
+        .iter()
+        .filter(|f| !is_managed_timestamp(f, options))
+        .map(|f| {
+            let field_name = &f.ident;
+            let field_type = &f.ty;
+            let method_name = format_ident!(
+                "with_{}",
+                field_name.clone().expect("Missing field name")
+            );
+
+            quote! {
+                /// Returns the instance with the field replaced by `value`.
+                ///
+                /// Only the instance in memory changes. Use [`save`](Self::save) to write it,
+                /// or `set_<field>` to write a single field right away.
+                #[must_use = "the changed instance is returned, not written"]
+                pub fn #method_name(mut self, value: #field_type) -> Self {
+                    self.#field_name = value;
+                    self
+                }
+            }
+        })
+        .collect()
+}
+
+fn generate_set_methods(
+    fields: &Punctuated<Field, Comma>,
+    options: &StoreOptions,
+) -> Vec<TokenStream2> {
+    let touch = options.timestamps.then(|| {
+        quote! { self.touch_updated_at(txn).await?; }
+    });
+    let checksum_write = generate_checksum_write(options);
+
+    fields.iter().filter(|f| !is_managed_timestamp(f, options) && !FieldOptions::from_field(f).immutable).map(|f| {
+        let field_name = &f.ident;
+        let field_type = &f.ty;
+        let method_name = format_ident!("set_{}", field_name.clone().expect("Missing field name"));
+        let key_field = fields.iter().find(|f| f.attrs.iter().any(|a| a.path().is_ident("key")))
+            .expect("A field with #[key] attribute is required");
+        let key_ident = &key_field.ident;
+        let checks = generate_mutation_checks(options);
+        let audit = generate_audit_append(options, "Set", std::iter::once(f));
+        let invalidate = generate_cache_invalidation(options, key_field);
+
+        let (index_remove, index_insert) = match index_kind(f) {
+            Some(kind) => (
+                generate_index_remove(f, kind, key_field, quote! { &self.#field_name }),
+                generate_index_insert(f, kind, key_field),
+            ),
+            None => (quote! {}, quote! {}),
+        };
+        let buffered = generate_buffered_set_method(f, key_field, options);
+        let (encode, write) = match encode_field_write(f, set_record_path(f, key_field), quote! { &new_value }) {
+            Some(encoded) => (
+                quote! { let write = #encoded; },
+                quote! {
+                    let (key, value) = write;
+                    txn.put(key, value).await?;
+                },
+            ),
+            None => (quote! {}, write_field_value(f, quote! { Self::record_path(&self.#key_ident)? })),
+        };
+        // Plain encodings, as encrypting the same value twice gives different bytes
+        let encode_current = encode_plain_field_value(f, quote! { &self.#field_name });
+        let encode_new = encode_plain_field_value(f, quote! { &new_value });
+
+        quote! {
+            #buffered
+
+            #[doc = concat!("Sets the ", stringify!(#field_name), " field and writes it, along with its index entries.")]
+            #[doc = ""]
+            #[doc = "Nothing is written if the new value serializes to the same bytes as the current one."]
+            pub async fn #method_name(&mut self, new_value: #field_type, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+                let current = {
+                    let mut value = Vec::new();
+                    #encode_current.ok().map(|_| value)
+                };
+                let new = {
+                    let mut value = Vec::new();
+                    #encode_new.ok().map(|_| value)
+                };
+                if current.is_some() && current == new {
+                    return Ok(());
+                }
+
+                #checks
+                #encode
+                #audit
+                #invalidate
+
+                // Remove old index entry
+                #index_remove
+
+                // Update field
+                self.#field_name = new_value;
+
+                // Add new index entry
+                #index_insert
+
+                // Save updated field
+                #write
+                #touch
+                #checksum_write
+
+                Ok(())
+            }
+        }
+    }).chain(generate_touch_method(fields, options)).collect()
+}
+
+/// The record path a `set_<field>` writes the new value of `field` to, which for the key
+/// field is the one of the new key.
+fn set_record_path(
+    field: &Field,
+    key_field: &Field,
+) -> TokenStream2 {
+    if field.ident == key_field.ident {
+        quote! { Self::record_path(&new_value)? }
+    } else {
+        let key_ident = &key_field.ident;
+        quote! { Self::record_path(&self.#key_ident)? }
+    }
+}
+
+/// Generates `set_<field>_buffered`, which queues the write of `set_<field>` in an
+/// `ergokv::WriteBuffer` instead of writing it to a transaction.
+///
+/// Only writes that need no reads can wait in a buffer, so indexed and flattened fields,
+/// and models with migration checks, an audit log, managed timestamps, a cache or checksums
+/// get none.
+fn generate_buffered_set_method(
+    field: &Field,
+    key_field: &Field,
+    options: &StoreOptions,
+) -> Option<TokenStream2> {
+    if index_kind(field).is_some()
+        || FieldOptions::from_field(field).flatten
+        || is_strict(options)
+        || options.audit_log
+        || options.timestamps
+        || options.cache_ttl.is_some()
+        || options.checksum.is_some()
+    {
+        return None;
+    }
+
+    let field_name = &field.ident;
+    let field_type = &field.ty;
+    let method_name = format_ident!(
+        "set_{}_buffered",
+        field_name.clone().expect("Missing field name")
+    );
+    let encoded = encode_field_write(
+        field,
+        set_record_path(field, key_field),
+        quote! { &new_value },
+    )?;
+
+    Some(quote! {
+        #[doc = concat!("Like `set_", stringify!(#field_name), "`, but queues the write in `buffer` until it is flushed.")]
+        #[doc = ""]
+        #[doc = "Setting the field again before the flush replaces the queued write."]
+        pub fn #method_name(&mut self, new_value: #field_type, buffer: &mut ::ergokv::WriteBuffer) -> Result<(), tikv_client::Error> {
+            let (key, value) = #encoded;
+            self.#field_name = new_value;
+            buffer.put(key, value)?;
+            Ok(())
+        }
+    })
+}
+
+/// Generates `touch_updated_at` for models with `#[store(timestamps)]`, which sets
+/// `updated_at` to the current time after a `set_<field>`.
+fn generate_touch_method(
+    fields: &Punctuated<Field, Comma>,
+    options: &StoreOptions,
+) -> Option<TokenStream2> {
+    if !options.timestamps {
+        return None;
+    }
+
+    let key_field = fields
+        .iter()
+        .find(|f| {
+            f.attrs.iter().any(|a| a.path().is_ident("key"))
+        })
+        .expect("A field with #[key] attribute is required");
+    let key_ident = &key_field.ident;
+    let field = fields
+        .iter()
+        .find(|f| {
+            f.ident.as_ref().is_some_and(|i| i == "updated_at")
+        })
+        .expect("Missing updated_at field");
+    let field_type = &field.ty;
+
+    let (index_remove, index_insert) = match index_kind(field) {
+        Some(kind) => (
+            generate_index_remove(
+                field,
+                kind,
+                key_field,
+                quote! { &self.updated_at },
+            ),
+            generate_index_insert(field, kind, key_field),
+        ),
+        None => (quote! {}, quote! {}),
+    };
+    let encode =
+        encode_field_value(field, quote! { &self.updated_at });
+
+    Some(quote! {
+        async fn touch_updated_at(&mut self, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            #index_remove
+            self.updated_at = <#field_type as ::ergokv::AutoTimestamp>::now();
+            #index_insert
+
+            let key = format!(
+                "ergokv:{}:updated_at",
+                Self::record_path(&self.#key_ident)?,
+            );
+            let mut value = Vec::new();
+            #encode
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {}.updated_at at {}: {}", Self::MODEL_NAME, key, e)))?;
+            txn.put(key, value).await?;
+
+            Ok(())
+        }
+    })
+}
+
+/// Generates a `load_field_<field>` method for every non-key field, reading only that field.
+fn generate_projection_methods(
+    fields: &Punctuated<Field, Comma>,
+    key_field: &Field,
+) -> Vec<TokenStream2> {
+    let key_type = &key_field.ty;
+
+    fields
+        .iter()
+        .filter(|f| f.ident != key_field.ident)
+        .map(|f| {
+            let field_name = &f.ident;
+            let field_type = &f.ty;
+            let method_name = format_ident!(
+                "load_field_{}",
+                field_name.clone().expect("Missing field name")
+            );
+            let read = read_field_value(
+                f,
+                quote! { Self::record_path(key)? },
+                quote! { value },
+            );
+
+            let field_options = FieldOptions::from_field(f);
+            let archived = (field_options.format == Format::Rkyv
+                && !field_options.raw_bytes
+                && !field_options.flatten)
+                .then(|| {
+                    let archived_method_name = format_ident!(
+                        "load_field_{}_archived",
+                        field_name.clone().expect("Missing field name")
+                    );
+                    let plaintext = plaintext_field_value(f);
+
+                    quote! {
+                        #[doc = concat!("Like [`", stringify!(#method_name), "`](Self::", stringify!(#method_name), "), but returns the archived value, whose")]
+                        #[doc = "contents are read in place instead of being deserialized."]
+                        #[doc = ""]
+                        #[doc = "Values still stored in another format, from before the field moved to rkyv, are an error"]
+                        #[doc = "until they are written again, e.g. by `rewrite_all`."]
+                        pub async fn #archived_method_name(key: &#key_type, txn: &mut tikv_client::Transaction) -> Result<::ergokv::ArchivedValue<#field_type>, tikv_client::Error> {
+                            let key = format!(
+                                "ergokv:{}:{}",
+                                Self::record_path(key)?,
+                                stringify!(#field_name)
+                            );
+                            let value = txn.get(key.clone()).await?
+                                .ok_or_else(|| tikv_client::Error::StringError(format!("No {}.{} stored at {}", Self::MODEL_NAME, stringify!(#field_name), key)))?;
+                            #plaintext
+                            let value = match ::ergokv::split_format_tag(&value) {
+                                (::ergokv::StoredFormat::Rkyv, bytes) => bytes,
+                                (format, _) => return Err(tikv_client::Error::StringError(format!("{}.{} at {} is stored as {}, not archived with rkyv", Self::MODEL_NAME, stringify!(#field_name), key, format))),
+                            };
+                            ::ergokv::ArchivedValue::new(value)
+                                .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {}.{} at {}: {}", Self::MODEL_NAME, stringify!(#field_name), key, e)))
+                        }
+                    }
+                });
+
+            quote! {
+                #[doc = concat!("Loads only the ", stringify!(#field_name), " field of the instance with the given key.")]
+                #[doc = ""]
+                #[doc = "This reads only the keys of that field, instead of those of every field like [`load`](Self::load)."]
+                pub async fn #method_name(key: &#key_type, txn: &mut tikv_client::Transaction) -> Result<#field_type, tikv_client::Error> {
+                    #read
+                    Ok(value)
+                }
+
+                #archived
+            }
+        })
+        .collect()
+}
+
+/// Generates a `cas_<field>` compare-and-swap method for every non-key field.
+fn generate_cas_methods(
+    fields: &Punctuated<Field, Comma>,
+    key_field: &Field,
+    options: &StoreOptions,
+) -> Vec<TokenStream2> {
+    let key_ident = &key_field.ident;
+
+    fields
+        .iter()
+        .filter(|f| {
+            f.ident != key_field.ident
+                && !is_managed_timestamp(f, options)
+                && !FieldOptions::from_field(f).immutable
+        })
+        .map(|f| {
+            let field_name = &f.ident;
+            let field_type = &f.ty;
+            let field_str = field_name.clone().expect("Missing field name");
+            let read = read_field_value(
+                f,
+                quote! { Self::record_path(&self.#key_ident)? },
+                quote! { current },
+            );
+            let method_name = format_ident!("cas_{}", field_str);
+            let set_method = format_ident!("set_{}", field_str);
+
+            quote! {
+                #[doc = concat!("Sets the ", stringify!(#field_name), " field to `new`, but only if its stored value equals `expected`.")]
+                #[doc = ""]
+                #[doc = "The comparison uses the value stored in the transaction, not the one in `self`, which is"]
+                #[doc = "refreshed to the stored value before any change. Returns whether the swap happened."]
+                pub async fn #method_name<E>(&mut self, expected: E, new: #field_type, txn: &mut tikv_client::Transaction) -> Result<bool, tikv_client::Error>
+                where
+                    #field_type: PartialEq<E>,
+                {
+                    #read
+
+                    let matches = current == expected;
+                    // Indexes are maintained based on the field value in `self`
+                    self.#field_name = current;
+
+                    if matches {
+                        self.#set_method(new, txn).await?;
+                    }
+                    Ok(matches)
+                }
+            }
+        })
+        .collect()
+}
+
+/// Generates a `lock_and_set_<field>` method for every non-key field, updating the
+/// field under a pessimistic lock.
+fn generate_lock_methods(
+    fields: &Punctuated<Field, Comma>,
+    key_field: &Field,
+    options: &StoreOptions,
+) -> Vec<TokenStream2> {
+    let key_ident = &key_field.ident;
+
+    // A flattened field has no single key to lock
+    fields
+        .iter()
+        .filter(|f| {
+            f.ident != key_field.ident
+                && !is_managed_timestamp(f, options)
+                && !FieldOptions::from_field(f).flatten
+                && !FieldOptions::from_field(f).immutable
+        })
+        .map(|f| {
+            let field_name = &f.ident;
+            let field_type = &f.ty;
+            let field_str = field_name.clone().expect("Missing field name");
+            let decode = decode_field_value(f);
+            let method_name = format_ident!("lock_and_set_{}", field_str);
+            let set_method = format_ident!("set_{}", field_str);
+
+            quote! {
+                #[doc = concat!("Locks the stored ", stringify!(#field_name), " field and sets it to `update` applied to its locked value.")]
+                #[doc = ""]
+                #[doc = "The transaction must be pessimistic (`begin_pessimistic`). The lock is held until it"]
+                #[doc = "commits or rolls back, so concurrent updates of the field wait for each other instead"]
+                #[doc = "of conflicting on commit, and none of them is lost. `self` is refreshed to the stored"]
+                #[doc = "value before the update. In an optimistic transaction, this behaves like a plain"]
+                #[doc = "read followed by `set_` and conflicts are only detected on commit."]
+                pub async fn #method_name(&mut self, update: impl FnOnce(&#field_type) -> #field_type, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+                    let key = format!(
+                        "ergokv:{}:{}",
+                        Self::record_path(&self.#key_ident)?,
+                        stringify!(#field_name)
+                    );
+                    let value = txn.get_for_update(key.clone()).await?
+                        .ok_or_else(|| tikv_client::Error::StringError(format!("No {}.{} stored at {}", Self::MODEL_NAME, stringify!(#field_name), key)))?;
+                    let current: #field_type = #decode
+                        .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {}.{} at {}: {}", Self::MODEL_NAME, stringify!(#field_name), key, e)))?;
+
+                    let new_value = update(&current);
+                    // Indexes are maintained based on the field value in `self`
+                    self.#field_name = current;
+                    self.#set_method(new_value, txn).await
+                }
+            }
+        })
+        .collect()
+}
+
+/// Generates the `{Name}Patch` struct, holding an optional value for every non-key field,
+/// along with `apply_patch` and `upsert_patch`.
+fn generate_patch(
+    name: &Ident,
+    vis: &syn::Visibility,
+    fields: &Punctuated<Field, Comma>,
+    key_field: &Field,
+    options: &StoreOptions,
+) -> (TokenStream2, TokenStream2) {
+    let patch_name = format_ident!("{}Patch", name);
+    let key_type = &key_field.ty;
+    let key_ident = &key_field.ident;
+    let patch_fields: Vec<_> = fields
+        .iter()
+        .filter(|f| {
+            f.ident != key_field.ident
+                && !is_managed_timestamp(f, options)
+                && !is_computed(f)
+        })
+        .collect();
+
+    // Managed timestamps are set by `save`, which the created instance is reloaded after
+    let upserted = if options.timestamps {
+        quote! { Self::load(key, txn).await }
+    } else {
+        quote! { Ok(record) }
+    };
+
+    let struct_fields = patch_fields.iter().map(|f| {
+        let field_name = &f.ident;
+        let field_type = &f.ty;
+        quote! { #vis #field_name: Option<#field_type> }
+    });
+
+    let update_name = format_ident!("{}Update", name);
+    let checks = generate_mutation_checks(options);
+    let invalidate =
+        generate_cache_invalidation(options, key_field);
+    let audit = options.audit_log.then(|| {
+        quote! {
+            self.append_audit(::ergokv::AuditOperation::Set, &changed, txn).await?;
+        }
+    });
+    let touch = options.timestamps.then(|| {
+        quote! { self.touch_updated_at(txn).await?; }
+    });
+    let checksum_write = generate_checksum_write(options);
+
+    let changed_names = patch_fields
+        .iter()
+        .filter(|f| !FieldOptions::from_field(f).immutable)
+        .map(|f| {
+            let field_name = &f.ident;
+            quote! {
+                if patch.#field_name.is_some() {
+                    changed.push(stringify!(#field_name));
+                }
+            }
+        });
+
+    // Patches may carry immutable fields to create an instance, but not to change one
+    let immutable_checks = patch_fields
+        .iter()
+        .filter(|f| FieldOptions::from_field(f).immutable)
+        .map(|f| {
+            let field_name = &f.ident;
+            quote! {
+                if patch.#field_name.as_ref().is_some_and(|value| *value != self.#field_name) {
+                    return Err(tikv_client::Error::StringError(format!(
+                        "Cannot update {}: {} is immutable",
+                        Self::MODEL_NAME,
+                        stringify!(#field_name),
+                    )));
+                }
+            }
+        });
+
+    let field_encodings = patch_fields
+        .iter()
+        .filter(|f| !FieldOptions::from_field(f).immutable)
+        .filter_map(|f| {
+            let field_name = &f.ident;
+            encode_field_write(
+                f,
+                quote! { Self::record_path(&self.#key_ident)? },
+                quote! { new_value },
+            )
+            .map(|encoded| {
+                quote! {
+                    if let Some(new_value) = &patch.#field_name {
+                        encoded.push(#encoded);
+                    }
+                }
+            })
+        });
+
+    let field_updates = patch_fields
+        .iter()
+        .filter(|f| !FieldOptions::from_field(f).immutable)
+        .map(|f| {
+            let field_name = &f.ident;
+            let (index_remove, index_insert) =
+                match index_kind(f) {
+                    Some(kind) => (
+                        generate_index_remove(
+                            f,
+                            kind,
+                            key_field,
+                            quote! { &self.#field_name },
+                        ),
+                        generate_index_insert(
+                            f, kind, key_field,
+                        ),
+                    ),
+                    None => (quote! {}, quote! {}),
+                };
+            // Other fields were encoded up front, and are written after the loop
+            let write = FieldOptions::from_field(f).flatten.then(|| {
+                write_field_value(
+                    f,
+                    quote! { Self::record_path(&self.#key_ident)? },
+                )
+            });
+            quote! {
+                if let Some(new_value) = patch.#field_name {
+                    #index_remove
+                    self.#field_name = new_value;
+                    #index_insert
+                    #write
+                }
+            }
+        });
+
+    let builder_setters = patch_fields.iter().filter(|f| !FieldOptions::from_field(f).immutable).map(|f| {
+        let field_name = &f.ident;
+        let field_type = &f.ty;
+        let method_name = format_ident!(
+            "set_{}",
+            field_name.clone().expect("Missing field name")
+        );
+        quote! {
+            #[doc = concat!("Sets the ", stringify!(#field_name), " field when the update is applied.")]
+            pub fn #method_name(mut self, new_value: #field_type) -> Self {
+                self.patch.#field_name = Some(new_value);
+                self
+            }
+        }
+    });
+
+    let construct = generate_construct(fields, |f| {
+        let field_name = &f.ident;
+        let field_type = &f.ty;
+        if f.ident == key_field.ident {
+            quote! { key.clone() }
+        } else if is_managed_timestamp(f, options) {
+            quote! { <#field_type as ::ergokv::AutoTimestamp>::now() }
+        } else {
+            quote! {
+                patch.#field_name.ok_or_else(|| tikv_client::Error::StringError(
+                    format!("Cannot create {}: missing field {}", stringify!(#name), stringify!(#field_name))
+                ))?
+            }
+        }
+    });
+
+    let patch_struct = quote! {
+        #[doc = concat!("A partial update of [`", stringify!(#name), "`].")]
+        #[doc = ""]
+        #[doc = concat!("Fields set to `Some` are written by [`", stringify!(#name), "::apply_patch`], fields left as `None` keep their stored value.")]
+        #[derive(Default)]
+        #vis struct #patch_name {
+            #(#struct_fields,)*
+        }
+
+        #[doc = concat!("Collects changes to the fields of a [`", stringify!(#name), "`], see [`", stringify!(#name), "::update`].")]
+        #[must_use = "the changes are only written by `apply`"]
+        #vis struct #update_name<'a> {
+            record: &'a mut #name,
+            patch: #patch_name,
+        }
+
+        impl #update_name<'_> {
+            #(#builder_setters)*
+
+            /// Writes the collected changes, and updates the instance they were collected for.
+            pub async fn apply(self, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+                self.record.apply_update(self.patch, txn).await
+            }
+        }
+    };
+
+    let patch_methods = quote! {
+        #[doc = concat!("Applies a [`", stringify!(#patch_name), "`] to the stored instance with the given key.")]
+        #[doc = ""]
+        #[doc = "Only fields set in the patch are written, and indexes are updated for any changed indexed"]
+        #[doc = "field. Returns the updated instance, or an error if no instance with this key exists."]
+        pub async fn apply_patch(key: &#key_type, patch: #patch_name, txn: &mut tikv_client::Transaction) -> Result<Self, tikv_client::Error> {
+            let mut record = Self::load(key, txn).await?;
+            record.apply_update(patch, txn).await?;
+            Ok(record)
+        }
+
+        #[doc = concat!("Starts an update of several fields, applied at once with [`", stringify!(#update_name), "::apply`].")]
+        #[doc = ""]
+        #[doc = "Unlike a `set_<field>` call per field, the migration check, the audit entry and the"]
+        #[doc = "`updated_at` timestamp are written once for the whole update."]
+        pub fn update(&mut self) -> #update_name<'_> {
+            #update_name {
+                record: self,
+                patch: #patch_name::default(),
+            }
+        }
+
+        /// Writes the fields set in `patch` and updates their index entries.
+        async fn apply_update(&mut self, patch: #patch_name, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            #(#immutable_checks)*
+
+            #[allow(unused_mut)]
+            let mut changed: Vec<&str> = Vec::new();
+            #(#changed_names)*
+            if changed.is_empty() {
+                return Ok(());
+            }
+
+            #checks
+
+            #[allow(unused_mut)]
+            let mut encoded: Vec<(String, Vec<u8>)> = Vec::new();
+            #(#field_encodings)*
+
+            #audit
+            #invalidate
+
+            #(#field_updates)*
+            for (key, value) in encoded {
+                txn.put(key, value).await?;
+            }
+            #touch
+            #checksum_write
+
+            Ok(())
+        }
+
+        #[doc = concat!("Like [`apply_patch`](Self::apply_patch), but creates the instance if it does not exist yet.")]
+        #[doc = ""]
+        #[doc = "Creating an instance requires every field of the patch to be set, otherwise an error is returned."]
+        pub async fn upsert_patch(key: &#key_type, patch: #patch_name, txn: &mut tikv_client::Transaction) -> Result<Self, tikv_client::Error> {
+            if Self::key_exists(key, txn).await? {
+                Self::apply_patch(key, patch, txn).await
+            } else {
+                let record = #construct;
+                record.save(txn).await?;
+                #upserted
+            }
+        }
+    };
+
+    (patch_struct, patch_methods)
+}
+
+/// Generates an assertion that the key type has the bounds the generated methods rely on.
+///
+/// Without it, a missing bound surfaces as an error deep inside some generated method,
+/// while this one is reported at the type of the `#[key]` field.
+fn generate_key_bounds_check(key_field: &Field) -> TokenStream2 {
+    let key_type = &key_field.ty;
+    let assertion = quote_spanned! {syn::spanned::Spanned::span(key_type)=>
+        assert_key_bounds::<#key_type>();
+    };
+
+    quote! {
+        const _: fn() = || {
+            fn assert_key_bounds<T>()
+            where
+                T: ::ergokv::serde::Serialize
+                    + ::ergokv::serde::de::DeserializeOwned
+                    + ::core::clone::Clone
+                    + ::core::cmp::PartialEq,
+            {
             }
+            #assertion
+        };
+    }
+}
+
+/// Returns the options given to `#[key(...)]` on the key field, if any.
+fn key_options(key_field: &Field) -> Vec<String> {
+    key_field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("key"))
+        .filter(|attr| matches!(attr.meta, syn::Meta::List(_)))
+        .flat_map(|attr| {
+            attr.parse_args_with(
+                Punctuated::<Ident, Comma>::parse_terminated,
+            )
+            .expect("Expected #[key(option, ...)]")
+        })
+        .map(|option| match option.to_string().as_str() {
+            option @ ("as_str" | "auto") => option.to_string(),
+            other => panic!("Unknown key option: {other}"),
         })
         .collect()
 }
 
-fn generate_set_methods(
-    name: &Ident,
+/// Whether the key field is marked `#[key(as_str)]`, i.e. stored via `Display`/`FromStr`.
+fn key_as_str(key_field: &Field) -> bool {
+    key_options(key_field)
+        .iter()
+        .any(|option| option == "as_str")
+}
+
+/// Whether the key field is marked `#[key(auto)]`, i.e. generated with `ergokv::GenerateKey`.
+fn key_auto(key_field: &Field) -> bool {
+    key_options(key_field).iter().any(|option| option == "auto")
+}
+
+/// Generates `new_with_generated_key` and `save_new` for models with `#[key(auto)]`.
+fn generate_key_generation_methods(
     fields: &Punctuated<Field, Comma>,
-    prev_type: Option<&syn::Path>,
-) -> Vec<TokenStream2> {
-    fields.iter().map(|f| {
+    key_field: &Field,
+    options: &StoreOptions,
+) -> Option<TokenStream2> {
+    if !key_auto(key_field) {
+        return None;
+    }
+
+    let key_ident = &key_field.ident;
+    let key_type = &key_field.ty;
+    let save_ret = save_return_type(options);
+
+    let params = fields
+        .iter()
+        .filter(|f| {
+            f.ident != key_field.ident
+                && !is_computed(f)
+                && !is_managed_timestamp(f, options)
+        })
+        .map(|f| {
+            let field_name = &f.ident;
+            let field_type = &f.ty;
+            quote! { #field_name: #field_type }
+        });
+    let inits = fields.iter().map(|f| {
         let field_name = &f.ident;
         let field_type = &f.ty;
-        let method_name = format_ident!("set_{}", field_name.clone().expect("Missing field name"));
-        let is_indexed = f.attrs.iter().any(|a| a.path().is_ident("index"));
-        let key_field = fields.iter().find(|f| f.attrs.iter().any(|a| a.path().is_ident("key")))
-            .expect("A field with #[key] attribute is required");
-        let key_ident = &key_field.ident;
-        let checks = generate_mutation_checks(name, prev_type);
+        if f.ident == key_field.ident {
+            quote! { #field_name: <#key_type as ::ergokv::GenerateKey>::generate() }
+        } else if is_computed(f) {
+            quote! { #field_name: ::core::default::Default::default() }
+        } else if is_managed_timestamp(f, options) {
+            quote! { #field_name: <#field_type as ::ergokv::AutoTimestamp>::now() }
+        } else {
+            quote! { #field_name }
+        }
+    });
+
+    Some(quote! {
+        /// Creates an instance with a newly generated key and the given values of the
+        /// other fields.
+        ///
+        /// The instance is not saved, see [`save_new`](Self::save_new).
+        #[allow(clippy::too_many_arguments)]
+        pub fn new_with_generated_key(#(#params),*) -> Self {
+            Self {
+                #(#inits,)*
+            }
+        }
+
+        /// Gives the instance a newly generated key and saves it.
+        ///
+        /// A key that is already taken by a stored instance, or reserved with
+        /// [`reserve_key`](Self::reserve_key), is generated again, so that no instance is
+        /// overwritten. Fails if a few attempts in a row only generate taken keys.
+        pub async fn save_new(&mut self, txn: &mut tikv_client::Transaction) -> Result<#save_ret, tikv_client::Error> {
+            for _ in 0..8 {
+                self.#key_ident = <#key_type as ::ergokv::GenerateKey>::generate();
+                if !Self::key_exists(&self.#key_ident, txn).await?
+                    && !Self::is_pending(&self.#key_ident, txn).await?
+                {
+                    return self.save(txn).await;
+                }
+            }
+            Err(tikv_client::Error::StringError(format!(
+                "Failed to generate an unused {} key",
+                Self::MODEL_NAME
+            )))
+        }
+    })
+}
+
+/// Generates `checksum_key`, `write_checksum` and `verify_checksum` for models with
+/// `#[store(checksum)]`.
+fn generate_checksum_methods(
+    fields: &Punctuated<Field, Comma>,
+    key_field: &Field,
+    options: &StoreOptions,
+) -> Option<TokenStream2> {
+    let mode = options.checksum?;
+    let key_ident = &key_field.ident;
+    let key_type = &key_field.ty;
 
-        let index_ops = if is_indexed {
+    let field_bytes = fields.iter().filter(|f| !is_computed(f)).map(|f| {
+        let field_name = &f.ident;
+        if FieldOptions::from_field(f).flatten {
             quote! {
-                // Remove old index
-                let old_index_key = format!(
-                    "ergokv:{}:{}:{}",
-                    Self::MODEL_NAME,
-                    stringify!(#field_name),
-                    ::ergokv::serde_json::to_string(&self.#field_name)
-                        .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode struct field: {}", e)))?,
-                );
-                txn.delete(old_index_key).await?;
+                let prefix = format!("ergokv:{}:{}.", path, stringify!(#field_name));
+                let end = format!("ergokv:{}:{}/", path, stringify!(#field_name));
+                for pair in txn.scan(prefix.clone()..end, u32::MAX).await? {
+                    let (field_key, value): (tikv_client::Key, tikv_client::Value) = pair.into();
+                    let field_key: Vec<u8> = field_key.into();
+                    let name = format!("{}.{}", stringify!(#field_name), String::from_utf8_lossy(&field_key[prefix.len()..]));
+                    stored.push((name, Some(value)));
+                }
+            }
+        } else {
+            quote! {
+                let field_key = format!("ergokv:{}:{}", path, stringify!(#field_name));
+                stored.push((stringify!(#field_name).to_string(), txn.get(field_key).await?));
+            }
+        }
+    });
+    let on_mismatch = match mode {
+        ChecksumMode::Error => quote! {
+            Err(::ergokv::checksum_mismatch(&Self::record_path(key)?))
+        },
+        ChecksumMode::Warn => quote! {
+            ::ergokv::report_checksum_mismatch(&::ergokv::checksum_mismatch(&Self::record_path(key)?));
+            Ok(())
+        },
+    };
+
+    Some(quote! {
+        /// Returns the TiKV key under which the checksum of the instance with the given key
+        /// is stored.
+        fn checksum_key(key: &#key_type) -> Result<String, tikv_client::Error> {
+            Ok(format!("ergokv:{}:__checksum", Self::record_path(key)?))
+        }
+
+        /// Hashes the stored bytes of every field of the instance with the given key.
+        async fn stored_checksum(key: &#key_type, txn: &mut tikv_client::Transaction) -> Result<Vec<u8>, tikv_client::Error> {
+            let path = Self::record_path(key)?;
+            let mut stored: Vec<(String, Option<Vec<u8>>)> = Vec::new();
+            #(#field_bytes)*
+            Ok(::ergokv::record_checksum(stored.iter().map(|(name, value)| (name.as_str(), value.as_deref()))))
+        }
+
+        /// Stores the checksum of the fields of the instance, as they are written in `txn`.
+        async fn write_checksum(&self, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            let checksum = Self::stored_checksum(&self.#key_ident, txn).await?;
+            txn.put(Self::checksum_key(&self.#key_ident)?, checksum).await
+        }
+
+        /// Checks that the stored fields of the instance with the given key match its stored
+        /// checksum, which they do unless they were changed by something else than ergokv.
+        async fn verify_checksum(key: &#key_type, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            let expected = txn.get(Self::checksum_key(key)?).await?;
+            if expected.as_deref() == Some(Self::stored_checksum(key, txn).await?.as_slice()) {
+                return Ok(());
+            }
+            #on_mismatch
+        }
+    })
+}
+
+/// Generates the statement storing the checksum of `self` after a write, for models with
+/// `#[store(checksum)]`.
+fn generate_checksum_write(
+    options: &StoreOptions,
+) -> Option<TokenStream2> {
+    options.checksum.is_some().then(|| {
+        quote! {
+            self.write_checksum(txn).await?;
+        }
+    })
+}
+
+/// Generates `reserve_key`, `complete` and `is_pending`, which let a key be claimed before its
+/// instance is saved.
+fn generate_reservation_methods(
+    key_field: &Field,
+    options: &StoreOptions,
+) -> TokenStream2 {
+    let key_ident = &key_field.ident;
+    let key_type = &key_field.ty;
+    let save_ret = save_return_type(options);
+    let trie_insert = options.uses_trie().then(|| {
+        quote! {
+            ::ergokv::PrefixTrie::new("ergokv:__trie").insert(txn, &path).await?;
+        }
+    });
+
+    quote! {
+        /// Returns the key of the marker of a reserved instance, given its `record_path`.
+        fn pending_key(path: &str) -> String {
+            format!("ergokv:{}:__pending", path)
+        }
+
+        /// Reserves `key` for an instance that is saved later with [`complete`](Self::complete).
+        ///
+        /// The key is added to the master trie and marked as pending, with the time of the
+        /// reservation. Transactions reserving or saving the same key concurrently conflict,
+        /// and a later `reserve_key` or `save_new` sees the key as taken. Until the instance is
+        /// completed, [`load`](Self::load) fails with an error saying the key is pending and
+        /// `all` skips it, while `count` already includes it.
+        ///
+        /// # Errors
+        ///
+        /// Fails if an instance with the key is already stored or reserved.
+        pub async fn reserve_key(key: &#key_type, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+            let path = Self::record_path(key)?;
+            if Self::key_exists(key, txn).await? {
+                return Err(tikv_client::Error::StringError(format!("Cannot reserve {}, which is already stored", path)));
+            }
+            let pending_key = Self::pending_key(&path);
+            if txn.get(pending_key.clone()).await?.is_some() {
+                return Err(tikv_client::Error::StringError(format!("Cannot reserve {}, which is already reserved", path)));
+            }
+
+            #trie_insert
+
+            let mut value = Vec::new();
+            ::ergokv::ciborium::ser::into_writer(&::std::time::SystemTime::now(), &mut value)
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode reservation of {}: {}", path, e)))?;
+            txn.put(pending_key, value).await?;
+            Ok(())
+        }
+
+        /// Saves the instance whose key was reserved with [`reserve_key`](Self::reserve_key),
+        /// and removes the reservation.
+        ///
+        /// # Errors
+        ///
+        /// Fails if the key of the instance is not reserved, e.g. because it was already
+        /// completed, or if the save fails.
+        pub async fn complete(&self, txn: &mut tikv_client::Transaction) -> Result<#save_ret, tikv_client::Error> {
+            let path = Self::record_path(&self.#key_ident)?;
+            let pending_key = Self::pending_key(&path);
+            if txn.get(pending_key.clone()).await?.is_none() {
+                return Err(tikv_client::Error::StringError(format!("Cannot complete {}, which is not reserved", path)));
+            }
+
+            txn.delete(pending_key).await?;
+            self.save(txn).await
+        }
+
+        /// Checks whether `key` is reserved with [`reserve_key`](Self::reserve_key), but its
+        /// instance not yet [completed](Self::complete).
+        pub async fn is_pending(key: &#key_type, txn: &mut tikv_client::Transaction) -> Result<bool, tikv_client::Error> {
+            Ok(txn.get(Self::pending_key(&Self::record_path(key)?)).await?.is_some())
+        }
+
+        /// Loads the instance with the given key, or returns `None` if the key is only
+        /// reserved.
+        async fn load_unless_pending(key: &#key_type, txn: &mut tikv_client::Transaction) -> Result<Option<Self>, tikv_client::Error> {
+            match Self::load(key, txn).await {
+                Ok(record) => Ok(Some(record)),
+                Err(_) if Self::is_pending(key, txn).await? => Ok(None),
+                Err(e) => Err(e),
+            }
+        }
+    }
+}
+
+/// Generates `encode_key` and `decode_key`, converting keys to and from the string
+/// form used in storage keys and trie entries, and `record_path` and
+/// `decode_record_path`, which add and strip the model name and shard.
+fn generate_key_codec(
+    key_field: &Field,
+    options: &StoreOptions,
+) -> TokenStream2 {
+    let key_type = &key_field.ty;
+
+    let (path, path_decode) = match options.shards {
+        Some(shards) => (
+            quote! {
+                let key = Self::encode_key(key)?;
+                let hash = ::ergokv::blake3::hash(key.as_bytes());
+                let mut prefix = [0; 8];
+                prefix.copy_from_slice(&hash.as_bytes()[..8]);
+                let shard = u64::from_le_bytes(prefix) % #shards;
+                Ok(format!("{}:shard{}:{}", Self::MODEL_NAME, shard, key))
+            },
+            quote! {
+                let (shard, key) = path
+                    .strip_prefix(Self::MODEL_NAME)?
+                    .strip_prefix(":shard")?
+                    .split_once(':')?;
+                shard.parse::<u64>().ok()?;
+                Some(Self::decode_key(key))
+            },
+        ),
+        None => (
+            quote! {
+                Ok(format!("{}:{}", Self::MODEL_NAME, Self::encode_key(key)?))
+            },
+            quote! {
+                let key = path
+                    .strip_prefix(Self::MODEL_NAME)?
+                    .strip_prefix(':')?;
+                Some(Self::decode_key(key))
+            },
+        ),
+    };
+    let sort_key = match options.shards {
+        Some(_) => quote! {
+            let path = Self::record_path(key).ok()?;
+            let (shard, key) = path
+                .strip_prefix(Self::MODEL_NAME)?
+                .strip_prefix(":shard")?
+                .split_once(':')?;
+            Some((shard.parse::<u64>().ok()?, key.to_string()))
+        },
+        None => quote! {
+            Some((0, Self::encode_key(key).ok()?))
+        },
+    };
+    let trie_prefixes = match options.shards {
+        Some(shards) => quote! {
+            (0..#shards).map(|shard| format!("{}:shard{}:", Self::MODEL_NAME, shard)).collect()
+        },
+        None => quote! {
+            vec![format!("{}:", Self::MODEL_NAME)]
+        },
+    };
+
+    let (encode, decode) = if key_as_str(key_field) {
+        (
+            quote! { Ok(::std::string::ToString::to_string(key)) },
+            quote! {
+                <#key_type as ::std::str::FromStr>::from_str(key)
+                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} key {}: {}", Self::MODEL_NAME, key, e)))
+            },
+        )
+    } else {
+        (
+            quote! {
+                ::ergokv::serde_json::to_string(key)
+                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {} key: {}", Self::MODEL_NAME, e)))
+            },
+            quote! {
+                ::ergokv::serde_json::from_str(key)
+                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode {} key {}: {}", Self::MODEL_NAME, key, e)))
+            },
+        )
+    };
+
+    quote! {
+        /// Converts a key into its string form used in storage keys.
+        fn encode_key(key: &#key_type) -> Result<String, tikv_client::Error> {
+            #encode
+        }
+
+        /// Parses a key from its string form used in storage keys.
+        fn decode_key(key: &str) -> Result<#key_type, tikv_client::Error> {
+            #decode
+        }
+
+        /// Returns the path of an instance, `{MODEL_NAME}:{key}` or, for sharded models,
+        /// `{MODEL_NAME}:shard{n}:{key}`.
+        ///
+        /// This is the entry of the instance in the master trie, and its field values are
+        /// stored under `ergokv:{path}:{field}`.
+        fn record_path(key: &#key_type) -> Result<String, tikv_client::Error> {
+            #path
+        }
+
+        /// Parses the key from a path returned by `record_path`, if it is one of this model.
+        fn decode_record_path(path: &str) -> Option<Result<#key_type, tikv_client::Error>> {
+            #path_decode
+        }
+
+        /// Returns the trie prefixes under which the instances of this model are registered.
+        fn trie_prefixes() -> Vec<String> {
+            #trie_prefixes
+        }
+
+        /// Compares two keys in the order their instances are stored in, which is the order
+        /// [`all`](Self::all) yields them in, rather than by the `Ord` of the key type.
+        ///
+        /// Keys are compared by their serialized form, and for sharded models by their shard
+        /// first. This makes it possible to merge streams of instances of this type, such as
+        /// those of two clusters, consistently with how each is sorted. A key that cannot be
+        /// serialized, and so cannot be stored either, sorts before all others.
+        pub fn compare_keys(a: &#key_type, b: &#key_type) -> std::cmp::Ordering {
+            Self::storage_sort_key(a).cmp(&Self::storage_sort_key(b))
+        }
+
+        /// The shard and serialized form of a key, which instances are stored sorted by.
+        fn storage_sort_key(key: &#key_type) -> Option<(u64, String)> {
+            #sort_key
+        }
+    }
+}
+
+/// Generates `stale_timestamp`, `load_stale`, `load_many_stale` and `all_stale` for models
+/// with `#[store(stale_snapshot(max_staleness = "..."))]`.
+fn generate_stale_snapshot_methods(
+    key_field: &Field,
+    options: &StoreOptions,
+) -> Option<TokenStream2> {
+    let max_staleness = options.stale_snapshot?;
+    let key_type = &key_field.ty;
+
+    Some(quote! {
+        /// Returns the TiKV timestamp the `_stale` reads of this type read at, the model's
+        /// `max_staleness` before the current one, or the earliest timestamp if that is
+        /// further back than TiKV time goes.
+        pub async fn stale_timestamp(client: &tikv_client::TransactionClient) -> Result<tikv_client::Timestamp, tikv_client::Error> {
+            let mut timestamp = client.current_timestamp().await?;
+            let staleness = i64::try_from(#max_staleness).unwrap_or(i64::MAX);
+            timestamp.physical = timestamp.physical.saturating_sub(staleness).max(0);
+            timestamp.logical = 0;
+            Ok(timestamp)
+        }
+
+        /// Like [`load`](Self::load), but reads a snapshot at the [`stale_timestamp`](Self::stale_timestamp),
+        /// see [`load_at`](Self::load_at).
+        ///
+        /// The instance is returned as it was up to `max_staleness` ago, so recent writes
+        /// are missing, including the instance itself if it is newer than that. In return,
+        /// the read takes no locks, and rarely meets those of transactions still committing.
+        pub async fn load_stale(key: &#key_type, client: &tikv_client::TransactionClient) -> Result<Self, tikv_client::Error> {
+            Self::load_at(key, client, Self::stale_timestamp(client).await?).await
+        }
+
+        /// Like [`load_many_at`](Self::load_many_at), but reads at the
+        /// [`stale_timestamp`](Self::stale_timestamp), with the consistency of [`load_stale`](Self::load_stale).
+        pub async fn load_many_stale(keys: &[#key_type], client: &tikv_client::TransactionClient, concurrency: usize) -> Result<Vec<Self>, tikv_client::Error> {
+            Self::load_many_at(keys, client, Self::stale_timestamp(client).await?, concurrency).await
+        }
+
+        /// Like [`all_at`](Self::all_at), but reads at the [`stale_timestamp`](Self::stale_timestamp),
+        /// with the consistency of [`load_stale`](Self::load_stale).
+        pub fn all_stale(client: &tikv_client::TransactionClient) -> impl futures::Stream<Item = Result<Self, tikv_client::Error>> + '_ {
+            use ::ergokv::futures::StreamExt;
+
+            async_stream::try_stream! {
+                let timestamp = Self::stale_timestamp(client).await?;
+                let mut all = ::std::pin::pin!(Self::all_at(client, timestamp));
+                while let Some(record) = all.next().await {
+                    yield record?;
+                }
+            }
+        }
+    })
+}
+
+fn generate_all_method(
+    key_field: &Field,
+    options: &StoreOptions,
+) -> TokenStream2 {
+    let key_ident = &key_field.ident;
+    let key_type = &key_field.ty;
+
+    let scan_methods = options.key_range_scan.then(|| quote! {
+        /// Streams all instances of this type.
+        ///
+        /// The instances are found by scanning the keys of this model, and yielded sorted by
+        /// their serialized key. Instances of sharded models are sorted within each shard, and
+        /// the shards follow one another in order.
+        ///
+        /// The stream only reads through `txn`, and holds no state outside of it. Dropping
+        /// it at any point, even while an instance is being loaded, ends the borrow of `txn`
+        /// and leaves the transaction usable, with nothing written.
+        pub fn all(txn: &mut tikv_client::Transaction) -> impl futures::Stream<Item = Result<Self, tikv_client::Error>> + '_ {
+            async_stream::try_stream! {
+                for key in Self::stored_keys(txn).await? {
+                    yield Self::load(&key, txn).await?;
+                }
+            }
+        }
+
+        /// Counts the stored instances of this type, without loading them.
+        pub async fn count(txn: &mut tikv_client::Transaction) -> Result<usize, tikv_client::Error> {
+            Ok(Self::stored_keys(txn).await?.len())
+        }
+
+        /// Returns the keys of all stored instances, found by scanning the keys of this model.
+        async fn stored_keys(txn: &mut tikv_client::Transaction) -> Result<Vec<#key_type>, tikv_client::Error> {
+            txn.scan_keys(Self::scan_range(), u32::MAX)
+                .await?
+                .filter_map(|raw_key| Self::decode_scanned_key(raw_key.into()))
+                .collect()
+        }
+    });
+    let trie_methods = options.uses_trie().then(|| quote! {
+        /// Streams all instances of this type.
+        ///
+        /// Instances are yielded in a deterministic order, sorted by their serialized key.
+        /// Instances of sharded models are sorted within each shard, and the shards follow
+        /// one another in order.
+        ///
+        /// The stream only reads through `txn`, and holds no state outside of it. Dropping
+        /// it at any point, even while an instance is being loaded, ends the borrow of `txn`
+        /// and leaves the transaction usable, with nothing written.
+        pub fn all(txn: &mut tikv_client::Transaction) -> impl futures::Stream<Item = Result<Self, tikv_client::Error>> + '_ {
+            use futures::StreamExt;
+            let trie = ::ergokv::PrefixTrie::new("ergokv:__trie");
+
+            async_stream::try_stream! {
+                for prefix in Self::trie_prefixes() {
+                    let paths = trie.find_by_prefix(txn, &prefix).await?;
+                    for path in paths {
+                        if let Some(key) = Self::decode_record_path(&path) {
+                            if let Some(record) = Self::load_unless_pending(&key?, txn).await? {
+                                yield record;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        /// Counts the stored instances of this type, without loading them.
+        pub async fn count(txn: &mut tikv_client::Transaction) -> Result<usize, tikv_client::Error> {
+            let trie = ::ergokv::PrefixTrie::new("ergokv:__trie");
+
+            let mut count = 0;
+            for prefix in Self::trie_prefixes() {
+                count += trie
+                    .find_by_prefix(txn, &prefix)
+                    .await?
+                    .iter()
+                    .filter(|path| Self::decode_record_path(path).is_some())
+                    .count();
+            }
+            Ok(count)
+        }
+
+        /// Returns the keys of all stored instances, found through the master trie.
+        async fn stored_keys(txn: &mut tikv_client::Transaction) -> Result<Vec<#key_type>, tikv_client::Error> {
+            let trie = ::ergokv::PrefixTrie::new("ergokv:__trie");
+
+            let mut keys = Vec::new();
+            for prefix in Self::trie_prefixes() {
+                for path in trie.find_by_prefix(txn, &prefix).await? {
+                    if let Some(key) = Self::decode_record_path(&path) {
+                        keys.push(key?);
+                    }
+                }
+            }
+            Ok(keys)
+        }
+    });
+
+    let drain = (!options.no_trie).then(|| quote! {
+        /// Streams all instances of this type, deleting each one before it is yielded.
+        ///
+        /// Every instance is removed with [`delete`](Self::delete), so with its index entries
+        /// and trie entry, and in the same order as [`all`](Self::all) yields them. Once the
+        /// stream is exhausted and the transaction committed, no instance is left. Dropping the
+        /// stream early leaves the instances not yet yielded as they are, while those already
+        /// yielded are deleted within the transaction.
+        pub fn drain(txn: &mut tikv_client::Transaction) -> impl futures::Stream<Item = Result<Self, tikv_client::Error>> + '_ {
+            async_stream::try_stream! {
+                for key in Self::stored_keys(txn).await? {
+                    let Some(record) = Self::load_unless_pending(&key, txn).await? else {
+                        continue;
+                    };
+                    record.delete(txn).await?;
+                    yield record;
+                }
+            }
+        }
+    });
+
+    quote! {
+        #scan_methods
+        #trie_methods
+        #drain
+
+        /// Streams all instances of this type as they were at the given TiKV timestamp.
+        ///
+        /// Every instance is read from one read-only snapshot, see [`load_at`](Self::load_at)
+        /// for the isolation this provides. Instances are yielded sorted by their serialized key.
+        pub fn all_at(client: &tikv_client::TransactionClient, timestamp: tikv_client::Timestamp) -> impl futures::Stream<Item = Result<Self, tikv_client::Error>> + '_ {
+            async_stream::try_stream! {
+                let mut snapshot = client.snapshot(timestamp, tikv_client::TransactionOptions::new_optimistic().read_only());
+
+                // The master trie is only readable in a transaction, so the instances are
+                // found through the keys of their key fields instead
+                let keys = snapshot
+                    .scan_keys(Self::scan_range(), u32::MAX)
+                    .await?
+                    .filter_map(|raw_key| Self::decode_scanned_key(raw_key.into()))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                for key in keys {
+                    yield Self::load_snapshot(&key, &mut snapshot).await?;
+                }
+            }
+        }
+
+        /// The range of TiKV keys holding the instances of this model, and its indexes.
+        fn scan_range() -> std::ops::Range<String> {
+            format!("ergokv:{}:", Self::MODEL_NAME)..format!("ergokv:{};", Self::MODEL_NAME)
+        }
 
-                // Add new index after update
-                let new_index_key = format!(
-                    "ergokv:{}:{}:{}",
-                    Self::MODEL_NAME,
-                    stringify!(#field_name),
-                    ::ergokv::serde_json::to_string(&self.#field_name)
-                        .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode struct field: {}", e)))?,
-                );
-                let mut value = Vec::new();
-                ::ergokv::ciborium::ser::into_writer(&self.#key_ident, &mut value)
-                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode key: {}", e)))?;
-                txn.put(new_index_key, value).await?;
+        /// Parses the key of an instance from a TiKV key in [`scan_range`](Self::scan_range),
+        /// if it is the key of the instance's key field.
+        fn decode_scanned_key(raw_key: Vec<u8>) -> Option<Result<#key_type, tikv_client::Error>> {
+            let path = std::str::from_utf8(&raw_key).ok()?.strip_prefix("ergokv:")?;
+            let rest = path.strip_prefix(Self::MODEL_NAME)?.strip_prefix(':')?;
+            if ["unique_index:", "index:", "hashed_index:", "range_index:", "__audit", "__outbox", "fti:", "partition:"]
+                .iter()
+                .any(|p| rest.starts_with(p))
+            {
+                return None;
             }
-        } else {
-            quote! {}
-        };
+            path.strip_suffix(concat!(":", stringify!(#key_ident)))
+                .and_then(Self::decode_record_path)
+        }
+    }
+}
+
+/// Generates `restore_with`, which restores a backup like `restore`, resolving instances
+/// already stored under a restored key by an `ergokv::ConflictPolicy`.
+fn generate_restore_with(
+    key_field: &Field,
+    options: &StoreOptions,
+) -> TokenStream2 {
+    let key_ident = &key_field.ident;
 
+    let newer_wins = if options.timestamps {
         quote! {
-            pub async fn #method_name(&mut self, new_value: #field_type, txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
-                #checks
-                #index_ops
+            if Self::key_exists(&record.#key_ident, txn).await? {
+                record.updated_at > Self::load(&record.#key_ident, txn).await?.updated_at
+            } else {
+                true
+            }
+        }
+    } else {
+        // Rejected before the backup is read
+        quote! {
+            unreachable!()
+        }
+    };
+    let timestamps_check = (!options.timestamps).then(|| quote! {
+        if policy == ::ergokv::ConflictPolicy::NewerWins {
+            return Err(tikv_client::Error::StringError(format!(
+                "Cannot restore {} with ConflictPolicy::NewerWins: it has no #[store(timestamps)]",
+                Self::MODEL_NAME
+            )));
+        }
+    });
 
-                // Update field
-                self.#field_name = new_value;
+    quote! {
+        /// Restores instances from a backup file created by [`backup`](Self::backup), using
+        /// `policy` for those whose key is already stored, and returns how many were written.
+        ///
+        /// The file is verified like in [`restore`](Self::restore) before anything is written,
+        /// and every instance written is written with `Self::save`. With
+        /// `ConflictPolicy::NewerWins`, the `updated_at` of the instance in the backup is
+        /// compared to that of the stored one. As `save` sets `updated_at` to the current time,
+        /// a restored instance does not keep the `updated_at` it had in the backup.
+        ///
+        /// # Errors
+        ///
+        /// In addition to the errors of [`restore`](Self::restore), this fails with
+        /// `ConflictPolicy::NewerWins` on models without `#[store(timestamps)]`.
+        pub async fn restore_with(
+            txn: &mut tikv_client::Transaction,
+            path: impl AsRef<std::path::Path>,
+            policy: ::ergokv::ConflictPolicy,
+        ) -> Result<usize, tikv_client::Error> {
+            use std::io::BufRead;
 
-                // Save updated field
-                let key = format!(
-                    "ergokv:{}:{}:{}",
-                    Self::MODEL_NAME,
-                    ::ergokv::serde_json::to_string(&self.#key_ident)
-                        .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode struct key: {}", e)))?,
-                    stringify!(#field_name)
-                );
-                let mut value = Vec::new();
-                ::ergokv::ciborium::ser::into_writer(&self.#field_name, &mut value)
-                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to encode {}: {}", stringify!(#field_name), e)))?;
-                txn.put(key, value).await?;
+            #timestamps_check
+            Self::verify_backup_file(&path)?;
 
-                Ok(())
+            let file = std::fs::File::open(path)
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to open backup file: {}", e)))?;
+
+            let mut written = 0;
+            for line in std::io::BufReader::new(file).lines() {
+                let line = line
+                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to read line: {}", e)))?;
+                let record = Self::from_backup_json(&line)?;
+
+                let write = match policy {
+                    ::ergokv::ConflictPolicy::Overwrite => true,
+                    ::ergokv::ConflictPolicy::Skip => !Self::key_exists(&record.#key_ident, txn).await?,
+                    ::ergokv::ConflictPolicy::NewerWins => { #newer_wins }
+                };
+                if write {
+                    Self::save(&record, txn).await?;
+                    written += 1;
+                }
             }
+
+            Ok(written)
         }
-    }).collect()
+    }
 }
 
-fn generate_all_method(key_field: &Field) -> TokenStream2 {
-    let key_type = &key_field.ty;
+/// Generates `rewrite_all`, which writes every instance again in the current formats of its
+/// fields, for models with bincode or rkyv fields.
+fn generate_rewrite_method(
+    fields: &Punctuated<Field, Comma>,
+    options: &StoreOptions,
+) -> Option<TokenStream2> {
+    if options.no_trie
+        || !fields.iter().any(|f| {
+            FieldOptions::from_field(f).format != Format::Cbor
+        })
+    {
+        return None;
+    }
 
-    quote! {
-        pub fn all(txn: &mut tikv_client::Transaction) -> impl futures::Stream<Item = Result<Self, tikv_client::Error>> + '_ {
-            use futures::StreamExt;
-            let trie = ::ergokv::PrefixTrie::new("ergokv:__trie");
+    Some(quote! {
+        /// Loads every instance of this type and writes its fields again, returning the
+        /// number of instances written.
+        ///
+        /// Values of bincode and rkyv fields that were stored in another format, e.g. as CBOR
+        /// before the fields moved to their format, are read in it and written back in the
+        /// field's format.
+        pub async fn rewrite_all(txn: &mut tikv_client::Transaction) -> Result<usize, tikv_client::Error> {
+            let keys = Self::stored_keys(txn).await?;
 
-            async_stream::try_stream! {
-                let keys = trie.find_by_prefix(txn, Self::MODEL_NAME).await?;
-                for key in keys {
-                    if let Some(stripped) = key.strip_prefix(&format!("{}:", Self::MODEL_NAME)) {
-                        let key: #key_type = ::ergokv::serde_json::from_str(stripped)
-                            .map_err(|e| tikv_client::Error::StringError(format!("Failed to decode key: {}", e)))?;
-                        yield Self::load(&key, txn).await?;
-                    }
+            let mut rewritten = 0;
+            for key in &keys {
+                if let Some(record) = Self::load_unless_pending(key, txn).await? {
+                    record.save_unchecked(txn).await?;
+                    rewritten += 1;
                 }
             }
+            Ok(rewritten)
         }
-    }
+    })
 }
 
 fn generate_migration_trait(
@@ -571,15 +5911,23 @@ fn generate_migration_trait(
     }
 }
 
-fn generate_ensure_migrations(
+/// The name under which the migration from `prev_type` to `name` is recorded.
+fn migration_name(
     name: &Ident,
     prev_type: &syn::Path,
-) -> TokenStream2 {
-    let migration_name = format!(
+) -> String {
+    format!(
         "{}->{}",
         prev_type.segments.last().unwrap().ident,
         name
-    );
+    )
+}
+
+fn generate_ensure_migrations(
+    name: &Ident,
+    prev_type: &syn::Path,
+) -> TokenStream2 {
+    let migration_name = migration_name(name, prev_type);
     let method_name = format_ident!(
         "from_{}",
         prev_type
@@ -598,7 +5946,7 @@ fn generate_ensure_migrations(
 
             let migrations: Vec<String> = if let Some(data) = txn.get(migrations_key.as_bytes().to_vec()).await? {
                 ::ergokv::ciborium::de::from_reader(&data[..])
-                    .map_err(|e| ::tikv_client::Error::StringError(format!("{e}")))?
+                    .map_err(|e| ::tikv_client::Error::StringError(format!("Failed to decode {} migrations: {}", Self::MODEL_NAME, e)))?
             } else {
                 Vec::new()
             };
@@ -620,7 +5968,10 @@ fn generate_ensure_migrations(
 
                         match Self::#method_name(&prev_item) {
                             Ok(new) => {
-                                new.save(&mut new_txn).await?;
+                                // Index kinds and indexed values may have changed, so the
+                                // old entries are dropped and rewritten in the new layout
+                                prev_item.remove_index_entries(&mut new_txn).await?;
+                                new.save_unchecked(&mut new_txn).await?;
                                 new_txn.commit().await?;
                             }
                             e @ Err(_) => {
@@ -636,7 +5987,7 @@ fn generate_ensure_migrations(
 
                 let mut buf = vec![];
                 ::ergokv::ciborium::ser::into_writer(&new_migrations, &mut buf)
-                    .map_err(|e| ::tikv_client::Error::StringError(format!("{e}")))?;
+                    .map_err(|e| ::tikv_client::Error::StringError(format!("Failed to encode {} migrations: {}", Self::MODEL_NAME, e)))?;
 
                 txn.put(migrations_key.as_bytes().to_vec(), buf).await?;
 
@@ -648,60 +5999,158 @@ fn generate_ensure_migrations(
     }
 }
 
+fn is_strict(options: &StoreOptions) -> bool {
+    options.strict || cfg!(feature = "strict-migrations")
+}
+
 fn generate_mutation_checks(
+    options: &StoreOptions,
+) -> TokenStream2 {
+    if is_strict(options) {
+        quote! {
+            Self::check_migrations(txn).await?;
+        }
+    } else {
+        quote! {}
+    }
+}
+
+fn generate_check_migrations_method(
     name: &Ident,
     prev_type: Option<&syn::Path>,
+    options: &StoreOptions,
 ) -> TokenStream2 {
-    #[cfg(feature = "strict-migrations")]
-    {
-        let prev_check = prev_type.map(|prev| {
+    if !is_strict(options) {
+        return quote! {};
+    }
+
+    // Migrations are recorded in order, so this version is current exactly
+    // when its own migration is the last one applied (or, for the first
+    // version, when no migration has been applied at all).
+    let version_check = match prev_type {
+        Some(prev) => {
+            let migration_name = migration_name(name, prev);
+
             quote! {
-                if !migrations.contains(&format!("{}->{}",
-                    stringify!(#prev),
-                    stringify!(#name)
-                )) {
-                    return Err(::tikv_client::Error::StringError(
-                        format!("Previous migration {}=>{} not applied", stringify!(#prev), stringify!(#name))
-                    ));
+                match migrations.iter().position(|m| m == #migration_name) {
+                    None => {
+                        return Err(::tikv_client::Error::StringError(
+                            format!("Previous migration {} not applied", #migration_name)
+                        ));
+                    }
+                    Some(i) if i + 1 < migrations.len() => {
+                        return Err(::tikv_client::Error::StringError(
+                            format!("Cannot modify {} - newer version exists", stringify!(#name))
+                        ));
+                    }
+                    Some(_) => {}
                 }
             }
-        });
+        }
+        None => quote! {
+            if !migrations.is_empty() {
+                return Err(::tikv_client::Error::StringError(
+                    format!("Cannot modify {} - newer version exists", stringify!(#name))
+                ));
+            }
+        },
+    };
 
-        quote! {
+    quote! {
+        /// Checks that this version of the model may be written to.
+        ///
+        /// Fails if a newer version of the model has been migrated to, or if the
+        /// migration to this version has not been applied yet. Every mutating method
+        /// of a strict model calls this first.
+        ///
+        /// The migration list is read through `txn`, and tikv-client caches reads in
+        /// the transaction buffer, so only the first check in a transaction costs a
+        /// round-trip to TiKV. Batching many writes into one transaction keeps the
+        /// overhead of strict models to a single read.
+        pub async fn check_migrations(txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
             let migrations_key = format!("{}:__migrations", Self::MODEL_NAME);
-            let migrations: Vec<String> = if let Some(data) = txn.get(&migrations_key).await? {
-                ::ergokv::ciborium::de::from_reader(&data[..])?
+            let migrations: Vec<String> = if let Some(data) = txn.get(migrations_key).await? {
+                ::ergokv::ciborium::de::from_reader(&data[..])
+                    .map_err(|e| ::tikv_client::Error::StringError(format!("Failed to decode {} migrations: {}", Self::MODEL_NAME, e)))?
             } else {
                 Vec::new()
             };
 
-            if migrations.iter().any(|m| m.starts_with(&format!("{}->", stringify!(#name)))) {
-                return Err(::tikv_client::Error::StringError(
-                    format!("Cannot modify {} - newer version exists", stringify!(#name))
-                ));
-            }
+            #version_check
 
-            #prev_check
+            Ok(())
         }
     }
+}
 
-    #[cfg(not(feature = "strict-migrations"))]
-    {
-        // so we can prevent a warning about the params being unused with strict migrations
-        let _unused_prev = prev_type;
-        let _unused_name = name;
-        quote! {}
+// TODO: Consider using RON instead, or providing it as an option
+/// Submits the model to the `ergokv::registered_models` registry.
+/// Implements `ergokv::Store`, listing the names of the fields.
+fn generate_reflection(
+    name: &Ident,
+    fields: &Punctuated<Field, Comma>,
+) -> TokenStream2 {
+    let names = |filter: fn(&Field) -> bool| {
+        let names = fields
+            .iter()
+            .filter(|f| filter(f))
+            .map(|f| f.ident.as_ref().unwrap());
+        quote! { &[#(stringify!(#names)),*] }
+    };
+    let field_names = names(|_| true);
+    let index_fields = names(|f| index_kind(f).is_some());
+    let unique_index_fields =
+        names(|f| index_kind(f) == Some(IndexKind::Unique));
+
+    quote! {
+        impl ::ergokv::Store for #name {
+            const FIELD_NAMES: &'static [&'static str] = #field_names;
+            const INDEX_FIELDS: &'static [&'static str] = #index_fields;
+            const UNIQUE_INDEX_FIELDS: &'static [&'static str] = #unique_index_fields;
+        }
     }
 }
 
-// TODO: Consider using RON instead, or providing it as an option
-fn generate_backup_restore_methods() -> TokenStream2 {
+fn generate_registration(
+    name: &Ident,
+    prev_type: Option<&syn::Path>,
+    options: &StoreOptions,
+) -> TokenStream2 {
+    // The registry is for backups, which need the trie
+    if options.no_trie {
+        return quote! {};
+    }
+
+    let previous = match prev_type {
+        Some(prev) => {
+            quote! { Some(::std::any::TypeId::of::<#prev>) }
+        }
+        None => quote! { None },
+    };
+
     quote! {
+        ::ergokv::inventory::submit! {
+            ::ergokv::ModelRegistration::new(
+                #name::MODEL_NAME,
+                ::std::any::TypeId::of::<#name>,
+                #previous,
+                #name::backup_erased,
+            )
+        }
+    }
+}
+
+fn generate_backup_restore_methods(
+    options: &StoreOptions,
+) -> TokenStream2 {
+    // Backups are made from `all`, which needs the trie
+    let backup = (!options.no_trie).then(|| quote! {
          /// Creates a backup of all instances of this type in JSON format.
          ///
          /// The backup is stored in a file named `{MODEL_NAME}_{timestamp}.json` under the specified path,
          /// where timestamp is the Unix epoch time in seconds. Each line in the file contains one JSON-serialized
-         /// instance.
+         /// instance. A BLAKE3 checksum of the file is written next to it, see
+         /// [`backup_checksum_path`](Self::backup_checksum_path).
          ///
          /// # Arguments
          ///
@@ -736,6 +6185,33 @@ fn generate_backup_restore_methods() -> TokenStream2 {
          /// # }
          /// ```
          pub async fn backup(txn: &mut tikv_client::Transaction, path: impl AsRef<std::path::Path>) -> Result<std::path::PathBuf, tikv_client::Error> {
+            Self::write_backup(txn, path.as_ref(), "json", |json| format!("{}\n", json).into_bytes()).await
+        }
+
+        /// Like [`backup`](Self::backup), but frames every instance with its length instead
+        /// of a newline.
+        ///
+        /// The backup is stored in a file named `{MODEL_NAME}_{timestamp}.framed`. Each instance
+        /// is written as its JSON length in bytes, a little-endian `u64`, followed by the JSON
+        /// itself, so the framing does not depend on the contents of the records. Restore it with
+        /// [`restore_framed`](Self::restore_framed).
+        pub async fn backup_framed(txn: &mut tikv_client::Transaction, path: impl AsRef<std::path::Path>) -> Result<std::path::PathBuf, tikv_client::Error> {
+            Self::write_backup(txn, path.as_ref(), "framed", |json| {
+                let mut frame = (json.len() as u64).to_le_bytes().to_vec();
+                frame.extend_from_slice(json.as_bytes());
+                frame
+            })
+            .await
+        }
+
+        /// Writes every instance, turned into bytes by `frame`, to a new backup file with the
+        /// given extension, along with its checksum file.
+        async fn write_backup(
+            txn: &mut tikv_client::Transaction,
+            path: &std::path::Path,
+            extension: &str,
+            frame: impl Fn(String) -> Vec<u8>,
+        ) -> Result<std::path::PathBuf, tikv_client::Error> {
             use std::io::Write;
             use futures::StreamExt;
 
@@ -744,30 +6220,95 @@ fn generate_backup_restore_methods() -> TokenStream2 {
                 .map_err(|e| tikv_client::Error::StringError(e.to_string()))?
                 .as_secs();
 
-            let filename = format!("{}_{}.json", Self::MODEL_NAME, timestamp);
-            let backup_path = path.as_ref().join(filename);
+            let filename = format!("{}_{}.{}", Self::MODEL_NAME, timestamp, extension);
+            let backup_path = path.join(filename);
 
             let mut file = std::fs::File::create(&backup_path)
                 .map_err(|e| tikv_client::Error::StringError(format!("Failed to create backup file: {}", e)))?;
 
+            let mut hasher = ::ergokv::blake3::Hasher::new();
             let mut stream = Box::pin(Self::all(txn));
             while let Some(item) = stream.next().await {
-                let item = item?;
-                let json = serde_json::to_string(&item)
-                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to serialize: {}", e)))?;
-                writeln!(file, "{}", json)
+                let bytes = frame(item?.to_backup_json()?);
+                hasher.update(&bytes);
+                file.write_all(&bytes)
                     .map_err(|e| tikv_client::Error::StringError(format!("Failed to write: {}", e)))?;
             }
 
+            std::fs::write(Self::backup_checksum_path(&backup_path), format!("{}\n", hasher.finalize().to_hex()))
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to write checksum: {}", e)))?;
+
             Ok(backup_path)
         }
 
+        /// Writes all instances of this type to `writer` as CSV, returning the number of rows.
+        ///
+        /// The first row holds the field names, in declaration order, and every instance is
+        /// written as one row below it. Each field is taken from the instance's JSON, as
+        /// [`as_map`](Self::as_map) returns it: strings are written as they are, numbers and
+        /// booleans as JSON, `None` as an empty cell, and collections and nested structs as
+        /// JSON text. A field serialized under another name, e.g. with `#[serde(rename)]`, or
+        /// skipped by serde, is written as an empty cell. Cells containing commas, quotes or
+        /// line breaks are quoted as described by RFC 4180.
+        ///
+        /// # Examples
+        ///
+        /// ```no_run
+        /// # use ergokv::Store;
+        /// # use serde::{Serialize, Deserialize};
+        /// # #[derive(Store, Serialize, Deserialize)]
+        /// # struct User { #[key] id: u64, name: String }
+        /// # async fn example(txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+        /// let file = std::fs::File::create("users.csv").unwrap();
+        /// let rows = User::export_csv(txn, std::io::BufWriter::new(file)).await?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn export_csv(txn: &mut tikv_client::Transaction, mut writer: impl std::io::Write) -> Result<usize, tikv_client::Error> {
+            use std::io::Write;
+            use futures::StreamExt;
+
+            let write_error = |e: std::io::Error| tikv_client::Error::StringError(format!("Failed to write CSV: {}", e));
+            let names = <Self as ::ergokv::Store>::FIELD_NAMES;
+            ::ergokv::write_csv_row(&mut writer, names).map_err(write_error)?;
+
+            let mut rows = 0;
+            let mut stream = Box::pin(Self::all(txn));
+            while let Some(item) = stream.next().await {
+                let fields = match ::ergokv::serde_json::to_value(item?) {
+                    Ok(::ergokv::serde_json::Value::Object(fields)) => fields,
+                    Ok(_) => return Err(tikv_client::Error::StringError(format!("{} is not serialized as a JSON object", Self::MODEL_NAME))),
+                    Err(e) => return Err(tikv_client::Error::StringError(format!("Failed to serialize {}: {}", Self::MODEL_NAME, e))),
+                };
+                let cells = names.iter().map(|name| {
+                    fields.get(*name).map(::ergokv::csv_cell).unwrap_or_default()
+                });
+                ::ergokv::write_csv_row(&mut writer, cells).map_err(write_error)?;
+                rows += 1;
+            }
+
+            writer.flush().map_err(write_error)?;
+            Ok(rows)
+        }
+
+        /// [`backup`](Self::backup) with the signature of `ergokv::BackupFn`.
+        fn backup_erased<'a>(txn: &'a mut tikv_client::Transaction, path: &'a std::path::Path) -> ::ergokv::futures::future::BoxFuture<'a, Result<std::path::PathBuf, tikv_client::Error>> {
+            Box::pin(Self::backup(txn, path))
+        }
+    });
+
+    quote! {
+        #backup
+
         /// Restores instances from a backup file created by [`backup`](Self::backup).
         ///
         /// Reads the backup file line by line, deserializing each line as an instance
         /// and saving it to TiKV. The operation is performed within the provided transaction,
         /// allowing you to control atomicity.
         ///
+        /// The file is checked with [`verify_backup_file`](Self::verify_backup_file) before
+        /// anything is written, so a corrupt backup is rejected as a whole.
+        ///
         /// # Arguments
         ///
         /// * `txn` - TiKV transaction to use for writing the data
@@ -777,6 +6318,7 @@ fn generate_backup_restore_methods() -> TokenStream2 {
         ///
         /// This function will return an error if:
         /// - The backup file cannot be read
+        /// - The backup file does not match its checksum
         /// - Any line fails to deserialize from JSON
         /// - The TiKV transaction fails
         /// - Any instance fails to save
@@ -802,23 +6344,230 @@ fn generate_backup_restore_methods() -> TokenStream2 {
         /// # }
         /// ```
         pub async fn restore(txn: &mut tikv_client::Transaction, path: impl AsRef<std::path::Path>) -> Result<(), tikv_client::Error> {
+            Self::restore_mapping(txn, path, Self::from_backup_json).await
+        }
+
+        /// Restores instances from a backup file, turning each line into an instance with `map`.
+        ///
+        /// Backup lines carry no model name, so this can restore a backup made by another
+        /// model, e.g. one that was since renamed, as long as `map` can read its lines.
+        /// Every instance is written with `Self::save`, and the file is verified like in
+        /// [`restore`](Self::restore) before anything is written.
+        ///
+        /// # Example
+        ///
+        /// ```no_run
+        /// # use ergokv::Store;
+        /// # use tikv_client::TransactionClient;
+        /// # #[derive(Store)]
+        /// # struct User { }
+        /// # #[derive(Store)]
+        /// # struct UserV2 { }
+        /// # async fn example() -> Result<(), tikv_client::Error> {
+        /// # let client = TransactionClient::new(vec!["127.0.0.1:2379"]).await?;
+        /// let mut txn = client.begin_optimistic().await?;
+        /// UserV2::restore_mapping(&mut txn, "backups/User_1234567890.json", |line| {
+        ///     User::from_backup_json(line).map(UserV2::from)
+        /// })
+        /// .await?;
+        /// txn.commit().await?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn restore_mapping(
+            txn: &mut tikv_client::Transaction,
+            path: impl AsRef<std::path::Path>,
+            mut map: impl FnMut(&str) -> Result<Self, tikv_client::Error>,
+        ) -> Result<(), tikv_client::Error> {
+            use std::io::BufRead;
+
+            Self::verify_backup_file(&path)?;
+
+            let file = std::fs::File::open(path)
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to open backup file: {}", e)))?;
+
+            for line in std::io::BufReader::new(file).lines() {
+                let line = line
+                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to read line: {}", e)))?;
+
+                Self::save(&map(&line)?, txn).await?;
+            }
+
+            Ok(())
+        }
+
+        /// Restores instances from a backup file created by [`backup_framed`](Self::backup_framed).
+        ///
+        /// Behaves like [`restore`](Self::restore): the file is verified first, and every
+        /// instance is written with `Self::save` within `txn`.
+        ///
+        /// # Errors
+        ///
+        /// In addition to the errors of [`restore`](Self::restore), this fails if the file
+        /// ends in the middle of a frame.
+        pub async fn restore_framed(txn: &mut tikv_client::Transaction, path: impl AsRef<std::path::Path>) -> Result<(), tikv_client::Error> {
+            use std::io::Read;
+
+            Self::verify_backup_file(&path)?;
+
+            let file = std::fs::File::open(path)
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to open backup file: {}", e)))?;
+            let mut reader = std::io::BufReader::new(file);
+
+            loop {
+                let mut header = [0u8; 8];
+                let mut filled = 0;
+                while filled < header.len() {
+                    match reader.read(&mut header[filled..]) {
+                        Ok(0) => break,
+                        Ok(n) => filled += n,
+                        Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                        Err(e) => {
+                            return Err(tikv_client::Error::StringError(format!("Failed to read frame: {}", e)))
+                        }
+                    }
+                }
+                match filled {
+                    0 => break,
+                    8 => {}
+                    _ => return Err(tikv_client::Error::StringError("Backup truncated in a frame header".to_string())),
+                }
+
+                let len = usize::try_from(u64::from_le_bytes(header))
+                    .map_err(|e| tikv_client::Error::StringError(format!("Invalid frame length: {}", e)))?;
+                let mut json = vec![0u8; len];
+                reader.read_exact(&mut json)
+                    .map_err(|e| tikv_client::Error::StringError(format!("Backup truncated in a frame: {}", e)))?;
+                let json = String::from_utf8(json)
+                    .map_err(|e| tikv_client::Error::StringError(format!("Invalid frame: {}", e)))?;
+
+                Self::save(&Self::from_backup_json(&json)?, txn).await?;
+            }
+
+            Ok(())
+        }
+
+        /// Reads all instances from a backup file created by [`backup`](Self::backup) into memory.
+        ///
+        /// Unlike [`restore`](Self::restore), this does not touch TiKV at all, which makes it
+        /// useful for inspecting or processing a backup offline.
+        ///
+        /// # Errors
+        ///
+        /// This function will return an error if:
+        /// - The backup file cannot be read
+        /// - Any line fails to deserialize from JSON
+        ///
+        /// # Example
+        ///
+        /// ```no_run
+        /// # use ergokv::Store;
+        /// # #[derive(Store)]
+        /// # struct User { }
+        /// # fn example() -> Result<(), tikv_client::Error> {
+        /// let users = User::from_backup_file("backups/User_1234567890.json")?;
+        /// println!("Backup contains {} users", users.len());
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn from_backup_file(path: impl AsRef<std::path::Path>) -> Result<Vec<Self>, tikv_client::Error> {
+            Self::iter_backup_file(path)?.collect()
+        }
+
+        /// Streaming variant of [`from_backup_file`](Self::from_backup_file).
+        ///
+        /// Returns an iterator that reads and deserializes one instance per line, so the
+        /// whole backup never has to be held in memory. Opening the file fails eagerly,
+        /// while read and decode errors are yielded by the iterator.
+        #[allow(clippy::result_large_err)]
+        pub fn iter_backup_file(path: impl AsRef<std::path::Path>) -> Result<impl Iterator<Item = Result<Self, tikv_client::Error>>, tikv_client::Error> {
             use std::io::BufRead;
 
             let file = std::fs::File::open(path)
                 .map_err(|e| tikv_client::Error::StringError(format!("Failed to open backup file: {}", e)))?;
 
             let reader = std::io::BufReader::new(file);
-            for line in reader.lines() {
+            Ok(reader.lines().map(|line| {
                 let line = line
                     .map_err(|e| tikv_client::Error::StringError(format!("Failed to read line: {}", e)))?;
 
-                let item: Self = serde_json::from_str(&line)
-                    .map_err(|e| tikv_client::Error::StringError(format!("Failed to deserialize: {}", e)))?;
+                Self::from_backup_json(&line)
+            }))
+        }
+
+        /// Returns the path of the checksum file belonging to the backup at `path`.
+        ///
+        /// This is the backup path with `.blake3` appended, and it holds the hex encoded
+        /// BLAKE3 hash of the backup's contents.
+        pub fn backup_checksum_path(path: impl AsRef<std::path::Path>) -> std::path::PathBuf {
+            let mut checksum_path = path.as_ref().as_os_str().to_owned();
+            checksum_path.push(".blake3");
+            checksum_path.into()
+        }
+
+        /// Checks a backup file against its checksum file.
+        ///
+        /// Backups without a checksum file, such as ones made by older versions, are
+        /// accepted as they are.
+        ///
+        /// # Errors
+        ///
+        /// Returns a "backup corrupt" error if the contents don't match the checksum,
+        /// or an error if either file cannot be read.
+        pub fn verify_backup_file(path: impl AsRef<std::path::Path>) -> Result<(), tikv_client::Error> {
+            let path = path.as_ref();
+
+            let expected = match std::fs::read_to_string(Self::backup_checksum_path(path)) {
+                Ok(expected) => expected,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+                Err(e) => {
+                    return Err(tikv_client::Error::StringError(format!("Failed to read checksum file: {}", e)))
+                }
+            };
+
+            let mut file = std::fs::File::open(path)
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to open backup file: {}", e)))?;
+            let mut hasher = ::ergokv::blake3::Hasher::new();
+            std::io::copy(&mut file, &mut hasher)
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to read backup file: {}", e)))?;
 
-                item.save(txn).await?;
+            if hasher.finalize().to_hex().as_str() != expected.trim() {
+                return Err(tikv_client::Error::StringError(format!(
+                    "Backup corrupt: {} does not match its checksum",
+                    path.display()
+                )));
             }
 
             Ok(())
         }
+
+        /// Serializes the instance exactly as [`backup`](Self::backup) writes it, as a single line of JSON.
+        pub fn to_backup_json(&self) -> Result<String, tikv_client::Error> {
+            ::ergokv::serde_json::to_string(self)
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to serialize {}: {}", Self::MODEL_NAME, e)))
+        }
+
+        /// Returns the fields of the instance as JSON values, by their serialized names.
+        ///
+        /// The values are those of [`to_backup_json`](Self::to_backup_json), so serde attributes
+        /// like `rename` and `with` apply.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the instance cannot be serialized as a JSON object, e.g. if it holds a map
+        /// with non-string keys.
+        pub fn as_map(&self) -> std::collections::HashMap<String, ::ergokv::serde_json::Value> {
+            match ::ergokv::serde_json::to_value(self) {
+                Ok(::ergokv::serde_json::Value::Object(fields)) => fields.into_iter().collect(),
+                Ok(_) => panic!("{} is not serialized as a JSON object", Self::MODEL_NAME),
+                Err(e) => panic!("Failed to serialize {}: {}", Self::MODEL_NAME, e),
+            }
+        }
+
+        /// Deserializes an instance from one line of a backup, as [`restore`](Self::restore) reads it.
+        pub fn from_backup_json(line: &str) -> Result<Self, tikv_client::Error> {
+            ::ergokv::serde_json::from_str(line)
+                .map_err(|e| tikv_client::Error::StringError(format!("Failed to deserialize {}: {}", Self::MODEL_NAME, e)))
+        }
     }
 }
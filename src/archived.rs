@@ -0,0 +1,59 @@
+//! Archived field values of `#[store(format = "rkyv")]` fields.
+use rkyv::{
+    api::high::{HighDeserializer, HighValidator},
+    bytecheck::CheckBytes,
+    rancor,
+    util::AlignedVec,
+    Archive, Deserialize,
+};
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+/// A validated, archived `T`, dereferencing to `T`'s archived form.
+///
+/// Reading through it skips deserializing the value, which for large values
+/// is most of the cost of loading them. Returned by the generated
+/// `load_field_<field>_archived` methods.
+pub struct ArchivedValue<T> {
+    bytes: AlignedVec,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T: Archive> ArchivedValue<T>
+where
+    T::Archived:
+        for<'a> CheckBytes<HighValidator<'a, rancor::Error>>,
+{
+    /// Validates archived bytes of a `T`, copying them to aligned memory.
+    pub fn new(bytes: &[u8]) -> Result<Self, rancor::Error> {
+        let mut aligned = AlignedVec::new();
+        aligned.extend_from_slice(bytes);
+        rkyv::access::<T::Archived, rancor::Error>(&aligned)?;
+
+        Ok(Self {
+            bytes: aligned,
+            _value: PhantomData,
+        })
+    }
+
+    /// Deserializes the archived value into an owned `T`.
+    pub fn deserialize(&self) -> Result<T, rancor::Error>
+    where
+        T::Archived:
+            Deserialize<T, HighDeserializer<rancor::Error>>,
+    {
+        rkyv::deserialize::<T, rancor::Error>(&**self)
+    }
+}
+
+impl<T: Archive> Deref for ArchivedValue<T> {
+    type Target = T::Archived;
+
+    fn deref(&self) -> &T::Archived {
+        // SAFETY: the bytes were validated as a `T::Archived` in `new`, and
+        // are never changed afterwards
+        unsafe {
+            rkyv::access_unchecked::<T::Archived>(&self.bytes)
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! Audit trail entries for models with `#[store(audit_log)]`.
+//!
+//! Every `save`, `set_*` and `delete` of such a model appends an
+//! [`AuditEntry`] in the same transaction as the mutation itself, so the
+//! log can never disagree with the data. Entries are stored per record
+//! and read back with the generated `audit_log` method.
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+/// The kind of mutation recorded in an [`AuditEntry`].
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq,
+)]
+pub enum AuditOperation {
+    /// The whole record was written with `save`.
+    Save,
+    /// A single field was updated with `set_<field>`.
+    Set,
+    /// The record was removed with `delete`.
+    Delete,
+}
+
+/// A single entry in the audit log of a record.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    /// When the mutation was made, according to the writer's clock.
+    pub timestamp: SystemTime,
+    /// What kind of mutation was made.
+    pub operation: AuditOperation,
+    /// The primary key of the record, serialized as JSON.
+    pub key: String,
+    /// The fields written by the mutation.
+    ///
+    /// `save` and `delete` always list every field of the model.
+    pub changed_fields: Vec<String>,
+}
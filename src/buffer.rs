@@ -0,0 +1,154 @@
+//! Buffered writes, applied to a transaction in one go.
+//!
+//! The generated `set_<field>_buffered` methods write into a [`WriteBuffer`]
+//! instead of a transaction. Writes to the same key replace each other in the
+//! buffer, so a field set several times before [`WriteBuffer::flush`] is only
+//! written once.
+//!
+//! The buffer keeps a running estimate of how many bytes its writes add to
+//! a transaction. With a soft limit set, a write that would take the estimate
+//! over it fails right away, well before TiKV rejects the commit of a
+//! transaction that grew too large.
+use std::collections::BTreeMap;
+use tikv_client::{Error, Transaction};
+
+/// Start of the message of the error returned for writes over the soft limit.
+const SIZE_LIMIT_EXCEEDED: &str =
+    "WriteBuffer soft size limit exceeded";
+
+/// Pending writes, keyed by the TiKV key they go to.
+///
+/// Nothing is written until [`flush`](Self::flush), and reads through the
+/// transaction do not see the pending writes before that.
+#[derive(Debug, Default, Clone)]
+pub struct WriteBuffer {
+    /// The value to put under each key, or `None` to delete it
+    pending: BTreeMap<String, Option<Vec<u8>>>,
+    /// Approximate bytes of the pending writes, keys included
+    size: usize,
+    /// Most bytes the pending writes may take, if limited
+    soft_limit: Option<usize>,
+}
+
+impl WriteBuffer {
+    /// Creates an empty buffer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the pending writes to about `bytes` bytes.
+    ///
+    /// A write that would take [`estimated_txn_size`](Self::estimated_txn_size)
+    /// over the limit fails with an error recognized by
+    /// [`is_size_limit_exceeded`], and leaves the buffer as it was.
+    #[must_use]
+    pub fn with_soft_limit(mut self, bytes: usize) -> Self {
+        self.soft_limit = Some(bytes);
+        self
+    }
+
+    /// Queues a put of `value` under `key`, replacing any write queued for it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the write would exceed the soft limit.
+    #[allow(clippy::result_large_err)]
+    pub fn put(
+        &mut self,
+        key: impl Into<String>,
+        value: Vec<u8>,
+    ) -> Result<(), Error> {
+        self.queue(key.into(), Some(value))
+    }
+
+    /// Queues a delete of `key`, replacing any write queued for it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the write would exceed the soft limit.
+    #[allow(clippy::result_large_err)]
+    pub fn delete(
+        &mut self,
+        key: impl Into<String>,
+    ) -> Result<(), Error> {
+        self.queue(key.into(), None)
+    }
+
+    /// Number of keys with a queued write.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Whether no write is queued.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Approximate number of bytes the queued writes add to a transaction,
+    /// counting each key and value once.
+    pub fn estimated_txn_size(&self) -> usize {
+        self.size
+    }
+
+    /// Applies the queued writes to `txn` in key order, returning how many
+    /// were applied. The buffer is empty afterwards, even if a write failed.
+    pub async fn flush(
+        &mut self,
+        txn: &mut Transaction,
+    ) -> Result<usize, Error> {
+        let pending = std::mem::take(&mut self.pending);
+        let count = pending.len();
+        self.size = 0;
+
+        for (key, value) in pending {
+            match value {
+                Some(value) => txn.put(key, value).await?,
+                None => txn.delete(key).await?,
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Queues a write, unless it would take the buffer over its soft limit.
+    #[allow(clippy::result_large_err)]
+    fn queue(
+        &mut self,
+        key: String,
+        value: Option<Vec<u8>>,
+    ) -> Result<(), Error> {
+        let replaced = self
+            .pending
+            .get(&key)
+            .map_or(0, |old| entry_size(&key, old.as_deref()));
+        let size = self.size - replaced
+            + entry_size(&key, value.as_deref());
+
+        if let Some(limit) = self.soft_limit {
+            if size > limit {
+                return Err(Error::StringError(format!(
+                    "{SIZE_LIMIT_EXCEEDED}: writing {key} would take it to {size} bytes, over {limit}"
+                )));
+            }
+        }
+
+        self.pending.insert(key, value);
+        self.size = size;
+        Ok(())
+    }
+}
+
+/// Approximate bytes a write of `value` under `key` adds to a transaction.
+fn entry_size(key: &str, value: Option<&[u8]>) -> usize {
+    key.len() + value.map_or(0, <[u8]>::len)
+}
+
+/// Tells whether `err` is a [`WriteBuffer`] write refused for exceeding
+/// the buffer's soft limit.
+pub fn is_size_limit_exceeded(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::StringError(message)
+            if message.starts_with(SIZE_LIMIT_EXCEEDED)
+    )
+}
@@ -0,0 +1,290 @@
+//! In-process read cache for models with `#[store(cache_ttl = "...")]`.
+//!
+//! Every such model gets its own [`ReadCache`], keyed by the serialized
+//! primary key. `load` serves instances from the cache while they are
+//! younger than the TTL, and every mutation invalidates the written key.
+//!
+//! TiKV transactions give no notice of their commit or rollback, so the
+//! cache remembers which transactions, by their start timestamp, wrote
+//! which keys. A transaction never reads a key it wrote from the cache, nor
+//! caches it, which keeps uncommitted (and possibly rolled back) values out
+//! of the cache for as long as the transaction lives. Other transactions
+//! also bypass the cache for a written key for one full TTL. Note that a
+//! cached instance may still be newer or older than the snapshot of the
+//! transaction reading it, by up to the TTL.
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+enum Slot<T> {
+    /// A cached instance, with the time it was cached and a recency tick
+    Cached {
+        value: T,
+        cached_at: Instant,
+        last_used: u64,
+    },
+    /// The key was written at the given time, don't cache it for a while
+    Dirty { since: Instant },
+}
+
+struct Inner<T> {
+    slots: HashMap<String, Slot<T>>,
+    /// The keys written by each transaction, by its start timestamp
+    writes: BTreeSet<(u64, String)>,
+    tick: u64,
+}
+
+impl<T> Inner<T> {
+    /// Whether the transaction starting at `txn` wrote `key`.
+    fn written_by(&self, txn: u64, key: &str) -> bool {
+        self.writes.contains(&(txn, key.to_string()))
+    }
+}
+
+/// A TTL and LRU bounded cache of model instances, used by generated code.
+///
+/// Transactions are identified by their start timestamp, i.e. the version
+/// of `Transaction::start_timestamp`.
+pub struct ReadCache<T> {
+    ttl: Duration,
+    capacity: usize,
+    inner: Mutex<Inner<T>>,
+}
+
+impl<T: Clone> ReadCache<T> {
+    /// Creates an empty cache holding up to `capacity` instances for `ttl` each.
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            inner: Mutex::new(Inner {
+                slots: HashMap::new(),
+                writes: BTreeSet::new(),
+                tick: 0,
+            }),
+        }
+    }
+
+    /// Returns the cached instance for `key`, if there is a fresh one and
+    /// the transaction starting at `txn` did not write it.
+    pub fn get(&self, key: &str, txn: u64) -> Option<T> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.written_by(txn, key) {
+            return None;
+        }
+        inner.tick += 1;
+        let tick = inner.tick;
+
+        match inner.slots.get_mut(key) {
+            Some(Slot::Cached {
+                value,
+                cached_at,
+                last_used,
+            }) if cached_at.elapsed() < self.ttl => {
+                *last_used = tick;
+                Some(value.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Caches an instance loaded from TiKV by the transaction starting at
+    /// `txn`, unless `key` was written recently or by that transaction.
+    pub fn insert(&self, key: String, value: T, txn: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.written_by(txn, &key) {
+            return;
+        }
+
+        if let Some(Slot::Dirty { since }) =
+            inner.slots.get(&key)
+        {
+            if since.elapsed() < self.ttl {
+                return;
+            }
+        }
+
+        if !inner.slots.contains_key(&key)
+            && inner.slots.len() >= self.capacity
+        {
+            self.evict(&mut inner);
+        }
+
+        inner.tick += 1;
+        let last_used = inner.tick;
+        inner.slots.insert(
+            key,
+            Slot::Cached {
+                value,
+                cached_at: Instant::now(),
+                last_used,
+            },
+        );
+    }
+
+    /// Drops the cached instance for `key`, written by the transaction
+    /// starting at `txn`. The key stays uncached for that transaction, and
+    /// for all others for one TTL.
+    ///
+    /// The writes of as many transactions as the cache holds instances are
+    /// remembered, those of the oldest transactions are forgotten first.
+    pub fn invalidate(&self, key: String, txn: u64) {
+        let mut inner = self.inner.lock().unwrap();
+
+        inner.writes.insert((txn, key.clone()));
+        while inner.writes.len() > self.capacity {
+            inner.writes.pop_first();
+        }
+
+        if !inner.slots.contains_key(&key)
+            && inner.slots.len() >= self.capacity
+        {
+            self.evict(&mut inner);
+        }
+
+        inner.slots.insert(
+            key,
+            Slot::Dirty {
+                since: Instant::now(),
+            },
+        );
+    }
+
+    /// Drops every cached instance.
+    ///
+    /// Written keys stay uncached for their writers, and for everyone else
+    /// until their TTL runs out.
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.slots.retain(|_, slot| {
+            matches!(slot, Slot::Dirty { .. })
+        });
+    }
+
+    /// Makes room for one more slot, dropping expired slots first, then the
+    /// least recently used instance, or the oldest written key if only
+    /// those are left.
+    fn evict(&self, inner: &mut Inner<T>) {
+        let ttl = self.ttl;
+        inner.slots.retain(|_, slot| match slot {
+            Slot::Cached { cached_at, .. } => {
+                cached_at.elapsed() < ttl
+            }
+            Slot::Dirty { since } => since.elapsed() < ttl,
+        });
+
+        if inner.slots.len() < self.capacity {
+            return;
+        }
+
+        let least_recent = inner
+            .slots
+            .iter()
+            .filter_map(|(key, slot)| match slot {
+                Slot::Cached { last_used, .. } => {
+                    Some((*last_used, key))
+                }
+                Slot::Dirty { .. } => None,
+            })
+            .min()
+            .map(|(_, key)| key.clone());
+        let oldest_written = || {
+            inner
+                .slots
+                .iter()
+                .filter_map(|(key, slot)| match slot {
+                    Slot::Dirty { since } => Some((*since, key)),
+                    Slot::Cached { .. } => None,
+                })
+                .min()
+                .map(|(_, key)| key.clone())
+        };
+
+        if let Some(key) = least_recent.or_else(oldest_written) {
+            inner.slots.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    #[test]
+    fn test_ttl_and_invalidation() {
+        let cache =
+            ReadCache::new(Duration::from_millis(50), 16);
+
+        cache.insert("a".to_string(), 1, 1);
+        assert_eq!(cache.get("a", 1), Some(1));
+
+        // Written keys are not cached again until the TTL passes
+        cache.invalidate("a".to_string(), 2);
+        assert_eq!(cache.get("a", 1), None);
+        cache.insert("a".to_string(), 2, 1);
+        assert_eq!(cache.get("a", 1), None);
+
+        sleep(Duration::from_millis(60));
+        cache.insert("a".to_string(), 3, 1);
+        assert_eq!(cache.get("a", 1), Some(3));
+
+        sleep(Duration::from_millis(60));
+        assert_eq!(cache.get("a", 1), None);
+    }
+
+    #[test]
+    fn test_writer_never_cached() {
+        let cache =
+            ReadCache::new(Duration::from_millis(50), 16);
+
+        cache.invalidate("a".to_string(), 2);
+        sleep(Duration::from_millis(60));
+
+        // Long after the TTL, the writer neither caches the key nor reads
+        // it from the cache, while other transactions do
+        cache.insert("a".to_string(), 1, 2);
+        assert_eq!(cache.get("a", 2), None);
+        cache.insert("a".to_string(), 1, 3);
+        assert_eq!(cache.get("a", 3), Some(1));
+        assert_eq!(cache.get("a", 2), None);
+    }
+
+    #[test]
+    fn test_lru_eviction_and_clear() {
+        let cache = ReadCache::new(Duration::from_secs(60), 2);
+
+        cache.insert("a".to_string(), 1, 1);
+        cache.insert("b".to_string(), 2, 1);
+        assert_eq!(cache.get("a", 1), Some(1));
+
+        // "b" is the least recently used
+        cache.insert("c".to_string(), 3, 1);
+        assert_eq!(cache.get("a", 1), Some(1));
+        assert_eq!(cache.get("b", 1), None);
+        assert_eq!(cache.get("c", 1), Some(3));
+
+        cache.clear();
+        assert_eq!(cache.get("a", 1), None);
+        assert_eq!(cache.get("c", 1), None);
+    }
+
+    #[test]
+    fn test_written_keys_bounded() {
+        let cache: ReadCache<u32> =
+            ReadCache::new(Duration::from_secs(60), 2);
+
+        for (txn, key) in ["a", "b", "c"].into_iter().enumerate()
+        {
+            cache.invalidate(key.to_string(), txn as u64);
+        }
+        let inner = cache.inner.lock().unwrap();
+        assert_eq!(inner.slots.len(), 2);
+        assert!(!inner.slots.contains_key("a"));
+
+        // The oldest transaction's write is forgotten first
+        assert_eq!(inner.writes.len(), 2);
+        assert!(!inner.written_by(0, "a"));
+        assert!(inner.written_by(2, "c"));
+    }
+}
@@ -0,0 +1,120 @@
+//! Checksums of stored instances, for models with `#[store(checksum)]`.
+//!
+//! Every write of such an instance stores a BLAKE3 hash of the stored bytes
+//! of all its fields under `ergokv:{MODEL}:{key}:__checksum`, and `load`
+//! compares it with a hash of the bytes it read. A field changed by anything
+//! but ergokv, whether through corruption or tampering, makes them differ.
+use std::sync::{Arc, RwLock};
+
+use tikv_client::Error;
+
+/// Start of the message of the error returned for a checksum mismatch.
+const CHECKSUM_MISMATCH: &str = "Checksum mismatch";
+
+/// Hashes the stored bytes of the fields of an instance, given as pairs of
+/// a field name and its value, `None` for a field with no stored value,
+/// used by generated code.
+pub fn record_checksum<'a>(
+    fields: impl IntoIterator<Item = (&'a str, Option<&'a [u8]>)>,
+) -> Vec<u8> {
+    let mut hasher = blake3::Hasher::new();
+    for (name, value) in fields {
+        hasher.update(&(name.len() as u64).to_le_bytes());
+        hasher.update(name.as_bytes());
+        match value {
+            Some(value) => {
+                hasher.update(&[1]);
+                hasher
+                    .update(&(value.len() as u64).to_le_bytes());
+                hasher.update(value);
+            }
+            None => {
+                hasher.update(&[0]);
+            }
+        }
+    }
+    hasher.finalize().as_bytes().to_vec()
+}
+
+/// The error for an instance at `path` whose stored fields do not match its
+/// checksum, used by generated code.
+pub fn checksum_mismatch(path: &str) -> Error {
+    Error::StringError(format!(
+        "{CHECKSUM_MISMATCH} for {path}: its stored fields do not match the checksum written with them"
+    ))
+}
+
+type MismatchHandler = Arc<dyn Fn(&Error) + Send + Sync>;
+
+static MISMATCH_HANDLER: RwLock<Option<MismatchHandler>> =
+    RwLock::new(None);
+
+/// Installs the handler called with the mismatch error of every instance
+/// that `load` of a `#[store(checksum = "warn")]` model returns despite a
+/// checksum mismatch, replacing any previously installed one. Without a
+/// handler, such mismatches go unreported.
+pub fn set_checksum_mismatch_handler(
+    handler: impl Fn(&Error) + Send + Sync + 'static,
+) {
+    *MISMATCH_HANDLER.write().unwrap() = Some(Arc::new(handler));
+}
+
+/// Reports a checksum mismatch to the installed handler, if any, used by
+/// generated code.
+pub fn report_checksum_mismatch(err: &Error) {
+    let handler = MISMATCH_HANDLER.read().unwrap().clone();
+    if let Some(handler) = handler {
+        handler(err);
+    }
+}
+
+/// Tells whether `err` is the error `load` returns for an instance whose
+/// stored fields do not match their checksum.
+pub fn is_checksum_mismatch(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::StringError(message)
+            if message.starts_with(CHECKSUM_MISMATCH)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_checksum() {
+        let checksum = record_checksum([
+            ("id", Some(&b"1"[..])),
+            ("name", Some(&b"ada"[..])),
+        ]);
+        assert_eq!(checksum.len(), 32);
+        assert_eq!(
+            checksum,
+            record_checksum([
+                ("id", Some(&b"1"[..])),
+                ("name", Some(&b"ada"[..])),
+            ])
+        );
+
+        // Bytes moved between fields, or a missing value, change it
+        assert_ne!(
+            checksum,
+            record_checksum([
+                ("id", Some(&b"1a"[..])),
+                ("name", Some(&b"da"[..])),
+            ])
+        );
+        assert_ne!(
+            record_checksum([("name", Some(&b""[..]))]),
+            record_checksum([("name", None)])
+        );
+
+        assert!(is_checksum_mismatch(&checksum_mismatch(
+            "User:1"
+        )));
+        assert!(!is_checksum_mismatch(&Error::StringError(
+            "Checksum".into()
+        )));
+    }
+}
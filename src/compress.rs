@@ -0,0 +1,85 @@
+//! Compression of the stored bytes of fields, for models with
+//! `#[store(compression_threshold = N)]`.
+//!
+//! Every value of such a field starts with a header byte telling whether the
+//! rest is compressed. Values larger than the threshold are compressed with
+//! LZ4, while smaller ones, which would gain little and could even grow, are
+//! stored as they are behind the header.
+use tikv_client::Error;
+
+/// Header of a value stored as it is.
+const STORED: u8 = 0;
+/// Header of a value compressed with LZ4, prefixed by its uncompressed size.
+const COMPRESSED: u8 = 1;
+
+/// Prefixes the encoded value of a field with its header, compressing it if
+/// it is larger than `threshold` bytes, used by generated code.
+pub fn compress_field(
+    value: &[u8],
+    threshold: usize,
+) -> Vec<u8> {
+    if value.len() <= threshold {
+        let mut stored = Vec::with_capacity(value.len() + 1);
+        stored.push(STORED);
+        stored.extend_from_slice(value);
+        return stored;
+    }
+
+    let mut stored = vec![COMPRESSED];
+    stored.extend(lz4_flex::compress_prepend_size(value));
+    stored
+}
+
+/// Reads a value written by [`compress_field`] back into the encoded value,
+/// used by generated code.
+///
+/// # Errors
+///
+/// Fails if the header is missing or unknown, or if the compressed bytes are
+/// corrupt.
+#[allow(clippy::result_large_err)]
+pub fn decompress_field(
+    stored: &[u8],
+) -> Result<Vec<u8>, Error> {
+    match stored.split_first() {
+        Some((&STORED, value)) => Ok(value.to_vec()),
+        Some((&COMPRESSED, value)) => {
+            lz4_flex::decompress_size_prepended(value).map_err(
+                |e| {
+                    Error::StringError(format!(
+                        "Failed to decompress: {e}"
+                    ))
+                },
+            )
+        }
+        Some((header, _)) => Err(Error::StringError(format!(
+            "Unknown compression header {header}"
+        ))),
+        None => Err(Error::StringError(
+            "Missing compression header".into(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_threshold() {
+        let small = b"tiny".to_vec();
+        let stored = compress_field(&small, 16);
+        assert_eq!(stored[0], STORED);
+        assert_eq!(&stored[1..], small.as_slice());
+        assert_eq!(decompress_field(&stored).unwrap(), small);
+
+        let large = vec![b'a'; 4096];
+        let stored = compress_field(&large, 16);
+        assert_eq!(stored[0], COMPRESSED);
+        assert!(stored.len() < large.len());
+        assert_eq!(decompress_field(&stored).unwrap(), large);
+
+        assert!(decompress_field(&[]).is_err());
+        assert!(decompress_field(&[7, 1, 2]).is_err());
+    }
+}
@@ -0,0 +1,160 @@
+//! Encryption at rest for fields marked `#[store(encrypt)]`.
+//!
+//! Such fields are CBOR encoded as usual, and the resulting bytes are passed
+//! through the process-wide [`Encryptor`] installed with [`set_encryptor`]
+//! before they are written to TiKV. Reading them back fails until an
+//! encryptor is installed, so the key never has to be known at compile time.
+use std::sync::{Arc, RwLock};
+use tikv_client::Error;
+
+/// Encrypts and decrypts the stored bytes of `#[store(encrypt)]` fields.
+///
+/// `decrypt` must accept everything `encrypt` produced, and should fail on
+/// anything else, e.g. bytes written with another key.
+pub trait Encryptor: Send + Sync {
+    /// Encrypts the encoded value of a field.
+    #[allow(clippy::result_large_err)]
+    fn encrypt(
+        &self,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, Error>;
+    /// Decrypts bytes returned by [`encrypt`](Self::encrypt).
+    #[allow(clippy::result_large_err)]
+    fn decrypt(
+        &self,
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, Error>;
+}
+
+static ENCRYPTOR: RwLock<Option<Arc<dyn Encryptor>>> =
+    RwLock::new(None);
+
+/// Installs the encryptor used for all `#[store(encrypt)]` fields, replacing
+/// any previously installed one.
+pub fn set_encryptor(encryptor: impl Encryptor + 'static) {
+    *ENCRYPTOR.write().unwrap() = Some(Arc::new(encryptor));
+}
+
+#[allow(clippy::result_large_err)]
+fn encryptor() -> Result<Arc<dyn Encryptor>, Error> {
+    ENCRYPTOR.read().unwrap().clone().ok_or_else(|| {
+        Error::StringError(
+            "No encryptor installed, see ergokv::set_encryptor"
+                .into(),
+        )
+    })
+}
+
+/// Encrypts the encoded value of a field with the installed encryptor, used by generated code.
+#[allow(clippy::result_large_err)]
+pub fn encrypt_field(
+    plaintext: &[u8],
+) -> Result<Vec<u8>, Error> {
+    encryptor()?.encrypt(plaintext)
+}
+
+/// Decrypts the stored value of a field with the installed encryptor, used by generated code.
+#[allow(clippy::result_large_err)]
+pub fn decrypt_field(
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, Error> {
+    encryptor()?.decrypt(ciphertext)
+}
+
+/// An [`Encryptor`] using AES-256-GCM, available with the `encryption` feature.
+///
+/// Every value is encrypted with a fresh random nonce, which is stored in
+/// front of the ciphertext, so equal values are stored as different bytes.
+#[cfg(feature = "encryption")]
+pub struct AesGcmEncryptor {
+    cipher: aes_gcm::Aes256Gcm,
+}
+
+#[cfg(feature = "encryption")]
+impl AesGcmEncryptor {
+    /// Size of the nonce stored in front of every ciphertext.
+    const NONCE_SIZE: usize = 12;
+
+    /// Creates an encryptor from a 256-bit key.
+    pub fn new(key: &[u8; 32]) -> Self {
+        use aes_gcm::KeyInit;
+
+        Self {
+            cipher: aes_gcm::Aes256Gcm::new(key.into()),
+        }
+    }
+}
+
+#[cfg(feature = "encryption")]
+impl Encryptor for AesGcmEncryptor {
+    fn encrypt(
+        &self,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        use aes_gcm::aead::{Aead, AeadCore, OsRng};
+
+        let nonce =
+            aes_gcm::Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|e| {
+                Error::StringError(format!(
+                    "Failed to encrypt: {e}"
+                ))
+            })?;
+
+        let mut value = nonce.to_vec();
+        value.extend_from_slice(&ciphertext);
+        Ok(value)
+    }
+
+    fn decrypt(
+        &self,
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        use aes_gcm::aead::Aead;
+
+        if ciphertext.len() < Self::NONCE_SIZE {
+            return Err(Error::StringError(
+                "Failed to decrypt: value too short".into(),
+            ));
+        }
+        let (nonce, ciphertext) =
+            ciphertext.split_at(Self::NONCE_SIZE);
+
+        self.cipher.decrypt(nonce.into(), ciphertext).map_err(
+            |e| {
+                Error::StringError(format!(
+                    "Failed to decrypt: {e}"
+                ))
+            },
+        )
+    }
+}
+
+#[cfg(all(test, feature = "encryption"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aes_gcm_round_trip() {
+        let encryptor = AesGcmEncryptor::new(&[7; 32]);
+
+        let first = encryptor.encrypt(b"secret").unwrap();
+        let second = encryptor.encrypt(b"secret").unwrap();
+        assert_ne!(first, second);
+        assert_eq!(
+            encryptor.decrypt(&first).unwrap(),
+            b"secret"
+        );
+
+        // Other keys and tampered values are rejected
+        let other = AesGcmEncryptor::new(&[8; 32]);
+        assert!(other.decrypt(&first).is_err());
+        let mut tampered = first.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(encryptor.decrypt(&tampered).is_err());
+        assert!(encryptor.decrypt(&[1, 2, 3]).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+//! CSV formatting for the generated `export_csv` methods.
+//!
+//! Rows follow RFC 4180: cells are separated by commas, rows end in
+//! `\r\n`, and a cell containing a comma, a quote or a line break is quoted,
+//! with its quotes doubled.
+use serde_json::Value;
+use std::io::{self, Write};
+
+/// Formats a JSON value as a CSV cell, used by generated code.
+///
+/// Strings are written as they are and `null` as an empty cell. Numbers and
+/// booleans are written as JSON, as are arrays and objects, which end up
+/// quoted as they contain commas or quotes.
+pub fn csv_cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Writes one row of cells, quoting those that need it, used by generated
+/// code.
+pub fn write_csv_row<W: Write + ?Sized>(
+    writer: &mut W,
+    cells: impl IntoIterator<Item = impl AsRef<str>>,
+) -> io::Result<()> {
+    for (i, cell) in cells.into_iter().enumerate() {
+        if i > 0 {
+            writer.write_all(b",")?;
+        }
+
+        let cell = cell.as_ref();
+        if cell.contains([',', '"', '\n', '\r']) {
+            write!(writer, "\"{}\"", cell.replace('"', "\"\""))?;
+        } else {
+            writer.write_all(cell.as_bytes())?;
+        }
+    }
+
+    writer.write_all(b"\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_quoting() {
+        let mut out = Vec::new();
+        write_csv_row(
+            &mut out,
+            ["plain", "a,b", "say \"hi\"", "two\nlines", ""],
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "plain,\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\",\r\n"
+        );
+    }
+
+    #[test]
+    fn test_cells() {
+        assert_eq!(csv_cell(&json!(null)), "");
+        assert_eq!(csv_cell(&json!("text")), "text");
+        assert_eq!(csv_cell(&json!(4.5)), "4.5");
+        assert_eq!(csv_cell(&json!(true)), "true");
+        assert_eq!(csv_cell(&json!([1, 2])), "[1,2]");
+        assert_eq!(
+            csv_cell(&json!({"a": "b"})),
+            "{\"a\":\"b\"}"
+        );
+    }
+}
@@ -0,0 +1,110 @@
+//! Storage of `#[store(flatten)]` fields, one key per sub-field.
+//!
+//! The value of such a field is serialized into a map, and every entry is
+//! stored as CBOR under `ergokv:{MODEL}:{key}:{field}.{subfield}`. Loading
+//! collects the entries back into a map and deserializes the value from it.
+use ciborium::Value;
+use serde::{de::DeserializeOwned, Serialize};
+use tikv_client::Error;
+
+/// Splits a value into its sub-fields and their CBOR encodings, used by generated code.
+///
+/// # Errors
+///
+/// Fails if the value does not serialize to a map with string keys.
+#[allow(clippy::result_large_err)]
+pub fn flatten_fields<T: Serialize>(
+    value: &T,
+) -> Result<Vec<(String, Vec<u8>)>, Error> {
+    let Value::Map(entries) =
+        Value::serialized(value).map_err(|e| {
+            Error::StringError(format!(
+                "Failed to serialize: {e}"
+            ))
+        })?
+    else {
+        return Err(Error::StringError(
+            "A #[store(flatten)] field must serialize to a map"
+                .into(),
+        ));
+    };
+
+    entries
+        .into_iter()
+        .map(|(name, value)| {
+            let Value::Text(name) = name else {
+                return Err(Error::StringError(
+                    "A #[store(flatten)] field must have string keys"
+                        .into(),
+                ));
+            };
+
+            let mut bytes = Vec::new();
+            ciborium::ser::into_writer(&value, &mut bytes).map_err(
+                |e| Error::StringError(format!("Failed to encode {name}: {e}")),
+            )?;
+            Ok((name, bytes))
+        })
+        .collect()
+}
+
+/// Reassembles a value from its sub-fields, as returned by [`flatten_fields`], used by
+/// generated code.
+#[allow(clippy::result_large_err)]
+pub fn unflatten_fields<T: DeserializeOwned>(
+    entries: impl IntoIterator<Item = (String, Vec<u8>)>,
+) -> Result<T, Error> {
+    let entries = entries
+        .into_iter()
+        .map(|(name, bytes)| {
+            let value =
+                ciborium::de::from_reader(bytes.as_slice())
+                    .map_err(|e| {
+                        Error::StringError(format!(
+                            "Failed to decode {name}: {e}"
+                        ))
+                    })?;
+            Ok((Value::Text(name), value))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Value::Map(entries).deserialized().map_err(|e| {
+        Error::StringError(format!("Failed to deserialize: {e}"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Address {
+        street: String,
+        zip: u32,
+        note: Option<String>,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let address = Address {
+            street: "Main".into(),
+            zip: 12345,
+            note: None,
+        };
+
+        let entries = flatten_fields(&address).unwrap();
+        let names: Vec<&str> = entries
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, ["street", "zip", "note"]);
+
+        // Entries may come back in any order
+        let reversed: Address =
+            unflatten_fields(entries.into_iter().rev()).unwrap();
+        assert_eq!(reversed, address);
+
+        assert!(flatten_fields(&42u32).is_err());
+    }
+}
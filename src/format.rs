@@ -0,0 +1,85 @@
+//! Tags of field values stored in a format other than CBOR, for fields with
+//! `#[store(format = "...")]`.
+//!
+//! Every value of a `bincode` or `rkyv` field starts with a tag byte naming
+//! its format, followed by the encoded value. CBOR values, including those
+//! stored before a field moved to another format, have no tag. The tags are
+//! bytes no CBOR value starts with, i.e. an unsigned integer with reserved
+//! additional information, so every stored value is read in the format it
+//! was written in, and none is ambiguous.
+use std::fmt;
+
+/// Tag of a value encoded with bincode.
+pub const BINCODE_TAG: u8 = 0x1c;
+/// Tag of an archived rkyv value.
+pub const RKYV_TAG: u8 = 0x1d;
+
+/// The format a stored field value was written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoredFormat {
+    /// An untagged CBOR value
+    Cbor,
+    /// A value encoded with bincode, behind [`BINCODE_TAG`]
+    Bincode,
+    /// An archived rkyv value, behind [`RKYV_TAG`]
+    Rkyv,
+}
+
+impl fmt::Display for StoredFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            StoredFormat::Cbor => "CBOR",
+            StoredFormat::Bincode => "bincode",
+            StoredFormat::Rkyv => "rkyv",
+        })
+    }
+}
+
+/// Splits a stored field value into the format it was written in and its
+/// encoded bytes, without the tag, used by generated code.
+pub fn split_format_tag(stored: &[u8]) -> (StoredFormat, &[u8]) {
+    match stored.split_first() {
+        Some((&BINCODE_TAG, rest)) => {
+            (StoredFormat::Bincode, rest)
+        }
+        Some((&RKYV_TAG, rest)) => (StoredFormat::Rkyv, rest),
+        _ => (StoredFormat::Cbor, stored),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_format_tag() {
+        assert_eq!(
+            split_format_tag(&[BINCODE_TAG, 100, 0]),
+            (StoredFormat::Bincode, &[100, 0][..])
+        );
+        assert_eq!(
+            split_format_tag(&[RKYV_TAG, 100, 0]),
+            (StoredFormat::Rkyv, &[100, 0][..])
+        );
+        assert_eq!(
+            split_format_tag(&[]),
+            (StoredFormat::Cbor, &[][..])
+        );
+
+        // No CBOR value starts with a tag, e.g. 100u16 as CBOR
+        let mut cbor = Vec::new();
+        ciborium::ser::into_writer(&100u16, &mut cbor).unwrap();
+        assert_eq!(cbor, [0x18, 0x64]);
+        assert_eq!(
+            split_format_tag(&cbor),
+            (StoredFormat::Cbor, &cbor[..])
+        );
+        for tag in [BINCODE_TAG, RKYV_TAG] {
+            assert!(ciborium::de::from_reader::<
+                ciborium::Value,
+                _,
+            >(&[tag, 0][..])
+            .is_err());
+        }
+    }
+}
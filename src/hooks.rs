@@ -0,0 +1,55 @@
+//! Lifecycle hooks for models with `#[store(hooks)]`.
+//!
+//! Such models implement [`StoreHooks`], whose methods their generated
+//! `save` and `delete` call within the same transaction, e.g. to publish
+//! events or to keep derived data in sync.
+
+use std::future::Future;
+
+use tikv_client::{Error, Transaction};
+
+/// Callbacks run by `save` and `delete` of a `#[store(hooks)]` model.
+///
+/// Every method does nothing by default, so an implementation only needs
+/// the ones it cares about. An error returned by a hook fails the `save` or
+/// `delete` calling it; the writes it already made stay in the transaction,
+/// which should then be rolled back.
+///
+/// `save` only calls the hooks when it writes the instance, so not for an
+/// instance skipped by `#[store(on_conflict = "skip")]` or rejected as a
+/// conflict.
+pub trait StoreHooks {
+    /// Called by `save` before any of the instance is written.
+    fn before_save(
+        &self,
+        _txn: &mut Transaction,
+    ) -> impl Future<Output = Result<(), Error>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Called by `save` after the instance and its index entries are written.
+    fn after_save(
+        &self,
+        _txn: &mut Transaction,
+    ) -> impl Future<Output = Result<(), Error>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Called by `delete` and `delete_many` before any of the instance is
+    /// removed.
+    fn before_delete(
+        &self,
+        _txn: &mut Transaction,
+    ) -> impl Future<Output = Result<(), Error>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Called by `delete` and `delete_many` after the instance and its index
+    /// entries are removed.
+    fn after_delete(
+        &self,
+        _txn: &mut Transaction,
+    ) -> impl Future<Output = Result<(), Error>> + Send {
+        async { Ok(()) }
+    }
+}
@@ -0,0 +1,47 @@
+//! Reports of the generated `verify_integrity` consistency check.
+//!
+//! `verify_integrity` compares the master trie and every secondary index
+//! of a model with what its stored field values call for, and lists the
+//! differences without repairing them. `reindex_all` does the repairs.
+
+/// An index entry pointing to a record, as listed in an [`IntegrityReport`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct IndexPointer {
+    /// The TiKV key of the index entry.
+    pub index_key: String,
+    /// The primary key of the record, serialized like in the record's own keys.
+    pub key: String,
+}
+
+/// The outcome of a `verify_integrity` run.
+///
+/// Trie entries are given as record paths, `{MODEL}:{key}`, and records by
+/// their serialized primary key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// Number of records found in the field data.
+    pub records: usize,
+    /// Trie entries with no field data behind them.
+    pub orphaned_trie_entries: Vec<String>,
+    /// Records missing from the trie.
+    pub missing_trie_entries: Vec<String>,
+    /// Records whose field data is present but cannot be loaded, e.g. because
+    /// a field is missing.
+    pub unloadable_records: Vec<String>,
+    /// Index entries pointing to a record that doesn't exist, can't be loaded,
+    /// or no longer has the indexed value.
+    pub dangling_index_entries: Vec<IndexPointer>,
+    /// Index entries a record should have but doesn't.
+    pub missing_index_entries: Vec<IndexPointer>,
+}
+
+impl IntegrityReport {
+    /// Whether no inconsistency was found.
+    pub fn is_consistent(&self) -> bool {
+        self.orphaned_trie_entries.is_empty()
+            && self.missing_trie_entries.is_empty()
+            && self.unloadable_records.is_empty()
+            && self.dangling_index_entries.is_empty()
+            && self.missing_index_entries.is_empty()
+    }
+}
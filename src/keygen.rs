@@ -0,0 +1,25 @@
+//! Key generation for models with `#[key(auto)]`.
+//!
+//! Such models get `new_with_generated_key` and `save_new`, which fill in
+//! their key through [`GenerateKey`].
+
+/// A key type whose values can be generated, for the `#[key(auto)]` field of
+/// a model.
+///
+/// Generated keys should be unique with overwhelming probability, e.g.
+/// random. `save_new` generates another key when one is already taken, but
+/// only a few times.
+///
+/// An implementation is provided for `uuid::Uuid` behind the `uuid` feature,
+/// generating random (version 4) UUIDs.
+pub trait GenerateKey {
+    /// Returns a new key.
+    fn generate() -> Self;
+}
+
+#[cfg(feature = "uuid")]
+impl GenerateKey for uuid::Uuid {
+    fn generate() -> Self {
+        uuid::Uuid::new_v4()
+    }
+}
@@ -29,22 +29,168 @@
 //! ```
 //!
 //! This will generate `load`, `save`, `delete`, `by_username`, `set_username`, and `set_email` methods for `User`.
+//!
+//! ## Key types
+//!
+//! The type of the `#[key]` field must implement `Serialize`,
+//! `DeserializeOwned`, `Clone` and `PartialEq`. A key type missing one of
+//! them is reported at the key field.
+//!
+//! ## Immutable fields
+//!
+//! Fields marked `#[store(immutable)]` keep the value their instance was
+//! created with. No `set_*` method is generated for them, and `save` rejects
+//! an instance whose immutable field differs from the stored one:
+//!
+//! ```compile_fail
+//! use ergokv::Store;
+//! use serde::{Serialize, Deserialize};
+//! use uuid::Uuid;
+//!
+//! #[derive(Store, Serialize, Deserialize)]
+//! struct Project {
+//!     #[key]
+//!     id: Uuid,
+//!     #[store(immutable)]
+//!     tenant: String,
+//!     name: String,
+//! }
+//!
+//! async fn move_project(project: &mut Project, txn: &mut tikv_client::Transaction) {
+//!     project.set_name("Rockets".to_string(), txn).await.unwrap();
+//!     project.set_tenant("globex".to_string(), txn).await.unwrap();
+//! }
+//! ```
 
 pub use ergokv_macro::Store;
 
+pub use bincode;
+pub use blake3;
 pub use ciborium;
 pub use futures;
+pub use inventory;
+pub use serde;
 pub use serde_json;
 
+#[cfg(feature = "metrics")]
+pub use metrics;
+#[cfg(feature = "rkyv")]
+pub use rkyv;
+
+#[cfg(feature = "rkyv")]
+mod archived;
+mod audit;
+mod buffer;
+mod cache;
+mod checksum;
+mod compress;
+mod encrypt;
+mod export;
+mod flatten;
+mod format;
+mod hooks;
+mod integrity;
+mod keygen;
 mod local_cluster;
+mod log;
+mod outbox;
+mod range_key;
+mod registry;
+mod reindex;
+mod restore;
+mod save;
+mod store;
+mod timeline;
+mod timestamps;
 mod trie;
+mod txn;
+mod value_size;
 
+#[cfg(feature = "rkyv")]
+pub use archived::ArchivedValue;
+pub use audit::{AuditEntry, AuditOperation};
+pub use buffer::{is_size_limit_exceeded, WriteBuffer};
+pub use cache::ReadCache;
+pub use checksum::{
+    checksum_mismatch, is_checksum_mismatch, record_checksum,
+    report_checksum_mismatch, set_checksum_mismatch_handler,
+};
+pub use compress::{compress_field, decompress_field};
+#[cfg(feature = "encryption")]
+pub use encrypt::AesGcmEncryptor;
+pub use encrypt::{
+    decrypt_field, encrypt_field, set_encryptor, Encryptor,
+};
+pub use export::{csv_cell, write_csv_row};
+pub use flatten::{flatten_fields, unflatten_fields};
+pub use format::{
+    split_format_tag, StoredFormat, BINCODE_TAG, RKYV_TAG,
+};
+pub use hooks::StoreHooks;
+pub use integrity::{IndexPointer, IntegrityReport};
+pub use keygen::GenerateKey;
 pub use local_cluster::LocalCluster;
+pub use log::{Log, LogEntry};
+pub use outbox::{OutboxEvent, OutboxOperation};
+pub use range_key::RangeKey;
+pub use registry::{
+    backup_all, registered_models, BackupFn, ModelRegistration,
+};
+pub use reindex::ReindexReport;
+pub use restore::ConflictPolicy;
+pub use save::SaveOutcome;
+pub use store::Store;
+pub use timeline::{
+    append_timeline, recent_activity, TimelineEntry,
+};
+pub use timestamps::AutoTimestamp;
 pub use trie::PrefixTrie;
+pub use txn::{is_retryable, run_txn};
+pub use value_size::{is_value_too_large, value_too_large};
+
+use std::collections::HashMap;
 
 /// Helper function to connect to a single or multiple TiKV pd-server
 pub async fn connect(
     endpoints: Vec<&str>,
 ) -> Result<tikv_client::TransactionClient, tikv_client::Error> {
-    tikv_client::TransactionClient::new(endpoints).await
+    connect_with(endpoints, tikv_client::Config::default()).await
+}
+
+/// Connects to a single or multiple TiKV pd-server with the given client
+/// configuration.
+///
+/// Unlike [`connect`], the endpoints can be owned strings, e.g. read from a
+/// configuration file at runtime, and `config` can set up timeouts and TLS
+/// (`Config::with_security`).
+pub async fn connect_with(
+    endpoints: impl IntoIterator<Item = impl Into<String>>,
+    config: tikv_client::Config,
+) -> Result<tikv_client::TransactionClient, tikv_client::Error> {
+    let endpoints: Vec<String> =
+        endpoints.into_iter().map(Into::into).collect();
+    tikv_client::TransactionClient::new_with_config(
+        endpoints, config,
+    )
+    .await
+}
+
+/// Counts the stored instances of every model registered in a master trie.
+///
+/// Models derived with [`Store`] register their instances in the trie with
+/// the prefix `"ergokv:__trie"`. The result maps each model name to its
+/// number of instances.
+pub async fn model_stats(
+    txn: &mut tikv_client::Transaction,
+    trie_prefix: &str,
+) -> Result<HashMap<String, usize>, tikv_client::Error> {
+    let mut stats = HashMap::new();
+
+    for key in PrefixTrie::new(trie_prefix).all(txn).await? {
+        if let Some((model, _)) = key.split_once(':') {
+            *stats.entry(model.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    Ok(stats)
 }
@@ -2,7 +2,7 @@ use tikv_client::TransactionClient;
 
 use std::env;
 use std::net::{Ipv4Addr, SocketAddrV4, TcpListener};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command};
 use std::thread::sleep;
 use std::time::Duration;
@@ -17,12 +17,25 @@ use std::time::Duration;
 /// [`LocalCluster`] will automatically pick free ports, meaning that you can
 /// have multiple apps running seamlessly at the same time.
 ///
+/// Use [`LocalCluster::start_cluster()`] to run several TiKV nodes against
+/// the same PD, e.g. to test behavior when a node goes down. Single
+/// components can also be paused and resumed, see
+/// [`LocalCluster::pause_tikv()`] and [`LocalCluster::pause_pd()`].
+///
+/// The output of every process is written to the `logs` directory under the
+/// data dir, and can be read back with [`LocalCluster::logs()`]. With
+/// [`LocalCluster::set_print_logs_on_panic()`], it is also printed when the
+/// cluster is dropped by a panicking test.
+///
 /// Still, you should probably deploy a proper production cluster for your app
 /// in production.
 pub struct LocalCluster {
     pd_process: Child,
-    tikv_process: Child,
+    tikv_processes: Vec<Child>,
     pd_port: u16,
+    log_dir: PathBuf,
+    process_names: Vec<String>,
+    print_logs_on_panic: bool,
 }
 
 impl LocalCluster {
@@ -34,18 +47,14 @@ impl LocalCluster {
             .expect("You have no free ports, fuck off")
     }
 
-    fn generate_service_ports() -> [u16; 4] {
-        let mut result = [0; 4];
+    fn generate_service_ports(count: usize) -> Vec<u16> {
+        let mut result = Vec::with_capacity(count);
 
-        result[0] = Self::find_free_port();
+        while result.len() < count {
+            let attempt = Self::find_free_port();
 
-        for i in 1..4 {
-            result[i] = loop {
-                let attempt = Self::find_free_port();
-
-                if !&result[..i].contains(&attempt) {
-                    break attempt;
-                }
+            if !result.contains(&attempt) {
+                result.push(attempt);
             }
         }
 
@@ -103,20 +112,37 @@ impl LocalCluster {
     pub fn start<P: AsRef<Path>>(
         data_dir: P,
     ) -> std::io::Result<Self> {
+        Self::start_cluster(data_dir, 1)
+    }
+
+    /// Like [`LocalCluster::start()`], but launches `num_tikv` TiKV nodes
+    /// against a single PD.
+    ///
+    /// The first node keeps its data in `tikv` under `data_dir`, so that a
+    /// single node cluster reuses the same directory as [`LocalCluster::start()`].
+    /// The other nodes use `tikv2`, `tikv3` and so on.
+    pub fn start_cluster<P: AsRef<Path>>(
+        data_dir: P,
+        num_tikv: usize,
+    ) -> std::io::Result<Self> {
+        assert!(
+            num_tikv > 0,
+            "A cluster needs at least one TiKV node"
+        );
+
         // TODO: Import std::fs things here like a normal person
         Self::setup_components()?;
 
         let data_dir = data_dir.as_ref().to_path_buf();
         let pd_dir = data_dir.join("pd");
-        let tikv_dir = data_dir.join("tikv");
         let log_dir = data_dir.join("logs");
 
         std::fs::create_dir_all(&pd_dir)?;
-        std::fs::create_dir_all(&tikv_dir)?;
         std::fs::create_dir_all(&log_dir)?;
 
-        let [pd_port, pd_peer_port, tikv_port, tikv_status_port] =
-            Self::generate_service_ports();
+        let ports =
+            Self::generate_service_ports(2 + 2 * num_tikv);
+        let (pd_port, pd_peer_port) = (ports[0], ports[1]);
 
         let pd_process = Command::new("pd-server")
             .args([
@@ -143,34 +169,167 @@ impl LocalCluster {
 
         sleep(Duration::from_secs(2));
 
-        let tikv_process = Command::new("tikv-server")
-            .args([
-                "--pd",
-                &format!("127.0.0.1:{}", pd_port),
-                "--addr",
-                &format!("127.0.0.1:{}", tikv_port),
-                "--status-addr",
-                &format!("127.0.0.1:{}", tikv_status_port),
-                "--data-dir",
-                tikv_dir.to_str().unwrap(),
-            ])
-            .stdout(std::fs::File::create(
-                log_dir.join("tikv.stdout.log"),
-            )?)
-            .stderr(std::fs::File::create(
-                log_dir.join("tikv.stderr.log"),
-            )?)
-            .spawn()?;
+        let mut process_names = vec!["pd".to_string()];
+        let mut tikv_processes = Vec::with_capacity(num_tikv);
+        for (i, node_ports) in ports[2..].chunks(2).enumerate() {
+            let name = match i {
+                0 => "tikv".to_string(),
+                _ => format!("tikv{}", i + 1),
+            };
+            let tikv_dir = data_dir.join(&name);
+            std::fs::create_dir_all(&tikv_dir)?;
+
+            let tikv_process = Command::new("tikv-server")
+                .args([
+                    "--pd",
+                    &format!("127.0.0.1:{}", pd_port),
+                    "--addr",
+                    &format!("127.0.0.1:{}", node_ports[0]),
+                    "--status-addr",
+                    &format!("127.0.0.1:{}", node_ports[1]),
+                    "--data-dir",
+                    tikv_dir.to_str().unwrap(),
+                ])
+                .stdout(std::fs::File::create(
+                    log_dir.join(format!("{name}.stdout.log")),
+                )?)
+                .stderr(std::fs::File::create(
+                    log_dir.join(format!("{name}.stderr.log")),
+                )?)
+                .spawn()?;
+
+            tikv_processes.push(tikv_process);
+            process_names.push(name);
+        }
 
         sleep(Duration::from_secs(3));
 
         Ok(Self {
             pd_process,
-            tikv_process,
+            tikv_processes,
             pd_port,
+            log_dir,
+            process_names,
+            print_logs_on_panic: false,
         })
     }
 
+    /// Number of TiKV nodes this cluster was started with, including stopped ones.
+    pub fn num_tikv(&self) -> usize {
+        self.tikv_processes.len()
+    }
+
+    /// Kill the TiKV node with the given index, e.g. to test failover.
+    ///
+    /// Indexes go from `0` to [`LocalCluster::num_tikv()`] (exclusive).
+    pub fn stop_tikv(
+        &mut self,
+        index: usize,
+    ) -> std::io::Result<()> {
+        let process = &mut self.tikv_processes[index];
+        process.kill()?;
+        process.wait()?;
+        Ok(())
+    }
+
+    /// Suspend the TiKV node with the given index, keeping its process and data
+    /// around for [`LocalCluster::resume_tikv()`].
+    ///
+    /// A paused node accepts no requests, so requests routed to it fail once
+    /// the client times out, as if the node had dropped off the network.
+    /// Only supported on Unix.
+    pub fn pause_tikv(
+        &mut self,
+        index: usize,
+    ) -> std::io::Result<()> {
+        Self::signal(&self.tikv_processes[index], "STOP")
+    }
+
+    /// Resume the TiKV node with the given index after
+    /// [`LocalCluster::pause_tikv()`].
+    pub fn resume_tikv(
+        &mut self,
+        index: usize,
+    ) -> std::io::Result<()> {
+        Self::signal(&self.tikv_processes[index], "CONT")
+    }
+
+    /// Suspend PD, keeping its process and data around for
+    /// [`LocalCluster::resume_pd()`].
+    ///
+    /// The TiKV nodes keep running, but new clients cannot connect and
+    /// existing ones cannot look up regions or get timestamps.
+    /// Only supported on Unix.
+    pub fn pause_pd(&mut self) -> std::io::Result<()> {
+        Self::signal(&self.pd_process, "STOP")
+    }
+
+    /// Resume PD after [`LocalCluster::pause_pd()`].
+    pub fn resume_pd(&mut self) -> std::io::Result<()> {
+        Self::signal(&self.pd_process, "CONT")
+    }
+
+    fn signal(
+        process: &Child,
+        signal: &str,
+    ) -> std::io::Result<()> {
+        let status = Command::new("kill")
+            .args([
+                format!("-{signal}"),
+                process.id().to_string(),
+            ])
+            .status()?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(std::io::Error::other(format!(
+                "Failed to send SIG{signal} to process {}",
+                process.id()
+            )))
+        }
+    }
+
+    /// The directory the output of the PD and TiKV processes is written to.
+    ///
+    /// Every process has a `{name}.stdout.log` and a `{name}.stderr.log` file
+    /// there, where the name is `pd`, `tikv`, `tikv2` and so on.
+    pub fn log_dir(&self) -> &Path {
+        &self.log_dir
+    }
+
+    /// Reads the output the processes of the cluster have written so far, as
+    /// `(stdout, stderr)`.
+    ///
+    /// The output of each process is preceded by a `==> {name} <==` header.
+    /// Log files that cannot be read are skipped.
+    pub fn logs(&self) -> (String, String) {
+        let read = |stream: &str| {
+            let mut output = String::new();
+            for name in &self.process_names {
+                let path = self
+                    .log_dir
+                    .join(format!("{name}.{stream}.log"));
+                if let Ok(contents) =
+                    std::fs::read_to_string(path)
+                {
+                    output
+                        .push_str(&format!("==> {name} <==\n"));
+                    output.push_str(&contents);
+                }
+            }
+            output
+        };
+
+        (read("stdout"), read("stderr"))
+    }
+
+    /// Print the output of the cluster to stderr when it is dropped during a
+    /// panic, e.g. by a failing test. Disabled by default.
+    pub fn set_print_logs_on_panic(&mut self, enabled: bool) {
+        self.print_logs_on_panic = enabled;
+    }
+
     /// Get the address of the PD endpoint. Use this if you for some reason
     /// do not want [`LocalCluster::spawn_client()`]
     pub fn pd_endpoint(&self) -> String {
@@ -178,6 +337,9 @@ impl LocalCluster {
     }
 
     /// Spawn a new transactional client
+    ///
+    /// The client only talks to PD directly, which routes it to every TiKV node
+    /// in the cluster.
     pub async fn spawn_client(
         &self,
     ) -> tikv_client::Result<TransactionClient> {
@@ -187,7 +349,15 @@ impl LocalCluster {
 
 impl Drop for LocalCluster {
     fn drop(&mut self) {
-        let _ = self.tikv_process.kill();
+        for tikv_process in &mut self.tikv_processes {
+            let _ = tikv_process.kill();
+        }
         let _ = self.pd_process.kill();
+
+        if self.print_logs_on_panic && std::thread::panicking() {
+            let (stdout, stderr) = self.logs();
+            eprintln!("LocalCluster stdout:\n{stdout}");
+            eprintln!("LocalCluster stderr:\n{stderr}");
+        }
     }
 }
@@ -0,0 +1,226 @@
+//! An append-only log of values, built on [`PrefixTrie`].
+//!
+//! Unlike models deriving `Store`, a [`Log`] has no keys of its own: every
+//! appended item gets the next entry of the log, whose trie key is made of
+//! the time of the append and a counter. Keys never go backwards, so the
+//! lexicographic order of the trie is the order of the appends.
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::marker::PhantomData;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tikv_client::{Error, Transaction};
+
+use crate::PrefixTrie;
+
+/// An entry of a [`Log`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry<T> {
+    /// When the item was appended, according to the writer's clock.
+    ///
+    /// Entries appended while the clock went backwards carry the time of the
+    /// entry before them instead.
+    pub timestamp: SystemTime,
+    /// The appended item.
+    pub item: T,
+}
+
+/// The position of the last entry of a log, stored next to its trie.
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
+struct Position {
+    nanos: u64,
+    counter: u64,
+}
+
+impl Position {
+    /// The trie key of the entry, the time and counter as fixed-width hex.
+    fn key(&self) -> String {
+        format!("{:016x}:{:016x}", self.nanos, self.counter)
+    }
+}
+
+/// An append-only, ordered log of CBOR-encoded items, stored under
+/// `ergokv:__log:{name}`.
+///
+/// Appends within one transaction, and appends by transactions that commit
+/// one after another, are ordered as they happened. Concurrent appends to the
+/// same log conflict on its position, so one of the transactions has to be
+/// retried, e.g. with [`run_txn`](crate::run_txn).
+///
+/// # Examples
+///
+/// ```no_run
+/// # use ergokv::Log;
+/// # async fn example(txn: &mut tikv_client::Transaction) -> Result<(), tikv_client::Error> {
+/// let events: Log<String> = Log::new("events");
+/// events.append(txn, &"signed up".to_string()).await?;
+///
+/// for entry in events.tail(txn, 10).await? {
+///     println!("{:?}: {}", entry.timestamp, entry.item);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Log<T> {
+    name: String,
+    trie: PrefixTrie,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned> Log<T> {
+    /// Opens the log with the given name, which holds no entries until the
+    /// first append.
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self {
+            trie: PrefixTrie::new(format!(
+                "ergokv:__log:{name}"
+            )),
+            name,
+            _item: PhantomData,
+        }
+    }
+
+    fn position_key(&self) -> String {
+        format!("ergokv:__log:{}:position", self.name)
+    }
+
+    /// Appends `item` to the end of the log, returning the time it was
+    /// recorded under.
+    pub async fn append(
+        &self,
+        txn: &mut Transaction,
+        item: &T,
+    ) -> Result<SystemTime, Error> {
+        let last = match txn.get(self.position_key()).await? {
+            Some(bytes) => Some(
+                ciborium::de::from_reader::<Position, _>(
+                    bytes.as_slice(),
+                )
+                .map_err(|e| {
+                    Error::StringError(format!(
+                        "Failed to decode position of log {}: {e}",
+                        self.name
+                    ))
+                })?,
+            ),
+            None => None,
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        let position = match last {
+            Some(last) if last.nanos >= now => Position {
+                nanos: last.nanos,
+                counter: last.counter + 1,
+            },
+            _ => Position {
+                nanos: now,
+                counter: 0,
+            },
+        };
+
+        let mut value = Vec::new();
+        ciborium::ser::into_writer(item, &mut value).map_err(
+            |e| {
+                Error::StringError(format!(
+                    "Failed to encode entry of log {}: {e}",
+                    self.name
+                ))
+            },
+        )?;
+        self.trie
+            .insert_with_value(txn, &position.key(), value)
+            .await?;
+
+        let mut value = Vec::new();
+        ciborium::ser::into_writer(&position, &mut value)
+            .map_err(|e| {
+                Error::StringError(format!(
+                    "Failed to encode position of log {}: {e}",
+                    self.name
+                ))
+            })?;
+        txn.put(self.position_key(), value).await?;
+
+        Ok(UNIX_EPOCH + Duration::from_nanos(position.nanos))
+    }
+
+    /// Returns the entries appended at or after `since`, oldest first.
+    #[allow(clippy::result_large_err)]
+    pub async fn iter_from(
+        &self,
+        txn: &mut Transaction,
+        since: SystemTime,
+    ) -> Result<Vec<LogEntry<T>>, Error> {
+        let since = Position {
+            nanos: since
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or_default(),
+            counter: 0,
+        }
+        .key();
+
+        self.trie
+            .find_entries_by_prefix(txn, "")
+            .await?
+            .into_iter()
+            .filter(|(key, _)| *key >= since)
+            .map(|entry| self.decode_entry(entry))
+            .collect()
+    }
+
+    /// Returns the last `n` entries of the log, oldest first.
+    #[allow(clippy::result_large_err)]
+    pub async fn tail(
+        &self,
+        txn: &mut Transaction,
+        n: usize,
+    ) -> Result<Vec<LogEntry<T>>, Error> {
+        let entries =
+            self.trie.find_entries_by_prefix(txn, "").await?;
+        let skip = entries.len().saturating_sub(n);
+
+        entries
+            .into_iter()
+            .skip(skip)
+            .map(|entry| self.decode_entry(entry))
+            .collect()
+    }
+
+    /// Turns a trie key and its payload back into an entry.
+    #[allow(clippy::result_large_err)]
+    fn decode_entry(
+        &self,
+        (key, value): (String, Option<Vec<u8>>),
+    ) -> Result<LogEntry<T>, Error> {
+        let invalid = || {
+            Error::StringError(format!(
+                "Invalid entry {key} of log {}",
+                self.name
+            ))
+        };
+
+        let nanos = key
+            .split_once(':')
+            .and_then(|(nanos, _)| {
+                u64::from_str_radix(nanos, 16).ok()
+            })
+            .ok_or_else(invalid)?;
+        let value = value.ok_or_else(invalid)?;
+        let item = ciborium::de::from_reader(value.as_slice())
+            .map_err(|e| {
+            Error::StringError(format!(
+                "Failed to decode entry {key} of log {}: {e}",
+                self.name
+            ))
+        })?;
+
+        Ok(LogEntry {
+            timestamp: UNIX_EPOCH + Duration::from_nanos(nanos),
+            item,
+        })
+    }
+}
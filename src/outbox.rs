@@ -0,0 +1,39 @@
+//! Outbox events for models with `#[store(outbox)]`.
+//!
+//! Every `save` and `delete` of such a model appends an [`OutboxEvent`] to
+//! the model's outbox, `ergokv:{MODEL}:__outbox`, in the same transaction
+//! as the change itself, so an event is committed if and only if its change
+//! is. Consumers read the events with the generated `poll_outbox` and remove
+//! those they have handled with `ack_outbox`.
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+/// The kind of change recorded in an [`OutboxEvent`].
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq,
+)]
+pub enum OutboxOperation {
+    /// The instance was written with `save`.
+    Save,
+    /// The instance was removed with `delete`.
+    Delete,
+}
+
+/// A change of an instance, as recorded in its model's outbox.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OutboxEvent {
+    /// The position of the event in the outbox, starting at 1.
+    ///
+    /// Sequence numbers only grow and are never reused, so a consumer can
+    /// resume after the last one it saw.
+    pub seq: u64,
+    /// When the change was made, according to the writer's clock.
+    pub timestamp: SystemTime,
+    /// What kind of change was made.
+    pub operation: OutboxOperation,
+    /// The primary key of the instance, as it is encoded in storage keys.
+    pub key: String,
+    /// The saved instance in the JSON of its backups, or `None` for a
+    /// delete.
+    pub value: Option<String>,
+}
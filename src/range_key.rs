@@ -0,0 +1,144 @@
+//! Sortable encodings for range-indexed fields.
+//!
+//! Fields marked with `#[index(range)]` are indexed under a string
+//! encoding of their value that sorts lexicographically in the same
+//! order as the values themselves. This lets ergokv answer range
+//! queries with a single TiKV scan instead of loading every record.
+//!
+//! Signed values (and timestamps before the Unix epoch) are encoded
+//! with their sign bit flipped, so that negative values sort before
+//! positive ones.
+
+/// A type that can be used as the value of a `#[index(range)]` field.
+///
+/// Implementors must return strings of a fixed width whose
+/// lexicographic order matches the order of the encoded values.
+///
+/// Implementations are provided for the primitive integers and
+/// [`std::time::SystemTime`], and for `chrono::DateTime` and
+/// `time::OffsetDateTime` behind the `chrono` and `time` features
+/// respectively.
+pub trait RangeKey {
+    /// Encodes the value into its sortable string form.
+    fn range_key(&self) -> String;
+}
+
+macro_rules! impl_unsigned {
+    ($($t:ty),*) => {
+        $(
+            impl RangeKey for $t {
+                fn range_key(&self) -> String {
+                    format!("{:016x}", *self as u64)
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_signed {
+    ($($t:ty),*) => {
+        $(
+            impl RangeKey for $t {
+                fn range_key(&self) -> String {
+                    format!(
+                        "{:016x}",
+                        (*self as i64 as u64) ^ (1 << 63)
+                    )
+                }
+            }
+        )*
+    };
+}
+
+impl_unsigned!(u8, u16, u32, u64, usize);
+impl_signed!(i8, i16, i32, i64, isize);
+
+/// Encodes a point in time given as seconds relative to the Unix epoch
+/// and a nanosecond offset into that second.
+fn encode_timestamp(secs: i64, nanos: u32) -> String {
+    format!("{}{:08x}", secs.range_key(), nanos)
+}
+
+impl RangeKey for std::time::SystemTime {
+    fn range_key(&self) -> String {
+        match self.duration_since(std::time::UNIX_EPOCH) {
+            Ok(d) => encode_timestamp(
+                d.as_secs() as i64,
+                d.subsec_nanos(),
+            ),
+            Err(e) => {
+                let d = e.duration();
+                if d.subsec_nanos() == 0 {
+                    encode_timestamp(-(d.as_secs() as i64), 0)
+                } else {
+                    encode_timestamp(
+                        -(d.as_secs() as i64) - 1,
+                        1_000_000_000 - d.subsec_nanos(),
+                    )
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> RangeKey for chrono::DateTime<Tz> {
+    fn range_key(&self) -> String {
+        encode_timestamp(
+            self.timestamp(),
+            self.timestamp_subsec_nanos(),
+        )
+    }
+}
+
+#[cfg(feature = "time")]
+impl RangeKey for time::OffsetDateTime {
+    fn range_key(&self) -> String {
+        encode_timestamp(
+            self.unix_timestamp(),
+            self.nanosecond(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    fn assert_sorted<T: RangeKey>(values: &[T]) {
+        let keys: Vec<String> =
+            values.iter().map(RangeKey::range_key).collect();
+        let mut sorted = keys.clone();
+        sorted.sort();
+        assert_eq!(keys, sorted);
+    }
+
+    #[test]
+    fn test_integer_ordering() {
+        assert_sorted(&[
+            i64::MIN,
+            -1000,
+            -1,
+            0,
+            1,
+            1000,
+            i64::MAX,
+        ]);
+        assert_sorted(&[0u64, 1, 255, 256, u64::MAX]);
+    }
+
+    #[test]
+    fn test_system_time_ordering() {
+        assert_sorted(&[
+            UNIX_EPOCH - Duration::from_secs(86_400),
+            UNIX_EPOCH - Duration::from_millis(1500),
+            UNIX_EPOCH - Duration::from_secs(1),
+            UNIX_EPOCH - Duration::from_nanos(1),
+            UNIX_EPOCH,
+            UNIX_EPOCH + Duration::from_nanos(1),
+            UNIX_EPOCH + Duration::from_millis(1500),
+            SystemTime::now(),
+        ]);
+    }
+}
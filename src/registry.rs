@@ -0,0 +1,127 @@
+//! A process-wide registry of every model derived with `Store`.
+//!
+//! The derive submits a [`ModelRegistration`] for each model that supports
+//! `backup`, which lets tools like [`backup_all`] work with all models
+//! without naming their types.
+use futures::future::BoxFuture;
+use std::any::TypeId;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tikv_client::{Error, Transaction, TransactionClient};
+
+/// A type-erased `backup` of a model, see [`ModelRegistration::backup`].
+pub type BackupFn =
+    for<'a> fn(
+        &'a mut Transaction,
+        &'a Path,
+    ) -> BoxFuture<'a, Result<PathBuf, Error>>;
+
+/// A model registered by the `Store` derive.
+pub struct ModelRegistration {
+    name: &'static str,
+    type_id: fn() -> TypeId,
+    previous: Option<fn() -> TypeId>,
+    backup: BackupFn,
+}
+
+impl ModelRegistration {
+    /// Creates a registration, used by generated code.
+    ///
+    /// `previous` identifies the model given to `#[migrate_from]`, which is
+    /// superseded by this one.
+    pub const fn new(
+        name: &'static str,
+        type_id: fn() -> TypeId,
+        previous: Option<fn() -> TypeId>,
+        backup: BackupFn,
+    ) -> Self {
+        Self {
+            name,
+            type_id,
+            previous,
+            backup,
+        }
+    }
+
+    /// The model name, under which its instances are stored.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Backs up all instances of the model, like its generated `backup`.
+    pub async fn backup(
+        &self,
+        txn: &mut Transaction,
+        path: &Path,
+    ) -> Result<PathBuf, Error> {
+        (self.backup)(txn, path).await
+    }
+}
+
+inventory::collect!(ModelRegistration);
+
+/// Returns the current version of every registered model, sorted by name.
+///
+/// Models superseded by a newer version through `#[migrate_from]` are left
+/// out, as their instances are stored under the newer version.
+///
+/// # Errors
+///
+/// Returns an error if two current models share a model name.
+#[allow(clippy::result_large_err)]
+pub fn registered_models(
+) -> Result<Vec<&'static ModelRegistration>, Error> {
+    let superseded: HashSet<TypeId> =
+        inventory::iter::<ModelRegistration>()
+            .filter_map(|model| {
+                model.previous.map(|previous| previous())
+            })
+            .collect();
+
+    let mut models: Vec<&'static ModelRegistration> =
+        inventory::iter::<ModelRegistration>()
+            .filter(|model| {
+                !superseded.contains(&(model.type_id)())
+            })
+            .collect();
+    models.sort_by_key(|model| model.name);
+
+    if let Some(pair) = models
+        .windows(2)
+        .find(|pair| pair[0].name == pair[1].name)
+    {
+        return Err(Error::StringError(format!(
+            "Model {} is registered by more than one type",
+            pair[0].name
+        )));
+    }
+
+    Ok(models)
+}
+
+/// Backs up every registered model into `dir`, one file per model.
+///
+/// All backups are made in a single transaction, so together they capture
+/// one consistent state of the database. Returns the paths of the created
+/// files, in the order of [`registered_models`].
+pub async fn backup_all(
+    client: &TransactionClient,
+    dir: impl AsRef<Path>,
+) -> Result<Vec<PathBuf>, Error> {
+    let models = registered_models()?;
+    let mut txn = client.begin_optimistic().await?;
+
+    let mut paths = Vec::with_capacity(models.len());
+    for model in models {
+        match model.backup(&mut txn, dir.as_ref()).await {
+            Ok(path) => paths.push(path),
+            Err(e) => {
+                txn.rollback().await?;
+                return Err(e);
+            }
+        }
+    }
+
+    txn.rollback().await?;
+    Ok(paths)
+}
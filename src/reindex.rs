@@ -0,0 +1,20 @@
+//! Reports of the generated `reindex_all` maintenance routine.
+//!
+//! `reindex_all` treats the stored field values as the source of truth,
+//! and rebuilds the master trie and every secondary index of a model
+//! from them, dropping entries that no stored record accounts for.
+
+/// The outcome of a `reindex_all` run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReindexReport {
+    /// Number of records found in the field data.
+    pub records: usize,
+    /// Number of index entries present after the rebuild.
+    pub index_entries: usize,
+    /// Number of index entries removed because no record accounts for them.
+    pub orphaned_index_entries: usize,
+    /// Number of trie entries removed because no record accounts for them.
+    pub orphaned_trie_entries: usize,
+    /// Number of records that were missing from the trie and were added back.
+    pub missing_trie_entries: usize,
+}
@@ -0,0 +1,20 @@
+//! What `restore_with` does with instances already stored under the key of
+//! a restored one.
+
+/// How the generated `restore_with` resolves a restored instance whose key is
+/// already stored.
+///
+/// Instances whose key is not stored yet are always written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep the stored instance, and leave out the restored one.
+    Skip,
+    /// Replace the stored instance with the restored one, like `restore`.
+    Overwrite,
+    /// Keep whichever instance has the later `updated_at`, and the stored
+    /// one if they are equal.
+    ///
+    /// Only models with `#[store(timestamps)]` have an `updated_at` to compare,
+    /// `restore_with` fails with this policy on other models.
+    NewerWins,
+}
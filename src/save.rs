@@ -0,0 +1,14 @@
+//! The result of saving an instance.
+//!
+//! Unless a model sets `#[store(on_conflict = "skip")]`, its generated
+//! `save` reports through [`SaveOutcome`] whether the key was stored before,
+//! e.g. to count inserts apart from updates or to emit the right event.
+
+/// Whether `save` created a new instance or replaced a stored one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveOutcome {
+    /// No instance was stored under the key before.
+    Created,
+    /// An instance with the same key was stored and has been replaced.
+    Updated,
+}
@@ -0,0 +1,43 @@
+//! Reflection on models deriving `Store`.
+
+/// Describes the fields of a model, implemented by `#[derive(Store)]`.
+///
+/// Lets generic code, such as exporters or admin interfaces, list the fields
+/// of any model and see which of them can be queried through an index.
+///
+/// # Examples
+///
+/// ```
+/// use ergokv::Store;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Store, Serialize, Deserialize)]
+/// struct User {
+///     #[key]
+///     id: u64,
+///     #[unique_index]
+///     username: String,
+///     #[index]
+///     department: String,
+///     bio: String,
+/// }
+///
+/// fn columns<T: Store>() -> Vec<&'static str> {
+///     T::FIELD_NAMES.to_vec()
+/// }
+///
+/// assert_eq!(columns::<User>(), ["id", "username", "department", "bio"]);
+/// assert_eq!(User::INDEX_FIELDS, ["username", "department"]);
+/// assert_eq!(User::UNIQUE_INDEX_FIELDS, ["username"]);
+/// ```
+pub trait Store {
+    /// The names of all fields, including the key, in declaration order.
+    const FIELD_NAMES: &'static [&'static str];
+
+    /// The names of the fields with an index of any kind, which have a
+    /// `by_<field>` method, in declaration order.
+    const INDEX_FIELDS: &'static [&'static str];
+
+    /// The names of the `#[unique_index]` fields, in declaration order.
+    const UNIQUE_INDEX_FIELDS: &'static [&'static str];
+}
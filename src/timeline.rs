@@ -0,0 +1,89 @@
+//! A global feed of saves for models with `#[store(timeline)]`.
+//!
+//! Every `save` of such a model appends a [`TimelineEntry`] in the same
+//! transaction, under `ergokv:__timeline:`. Entries of all models share one
+//! keyspace ordered newest first, so [`recent_activity`] reads a
+//! chronological feed across model types with a single scan.
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tikv_client::{Error, Transaction};
+
+const TIMELINE_PREFIX: &str = "ergokv:__timeline:";
+
+/// A single save recorded in the timeline.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TimelineEntry {
+    /// When the instance was saved, according to the writer's clock.
+    pub timestamp: SystemTime,
+    /// The name of the saved model.
+    pub model: String,
+    /// The primary key of the saved instance, serialized as JSON.
+    pub key: String,
+}
+
+/// Records a save of the instance with the given key in the timeline, used by
+/// generated code.
+///
+/// The entry's key holds the time counted down from `u64::MAX` nanoseconds,
+/// so that a forward scan yields the newest entries first. The model and key
+/// follow it, keeping entries written at the same instant apart.
+pub async fn append_timeline(
+    txn: &mut Transaction,
+    model: &str,
+    key: &str,
+) -> Result<(), Error> {
+    let timestamp = SystemTime::now();
+    let nanos = timestamp
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+
+    let entry = TimelineEntry {
+        timestamp,
+        model: model.to_string(),
+        key: key.to_string(),
+    };
+    let mut value = Vec::new();
+    ciborium::ser::into_writer(&entry, &mut value).map_err(|e| {
+        Error::StringError(format!(
+            "Failed to encode timeline entry of {model} {key}: {e}"
+        ))
+    })?;
+
+    txn.put(
+        format!(
+            "{TIMELINE_PREFIX}{:016x}:{model}:{key}",
+            u64::MAX - nanos
+        ),
+        value,
+    )
+    .await
+}
+
+/// Returns up to `limit` of the most recent saves of all `#[store(timeline)]`
+/// models, newest first.
+///
+/// Load the instances themselves with the `load` of the model named by each
+/// entry, after decoding its key.
+#[allow(clippy::result_large_err)]
+pub async fn recent_activity(
+    txn: &mut Transaction,
+    limit: u32,
+) -> Result<Vec<TimelineEntry>, Error> {
+    let end = format!(
+        "{};",
+        &TIMELINE_PREFIX[..TIMELINE_PREFIX.len() - 1]
+    );
+
+    txn.scan(TIMELINE_PREFIX.to_string()..end, limit)
+        .await?
+        .map(|pair| {
+            ciborium::de::from_reader(pair.value().as_slice())
+                .map_err(|e| {
+                    Error::StringError(format!(
+                        "Failed to decode timeline entry: {e}"
+                    ))
+                })
+        })
+        .collect()
+}
@@ -0,0 +1,35 @@
+//! Clocks for models with `#[store(timestamps)]`.
+//!
+//! Such models have their `created_at` and `updated_at` fields managed by
+//! ergokv, which reads the current time through [`AutoTimestamp`].
+
+/// A type that can be used for the `created_at` and `updated_at` fields of a
+/// `#[store(timestamps)]` model.
+///
+/// Implementations are provided for [`std::time::SystemTime`], and for
+/// `chrono::DateTime<Utc>` and `time::OffsetDateTime` behind the `chrono`
+/// and `time` features respectively.
+pub trait AutoTimestamp: Clone {
+    /// Returns the current time.
+    fn now() -> Self;
+}
+
+impl AutoTimestamp for std::time::SystemTime {
+    fn now() -> Self {
+        std::time::SystemTime::now()
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl AutoTimestamp for chrono::DateTime<chrono::Utc> {
+    fn now() -> Self {
+        chrono::Utc::now()
+    }
+}
+
+#[cfg(feature = "time")]
+impl AutoTimestamp for time::OffsetDateTime {
+    fn now() -> Self {
+        time::OffsetDateTime::now_utc()
+    }
+}
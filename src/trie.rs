@@ -7,21 +7,52 @@
 //! The trie supports basic operations like insertion, removal, and retrieval,
 //! as well as prefix-based searches and streaming of all stored keys.
 //! All operations are performed within a TiKV transaction context.
+use futures::Stream;
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, SetPreventDuplicates};
 use std::collections::HashSet;
 use tikv_client::{Error as TikvError, Transaction};
 
+/// The version of the node encoding written by this release.
+///
+/// Version 0 are the nodes written before the encoding had a version, which
+/// have the layout of version 1 without the field.
+const NODE_VERSION: u32 = 1;
+
 /// A node in the prefix trie.
 ///
-/// Each node can store a key (if it represents the end of a stored string)
-/// and maintains a set of child characters that lead to other nodes.
+/// Each node can store a key (if it represents the end of a stored string),
+/// along with an optional payload associated with that key, and maintains
+/// a set of child characters that lead to other nodes.
 #[serde_as]
 #[derive(Serialize, Deserialize, Debug)]
 struct TrieNode {
+    /// The encoding version the node was written with
+    #[serde(default)]
+    version: u32,
     #[serde_as(as = "SetPreventDuplicates<_>")]
     children: HashSet<char>,
     key: Option<String>,
+    #[serde(default)]
+    value: Option<Vec<u8>>,
+}
+
+impl Default for TrieNode {
+    fn default() -> Self {
+        Self {
+            version: NODE_VERSION,
+            children: HashSet::new(),
+            key: None,
+            value: None,
+        }
+    }
+}
+
+/// Just the version of a node, readable whatever the rest of its encoding.
+#[derive(Deserialize)]
+struct NodeVersion {
+    #[serde(default)]
+    version: u32,
 }
 
 /// A prefix trie implementation that stores its nodes in TiKV.
@@ -58,23 +89,56 @@ impl PrefixTrie {
     }
 
     /// Retrieves a node from TiKV at the given path.
+    ///
+    /// Nodes of older versions are upgraded to the current one as they are
+    /// read, so writing them back stores them in the current encoding.
     async fn get_node(
         &self,
         txn: &mut Transaction,
         path: &str,
     ) -> Result<Option<TrieNode>, TikvError> {
-        if let Some(data) = txn
-            .get(self.node_key(path))
-            .await?
-            .map(|d| {
-                ciborium::de::from_reader(d.as_slice()).ok()
-            })
-            .flatten()
-        {
-            Ok(Some(data))
-        } else {
-            Ok(None)
+        match txn.get(self.node_key(path)).await? {
+            Some(data) => {
+                let mut node = self.decode_node(path, &data)?;
+                node.version = NODE_VERSION;
+                Ok(Some(node))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Decodes a stored node, keeping the version it was written with.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the node was written by a newer release, whose
+    /// encoding this one cannot read, or if it cannot be decoded at all.
+    #[allow(clippy::result_large_err)]
+    fn decode_node(
+        &self,
+        path: &str,
+        data: &[u8],
+    ) -> Result<TrieNode, TikvError> {
+        let decode_error = |e| {
+            TikvError::StringError(format!(
+                "Failed to decode node {path:?} of trie {}: {e}",
+                self.prefix
+            ))
+        };
+
+        let NodeVersion { version } =
+            ciborium::de::from_reader(data)
+                .map_err(decode_error)?;
+        if version > NODE_VERSION {
+            return Err(TikvError::StringError(format!(
+                "Node {path:?} of trie {} has encoding version {version}, \
+                 but this release of ergokv only reads up to version \
+                 {NODE_VERSION}",
+                self.prefix
+            )));
         }
+
+        ciborium::de::from_reader(data).map_err(decode_error)
     }
 
     /// Stores a node in TiKV at the given path.
@@ -96,9 +160,30 @@ impl PrefixTrie {
         Ok(())
     }
 
+    /// Pushes the paths of a node's children onto a traversal stack.
+    ///
+    /// Children are pushed in reverse order, so that they are popped (and
+    /// thus keys are yielded) in lexicographic order.
+    fn push_children(
+        queue: &mut Vec<String>,
+        path: &str,
+        children: HashSet<char>,
+    ) {
+        let mut children: Vec<char> =
+            children.into_iter().collect();
+        children.sort_unstable_by(|a, b| b.cmp(a));
+
+        queue.extend(children.into_iter().map(|c| {
+            let mut child_path = path.to_string();
+            child_path.push(c);
+            child_path
+        }));
+    }
+
     /// Inserts a key into the trie.
     ///
-    /// Empty strings are not allowed as keys.
+    /// Empty strings are not allowed as keys. Inserting a key that is
+    /// already stored keeps its payload, if it has one.
     ///
     /// # Errors
     ///
@@ -107,6 +192,34 @@ impl PrefixTrie {
         &self,
         txn: &mut Transaction,
         key: &str,
+    ) -> Result<(), TikvError> {
+        self.insert_node(txn, key, None).await
+    }
+
+    /// Inserts a key into the trie, associating a payload with it.
+    ///
+    /// The payload replaces any previous payload of the key, and can be read
+    /// back with [`get_value`](Self::get_value) or
+    /// [`find_entries_by_prefix`](Self::find_entries_by_prefix).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is empty or if the TiKV operation fails.
+    pub async fn insert_with_value(
+        &self,
+        txn: &mut Transaction,
+        key: &str,
+        value: impl Into<Vec<u8>>,
+    ) -> Result<(), TikvError> {
+        self.insert_node(txn, key, Some(value.into())).await
+    }
+
+    /// Inserts a key, replacing its payload with `value` unless it is `None`.
+    async fn insert_node(
+        &self,
+        txn: &mut Transaction,
+        key: &str,
+        value: Option<Vec<u8>>,
     ) -> Result<(), TikvError> {
         if key.is_empty() {
             return Err(TikvError::StringError(
@@ -115,13 +228,8 @@ impl PrefixTrie {
         }
 
         let first_char = key.chars().next().unwrap();
-        let mut root = self
-            .get_node(txn, "")
-            .await?
-            .unwrap_or_else(|| TrieNode {
-                key: None,
-                children: HashSet::new(),
-            });
+        let mut root =
+            self.get_node(txn, "").await?.unwrap_or_default();
         root.children.insert(first_char);
         self.put_node(txn, "", &root).await?;
 
@@ -131,16 +239,16 @@ impl PrefixTrie {
             let mut node = self
                 .get_node(txn, &current_path)
                 .await?
-                .unwrap_or_else(|| TrieNode {
-                    key: None,
-                    children: HashSet::new(),
-                });
+                .unwrap_or_default();
 
             if i < key.len() - 1 {
                 node.children
                     .insert(key.chars().nth(i + 1).unwrap());
             } else {
                 node.key = Some(key.to_string());
+                if value.is_some() {
+                    node.value = value.clone();
+                }
             }
             self.put_node(txn, &current_path, &node).await?;
         }
@@ -156,6 +264,32 @@ impl PrefixTrie {
         txn: &mut Transaction,
         key: &str,
     ) -> Result<Option<String>, TikvError> {
+        Ok(self
+            .get_key_node(txn, key)
+            .await?
+            .and_then(|node| node.key))
+    }
+
+    /// Retrieves the payload associated with a key.
+    ///
+    /// Returns `None` if the key doesn't exist or was inserted without a payload.
+    pub async fn get_value(
+        &self,
+        txn: &mut Transaction,
+        key: &str,
+    ) -> Result<Option<Vec<u8>>, TikvError> {
+        Ok(self
+            .get_key_node(txn, key)
+            .await?
+            .and_then(|node| node.key.and(node.value)))
+    }
+
+    /// Retrieves the node at the end of `key`, if the whole path exists.
+    async fn get_key_node(
+        &self,
+        txn: &mut Transaction,
+        key: &str,
+    ) -> Result<Option<TrieNode>, TikvError> {
         let mut current_path = String::new();
         for (i, c) in key.chars().enumerate() {
             current_path.push(c);
@@ -173,20 +307,92 @@ impl PrefixTrie {
                 return Ok(None);
             }
         }
-        Ok(self
-            .get_node(txn, &current_path)
-            .await?
-            .and_then(|node| node.key))
+        self.get_node(txn, &current_path).await
+    }
+
+    /// Finds the longest key in the trie that is a prefix of `query`.
+    ///
+    /// Returns `None` if no stored key is a prefix of `query`.
+    pub async fn longest_prefix_match(
+        &self,
+        txn: &mut Transaction,
+        query: &str,
+    ) -> Result<Option<String>, TikvError> {
+        let mut longest = None;
+        let mut current_path = String::new();
+
+        for c in query.chars() {
+            current_path.push(c);
+            match self.get_node(txn, &current_path).await? {
+                Some(node) => {
+                    if node.key.is_some() {
+                        longest = node.key;
+                    }
+                }
+                None => break,
+            }
+        }
+
+        Ok(longest)
+    }
+
+    /// Finds the longest prefix shared by all keys in the trie.
+    ///
+    /// Walks down from the root for as long as the current node has exactly
+    /// one child and does not end a key itself. Returns an empty string when
+    /// the trie is empty or its keys differ in their first character. With a
+    /// single key stored, the key itself is returned.
+    pub async fn common_prefix(
+        &self,
+        txn: &mut Transaction,
+    ) -> Result<String, TikvError> {
+        let mut prefix = String::new();
+        let Some(mut node) = self.get_node(txn, "").await?
+        else {
+            return Ok(prefix);
+        };
+
+        while node.key.is_none() && node.children.len() == 1 {
+            let c = *node.children.iter().next().unwrap();
+            let path = format!("{prefix}{c}");
+            match self.get_node(txn, &path).await? {
+                Some(child) => {
+                    prefix = path;
+                    node = child;
+                }
+                None => break,
+            }
+        }
+
+        Ok(prefix)
     }
 
     /// Finds all keys in the trie that start with the given prefix.
     ///
-    /// Returns a vector of matching keys in no particular order.
+    /// Returns a vector of matching keys in lexicographic order.
     pub async fn find_by_prefix(
         &self,
         txn: &mut Transaction,
         prefix: &str,
     ) -> Result<Vec<String>, TikvError> {
+        Ok(self
+            .find_entries_by_prefix(txn, prefix)
+            .await?
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect())
+    }
+
+    /// Finds all keys in the trie that start with the given prefix, along with
+    /// their payloads.
+    ///
+    /// Returns a vector of `(key, payload)` pairs in lexicographic order of the
+    /// keys. Keys inserted without a payload are paired with `None`.
+    pub async fn find_entries_by_prefix(
+        &self,
+        txn: &mut Transaction,
+        prefix: &str,
+    ) -> Result<Vec<(String, Option<Vec<u8>>)>, TikvError> {
         let mut result = Vec::new();
         let mut queue = vec![prefix.to_string()];
 
@@ -194,22 +400,55 @@ impl PrefixTrie {
             if let Some(node) = self.get_node(txn, &path).await?
             {
                 if let Some(key) = node.key {
-                    result.push(key);
-                }
-                for c in node.children {
-                    let mut child_path = path.clone();
-                    child_path.push(c);
-                    queue.push(child_path);
+                    result.push((key, node.value));
                 }
+                Self::push_children(
+                    &mut queue,
+                    &path,
+                    node.children,
+                );
             }
         }
 
         Ok(result)
     }
 
+    /// Streams all keys in the trie that start with the given prefix, each
+    /// with its depth below the prefix.
+    ///
+    /// The depth of a key is the number of characters it has beyond `prefix`,
+    /// so `prefix` itself, if stored, has depth 0. Keys are yielded in
+    /// lexicographic order, which puts every key right after the closest key
+    /// it extends, so a tree can be built from the stream in one pass. Nodes
+    /// are read as the stream is polled.
+    pub fn find_by_prefix_with_depth<'a>(
+        &'a self,
+        txn: &'a mut Transaction,
+        prefix: &'a str,
+    ) -> impl Stream<Item = Result<(String, usize), TikvError>> + 'a
+    {
+        async_stream::try_stream! {
+            let base = prefix.chars().count();
+            let mut queue = vec![prefix.to_string()];
+
+            while let Some(path) = queue.pop() {
+                if let Some(node) = self.get_node(txn, &path).await? {
+                    if let Some(key) = node.key {
+                        yield (key, path.chars().count() - base);
+                    }
+                    Self::push_children(
+                        &mut queue,
+                        &path,
+                        node.children,
+                    );
+                }
+            }
+        }
+    }
+
     /// Returns a vector of all keys stored in the trie.
     ///
-    /// The keys are returned in no particular order.
+    /// The keys are returned in lexicographic order.
     pub async fn all(
         &self,
         txn: &mut Transaction,
@@ -217,18 +456,8 @@ impl PrefixTrie {
         let mut result = Vec::new();
         let mut queue = Vec::new();
 
-        if let Some(data) = txn.get(self.node_key("")).await? {
-            let root: TrieNode =
-                ciborium::de::from_reader(data.as_slice())
-                    .map_err(|e| {
-                        TikvError::StringError(format!(
-                            "Deserialization failed: {}",
-                            e
-                        ))
-                    })?;
-            queue.extend(
-                root.children.into_iter().map(|c| c.to_string()),
-            );
+        if let Some(root) = self.get_node(txn, "").await? {
+            Self::push_children(&mut queue, "", root.children);
         }
 
         while let Some(path) = queue.pop() {
@@ -237,11 +466,11 @@ impl PrefixTrie {
                 if let Some(key) = node.key {
                     result.push(key);
                 }
-                for c in node.children {
-                    let mut child_path = path.clone();
-                    child_path.push(c);
-                    queue.push(child_path);
-                }
+                Self::push_children(
+                    &mut queue,
+                    &path,
+                    node.children,
+                );
             }
         }
 
@@ -265,6 +494,7 @@ impl PrefixTrie {
             {
                 if i == key.len() - 1 {
                     node.key = None;
+                    node.value = None;
                     if node.children.is_empty() {
                         txn.delete(self.node_key(&current_path))
                             .await?;
@@ -302,9 +532,92 @@ impl PrefixTrie {
         }
         Ok(())
     }
+
+    /// Moves every node of the trie under the prefix of `new`.
+    ///
+    /// The structure and payloads are kept as they are, so all keys are found
+    /// through `new` afterwards, and none through `self`. Returns the number
+    /// of nodes moved.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `new` already holds a trie, which the moved nodes
+    /// would be mixed into, or if the TiKV operation fails.
+    pub async fn rename_prefix(
+        &self,
+        txn: &mut Transaction,
+        new: &PrefixTrie,
+    ) -> Result<usize, TikvError> {
+        if new.get_node(txn, "").await?.is_some() {
+            return Err(TikvError::StringError(format!(
+                "Cannot rename trie {} to {}: the new prefix is not empty",
+                self.prefix, new.prefix
+            )));
+        }
+
+        let mut moved = 0;
+        let mut queue = vec![String::new()];
+
+        while let Some(path) = queue.pop() {
+            if let Some(node) = self.get_node(txn, &path).await?
+            {
+                new.put_node(txn, &path, &node).await?;
+                txn.delete(self.node_key(&path)).await?;
+                moved += 1;
+                Self::push_children(
+                    &mut queue,
+                    &path,
+                    node.children,
+                );
+            }
+        }
+
+        Ok(moved)
+    }
+
+    /// Rewrites every node written in an older encoding in the current one.
+    ///
+    /// Older nodes are readable as they are, so this is only needed before
+    /// a future release drops support for reading them. Returns the number
+    /// of nodes rewritten; running it again returns 0.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a node cannot be decoded, e.g. because it was
+    /// written by a newer release, or if the TiKV operation fails.
+    pub async fn migrate_nodes(
+        &self,
+        txn: &mut Transaction,
+    ) -> Result<usize, TikvError> {
+        let mut migrated = 0;
+        let mut queue = vec![String::new()];
+
+        while let Some(path) = queue.pop() {
+            let Some(data) =
+                txn.get(self.node_key(&path)).await?
+            else {
+                continue;
+            };
+
+            let mut node = self.decode_node(&path, &data)?;
+            if node.version < NODE_VERSION {
+                node.version = NODE_VERSION;
+                self.put_node(txn, &path, &node).await?;
+                migrated += 1;
+            }
+            Self::push_children(
+                &mut queue,
+                &path,
+                node.children,
+            );
+        }
+
+        Ok(migrated)
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::result_large_err)]
 mod tests {
     use super::*;
     use crate::LocalCluster;
@@ -472,4 +785,330 @@ mod tests {
         txn.commit().await?;
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_longest_prefix_match() -> Result<(), TikvError>
+    {
+        let (_cluster, trie, mut txn, _tmp) = setup().await;
+
+        trie.insert(&mut txn, "a").await?;
+        trie.insert(&mut txn, "app").await?;
+        trie.insert(&mut txn, "apple").await?;
+
+        assert_eq!(
+            trie.longest_prefix_match(&mut txn, "apples")
+                .await?,
+            Some("apple".to_string())
+        );
+        assert_eq!(
+            trie.longest_prefix_match(&mut txn, "appl").await?,
+            Some("app".to_string())
+        );
+        assert_eq!(
+            trie.longest_prefix_match(&mut txn, "ax").await?,
+            Some("a".to_string())
+        );
+        assert_eq!(
+            trie.longest_prefix_match(&mut txn, "banana")
+                .await?,
+            None
+        );
+
+        txn.commit().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_common_prefix() -> Result<(), TikvError> {
+        let (_cluster, trie, mut txn, _tmp) = setup().await;
+
+        assert_eq!(trie.common_prefix(&mut txn).await?, "");
+
+        trie.insert(&mut txn, "user:alice").await?;
+        assert_eq!(
+            trie.common_prefix(&mut txn).await?,
+            "user:alice"
+        );
+
+        trie.insert(&mut txn, "user:bob").await?;
+        trie.insert(&mut txn, "user:ann").await?;
+        assert_eq!(trie.common_prefix(&mut txn).await?, "user:");
+
+        // A key that is a prefix of the others ends the shared prefix
+        trie.insert(&mut txn, "user").await?;
+        assert_eq!(trie.common_prefix(&mut txn).await?, "user");
+
+        // Keys diverging at the root share nothing
+        trie.insert(&mut txn, "order:1").await?;
+        assert_eq!(trie.common_prefix(&mut txn).await?, "");
+
+        txn.commit().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sorted_traversal() -> Result<(), TikvError> {
+        let (_cluster, trie, mut txn, _tmp) = setup().await;
+
+        for key in
+            ["zeta", "alpha", "alp", "beta", "al", "gamma"]
+        {
+            trie.insert(&mut txn, key).await?;
+        }
+
+        assert_eq!(
+            trie.all(&mut txn).await?,
+            vec!["al", "alp", "alpha", "beta", "gamma", "zeta"]
+        );
+        assert_eq!(
+            trie.find_by_prefix(&mut txn, "al").await?,
+            vec!["al", "alp", "alpha"]
+        );
+
+        txn.commit().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_values() -> Result<(), TikvError> {
+        let (_cluster, trie, mut txn, _tmp) = setup().await;
+
+        trie.insert_with_value(
+            &mut txn,
+            "user:1",
+            b"alice".to_vec(),
+        )
+        .await?;
+        trie.insert_with_value(
+            &mut txn,
+            "user:2",
+            b"bob".to_vec(),
+        )
+        .await?;
+        trie.insert(&mut txn, "user:3").await?;
+
+        assert_eq!(
+            trie.get_value(&mut txn, "user:1").await?,
+            Some(b"alice".to_vec())
+        );
+        assert_eq!(
+            trie.get_value(&mut txn, "user:3").await?,
+            None
+        );
+        assert_eq!(
+            trie.get_value(&mut txn, "user:").await?,
+            None
+        );
+        assert_eq!(
+            trie.get(&mut txn, "user:2").await?,
+            Some("user:2".to_string())
+        );
+
+        // Plain inserts keep the payload, payload inserts replace it
+        trie.insert(&mut txn, "user:1").await?;
+        trie.insert_with_value(
+            &mut txn,
+            "user:2",
+            b"robert".to_vec(),
+        )
+        .await?;
+        assert_eq!(
+            trie.find_entries_by_prefix(&mut txn, "user:")
+                .await?,
+            vec![
+                ("user:1".to_string(), Some(b"alice".to_vec())),
+                ("user:2".to_string(), Some(b"robert".to_vec())),
+                ("user:3".to_string(), None),
+            ]
+        );
+
+        // Removing a key drops its payload along with it
+        trie.remove(&mut txn, "user:1").await?;
+        trie.insert(&mut txn, "user:1").await?;
+        assert_eq!(
+            trie.get_value(&mut txn, "user:1").await?,
+            None
+        );
+
+        txn.commit().await?;
+        Ok(())
+    }
+    #[tokio::test]
+    async fn test_rename_prefix() -> Result<(), TikvError> {
+        let (_cluster, trie, mut txn, _tmp) = setup().await;
+
+        trie.insert(&mut txn, "apple").await?;
+        trie.insert(&mut txn, "app").await?;
+        trie.insert_with_value(
+            &mut txn,
+            "banana",
+            b"yellow".to_vec(),
+        )
+        .await?;
+
+        let renamed = PrefixTrie::new("renamed");
+        // The root, "a", "ap", "app", "appl", "apple" and six nodes for "banana"
+        assert_eq!(
+            trie.rename_prefix(&mut txn, &renamed).await?,
+            12
+        );
+
+        assert_eq!(
+            renamed.all(&mut txn).await?,
+            vec!["app", "apple", "banana"]
+        );
+        assert_eq!(
+            renamed.find_by_prefix(&mut txn, "ap").await?,
+            vec!["app", "apple"]
+        );
+        assert_eq!(
+            renamed.get_value(&mut txn, "banana").await?,
+            Some(b"yellow".to_vec())
+        );
+
+        assert!(trie.all(&mut txn).await?.is_empty());
+        assert_eq!(trie.get(&mut txn, "apple").await?, None);
+
+        // Renaming into a populated prefix would mix two tries
+        trie.insert(&mut txn, "cherry").await?;
+        assert!(trie
+            .rename_prefix(&mut txn, &renamed)
+            .await
+            .is_err());
+
+        txn.commit().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_find_by_prefix_with_depth(
+    ) -> Result<(), TikvError> {
+        use futures::TryStreamExt;
+
+        let (_cluster, trie, mut txn, _tmp) = setup().await;
+
+        for key in
+            ["docs", "docs/a", "docs/a/b", "docs/c", "dot", "x"]
+        {
+            trie.insert(&mut txn, key).await?;
+        }
+
+        let entries: Vec<(String, usize)> = trie
+            .find_by_prefix_with_depth(&mut txn, "docs")
+            .try_collect()
+            .await?;
+        assert_eq!(
+            entries,
+            vec![
+                ("docs".to_string(), 0),
+                ("docs/a".to_string(), 2),
+                ("docs/a/b".to_string(), 4),
+                ("docs/c".to_string(), 2),
+            ]
+        );
+
+        // Depths count from the prefix, which need not be a key itself
+        let entries: Vec<(String, usize)> = trie
+            .find_by_prefix_with_depth(&mut txn, "do")
+            .try_collect()
+            .await?;
+        assert_eq!(
+            entries
+                .iter()
+                .map(|(key, depth)| (key.as_str(), *depth))
+                .collect::<Vec<_>>(),
+            vec![
+                ("docs", 2),
+                ("docs/a", 4),
+                ("docs/a/b", 6),
+                ("docs/c", 4),
+                ("dot", 1),
+            ]
+        );
+
+        txn.commit().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_node_versions() -> Result<(), TikvError> {
+        let (_cluster, trie, mut txn, _tmp) = setup().await;
+
+        // Nodes as written before the encoding had a version
+        #[derive(Serialize)]
+        struct LegacyNode {
+            children: Vec<char>,
+            key: Option<String>,
+            value: Option<Vec<u8>>,
+        }
+        async fn put_legacy(
+            txn: &mut Transaction,
+            trie: &PrefixTrie,
+            path: &str,
+            node: LegacyNode,
+        ) -> Result<(), TikvError> {
+            let mut data = Vec::new();
+            ciborium::ser::into_writer(&node, &mut data)
+                .unwrap();
+            txn.put(trie.node_key(path), data).await
+        }
+
+        trie.insert(&mut txn, "new").await?;
+        put_legacy(
+            &mut txn,
+            &trie,
+            "",
+            LegacyNode {
+                children: vec!['n', 'o'],
+                key: None,
+                value: None,
+            },
+        )
+        .await?;
+        put_legacy(
+            &mut txn,
+            &trie,
+            "o",
+            LegacyNode {
+                children: vec![],
+                key: Some("o".to_string()),
+                value: Some(b"old".to_vec()),
+            },
+        )
+        .await?;
+
+        // Older nodes are still readable, and migrated exactly once
+        assert_eq!(trie.all(&mut txn).await?, vec!["new", "o"]);
+        assert_eq!(trie.migrate_nodes(&mut txn).await?, 2);
+        assert_eq!(trie.migrate_nodes(&mut txn).await?, 0);
+        assert_eq!(
+            trie.get_value(&mut txn, "o").await?,
+            Some(b"old".to_vec())
+        );
+
+        // Nodes from a newer release are rejected rather than misread
+        #[derive(Serialize)]
+        struct FutureNode {
+            version: u32,
+            edges: Vec<String>,
+        }
+        let future = PrefixTrie::new("future");
+        let mut data = Vec::new();
+        ciborium::ser::into_writer(
+            &FutureNode {
+                version: NODE_VERSION + 1,
+                edges: vec!["a".to_string()],
+            },
+            &mut data,
+        )
+        .unwrap();
+        txn.put(future.node_key(""), data).await?;
+
+        let err = future.all(&mut txn).await.unwrap_err();
+        assert!(err.to_string().contains("encoding version 2"));
+        assert!(future.migrate_nodes(&mut txn).await.is_err());
+
+        txn.commit().await?;
+        Ok(())
+    }
 }
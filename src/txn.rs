@@ -0,0 +1,78 @@
+//! Running a transaction body with retries on write conflicts.
+use futures::future::BoxFuture;
+use tikv_client::{Error, Transaction, TransactionClient};
+
+/// Runs `build_and_run` in a fresh optimistic transaction and commits it,
+/// retrying up to `attempts` times in total when the transaction conflicts
+/// with another one.
+///
+/// The closure is invoked again on every attempt with a brand new
+/// transaction, so everything it reads and writes is derived anew from the
+/// current state of TiKV. It must not keep side effects outside of the
+/// transaction, as those would be repeated. The returned future may borrow
+/// the transaction, anything else it needs has to be moved into it.
+///
+/// Only conflicts (see [`is_retryable`]) are retried. Any other error from
+/// the closure rolls the transaction back and is returned immediately.
+///
+/// ```rust,ignore
+/// let balance = ergokv::run_txn(&client, 3, |txn| {
+///     Box::pin(async move {
+///         let mut account = Account::load(&id, txn).await?;
+///         account.set_balance(account.balance + 10, txn).await?;
+///         Ok(account.balance)
+///     })
+/// })
+/// .await?;
+/// ```
+pub async fn run_txn<T, F>(
+    client: &TransactionClient,
+    attempts: usize,
+    mut build_and_run: F,
+) -> Result<T, Error>
+where
+    F: for<'a> FnMut(
+        &'a mut Transaction,
+    ) -> BoxFuture<'a, Result<T, Error>>,
+{
+    let attempts = attempts.max(1);
+    let mut attempt = 1;
+
+    loop {
+        let mut txn = client.begin_optimistic().await?;
+
+        let result = match build_and_run(&mut txn).await {
+            Ok(value) => txn.commit().await.map(|_| value),
+            Err(e) => {
+                txn.rollback().await?;
+                Err(e)
+            }
+        };
+
+        match result {
+            Err(e) if attempt < attempts && is_retryable(&e) => {
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Tells whether `err` is a conflict with another transaction, which a
+/// fresh attempt of the same work may not run into.
+///
+/// Everything else, including errors whose commit outcome is unknown, is
+/// considered fatal.
+pub fn is_retryable(err: &Error) -> bool {
+    match err {
+        Error::KeyError(key_error) => {
+            key_error.conflict.is_some()
+                || !key_error.retryable.is_empty()
+        }
+        Error::MultipleKeyErrors(errors)
+        | Error::ExtractedErrors(errors) => {
+            !errors.is_empty() && errors.iter().all(is_retryable)
+        }
+        _ => false,
+    }
+}
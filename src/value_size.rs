@@ -0,0 +1,49 @@
+//! Size caps of stored values, for models with
+//! `#[store(max_value_size = N)]`.
+//!
+//! TiKV rejects values over its own limit only when a transaction commits,
+//! long after the write that made one too large. A cap lets `save` and the
+//! setters refuse such a value up front, naming the field it was meant for.
+use tikv_client::Error;
+
+/// Start of the message of the error returned for a value over its cap.
+const VALUE_TOO_LARGE: &str = "Value too large";
+
+/// The error for a value of `model.field` stored in `size` bytes, over the
+/// cap of `max` bytes, used by generated code.
+pub fn value_too_large(
+    model: &str,
+    field: &str,
+    size: usize,
+    max: usize,
+) -> Error {
+    Error::StringError(format!(
+        "{VALUE_TOO_LARGE}: {model}.{field} would be stored in {size} bytes, over its max_value_size of {max}"
+    ))
+}
+
+/// Tells whether `err` is a write refused because a field value is larger
+/// than its `#[store(max_value_size = N)]`.
+pub fn is_value_too_large(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::StringError(message)
+            if message.starts_with(VALUE_TOO_LARGE)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_value_too_large() {
+        let err = value_too_large("User", "bio", 2048, 1024);
+        assert!(is_value_too_large(&err));
+        assert!(err.to_string().contains("User.bio"));
+
+        assert!(!is_value_too_large(&Error::StringError(
+            "Failed to encode User.bio".into()
+        )));
+    }
+}
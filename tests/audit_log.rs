@@ -0,0 +1,83 @@
+use ergokv::{AuditOperation, LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(audit_log)]
+struct Account {
+    #[key]
+    id: Uuid,
+    #[unique_index]
+    owner: String,
+    balance: u64,
+}
+
+#[tokio::test]
+async fn test_audit_log() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut account = Account {
+        id: Uuid::new_v4(),
+        owner: "alice".to_string(),
+        balance: 100,
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    account.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    account.set_balance(50, &mut txn).await.unwrap();
+    account
+        .set_owner("bob".to_string(), &mut txn)
+        .await
+        .unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    account.delete(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let log =
+        Account::audit_log(&account.id, &mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let summary: Vec<_> = log
+        .iter()
+        .map(|e| (e.operation, e.changed_fields.clone()))
+        .collect();
+    let all_fields = vec![
+        "id".to_string(),
+        "owner".to_string(),
+        "balance".to_string(),
+    ];
+    assert_eq!(
+        summary,
+        vec![
+            (AuditOperation::Save, all_fields.clone()),
+            (AuditOperation::Set, vec!["balance".to_string()]),
+            (AuditOperation::Set, vec!["owner".to_string()]),
+            (AuditOperation::Delete, all_fields),
+        ]
+    );
+
+    let key = serde_json::to_string(&account.id).unwrap();
+    assert!(log.iter().all(|e| e.key == key));
+    assert!(log
+        .windows(2)
+        .all(|w| w[0].timestamp <= w[1].timestamp));
+
+    // Other records have their own log
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert!(Account::audit_log(&Uuid::new_v4(), &mut txn)
+        .await
+        .unwrap()
+        .is_empty());
+    txn.commit().await.unwrap();
+}
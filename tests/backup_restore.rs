@@ -1,6 +1,7 @@
-use ergokv::{LocalCluster, Store};
+use ergokv::{ConflictPolicy, LocalCluster, Store};
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
 use tempfile::TempDir;
 use uuid::Uuid;
 
@@ -18,6 +19,29 @@ struct User {
     department: String,
 }
 
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(timestamps)]
+struct Note {
+    #[key]
+    id: u64,
+    body: String,
+    created_at: SystemTime,
+    updated_at: SystemTime,
+}
+
+impl Note {
+    fn new(id: u64, body: &str) -> Self {
+        Note {
+            id,
+            body: body.to_string(),
+            created_at: SystemTime::UNIX_EPOCH,
+            updated_at: SystemTime::UNIX_EPOCH,
+        }
+    }
+}
+
 #[tokio::test]
 async fn test_backup_restore() {
     let tmp = TempDir::new().expect("Failed to create temp dir");
@@ -73,6 +97,30 @@ async fn test_backup_restore() {
     txn.commit().await.unwrap();
     assert!(backup_path.exists());
 
+    // Every line matches the record's own backup representation
+    let contents =
+        std::fs::read_to_string(&backup_path).unwrap();
+    for user in &users {
+        let line = user.to_backup_json().unwrap();
+        assert!(contents.lines().any(|l| l == line));
+        assert_eq!(
+            &User::from_backup_json(&line).unwrap(),
+            user
+        );
+    }
+
+    // Backing up the same data again yields an identical file
+    let second_dir = tmp.path().join("backups2");
+    std::fs::create_dir(&second_dir).unwrap();
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let second_path =
+        User::backup(&mut txn, &second_dir).await.unwrap();
+    txn.commit().await.unwrap();
+    assert_eq!(
+        std::fs::read(&second_path).unwrap(),
+        contents.as_bytes()
+    );
+
     // Delete all users
     let mut txn = client.begin_optimistic().await.unwrap();
     for user in &users {
@@ -117,3 +165,335 @@ async fn test_backup_restore() {
     assert_eq!(users, restored);
     txn.commit().await.unwrap();
 }
+
+#[test]
+fn test_from_backup_file() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let backup_path = tmp.path().join("User_1700000000.json");
+
+    let alice = User {
+        id: Uuid::new_v4(),
+        username: "alice".to_string(),
+        email: "alice@example.com".to_string(),
+        department: "Engineering".to_string(),
+    };
+    let bob = User {
+        id: Uuid::new_v4(),
+        username: "bob".to_string(),
+        email: "bob@example.com".to_string(),
+        department: "Marketing".to_string(),
+    };
+
+    std::fs::write(
+        &backup_path,
+        format!(
+            concat!(
+                r#"{{"id":"{}","username":"alice","email":"alice@example.com","department":"Engineering"}}"#,
+                "\n",
+                r#"{{"id":"{}","username":"bob","email":"bob@example.com","department":"Marketing"}}"#,
+                "\n",
+            ),
+            alice.id, bob.id
+        ),
+    )
+    .unwrap();
+
+    let parsed = User::from_backup_file(&backup_path).unwrap();
+    assert_eq!(parsed, vec![alice.clone(), bob.clone()]);
+
+    let mut iter = User::iter_backup_file(&backup_path).unwrap();
+    assert_eq!(iter.next().unwrap().unwrap(), alice);
+    assert_eq!(iter.next().unwrap().unwrap(), bob);
+    assert!(iter.next().is_none());
+
+    // Broken lines are reported, missing files fail up front
+    std::fs::write(&backup_path, "not json\n").unwrap();
+    assert!(User::from_backup_file(&backup_path).is_err());
+    assert!(User::iter_backup_file(
+        tmp.path().join("missing.json")
+    )
+    .is_err());
+}
+
+#[tokio::test]
+async fn test_restore_rejects_corrupt_backup() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let alice = User {
+        id: Uuid::new_v4(),
+        username: "alice".to_string(),
+        email: "alice@example.com".to_string(),
+        department: "Engineering".to_string(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    alice.save(&mut txn).await.unwrap();
+    let backup_path =
+        User::backup(&mut txn, tmp.path()).await.unwrap();
+    alice.delete(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    assert!(User::backup_checksum_path(&backup_path).exists());
+    User::verify_backup_file(&backup_path).unwrap();
+
+    // Tamper with a record, keeping the file perfectly decodable
+    let contents =
+        std::fs::read_to_string(&backup_path).unwrap();
+    std::fs::write(
+        &backup_path,
+        contents.replace("Engineering", "Management"),
+    )
+    .unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let err =
+        User::restore(&mut txn, &backup_path).await.unwrap_err();
+    assert!(err.to_string().contains("Backup corrupt"));
+
+    // Nothing was written
+    assert!(User::load(&alice.id, &mut txn).await.is_err());
+    assert!(User::by_username("alice", &mut txn)
+        .await
+        .unwrap()
+        .is_none());
+    txn.commit().await.unwrap();
+
+    // Backups without a checksum file are still accepted
+    std::fs::write(&backup_path, contents).unwrap();
+    std::fs::remove_file(User::backup_checksum_path(
+        &backup_path,
+    ))
+    .unwrap();
+    let mut txn = client.begin_optimistic().await.unwrap();
+    User::restore(&mut txn, &backup_path).await.unwrap();
+    assert_eq!(
+        User::load(&alice.id, &mut txn).await.unwrap(),
+        alice
+    );
+    txn.commit().await.unwrap();
+}
+
+mod renamed {
+    use super::*;
+
+    #[derive(
+        Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+    )]
+    pub struct Member {
+        #[key]
+        pub id: Uuid,
+        #[unique_index]
+        pub handle: String,
+        pub team: String,
+    }
+
+    impl From<super::User> for Member {
+        fn from(user: super::User) -> Self {
+            Self {
+                id: user.id,
+                handle: user.username,
+                team: user.department,
+            }
+        }
+    }
+}
+
+#[tokio::test]
+// The mappings return `tikv_client::Error`, as `restore_mapping` expects
+#[allow(clippy::result_large_err)]
+async fn test_restore_mapping() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let alice = User {
+        id: Uuid::new_v4(),
+        username: "alice".to_string(),
+        email: "alice@example.com".to_string(),
+        department: "Engineering".to_string(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    alice.save(&mut txn).await.unwrap();
+    let backup_path =
+        User::backup(&mut txn, tmp.path()).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // A `User` backup restored as another model
+    let mut txn = client.begin_optimistic().await.unwrap();
+    renamed::Member::restore_mapping(
+        &mut txn,
+        &backup_path,
+        |line| {
+            User::from_backup_json(line)
+                .map(renamed::Member::from)
+        },
+    )
+    .await
+    .unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        renamed::Member::by_handle("alice", &mut txn)
+            .await
+            .unwrap(),
+        Some(renamed::Member {
+            id: alice.id,
+            handle: "alice".to_string(),
+            team: "Engineering".to_string(),
+        })
+    );
+
+    // Errors from the mapping abort the restore
+    assert!(renamed::Member::restore_mapping(
+        &mut txn,
+        &backup_path,
+        |_| Err(tikv_client::Error::StringError("nope".into()))
+    )
+    .await
+    .is_err());
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_framed_backup_restore() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let users = vec![
+        User {
+            id: Uuid::new_v4(),
+            username: "line\nbreak".to_string(),
+            email: "tab\there@example.com".to_string(),
+            department: "Null\0Bell\u{7}Escape\u{1b}"
+                .to_string(),
+        },
+        User {
+            id: Uuid::new_v4(),
+            username: "carriage\r\nreturn".to_string(),
+            email: "plain@example.com".to_string(),
+            department: "Ünïcödé\u{2028}".to_string(),
+        },
+    ];
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for user in &users {
+        user.save(&mut txn).await.unwrap();
+    }
+    let backup_path =
+        User::backup_framed(&mut txn, tmp.path()).await.unwrap();
+    for user in &users {
+        user.delete(&mut txn).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    assert_eq!(
+        backup_path.extension().and_then(|e| e.to_str()),
+        Some("framed")
+    );
+    User::verify_backup_file(&backup_path).unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    User::restore_framed(&mut txn, &backup_path).await.unwrap();
+    for user in &users {
+        assert_eq!(
+            User::load(&user.id, &mut txn).await.unwrap(),
+            *user
+        );
+    }
+    assert_eq!(
+        User::by_username("line\nbreak", &mut txn)
+            .await
+            .unwrap(),
+        Some(users[0].clone())
+    );
+    txn.rollback().await.unwrap();
+
+    // A backup cut off in the middle of a frame is rejected
+    let contents = std::fs::read(&backup_path).unwrap();
+    std::fs::remove_file(User::backup_checksum_path(
+        &backup_path,
+    ))
+    .unwrap();
+    std::fs::write(
+        &backup_path,
+        &contents[..contents.len() - 3],
+    )
+    .unwrap();
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let err = User::restore_framed(&mut txn, &backup_path)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("truncated"));
+    txn.rollback().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_restore_with() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+    let backup_dir = tmp.path().join("backups");
+    std::fs::create_dir(&backup_dir).unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    Note::new(1, "a1").save(&mut txn).await.unwrap();
+    Note::new(2, "b1").save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    // The backup is made of changes that are then rolled back, so it
+    // holds an older note 1, a newer note 2 and a note 3 never stored
+    let mut txn = client.begin_optimistic().await.unwrap();
+    Note::new(2, "b2").save(&mut txn).await.unwrap();
+    Note::new(3, "c").save(&mut txn).await.unwrap();
+    let backup_path =
+        Note::backup(&mut txn, &backup_dir).await.unwrap();
+    txn.rollback().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    Note::new(1, "a3").save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // Each policy is tried in a transaction of its own, rolled back
+    // afterwards, so all start from the same stored notes
+    let cases = [
+        (ConflictPolicy::Skip, 1, ["a3", "b1", "c"]),
+        (ConflictPolicy::Overwrite, 3, ["a1", "b2", "c"]),
+        (ConflictPolicy::NewerWins, 2, ["a3", "b2", "c"]),
+    ];
+    for (policy, written, bodies) in cases {
+        let mut txn = client.begin_optimistic().await.unwrap();
+        assert_eq!(
+            Note::restore_with(&mut txn, &backup_path, policy)
+                .await
+                .unwrap(),
+            written,
+            "{policy:?}"
+        );
+        for (id, body) in (1..).zip(bodies) {
+            assert_eq!(
+                Note::load(&id, &mut txn).await.unwrap().body,
+                body,
+                "{policy:?}"
+            );
+        }
+        txn.rollback().await.unwrap();
+    }
+
+    // Without timestamps, there is nothing to tell which is newer
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert!(User::restore_with(
+        &mut txn,
+        &backup_path,
+        ConflictPolicy::NewerWins
+    )
+    .await
+    .is_err());
+    txn.rollback().await.unwrap();
+}
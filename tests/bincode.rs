@@ -0,0 +1,249 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+enum Unit {
+    Celsius,
+    Kelvin { offset: f64 },
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct CborSeries {
+    #[key]
+    id: u64,
+    samples: Vec<u32>,
+    total: u64,
+    unit: Unit,
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(format = "bincode")]
+struct BincodeSeries {
+    #[key]
+    id: u64,
+    samples: Vec<u32>,
+    total: u64,
+    unit: Unit,
+    #[store(format = "cbor")]
+    label: Option<String>,
+}
+
+async fn stored_size(
+    model: &str,
+    id: u64,
+    fields: &[&str],
+    txn: &mut tikv_client::Transaction,
+) -> usize {
+    let mut size = 0;
+    for field in fields {
+        size += txn
+            .get(format!("ergokv:{}:{}:{}", model, id, field))
+            .await
+            .unwrap()
+            .unwrap()
+            .len();
+    }
+    size
+}
+
+#[tokio::test]
+async fn test_bincode_values_are_smaller() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let samples: Vec<u32> =
+        (0..100).map(|i| 100_000 + i * 7).collect();
+    let total =
+        samples.iter().map(|&s| s as u64).sum::<u64>() << 24;
+    let unit = Unit::Kelvin { offset: 273.15 };
+
+    let cbor = CborSeries {
+        id: 1,
+        samples: samples.clone(),
+        total,
+        unit: unit.clone(),
+    };
+    let bincode = BincodeSeries {
+        id: 1,
+        samples,
+        total,
+        unit,
+        label: Some("sensor".to_string()),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    cbor.save(&mut txn).await.unwrap();
+    bincode.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        CborSeries::load(&1, &mut txn).await.unwrap(),
+        cbor
+    );
+    assert_eq!(
+        BincodeSeries::load(&1, &mut txn).await.unwrap(),
+        bincode
+    );
+
+    let fields = ["samples", "total"];
+    let cbor_size =
+        stored_size("CborSeries", 1, &fields, &mut txn).await;
+    let bincode_size =
+        stored_size("BincodeSeries", 1, &fields, &mut txn).await;
+    assert!(
+        bincode_size < cbor_size,
+        "bincode {} bytes, CBOR {} bytes",
+        bincode_size,
+        cbor_size
+    );
+
+    // The label opted back into CBOR, a text string of 6 bytes
+    let label = txn
+        .get("ergokv:BincodeSeries:1:label".to_string())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(label, b"\x66sensor");
+    txn.commit().await.unwrap();
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Profile {
+    #[key]
+    id: u64,
+    name: String,
+    tags: Vec<String>,
+}
+
+/// `Profile` after moving to bincode, reading the same keys
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[model_name = "Profile"]
+#[store(format = "bincode")]
+struct BincodeProfile {
+    #[key]
+    id: u64,
+    name: String,
+    tags: Vec<String>,
+}
+
+#[tokio::test]
+async fn test_cbor_values() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let profile = Profile {
+        id: 7,
+        name: "ada".to_string(),
+        tags: vec!["admin".to_string()],
+    };
+    let mut txn = client.begin_optimistic().await.unwrap();
+    profile.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // Values stored as CBOR are still readable once the model moved to bincode
+    let expected = BincodeProfile {
+        id: 7,
+        name: "ada".to_string(),
+        tags: vec!["admin".to_string()],
+    };
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        BincodeProfile::load(&7, &mut txn).await.unwrap(),
+        expected
+    );
+
+    // ...and rewriting them stores them as bincode
+    assert_eq!(
+        BincodeProfile::rewrite_all(&mut txn).await.unwrap(),
+        1
+    );
+    let name = txn
+        .get("ergokv:Profile:7:name".to_string())
+        .await
+        .unwrap()
+        .unwrap();
+    let mut expected_name = vec![ergokv::BINCODE_TAG];
+    expected_name
+        .extend(ergokv::bincode::serialize("ada").unwrap());
+    assert_eq!(name, expected_name);
+    assert_eq!(
+        BincodeProfile::load(&7, &mut txn).await.unwrap(),
+        expected
+    );
+    txn.commit().await.unwrap();
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Counter {
+    #[key]
+    id: u64,
+    hits: u16,
+}
+
+/// `Counter` after moving to bincode, rewriting CBOR values as it loads them
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[model_name = "Counter"]
+#[store(format = "bincode", rewrite_on_load)]
+struct BincodeCounter {
+    #[key]
+    id: u64,
+    hits: u16,
+}
+
+#[tokio::test]
+async fn test_rewrite_on_load() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let counter = Counter { id: 1, hits: 100 };
+    let mut txn = client.begin_optimistic().await.unwrap();
+    counter.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // 100 as CBOR is also valid bincode for a u16, 0x6418, which the
+    // missing tag rules out
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let hits = txn
+        .get("ergokv:Counter:1:hits".to_string())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(hits, [0x18, 0x64]);
+    assert_eq!(
+        BincodeCounter::load(&1, &mut txn).await.unwrap(),
+        BincodeCounter { id: 1, hits: 100 }
+    );
+
+    // Loading wrote the value again, in bincode
+    let hits = txn
+        .get("ergokv:Counter:1:hits".to_string())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(hits, [ergokv::BINCODE_TAG, 100, 0]);
+    assert_eq!(
+        BincodeCounter::load(&1, &mut txn).await.unwrap().hits,
+        100
+    );
+
+    // The old model cannot read it back
+    assert!(Counter::load(&1, &mut txn).await.is_err());
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,85 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tempfile::TempDir;
+
+static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+/// A string counting how often it is cloned
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(transparent)]
+struct Tag(String);
+
+impl Clone for Tag {
+    fn clone(&self) -> Self {
+        CLONES.fetch_add(1, Ordering::SeqCst);
+        Tag(self.0.clone())
+    }
+}
+
+impl PartialEq<str> for Tag {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Article {
+    #[key]
+    id: u64,
+    #[unique_index]
+    slug: String,
+    #[index]
+    tag: Tag,
+    #[index(hashed)]
+    topic: Tag,
+}
+
+#[tokio::test]
+async fn test_borrowed_queries() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let article = Article {
+        id: 1,
+        slug: "hello-world".to_string(),
+        tag: Tag("rust".to_string()),
+        topic: Tag("databases".to_string()),
+    };
+    let mut txn = client.begin_optimistic().await.unwrap();
+    article.save(&mut txn).await.unwrap();
+
+    let tag = Tag("rust".to_string());
+    CLONES.store(0, Ordering::SeqCst);
+
+    // Borrowed values find the same instances as owned ones, without cloning
+    let by_slug = Article::by_slug_ref("hello-world", &mut txn)
+        .await
+        .unwrap();
+    let by_tag =
+        Article::by_tag_ref(&tag, &mut txn).await.unwrap();
+    let by_tag_str =
+        Article::by_tag_ref("rust", &mut txn).await.unwrap();
+    let by_topic = Article::by_topic_ref("databases", &mut txn)
+        .await
+        .unwrap();
+    let by_other_topic =
+        Article::by_topic_ref("rust", &mut txn).await.unwrap();
+    assert_eq!(CLONES.load(Ordering::SeqCst), 0);
+
+    assert_eq!(by_slug.as_ref(), Some(&article));
+    assert_eq!(by_tag, std::slice::from_ref(&article));
+    assert_eq!(by_tag_str, std::slice::from_ref(&article));
+    assert_eq!(by_topic, std::slice::from_ref(&article));
+    assert!(by_other_topic.is_empty());
+
+    // The owning methods still take anything convertible
+    assert_eq!(
+        Article::by_tag(tag, &mut txn).await.unwrap(),
+        [article]
+    );
+    txn.commit().await.unwrap();
+}
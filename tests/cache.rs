@@ -0,0 +1,108 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(cache_ttl = "1s")]
+struct Profile {
+    #[key]
+    id: Uuid,
+    bio: String,
+}
+
+/// Overwrites the stored `bio` without going through ergokv, so that only
+/// a cache hit can still return the old value.
+async fn write_bio_directly(
+    client: &tikv_client::TransactionClient,
+    id: &Uuid,
+    bio: &str,
+) {
+    let mut value = Vec::new();
+    ergokv::ciborium::ser::into_writer(&bio, &mut value)
+        .unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    txn.put(
+        format!(
+            "ergokv:Profile:{}:bio",
+            serde_json::to_string(id).unwrap()
+        ),
+        value,
+    )
+    .await
+    .unwrap();
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_read_cache() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let profile = Profile {
+        id: Uuid::new_v4(),
+        bio: "original".to_string(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    profile.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // Freshly written keys are not cached within the TTL
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        Profile::load(&profile.id, &mut txn).await.unwrap(),
+        profile
+    );
+    txn.commit().await.unwrap();
+
+    write_bio_directly(
+        &client,
+        &profile.id,
+        "changed behind our back",
+    )
+    .await;
+
+    // The second load is served from the cache
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        Profile::load(&profile.id, &mut txn).await.unwrap(),
+        profile
+    );
+    txn.commit().await.unwrap();
+
+    // Clearing the cache makes the stored value visible
+    Profile::clear_cache();
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        Profile::load(&profile.id, &mut txn).await.unwrap().bio,
+        "changed behind our back"
+    );
+
+    // Saving invalidates, so the same transaction reads its own write
+    let updated = Profile {
+        id: profile.id,
+        bio: "updated".to_string(),
+    };
+    updated.save(&mut txn).await.unwrap();
+    assert_eq!(
+        Profile::load(&profile.id, &mut txn).await.unwrap(),
+        updated
+    );
+    txn.rollback().await.unwrap();
+
+    // ...and the rolled back write never made it into the cache
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        Profile::load(&profile.id, &mut txn).await.unwrap().bio,
+        "changed behind our back"
+    );
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,131 @@
+use std::sync::{Arc, Mutex};
+
+use ergokv::{
+    is_checksum_mismatch, set_checksum_mismatch_handler,
+    LocalCluster, Store,
+};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(checksum)]
+struct Document {
+    #[key]
+    id: u64,
+    #[index]
+    owner: String,
+    body: String,
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(checksum = "warn")]
+struct Draft {
+    #[key]
+    id: u64,
+    body: String,
+}
+
+fn cbor(value: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    ergokv::ciborium::ser::into_writer(value, &mut bytes)
+        .unwrap();
+    bytes
+}
+
+#[tokio::test]
+async fn test_checksum() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut doc = Document {
+        id: 1,
+        owner: "ada".to_string(),
+        body: "original".to_string(),
+    };
+    let mut txn = client.begin_optimistic().await.unwrap();
+    doc.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // Writes through ergokv keep the checksum up to date
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert!(txn
+        .get("ergokv:Document:1:__checksum".to_string())
+        .await
+        .unwrap()
+        .is_some());
+    doc.set_body("edited".to_string(), &mut txn).await.unwrap();
+    assert_eq!(Document::load(&1, &mut txn).await.unwrap(), doc);
+    txn.commit().await.unwrap();
+
+    // A field rewritten behind ergokv's back, with bytes that still
+    // decode, is detected
+    let mut txn = client.begin_optimistic().await.unwrap();
+    txn.put(
+        "ergokv:Document:1:body".to_string(),
+        cbor("tampered"),
+    )
+    .await
+    .unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let err = Document::load(&1, &mut txn).await.unwrap_err();
+    assert!(is_checksum_mismatch(&err), "{err:?}");
+    let err =
+        Document::load_many(&[1], &mut txn).await.unwrap_err();
+    assert!(is_checksum_mismatch(&err), "{err:?}");
+    assert!(is_checksum_mismatch(
+        &Document::by_owner("ada", &mut txn).await.unwrap_err()
+    ));
+
+    // So is a missing checksum
+    doc.save(&mut txn).await.unwrap();
+    assert_eq!(Document::load(&1, &mut txn).await.unwrap(), doc);
+    txn.delete("ergokv:Document:1:__checksum".to_string())
+        .await
+        .unwrap();
+    assert!(is_checksum_mismatch(
+        &Document::load(&1, &mut txn).await.unwrap_err()
+    ));
+
+    // Deleting the instance removes its checksum
+    doc.save(&mut txn).await.unwrap();
+    doc.delete(&mut txn).await.unwrap();
+    assert!(txn
+        .get("ergokv:Document:1:__checksum".to_string())
+        .await
+        .unwrap()
+        .is_none());
+    txn.commit().await.unwrap();
+
+    // In warn mode, the instance is returned as it is stored, and the
+    // mismatch is reported to the handler
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let handler_reported = reported.clone();
+    set_checksum_mismatch_handler(move |err| {
+        handler_reported.lock().unwrap().push(err.to_string());
+    });
+    let draft = Draft {
+        id: 1,
+        body: "original".to_string(),
+    };
+    let mut txn = client.begin_optimistic().await.unwrap();
+    draft.save(&mut txn).await.unwrap();
+    txn.put("ergokv:Draft:1:body".to_string(), cbor("tampered"))
+        .await
+        .unwrap();
+    assert!(reported.lock().unwrap().is_empty());
+    assert_eq!(
+        Draft::load(&1, &mut txn).await.unwrap().body,
+        "tampered"
+    );
+    let reported = reported.lock().unwrap().clone();
+    assert_eq!(reported.len(), 1);
+    assert!(reported[0].contains("Draft:1"), "{reported:?}");
+    txn.commit().await.unwrap();
+}
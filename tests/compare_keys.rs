@@ -0,0 +1,81 @@
+use ergokv::{LocalCluster, Store};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use tempfile::TempDir;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Invoice {
+    #[key]
+    number: u64,
+    total: u64,
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(key_prefix_shards = 4)]
+struct ShardedInvoice {
+    #[key]
+    number: u64,
+    total: u64,
+}
+
+#[tokio::test]
+async fn test_compare_keys() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    // Serialized keys do not sort like numbers
+    assert_eq!(
+        Invoice::compare_keys(&9, &10),
+        Ordering::Greater
+    );
+    assert_eq!(Invoice::compare_keys(&10, &100), Ordering::Less);
+    assert_eq!(Invoice::compare_keys(&7, &7), Ordering::Equal);
+
+    let numbers: Vec<u64> =
+        (0..40).map(|i| i * 37 % 101).collect();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for &number in &numbers {
+        Invoice { number, total: 1 }
+            .save(&mut txn)
+            .await
+            .unwrap();
+        ShardedInvoice { number, total: 1 }
+            .save(&mut txn)
+            .await
+            .unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let stored: Vec<u64> = Invoice::all(&mut txn)
+        .map(|i| i.unwrap().number)
+        .collect()
+        .await;
+    let sharded: Vec<u64> = ShardedInvoice::all(&mut txn)
+        .map(|i| i.unwrap().number)
+        .collect()
+        .await;
+    txn.commit().await.unwrap();
+
+    // Sorting with compare_keys gives exactly the order of `all`,
+    // including across the shards of a sharded model
+    let mut sorted = numbers.clone();
+    sorted.sort_by(Invoice::compare_keys);
+    assert_eq!(sorted, stored);
+    assert_ne!(sorted, {
+        let mut by_ord = numbers.clone();
+        by_ord.sort();
+        by_ord
+    });
+
+    let mut sorted = numbers.clone();
+    sorted.sort_by(ShardedInvoice::compare_keys);
+    assert_eq!(sorted, sharded);
+}
@@ -0,0 +1,89 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(compression_threshold = 64)]
+struct Document {
+    #[key]
+    id: u64,
+    title: String,
+    body: String,
+    #[store(compression_threshold = 0)]
+    summary: String,
+}
+
+async fn stored_bytes(
+    txn: &mut tikv_client::Transaction,
+    field: &str,
+) -> Vec<u8> {
+    txn.get(format!("ergokv:Document:1:{field}"))
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_compression_threshold() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut document = Document {
+        id: 1,
+        title: "Short".to_string(),
+        body: "lorem ipsum ".repeat(100),
+        summary: "Short too".to_string(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    document.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+
+    // The small field is stored as it is, behind its header
+    let title = stored_bytes(&mut txn, "title").await;
+    assert_eq!(title[0], 0);
+    let mut plain = Vec::new();
+    ciborium::ser::into_writer(&document.title, &mut plain)
+        .unwrap();
+    assert_eq!(&title[1..], plain.as_slice());
+
+    // The large one is compressed
+    let body = stored_bytes(&mut txn, "body").await;
+    assert_eq!(body[0], 1);
+    assert!(body.len() < document.body.len() / 4);
+
+    // A field's own threshold replaces the model's
+    assert_eq!(stored_bytes(&mut txn, "summary").await[0], 1);
+
+    // Both round-trip, through whole and single-field loads
+    assert_eq!(
+        Document::load(&1, &mut txn).await.unwrap(),
+        document
+    );
+    assert_eq!(
+        Document::load_field_body(&1, &mut txn).await.unwrap(),
+        document.body
+    );
+
+    // Setting a field moves it across the threshold
+    document
+        .set_title("a very long title ".repeat(10), &mut txn)
+        .await
+        .unwrap();
+    document
+        .set_body("Now short".to_string(), &mut txn)
+        .await
+        .unwrap();
+    assert_eq!(stored_bytes(&mut txn, "title").await[0], 1);
+    assert_eq!(stored_bytes(&mut txn, "body").await[0], 0);
+    assert_eq!(
+        Document::load(&1, &mut txn).await.unwrap(),
+        document
+    );
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,67 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Person {
+    #[key]
+    id: Uuid,
+    first: String,
+    last: String,
+    #[store(compute = "full_name")]
+    #[serde(skip)]
+    full_name: String,
+}
+
+fn full_name(person: &Person) -> String {
+    format!("{} {}", person.first, person.last)
+}
+
+#[tokio::test]
+async fn test_computed_field() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut person = Person {
+        id: Uuid::new_v4(),
+        first: "Ada".to_string(),
+        last: "Lovelace".to_string(),
+        full_name: "ignored".to_string(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    person.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // The computed field is never written
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let record_path = format!(
+        "ergokv:Person:{}",
+        serde_json::to_string(&person.id).unwrap()
+    );
+    assert!(txn
+        .get(format!("{}:full_name", record_path))
+        .await
+        .unwrap()
+        .is_none());
+    assert!(txn
+        .get(format!("{}:first", record_path))
+        .await
+        .unwrap()
+        .is_some());
+
+    let loaded =
+        Person::load(&person.id, &mut txn).await.unwrap();
+    assert_eq!(loaded.full_name, "Ada Lovelace");
+
+    // Loading after a change computes the field from the new values
+    person.set_last("King".to_string(), &mut txn).await.unwrap();
+    let loaded =
+        Person::load_many(&[person.id], &mut txn).await.unwrap();
+    assert_eq!(loaded[0].full_name, "Ada King");
+    txn.commit().await.unwrap();
+}
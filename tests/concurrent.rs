@@ -0,0 +1,110 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Employee {
+    #[key]
+    id: Uuid,
+    name: String,
+    #[index]
+    department: String,
+    #[index(hashed)]
+    bio: String,
+}
+
+#[tokio::test]
+async fn test_concurrent_loads() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let employees: Vec<Employee> = (0..20)
+        .map(|i| Employee {
+            id: Uuid::new_v4(),
+            name: format!("employee{i}"),
+            department: if i % 4 == 0 {
+                "Legal"
+            } else {
+                "Sales"
+            }
+            .to_string(),
+            bio: "Joined recently".to_string(),
+        })
+        .collect();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for employee in &employees {
+        employee.save(&mut txn).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let sequential = Employee::by_department("Sales", &mut txn)
+        .await
+        .unwrap();
+    let by_bio = Employee::by_bio("Joined recently", &mut txn)
+        .await
+        .unwrap();
+    txn.commit().await.unwrap();
+    assert_eq!(sequential.len(), 15);
+
+    for concurrency in [0, 1, 4, 32] {
+        assert_eq!(
+            Employee::by_department_concurrent(
+                "Sales",
+                &client,
+                concurrency
+            )
+            .await
+            .unwrap(),
+            sequential
+        );
+    }
+    assert_eq!(
+        Employee::by_bio_concurrent(
+            "Joined recently",
+            &client,
+            8
+        )
+        .await
+        .unwrap(),
+        by_bio
+    );
+    assert!(Employee::by_department_concurrent(
+        "Marketing",
+        &client,
+        8
+    )
+    .await
+    .unwrap()
+    .is_empty());
+
+    // Keys are loaded in the given order, and missing keys fail the whole load
+    let timestamp = client.current_timestamp().await.unwrap();
+    let keys: Vec<Uuid> =
+        employees.iter().rev().map(|e| e.id).collect();
+    let loaded = Employee::load_many_at(
+        &keys,
+        &client,
+        timestamp.clone(),
+        5,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        loaded,
+        employees.iter().rev().cloned().collect::<Vec<_>>()
+    );
+    assert!(Employee::load_many_at(
+        &[Uuid::new_v4()],
+        &client,
+        timestamp,
+        5
+    )
+    .await
+    .is_err());
+}
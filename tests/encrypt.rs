@@ -0,0 +1,84 @@
+#![cfg(feature = "encryption")]
+
+use ergokv::{AesGcmEncryptor, LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, Clone, PartialEq,
+)]
+struct Customer {
+    #[key]
+    id: Uuid,
+    #[unique_index]
+    handle: String,
+    #[store(encrypt)]
+    email: String,
+    #[store(encrypt, raw_bytes)]
+    document: Vec<u8>,
+}
+
+#[tokio::test]
+async fn test_encrypted_fields() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    ergokv::set_encryptor(AesGcmEncryptor::new(&[42; 32]));
+
+    let mut customer = Customer {
+        id: Uuid::new_v4(),
+        handle: "jane".into(),
+        email: "jane@example.com".into(),
+        document: b"passport 1234".to_vec(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    customer.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // The stored bytes don't contain the plaintext
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for (field, plaintext) in [
+        ("email", &b"jane@example.com"[..]),
+        ("document", &b"passport 1234"[..]),
+    ] {
+        let raw = txn
+            .get(format!(
+                "ergokv:Customer:\"{}\":{}",
+                customer.id, field
+            ))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!raw
+            .windows(plaintext.len())
+            .any(|window| window == plaintext));
+    }
+
+    // ...but everything reading the model sees it
+    assert_eq!(
+        Customer::load(&customer.id, &mut txn).await.unwrap(),
+        customer
+    );
+    assert_eq!(
+        Customer::by_handle("jane", &mut txn)
+            .await
+            .unwrap()
+            .unwrap(),
+        customer
+    );
+
+    customer
+        .set_email("jane@example.org".into(), &mut txn)
+        .await
+        .unwrap();
+    assert_eq!(
+        Customer::load_field_email(&customer.id, &mut txn)
+            .await
+            .unwrap(),
+        "jane@example.org"
+    );
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,115 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Active,
+    Suspended,
+    Closed { reason: String },
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Account {
+    #[key]
+    id: Uuid,
+    #[index]
+    status: Status,
+}
+
+#[tokio::test]
+async fn test_enum_index() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let closed = Status::Closed {
+        reason: "fraud".into(),
+    };
+    let accounts: Vec<Account> = [
+        Status::Active,
+        Status::Active,
+        Status::Suspended,
+        closed.clone(),
+    ]
+    .into_iter()
+    .map(|status| Account {
+        id: Uuid::new_v4(),
+        status,
+    })
+    .collect();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for account in &accounts {
+        account.save(&mut txn).await.unwrap();
+    }
+
+    // The index key is the serde representation of the variant
+    assert!(txn
+        .get(
+            "ergokv:Account:index:status:\"suspended\""
+                .to_string()
+        )
+        .await
+        .unwrap()
+        .is_some());
+
+    let mut active =
+        Account::by_status(Status::Active, &mut txn)
+            .await
+            .unwrap();
+    active.sort_by_key(|a| a.id);
+    let mut expected = accounts[..2].to_vec();
+    expected.sort_by_key(|a| a.id);
+    assert_eq!(active, expected);
+    assert_eq!(
+        Account::by_status(Status::Suspended, &mut txn)
+            .await
+            .unwrap(),
+        vec![accounts[2].clone()]
+    );
+    assert_eq!(
+        Account::by_status(closed.clone(), &mut txn)
+            .await
+            .unwrap(),
+        vec![accounts[3].clone()]
+    );
+    assert!(Account::by_status(
+        Status::Closed {
+            reason: "moved".into()
+        },
+        &mut txn
+    )
+    .await
+    .unwrap()
+    .is_empty());
+
+    // Updates and deletes move accounts between variants
+    let mut suspended = accounts[2].clone();
+    suspended
+        .set_status(Status::Active, &mut txn)
+        .await
+        .unwrap();
+    accounts[3].delete(&mut txn).await.unwrap();
+    assert_eq!(
+        Account::by_status(Status::Active, &mut txn)
+            .await
+            .unwrap()
+            .len(),
+        3
+    );
+    assert!(!Account::by_status_exists(
+        Status::Suspended,
+        &mut txn
+    )
+    .await
+    .unwrap());
+    assert!(!Account::by_status_exists(closed, &mut txn)
+        .await
+        .unwrap());
+    txn.commit().await.unwrap();
+}
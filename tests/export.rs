@@ -0,0 +1,90 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tempfile::TempDir;
+
+#[derive(Store, Serialize, Deserialize, Debug, Clone)]
+struct Customer {
+    #[key]
+    id: u64,
+    name: String,
+    note: Option<String>,
+    balance: f64,
+    tags: Vec<String>,
+    address: BTreeMap<String, String>,
+}
+
+#[tokio::test]
+async fn test_export_csv() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let customers = [
+        Customer {
+            id: 1,
+            name: "Acme, Inc.".to_string(),
+            note: Some("says \"hi\"\non two lines".to_string()),
+            balance: 12.5,
+            tags: vec!["b2b".to_string(), "eu".to_string()],
+            address: BTreeMap::from([(
+                "city".to_string(),
+                "Prague".to_string(),
+            )]),
+        },
+        Customer {
+            id: 2,
+            name: "Globex".to_string(),
+            note: None,
+            balance: -3.0,
+            tags: vec![],
+            address: BTreeMap::new(),
+        },
+    ];
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for customer in &customers {
+        customer.save(&mut txn).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let mut out = Vec::new();
+    let rows =
+        Customer::export_csv(&mut txn, &mut out).await.unwrap();
+    txn.commit().await.unwrap();
+    assert_eq!(rows, 2);
+
+    let mut reader = csv::Reader::from_reader(out.as_slice());
+    assert_eq!(
+        reader.headers().unwrap(),
+        vec!["id", "name", "note", "balance", "tags", "address"]
+    );
+
+    let mut records: Vec<csv::StringRecord> =
+        reader.records().map(|record| record.unwrap()).collect();
+    records.sort_by(|a, b| a[0].cmp(&b[0]));
+    assert_eq!(records.len(), 2);
+
+    // Plain values round-trip as they are, even with commas, quotes and
+    // newlines in them
+    assert_eq!(&records[0][0], "1");
+    assert_eq!(&records[0][1], "Acme, Inc.");
+    assert_eq!(&records[0][2], "says \"hi\"\non two lines");
+    assert_eq!(records[0][3].parse::<f64>().unwrap(), 12.5);
+
+    // Complex values are JSON
+    let tags: Vec<String> =
+        serde_json::from_str(&records[0][4]).unwrap();
+    assert_eq!(tags, customers[0].tags);
+    let address: BTreeMap<String, String> =
+        serde_json::from_str(&records[0][5]).unwrap();
+    assert_eq!(address, customers[0].address);
+
+    // `None` is an empty cell
+    assert_eq!(&records[1][1], "Globex");
+    assert_eq!(&records[1][2], "");
+    assert_eq!(records[1][3].parse::<f64>().unwrap(), -3.0);
+    assert_eq!(&records[1][4], "[]");
+    assert_eq!(&records[1][5], "{}");
+}
@@ -0,0 +1,114 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Address {
+    street: String,
+    city: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    note: Option<String>,
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, Clone, PartialEq,
+)]
+struct Customer {
+    #[key]
+    id: Uuid,
+    name: String,
+    #[store(flatten)]
+    address: Address,
+}
+
+#[tokio::test]
+async fn test_flattened_field() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut customer = Customer {
+        id: Uuid::new_v4(),
+        name: "Jane".into(),
+        address: Address {
+            street: "Main Street 1".into(),
+            city: "Springfield".into(),
+            note: Some("ring twice".into()),
+        },
+    };
+    let id = customer.id;
+    let sub_key = |sub: &str| {
+        format!("ergokv:Customer:\"{id}\":address.{sub}")
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    customer.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // Every sub-field has a key of its own
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for sub in ["street", "city", "note"] {
+        assert!(txn.get(sub_key(sub)).await.unwrap().is_some());
+    }
+    assert!(txn
+        .get(format!(
+            "ergokv:Customer:\"{}\":address",
+            customer.id
+        ))
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        Customer::load(&customer.id, &mut txn).await.unwrap(),
+        customer
+    );
+    assert_eq!(
+        Customer::load_field_address(&customer.id, &mut txn)
+            .await
+            .unwrap(),
+        customer.address
+    );
+
+    // Only the city is written, so a concurrent change of the street
+    // neither conflicts nor is overwritten
+    let mut moved = customer.address.clone();
+    moved.city = "Shelbyville".into();
+    customer.set_address(moved, &mut txn).await.unwrap();
+
+    let mut other = client.begin_optimistic().await.unwrap();
+    let mut renamed =
+        Customer::load(&customer.id, &mut other).await.unwrap();
+    let mut address = renamed.address.clone();
+    address.street = "Main Street 2".into();
+    renamed.set_address(address, &mut other).await.unwrap();
+    other.commit().await.unwrap();
+
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let stored =
+        Customer::load(&customer.id, &mut txn).await.unwrap();
+    assert_eq!(stored.address.street, "Main Street 2");
+    assert_eq!(stored.address.city, "Shelbyville");
+
+    // Sub-fields that are no longer serialized are removed
+    let mut address = stored.address.clone();
+    address.note = None;
+    customer.set_address(address, &mut txn).await.unwrap();
+    assert!(txn.get(sub_key("note")).await.unwrap().is_none());
+    assert_eq!(
+        Customer::load(&customer.id, &mut txn)
+            .await
+            .unwrap()
+            .address
+            .note,
+        None
+    );
+
+    customer.delete(&mut txn).await.unwrap();
+    for sub in ["street", "city"] {
+        assert!(txn.get(sub_key(sub)).await.unwrap().is_none());
+    }
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,81 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Document {
+    #[key]
+    id: Uuid,
+    #[index(hashed)]
+    body: String,
+}
+
+fn index_key(body: &str) -> String {
+    let encoded = serde_json::to_string(body).unwrap();
+    format!(
+        "ergokv:Document:hashed_index:body:{}",
+        ergokv::blake3::hash(encoded.as_bytes()).to_hex()
+    )
+}
+
+#[tokio::test]
+async fn test_hashed_index() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let long = Document {
+        id: Uuid::new_v4(),
+        body: "lorem ipsum ".repeat(10_000),
+    };
+    let other = Document {
+        id: Uuid::new_v4(),
+        body: "dolor sit amet ".repeat(10_000),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    long.save(&mut txn).await.unwrap();
+    other.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+
+    // The index key stays short no matter the value
+    let key = index_key(&long.body);
+    assert!(key.len() < 128);
+    assert!(txn.get(key.clone()).await.unwrap().is_some());
+
+    assert_eq!(
+        Document::by_body(long.body.clone(), &mut txn)
+            .await
+            .unwrap(),
+        vec![long.clone()]
+    );
+
+    // Simulate a collision: list `other` under the hash of `long`
+    let mut value = Vec::new();
+    ergokv::ciborium::ser::into_writer(
+        &vec![long.id, other.id],
+        &mut value,
+    )
+    .unwrap();
+    txn.put(key, value).await.unwrap();
+
+    assert_eq!(
+        Document::by_body(long.body.clone(), &mut txn)
+            .await
+            .unwrap(),
+        vec![long.clone()]
+    );
+
+    // Deleting removes the record from the index
+    long.delete(&mut txn).await.unwrap();
+    assert!(Document::by_body(long.body.clone(), &mut txn)
+        .await
+        .unwrap()
+        .is_empty());
+    txn.commit().await.unwrap();
+}
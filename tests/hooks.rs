@@ -0,0 +1,99 @@
+use ergokv::{LocalCluster, Store, StoreHooks};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tempfile::TempDir;
+
+static SAVES: AtomicUsize = AtomicUsize::new(0);
+static DELETES: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(hooks)]
+struct Order {
+    #[key]
+    id: u64,
+    total: u64,
+}
+
+impl StoreHooks for Order {
+    async fn after_save(
+        &self,
+        _txn: &mut tikv_client::Transaction,
+    ) -> Result<(), tikv_client::Error> {
+        SAVES.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn before_delete(
+        &self,
+        _txn: &mut tikv_client::Transaction,
+    ) -> Result<(), tikv_client::Error> {
+        if self.total > 0 {
+            return Err(tikv_client::Error::StringError(
+                "Cannot delete a paid order".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn after_delete(
+        &self,
+        _txn: &mut tikv_client::Transaction,
+    ) -> Result<(), tikv_client::Error> {
+        DELETES.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_hooks() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut order = Order { id: 1, total: 0 };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    order.save(&mut txn).await.unwrap();
+    assert_eq!(SAVES.load(Ordering::SeqCst), 1);
+    order.total = 250;
+    order.save(&mut txn).await.unwrap();
+    assert_eq!(SAVES.load(Ordering::SeqCst), 2);
+
+    // A failing hook stops the delete before anything is removed
+    assert!(order.delete(&mut txn).await.is_err());
+    assert_eq!(Order::load(&1, &mut txn).await.unwrap(), order);
+    txn.commit().await.unwrap();
+
+    // `delete_many` runs the same hooks for every instance
+    let mut txn = client.begin_optimistic().await.unwrap();
+    Order { id: 2, total: 0 }.save(&mut txn).await.unwrap();
+    Order { id: 3, total: 0 }.save(&mut txn).await.unwrap();
+    assert!(Order::delete_many(&[2, 1], &mut txn)
+        .await
+        .is_err());
+    assert_eq!(DELETES.load(Ordering::SeqCst), 0);
+    txn.rollback().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(Order::load(&1, &mut txn).await.unwrap(), order);
+    Order { id: 2, total: 0 }.save(&mut txn).await.unwrap();
+    Order { id: 3, total: 0 }.save(&mut txn).await.unwrap();
+    Order::delete_many(&[2, 3], &mut txn).await.unwrap();
+    assert_eq!(DELETES.load(Ordering::SeqCst), 2);
+    assert!(Order::load(&2, &mut txn).await.is_err());
+    assert!(Order::load(&3, &mut txn).await.is_err());
+    txn.commit().await.unwrap();
+
+    // Rekeying moves the instance without saving or deleting it, so
+    // no hook runs and the paid order can move
+    let saves = SAVES.load(Ordering::SeqCst);
+    let mut txn = client.begin_optimistic().await.unwrap();
+    order.rekey(4, &mut txn).await.unwrap();
+    assert_eq!(SAVES.load(Ordering::SeqCst), saves);
+    assert_eq!(DELETES.load(Ordering::SeqCst), 2);
+    assert!(Order::load(&1, &mut txn).await.is_err());
+    assert_eq!(Order::load(&4, &mut txn).await.unwrap(), order);
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,86 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Project {
+    #[key]
+    id: Uuid,
+    #[store(immutable)]
+    tenant: String,
+    name: String,
+}
+
+#[tokio::test]
+async fn test_immutable_field() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut project = Project {
+        id: Uuid::new_v4(),
+        tenant: "acme".to_string(),
+        name: "Rockets".to_string(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    project.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // Other fields can still change, also through a full save
+    let mut txn = client.begin_optimistic().await.unwrap();
+    project.name = "Rockets 2".to_string();
+    project.save(&mut txn).await.unwrap();
+    project
+        .update()
+        .set_name("Rockets 3".to_string())
+        .apply(&mut txn)
+        .await
+        .unwrap();
+    txn.commit().await.unwrap();
+
+    // Saving a reconstructed instance with another tenant is rejected
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let moved = Project {
+        tenant: "globex".to_string(),
+        ..project.clone()
+    };
+    let err = moved.save(&mut txn).await.unwrap_err();
+    assert!(err.to_string().contains("tenant is immutable"));
+
+    let err = Project::apply_patch(
+        &project.id,
+        ProjectPatch {
+            tenant: Some("globex".to_string()),
+            ..Default::default()
+        },
+        &mut txn,
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("tenant is immutable"));
+
+    let stored =
+        Project::load(&project.id, &mut txn).await.unwrap();
+    assert_eq!(stored.tenant, "acme");
+    assert_eq!(stored.name, "Rockets 3");
+    txn.rollback().await.unwrap();
+
+    // An immutable field is set once, when the instance is created
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let created = Project::upsert_patch(
+        &Uuid::new_v4(),
+        ProjectPatch {
+            tenant: Some("initech".to_string()),
+            name: Some("Printers".to_string()),
+        },
+        &mut txn,
+    )
+    .await
+    .unwrap();
+    assert_eq!(created.tenant, "initech");
+    txn.commit().await.unwrap();
+}
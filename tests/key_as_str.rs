@@ -0,0 +1,90 @@
+use ergokv::{LocalCluster, Store};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use tempfile::TempDir;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct Sku(u32);
+
+impl fmt::Display for Sku {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SKU-{}", self.0)
+    }
+}
+
+impl FromStr for Sku {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.strip_prefix("SKU-")
+            .and_then(|n| n.parse().ok())
+            .map(Sku)
+            .ok_or_else(|| format!("Invalid SKU: {s}"))
+    }
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Product {
+    #[key(as_str)]
+    sku: Sku,
+    #[index]
+    category: String,
+}
+
+#[tokio::test]
+async fn test_key_as_str() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let products = vec![
+        Product {
+            sku: Sku(1),
+            category: "tools".to_string(),
+        },
+        Product {
+            sku: Sku(2),
+            category: "tools".to_string(),
+        },
+    ];
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for product in &products {
+        product.save(&mut txn).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+
+    // Storage keys use the Display form
+    assert!(txn
+        .get("ergokv:Product:SKU-1:category".to_string())
+        .await
+        .unwrap()
+        .is_some());
+
+    assert_eq!(
+        Product::load(&Sku(2), &mut txn).await.unwrap(),
+        products[1]
+    );
+    assert_eq!(
+        Product::by_category("tools", &mut txn).await.unwrap(),
+        products
+    );
+
+    // all() parses the keys back via FromStr
+    let mut found = Vec::new();
+    {
+        let stream = Product::all(&mut txn);
+        futures::pin_mut!(stream);
+        while let Some(product) = stream.next().await {
+            found.push(product.unwrap());
+        }
+    }
+    assert_eq!(found, products);
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,109 @@
+use ergokv::{GenerateKey, LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU32, Ordering};
+use tempfile::TempDir;
+
+/// Generates every key twice in a row, 0, 0, 1, 1, ...
+#[derive(
+    Serialize, Deserialize, Debug, PartialEq, Clone, Copy,
+)]
+struct TicketId(u32);
+
+static GENERATED: AtomicU32 = AtomicU32::new(0);
+
+impl GenerateKey for TicketId {
+    fn generate() -> Self {
+        TicketId(GENERATED.fetch_add(1, Ordering::SeqCst) / 2)
+    }
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Ticket {
+    #[key(auto)]
+    id: TicketId,
+    #[index]
+    queue: String,
+    title: String,
+}
+
+#[tokio::test]
+async fn test_generated_keys() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let first = Ticket::new_with_generated_key(
+        "support".to_string(),
+        "Printer on fire".to_string(),
+    );
+    let mut second = first.clone();
+    second.title = "Printer still on fire".to_string();
+
+    // The key `save_new` generates first is taken by then, so it moves on
+    let mut txn = client.begin_optimistic().await.unwrap();
+    first.save(&mut txn).await.unwrap();
+    second.save_new(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    assert_eq!(first.id, TicketId(0));
+    assert_eq!(second.id, TicketId(1));
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        Ticket::load(&first.id, &mut txn).await.unwrap(),
+        first
+    );
+    assert_eq!(
+        Ticket::load(&second.id, &mut txn).await.unwrap(),
+        second
+    );
+    assert_eq!(
+        Ticket::by_queue("support", &mut txn)
+            .await
+            .unwrap()
+            .len(),
+        2
+    );
+    txn.commit().await.unwrap();
+}
+
+#[cfg(feature = "uuid")]
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Account {
+    #[key(auto)]
+    id: uuid::Uuid,
+    name: String,
+}
+
+#[cfg(feature = "uuid")]
+#[tokio::test]
+async fn test_generated_uuids() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let alice =
+        Account::new_with_generated_key("alice".to_string());
+    let bob = Account::new_with_generated_key("bob".to_string());
+    assert_ne!(alice.id, bob.id);
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    alice.save(&mut txn).await.unwrap();
+    bob.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        Account::load(&alice.id, &mut txn).await.unwrap(),
+        alice
+    );
+    assert_eq!(
+        Account::load(&bob.id, &mut txn).await.unwrap(),
+        bob
+    );
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,109 @@
+use ergokv::{LocalCluster, PrefixTrie, Store};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Ticket {
+    #[key]
+    id: String,
+    #[index]
+    queue: String,
+    #[unique_index]
+    reference: String,
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(key_range_scan)]
+struct ScannedTicket {
+    #[key]
+    id: String,
+    #[index]
+    queue: String,
+    #[unique_index]
+    reference: String,
+}
+
+#[tokio::test]
+async fn test_key_range_scan() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    // Keys that sort differently as strings and as numbers, and one
+    // that looks like an index namespace
+    let ids = ["10", "9", "a:b", "index", "z"];
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for (i, id) in ids.iter().enumerate() {
+        let queue = format!("queue{}", i % 2);
+        let reference = format!("REF-{i}");
+        Ticket {
+            id: id.to_string(),
+            queue: queue.clone(),
+            reference: reference.clone(),
+        }
+        .save(&mut txn)
+        .await
+        .unwrap();
+        ScannedTicket {
+            id: id.to_string(),
+            queue,
+            reference,
+        }
+        .save(&mut txn)
+        .await
+        .unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let tickets: Vec<(String, String, String)> =
+        Ticket::all(&mut txn)
+            .map(|t| {
+                let t = t.unwrap();
+                (t.id, t.queue, t.reference)
+            })
+            .collect()
+            .await;
+    let scanned: Vec<(String, String, String)> =
+        ScannedTicket::all(&mut txn)
+            .map(|t| {
+                let t = t.unwrap();
+                (t.id, t.queue, t.reference)
+            })
+            .collect()
+            .await;
+
+    // The scan finds the same instances, in the same order, as the trie
+    assert_eq!(tickets.len(), ids.len());
+    assert_eq!(scanned, tickets);
+    assert_eq!(ScannedTicket::count(&mut txn).await.unwrap(), 5);
+
+    // Nothing of the scanned model is in the master trie
+    let trie = PrefixTrie::new("ergokv:__trie");
+    assert!(trie
+        .find_by_prefix(&mut txn, "ScannedTicket:")
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Deleted instances are no longer found
+    ScannedTicket::load(&"9".to_string(), &mut txn)
+        .await
+        .unwrap()
+        .delete(&mut txn)
+        .await
+        .unwrap();
+    assert_eq!(ScannedTicket::count(&mut txn).await.unwrap(), 4);
+    let remaining: Vec<String> = ScannedTicket::all(&mut txn)
+        .map(|t| t.unwrap().id)
+        .collect()
+        .await;
+    assert!(!remaining.contains(&"9".to_string()));
+    txn.commit().await.unwrap();
+}
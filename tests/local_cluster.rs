@@ -0,0 +1,146 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct User {
+    #[key]
+    id: Uuid,
+    #[unique_index]
+    username: String,
+}
+
+#[tokio::test]
+async fn test_multi_node_cluster() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let mut tikv_instance =
+        LocalCluster::start_cluster(tmp.path(), 3).unwrap();
+    assert_eq!(tikv_instance.num_tikv(), 3);
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let user = User {
+        id: Uuid::new_v4(),
+        username: "replicated".to_string(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    user.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        User::load(&user.id, &mut txn).await.unwrap(),
+        user
+    );
+    txn.commit().await.unwrap();
+
+    // The remaining two nodes still form a majority
+    tikv_instance.stop_tikv(2).unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        User::by_username("replicated", &mut txn).await.unwrap(),
+        Some(user)
+    );
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_connect_with_owned_endpoints() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let user = User {
+        id: Uuid::new_v4(),
+        username: "configured".to_string(),
+    };
+    let mut txn = client.begin_optimistic().await.unwrap();
+    user.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let endpoints: Vec<String> =
+        vec![tikv_instance.pd_endpoint()];
+    let config = tikv_client::Config::default()
+        .with_timeout(std::time::Duration::from_secs(5));
+    let other =
+        ergokv::connect_with(endpoints, config).await.unwrap();
+
+    let mut txn = other.begin_optimistic().await.unwrap();
+    assert_eq!(
+        User::load(&user.id, &mut txn).await.unwrap(),
+        user
+    );
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_logs_are_captured() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let mut tikv_instance =
+        LocalCluster::start_cluster(tmp.path(), 2).unwrap();
+    tikv_instance.set_print_logs_on_panic(true);
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let user = User {
+        id: Uuid::new_v4(),
+        username: "logged".to_string(),
+    };
+    let mut txn = client.begin_optimistic().await.unwrap();
+    user.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    assert!(tikv_instance
+        .log_dir()
+        .join("pd.stderr.log")
+        .exists());
+
+    // Every process gets a section, and the servers log to stderr
+    let (stdout, stderr) = tikv_instance.logs();
+    for name in ["pd", "tikv", "tikv2"] {
+        let header = format!("==> {name} <==");
+        assert!(stdout.contains(&header));
+        assert!(stderr.contains(&header));
+    }
+    assert!(stderr.lines().any(|line| !line.starts_with("==>")));
+}
+
+#[tokio::test]
+async fn test_paused_tikv() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let mut tikv_instance =
+        LocalCluster::start(tmp.path()).unwrap();
+    let config = tikv_client::Config::default()
+        .with_timeout(std::time::Duration::from_secs(1));
+    let client = ergokv::connect_with(
+        vec![tikv_instance.pd_endpoint()],
+        config,
+    )
+    .await
+    .unwrap();
+
+    let user = User {
+        id: Uuid::new_v4(),
+        username: "paused".to_string(),
+    };
+    let mut txn = client.begin_optimistic().await.unwrap();
+    user.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // PD still hands out timestamps, but the only node holding the data is gone
+    tikv_instance.pause_tikv(0).unwrap();
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert!(User::load(&user.id, &mut txn).await.is_err());
+    txn.rollback().await.unwrap();
+
+    tikv_instance.resume_tikv(0).unwrap();
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        User::load(&user.id, &mut txn).await.unwrap(),
+        user
+    );
+    txn.commit().await.unwrap();
+}
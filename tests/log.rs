@@ -0,0 +1,74 @@
+use ergokv::{LocalCluster, Log};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+enum Event {
+    SignedUp { user: String },
+    Purchased { user: String, amount: u64 },
+}
+
+fn items(entries: Vec<ergokv::LogEntry<Event>>) -> Vec<Event> {
+    entries.into_iter().map(|entry| entry.item).collect()
+}
+
+#[tokio::test]
+async fn test_log() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let events: Log<Event> = Log::new("events");
+    let first: Vec<Event> = (0..3)
+        .map(|i| Event::SignedUp {
+            user: format!("user{i}"),
+        })
+        .collect();
+    let second = vec![Event::Purchased {
+        user: "user1".to_string(),
+        amount: 42,
+    }];
+
+    // Appends within one transaction keep their order
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for event in &first {
+        events.append(&mut txn, event).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let since =
+        events.append(&mut txn, &second[0]).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let all = events
+        .iter_from(&mut txn, std::time::UNIX_EPOCH)
+        .await
+        .unwrap();
+    assert!(all
+        .windows(2)
+        .all(|pair| pair[0].timestamp <= pair[1].timestamp));
+    assert_eq!(
+        items(all),
+        first.iter().chain(&second).cloned().collect::<Vec<_>>()
+    );
+
+    assert_eq!(
+        items(events.iter_from(&mut txn, since).await.unwrap()),
+        second
+    );
+    assert_eq!(
+        items(events.tail(&mut txn, 2).await.unwrap()),
+        [first[2].clone(), second[0].clone()]
+    );
+    assert_eq!(
+        events.tail(&mut txn, 10).await.unwrap().len(),
+        4
+    );
+
+    // Other logs are kept apart
+    let other: Log<Event> = Log::new("other");
+    assert!(other.tail(&mut txn, 10).await.unwrap().is_empty());
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,68 @@
+use ergokv::{LocalCluster, Store};
+use futures::{StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Account {
+    #[key]
+    id: u64,
+    #[index]
+    is_active: bool,
+    #[index(low_cardinality)]
+    plan: String,
+}
+
+#[tokio::test]
+async fn test_streaming_low_cardinality_index() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let accounts: Vec<Account> = (0..200)
+        .map(|id| Account {
+            id,
+            is_active: id % 4 != 0,
+            plan: if id % 2 == 0 { "free" } else { "pro" }
+                .to_string(),
+        })
+        .collect();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for account in &accounts {
+        account.save(&mut txn).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+
+    // A page only loads the instances it yields
+    let page: Vec<Account> =
+        Account::by_is_active(true, &mut txn)
+            .skip(20)
+            .take(10)
+            .try_collect()
+            .await
+            .unwrap();
+    assert_eq!(page.len(), 10);
+    assert!(page.iter().all(|account| account.is_active));
+
+    let active: Vec<Account> =
+        Account::by_is_active(true, &mut txn)
+            .try_collect()
+            .await
+            .unwrap();
+    assert_eq!(active.len(), 150);
+
+    // Deleted instances leave the stream
+    accounts[1].delete(&mut txn).await.unwrap();
+    let pro = Account::by_plan("pro", &mut txn)
+        .map(|account| account.unwrap())
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(pro.len(), 99);
+    assert!(!pro.contains(&accounts[1]));
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,78 @@
+use ergokv::{is_value_too_large, LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(max_value_size = 1_048_576)]
+struct Attachment {
+    #[key]
+    id: u64,
+    name: String,
+    #[store(max_value_size = 64)]
+    caption: String,
+    data: Vec<u8>,
+}
+
+#[tokio::test]
+async fn test_max_value_size() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    // A value over the model's cap is refused before anything is
+    // written
+    let oversized = Attachment {
+        id: 1,
+        name: "scan.png".to_string(),
+        caption: "A scan".to_string(),
+        data: vec![0; 2 * 1_048_576],
+    };
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let err = oversized.save(&mut txn).await.unwrap_err();
+    assert!(is_value_too_large(&err));
+    assert!(err.to_string().contains("Attachment.data"));
+    assert!(Attachment::load(&1, &mut txn).await.is_err());
+    assert!(txn
+        .get("ergokv:Attachment:1:name".to_string())
+        .await
+        .unwrap()
+        .is_none());
+    txn.commit().await.unwrap();
+
+    // A field keeps its own cap
+    let attachment = Attachment {
+        id: 2,
+        name: "notes.txt".to_string(),
+        caption: "Notes".to_string(),
+        data: vec![1; 1024],
+    };
+    let mut txn = client.begin_optimistic().await.unwrap();
+    attachment.save(&mut txn).await.unwrap();
+    let mut stored = attachment.clone();
+    let err = stored
+        .set_caption("x".repeat(100), &mut txn)
+        .await
+        .unwrap_err();
+    assert!(is_value_too_large(&err));
+    assert!(err.to_string().contains("Attachment.caption"));
+    assert_eq!(stored, attachment);
+
+    // Patches are checked before any field is written
+    let mut updated = attachment.clone();
+    let err = updated
+        .update()
+        .set_name("renamed.txt".to_string())
+        .set_data(vec![0; 2 * 1_048_576])
+        .apply(&mut txn)
+        .await
+        .unwrap_err();
+    assert!(is_value_too_large(&err));
+    assert_eq!(updated, attachment);
+    assert_eq!(
+        Attachment::load(&2, &mut txn).await.unwrap(),
+        attachment
+    );
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,161 @@
+#![cfg(feature = "metrics")]
+
+use ergokv::{LocalCluster, Store};
+use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(Store, Serialize, Deserialize, Debug, Clone)]
+struct User {
+    #[key]
+    id: Uuid,
+    #[unique_index]
+    username: String,
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, Clone, PartialEq,
+)]
+struct Member {
+    #[key]
+    id: Uuid,
+    #[index]
+    team: String,
+    name: String,
+}
+
+/// Returns the value of the counter `name` for `model`, if it was recorded.
+fn counter(
+    snapshot: &[(
+        metrics_util::CompositeKey,
+        Option<ergokv::metrics::Unit>,
+        Option<ergokv::metrics::SharedString>,
+        DebugValue,
+    )],
+    name: &str,
+    model: &str,
+) -> Option<u64> {
+    snapshot.iter().find_map(|(key, _, _, value)| {
+        let matches = key.key().name() == name
+            && key.key().labels().any(|l| {
+                l.key() == "model" && l.value() == model
+            });
+        match value {
+            DebugValue::Counter(count) if matches => {
+                Some(*count)
+            }
+            _ => None,
+        }
+    })
+}
+
+#[tokio::test]
+async fn test_save_metrics() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    recorder.install().expect("Failed to install recorder");
+
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let user = User {
+        id: Uuid::new_v4(),
+        username: "metrics".to_string(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    user.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+
+    let saves: Vec<_> = snapshot
+        .iter()
+        .filter(|(key, _, _, _)| {
+            key.key().name() == "ergokv_save_total"
+                && key.key().labels().any(|l| {
+                    l.key() == "model" && l.value() == "User"
+                })
+        })
+        .map(|(_, _, _, value)| value)
+        .collect();
+    assert_eq!(saves, vec![&DebugValue::Counter(1)]);
+
+    assert!(snapshot.iter().any(|(key, _, _, value)| {
+        key.key().name() == "ergokv_operation_duration_seconds"
+            && key.key().labels().any(|l| {
+                l.key() == "operation" && l.value() == "save"
+            })
+            && matches!(value, DebugValue::Histogram(v) if v.len() == 1)
+    }));
+}
+
+#[test]
+fn test_index_lookup_loads_in_one_batch() {
+    // A local recorder keeps these counts apart from the other tests
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let members: Vec<Member> = ["ann", "ben", "cid", "dee"]
+        .into_iter()
+        .map(|name| Member {
+            id: Uuid::new_v4(),
+            team: "core".to_string(),
+            name: name.to_string(),
+        })
+        .collect();
+
+    let (found, loaded_one_by_one) =
+        ergokv::metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                let client =
+                    tikv_instance.spawn_client().await.unwrap();
+                let mut txn =
+                    client.begin_optimistic().await.unwrap();
+                for member in &members {
+                    member.save(&mut txn).await.unwrap();
+                }
+                txn.commit().await.unwrap();
+
+                let mut txn =
+                    client.begin_optimistic().await.unwrap();
+                let found = Member::by_team("core", &mut txn)
+                    .await
+                    .unwrap();
+                let mut loaded_one_by_one = Vec::new();
+                for member in &members {
+                    loaded_one_by_one.push(
+                        Member::load(&member.id, &mut txn)
+                            .await
+                            .unwrap(),
+                    );
+                }
+                txn.commit().await.unwrap();
+                (found, loaded_one_by_one)
+            })
+        });
+
+    // Same instances in the same order as loading them one by one...
+    assert_eq!(found, members);
+    assert_eq!(found, loaded_one_by_one);
+
+    // ...but read with one batch instead of a load per instance
+    let snapshot = snapshotter.snapshot().into_vec();
+    assert_eq!(
+        counter(&snapshot, "ergokv_load_many_total", "Member"),
+        Some(1)
+    );
+    assert_eq!(
+        counter(&snapshot, "ergokv_load_total", "Member"),
+        Some(members.len() as u64)
+    );
+}
@@ -77,6 +77,13 @@ async fn test_migrations() {
 
     let mut txn = client.begin_optimistic().await.unwrap();
     user_v1.save(&mut txn).await.unwrap();
+    // Records written before any migration carry no stamp
+    assert_eq!(
+        User::schema_version_of(&user_v1.id, &mut txn)
+            .await
+            .unwrap(),
+        None
+    );
     txn.commit().await.unwrap();
 
     // Run migrations
@@ -86,6 +93,36 @@ async fn test_migrations() {
     let mut txn = client.begin_optimistic().await.unwrap();
     let migrated =
         User::load(&user_v1.id, &mut txn).await.unwrap();
+    assert_eq!(
+        User::schema_version_of(&user_v1.id, &mut txn)
+            .await
+            .unwrap()
+            .as_deref(),
+        Some("User->User")
+    );
+
+    // Saving stamps the current version, deleting removes the stamp
+    let fresh = User {
+        id: Uuid::new_v4(),
+        first_name: "Jane".into(),
+        last_name: "Roe".into(),
+        email: "jane@example.com".into(),
+    };
+    fresh.save(&mut txn).await.unwrap();
+    assert_eq!(
+        User::schema_version_of(&fresh.id, &mut txn)
+            .await
+            .unwrap()
+            .as_deref(),
+        Some("User->User")
+    );
+    fresh.delete(&mut txn).await.unwrap();
+    assert_eq!(
+        User::schema_version_of(&fresh.id, &mut txn)
+            .await
+            .unwrap(),
+        None
+    );
     txn.commit().await.unwrap();
 
     assert_eq!(migrated.id, user_v1.id);
@@ -93,3 +130,377 @@ async fn test_migrations() {
     assert_eq!(migrated.last_name, "Doe");
     assert_eq!(migrated.email, user_v1.email);
 }
+
+mod strict_v1 {
+    use super::*;
+    use ergokv::Store;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(
+        Store, Serialize, Deserialize, Debug, PartialEq,
+    )]
+    #[store(strict)]
+    pub struct Account {
+        #[key]
+        pub id: Uuid,
+        pub balance: u64,
+    }
+}
+
+mod strict_v2 {
+    use super::*;
+    use ergokv::Store;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(
+        Store, Serialize, Deserialize, Debug, PartialEq,
+    )]
+    #[store(strict)]
+    #[migrate_from(strict_v1::Account)]
+    pub struct Account {
+        #[key]
+        pub id: Uuid,
+        pub balance: u64,
+        pub currency: String,
+    }
+
+    impl AccountToAccount for Account {
+        fn from_account(
+            prev: &super::strict_v1::Account,
+        ) -> Result<Self, tikv_client::Error> {
+            Ok(Self {
+                id: prev.id,
+                balance: prev.balance,
+                currency: "EUR".into(),
+            })
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_strict_model() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let account_v1 = strict_v1::Account {
+        id: Uuid::new_v4(),
+        balance: 100,
+    };
+
+    // The new version refuses writes until its migration has run
+    let mut txn = client.begin_optimistic().await.unwrap();
+    account_v1.save(&mut txn).await.unwrap();
+    let early_v2 = strict_v2::Account {
+        id: Uuid::new_v4(),
+        balance: 0,
+        currency: "EUR".into(),
+    };
+    assert!(early_v2.save(&mut txn).await.is_err());
+    txn.commit().await.unwrap();
+
+    strict_v2::Account::ensure_migrations(&client)
+        .await
+        .unwrap();
+
+    // The old version is now rejected, the new one is accepted
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let err = account_v1.save(&mut txn).await.unwrap_err();
+    assert!(err.to_string().contains("newer version exists"));
+
+    let mut account_v2 =
+        strict_v2::Account::load(&account_v1.id, &mut txn)
+            .await
+            .unwrap();
+    assert_eq!(account_v2.currency, "EUR");
+    account_v2.set_balance(200, &mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_strict_check_once_per_transaction() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    strict_v1::Account::check_migrations(&mut txn)
+        .await
+        .unwrap();
+    assert!(strict_v2::Account::check_migrations(&mut txn)
+        .await
+        .is_err());
+
+    // Many writes in one transaction share the single migrations read
+    let mut account = strict_v1::Account {
+        id: Uuid::new_v4(),
+        balance: 0,
+    };
+    account.save(&mut txn).await.unwrap();
+    for balance in 1..=50 {
+        account.set_balance(balance, &mut txn).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    strict_v2::Account::ensure_migrations(&client)
+        .await
+        .unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert!(strict_v1::Account::check_migrations(&mut txn)
+        .await
+        .is_err());
+    strict_v2::Account::check_migrations(&mut txn)
+        .await
+        .unwrap();
+    let account =
+        strict_v2::Account::load(&account.id, &mut txn)
+            .await
+            .unwrap();
+    assert_eq!(account.balance, 50);
+    txn.commit().await.unwrap();
+}
+
+mod flip_v1 {
+    use super::*;
+    use ergokv::Store;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(
+        Store, Serialize, Deserialize, Debug, PartialEq,
+    )]
+    pub struct Member {
+        #[key]
+        pub id: Uuid,
+        #[unique_index]
+        pub email: String,
+        #[index]
+        pub team: String,
+    }
+}
+
+mod flip_v2 {
+    use super::*;
+    use ergokv::Store;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(
+        Store, Serialize, Deserialize, Debug, PartialEq,
+    )]
+    #[model_name = "Member"]
+    #[migrate_from(flip_v1::Member)]
+    pub struct MemberV2 {
+        #[key]
+        pub id: Uuid,
+        #[index]
+        pub email: String,
+        #[unique_index]
+        pub team: String,
+    }
+
+    impl MemberToMemberV2 for MemberV2 {
+        fn from_member(
+            prev: &super::flip_v1::Member,
+        ) -> Result<Self, tikv_client::Error> {
+            Ok(Self {
+                id: prev.id,
+                email: prev.email.clone(),
+                team: prev.team.to_uppercase(),
+            })
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_index_kind_flip() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let member = flip_v1::Member {
+        id: Uuid::new_v4(),
+        email: "jane@example.com".into(),
+        team: "core".into(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    member.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    flip_v2::MemberV2::ensure_migrations(&client).await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+
+    // The old layout is gone...
+    for stale in [
+        "ergokv:Member:unique_index:email:\"jane@example.com\"",
+        "ergokv:Member:index:team:\"core\"",
+    ] {
+        assert!(txn
+            .get(stale.to_string())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    // ...and both lookups work in the new one
+    let migrated = flip_v2::MemberV2::load(&member.id, &mut txn)
+        .await
+        .unwrap();
+    assert_eq!(
+        flip_v2::MemberV2::by_email(
+            "jane@example.com",
+            &mut txn
+        )
+        .await
+        .unwrap(),
+        vec![flip_v2::MemberV2 {
+            id: member.id,
+            email: "jane@example.com".into(),
+            team: "CORE".into(),
+        }]
+    );
+    assert_eq!(
+        flip_v2::MemberV2::by_team("CORE", &mut txn)
+            .await
+            .unwrap(),
+        Some(migrated)
+    );
+    txn.commit().await.unwrap();
+}
+
+mod lazy_v1 {
+    use super::*;
+    use ergokv::Store;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(
+        Store, Serialize, Deserialize, Debug, PartialEq,
+    )]
+    pub struct Contact {
+        #[key]
+        pub id: Uuid,
+        #[unique_index]
+        pub name: String,
+        #[index]
+        pub city: String,
+    }
+}
+
+mod lazy_v2 {
+    use super::*;
+    use ergokv::Store;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(
+        Store, Serialize, Deserialize, Debug, PartialEq,
+    )]
+    #[model_name = "Contact"]
+    #[migrate_from(lazy_v1::Contact)]
+    #[store(lazy_migration)]
+    pub struct ContactV2 {
+        #[key]
+        pub id: Uuid,
+        #[unique_index]
+        pub display_name: String,
+        #[index]
+        pub city: String,
+    }
+
+    impl ContactToContactV2 for ContactV2 {
+        fn from_contact(
+            prev: &super::lazy_v1::Contact,
+        ) -> Result<Self, tikv_client::Error> {
+            Ok(Self {
+                id: prev.id,
+                display_name: prev.name.to_uppercase(),
+                city: prev.city.clone(),
+            })
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_lazy_migration() {
+    use lazy_v2::ContactV2;
+
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let ada = lazy_v1::Contact {
+        id: Uuid::new_v4(),
+        name: "Ada".into(),
+        city: "London".into(),
+    };
+    let alan = lazy_v1::Contact {
+        id: Uuid::new_v4(),
+        name: "Alan".into(),
+        city: "London".into(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    ada.save(&mut txn).await.unwrap();
+    alan.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // Without ensure_migrations, the first load migrates the instance
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let migrated =
+        ContactV2::load(&ada.id, &mut txn).await.unwrap();
+    assert_eq!(
+        migrated,
+        ContactV2 {
+            id: ada.id,
+            display_name: "ADA".into(),
+            city: "London".into(),
+        }
+    );
+    txn.commit().await.unwrap();
+
+    // The upgraded instance was written back, in the new layout
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        ContactV2::schema_version_of(&ada.id, &mut txn)
+            .await
+            .unwrap()
+            .as_deref(),
+        Some("Contact->ContactV2")
+    );
+    assert_eq!(
+        ContactV2::by_display_name("ADA", &mut txn)
+            .await
+            .unwrap(),
+        Some(migrated)
+    );
+    assert!(txn
+        .get(
+            "ergokv:Contact:unique_index:name:\"Ada\""
+                .to_string()
+        )
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        ContactV2::schema_version_of(&alan.id, &mut txn)
+            .await
+            .unwrap(),
+        None
+    );
+
+    // Batch loads, and the index lookups built on them, migrate too
+    let in_london =
+        ContactV2::by_city("London", &mut txn).await.unwrap();
+    assert_eq!(in_london.len(), 2);
+    assert!(in_london
+        .iter()
+        .any(|contact| contact.display_name == "ALAN"));
+    assert_eq!(
+        ContactV2::schema_version_of(&alan.id, &mut txn)
+            .await
+            .unwrap()
+            .as_deref(),
+        Some("Contact->ContactV2")
+    );
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,104 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Account {
+    #[key]
+    id: u64,
+    #[index]
+    email: String,
+    #[unique_index]
+    handle: String,
+}
+
+/// The next schema version, in a keyspace of its own
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(model_name = "Account_v2")]
+struct AccountV2 {
+    #[key]
+    id: u64,
+    #[index]
+    email: String,
+    #[unique_index]
+    handle: String,
+}
+
+/// A renamed struct reading the instances of `Account`
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[model_name = "Account"]
+struct RenamedAccount {
+    #[key]
+    id: u64,
+    #[index]
+    email: String,
+    #[unique_index]
+    handle: String,
+}
+
+#[tokio::test]
+async fn test_versioned_model_name() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let v1 = Account {
+        id: 1,
+        email: "old@example.com".to_string(),
+        handle: "ada".to_string(),
+    };
+    let v2 = AccountV2 {
+        id: 1,
+        email: "new@example.com".to_string(),
+        handle: "ada".to_string(),
+    };
+
+    // The same key and unique value are free in each keyspace
+    let mut txn = client.begin_optimistic().await.unwrap();
+    v1.save(&mut txn).await.unwrap();
+    v2.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(Account::load(&1, &mut txn).await.unwrap(), v1);
+    assert_eq!(AccountV2::load(&1, &mut txn).await.unwrap(), v2);
+    assert!(txn
+        .get("ergokv:Account_v2:1:email".to_string())
+        .await
+        .unwrap()
+        .is_some());
+
+    // Indexes and the trie are kept apart too
+    assert!(AccountV2::by_email("old@example.com", &mut txn)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        AccountV2::by_handle("ada", &mut txn).await.unwrap(),
+        Some(v2.clone())
+    );
+    assert_eq!(Account::count(&mut txn).await.unwrap(), 1);
+    assert_eq!(AccountV2::count(&mut txn).await.unwrap(), 1);
+
+    // `#[model_name]` reads under the given name as well
+    assert_eq!(
+        RenamedAccount::load(&1, &mut txn).await.unwrap().email,
+        v1.email
+    );
+
+    // Deleting the versioned instance leaves the other one alone
+    v2.delete(&mut txn).await.unwrap();
+    assert_eq!(AccountV2::count(&mut txn).await.unwrap(), 0);
+    assert_eq!(Account::load(&1, &mut txn).await.unwrap(), v1);
+    assert_eq!(
+        Account::by_handle("ada", &mut txn).await.unwrap(),
+        Some(v1)
+    );
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,77 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Session {
+    #[key]
+    id: Uuid,
+    #[index]
+    user: String,
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(no_trie)]
+struct Token {
+    #[key]
+    id: Uuid,
+    #[index]
+    user: String,
+}
+
+async fn key_count(
+    client: &tikv_client::TransactionClient,
+) -> usize {
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let count =
+        txn.scan_keys(.., u32::MAX).await.unwrap().count();
+    txn.rollback().await.unwrap();
+    count
+}
+
+#[tokio::test]
+async fn test_no_trie_writes_less() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let session = Session {
+        id: Uuid::new_v4(),
+        user: "ann".into(),
+    };
+    let token = Token {
+        id: session.id,
+        user: "ann".into(),
+    };
+
+    let before = key_count(&client).await;
+    session.save_auto(&client).await.unwrap();
+    let session_keys = key_count(&client).await - before;
+
+    let before = key_count(&client).await;
+    token.save_auto(&client).await.unwrap();
+    let token_keys = key_count(&client).await - before;
+
+    // Two fields and one index entry, and nothing in the trie
+    assert_eq!(token_keys, 3);
+    assert!(token_keys < session_keys);
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        Token::load(&token.id, &mut txn).await.unwrap(),
+        token
+    );
+    assert_eq!(
+        Token::by_user("ann", &mut txn).await.unwrap(),
+        vec![token.clone()]
+    );
+    token.delete(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    assert_eq!(key_count(&client).await, before);
+}
@@ -0,0 +1,176 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(on_conflict = "overwrite")]
+struct Setting {
+    #[key]
+    id: Uuid,
+    value: String,
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(on_conflict = "overwrite")]
+struct Profile {
+    #[key]
+    id: u64,
+    #[index]
+    team: String,
+    bio: String,
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(on_conflict = "error")]
+struct Ledger {
+    #[key]
+    id: Uuid,
+    #[unique_index]
+    reference: String,
+    amount: i64,
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(on_conflict = "skip")]
+struct Seed {
+    #[key]
+    id: Uuid,
+    #[index]
+    kind: String,
+}
+
+#[tokio::test]
+async fn test_overwrite() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut setting = Setting {
+        id: Uuid::new_v4(),
+        value: "old".into(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    setting.save(&mut txn).await.unwrap();
+    setting.value = "new".into();
+    setting.save(&mut txn).await.unwrap();
+    assert_eq!(
+        Setting::load(&setting.id, &mut txn).await.unwrap(),
+        setting
+    );
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_overwrite_corrupt() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut profile = Profile {
+        id: 1,
+        team: "red".into(),
+        bio: "old".into(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    profile.save(&mut txn).await.unwrap();
+
+    // An instance that no longer loads can still be overwritten
+    txn.put("ergokv:Profile:1:bio".to_string(), vec![0xff])
+        .await
+        .unwrap();
+    assert!(Profile::load(&1, &mut txn).await.is_err());
+    profile.bio = "new".into();
+    profile.save(&mut txn).await.unwrap();
+    assert_eq!(
+        Profile::load(&1, &mut txn).await.unwrap(),
+        profile
+    );
+
+    // So can one whose indexed value is corrupt, leaving its old
+    // index entry behind
+    txn.put("ergokv:Profile:1:team".to_string(), vec![0xff])
+        .await
+        .unwrap();
+    profile.team = "blue".into();
+    profile.save(&mut txn).await.unwrap();
+    assert_eq!(
+        Profile::by_team("blue", &mut txn).await.unwrap(),
+        vec![profile.clone()]
+    );
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_error() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let entry = Ledger {
+        id: Uuid::new_v4(),
+        reference: "inv-1".into(),
+        amount: 100,
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    entry.save(&mut txn).await.unwrap();
+
+    let conflicting = Ledger {
+        reference: "inv-2".into(),
+        amount: -100,
+        ..entry.clone()
+    };
+    let err = conflicting.save(&mut txn).await.unwrap_err();
+    assert!(err.to_string().contains("already exists"));
+
+    // Neither the fields nor the indexes were touched
+    assert_eq!(
+        Ledger::load(&entry.id, &mut txn).await.unwrap(),
+        entry
+    );
+    assert!(!Ledger::by_reference_exists("inv-2", &mut txn)
+        .await
+        .unwrap());
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_skip() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let seed = Seed {
+        id: Uuid::new_v4(),
+        kind: "default".into(),
+    };
+
+    assert!(seed.save_auto(&client).await.unwrap());
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let replacement = Seed {
+        kind: "custom".into(),
+        ..seed.clone()
+    };
+    assert!(!replacement.save(&mut txn).await.unwrap());
+    assert_eq!(
+        Seed::load(&seed.id, &mut txn).await.unwrap(),
+        seed
+    );
+    assert!(Seed::by_kind("custom", &mut txn)
+        .await
+        .unwrap()
+        .is_empty());
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,100 @@
+use ergokv::{LocalCluster, OutboxOperation, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(outbox)]
+struct Order {
+    #[key]
+    id: Uuid,
+    #[index]
+    customer: String,
+    total: u64,
+}
+
+#[tokio::test]
+async fn test_outbox() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let order = Order {
+        id: Uuid::new_v4(),
+        customer: "alice".to_string(),
+        total: 42,
+    };
+
+    // Events of a rolled back transaction are rolled back with it
+    let mut txn = client.begin_optimistic().await.unwrap();
+    order.save(&mut txn).await.unwrap();
+    txn.rollback().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert!(Order::poll_outbox(&mut txn, 0)
+        .await
+        .unwrap()
+        .is_empty());
+    txn.commit().await.unwrap();
+
+    // A save is not visible to others before its commit, and then
+    // produces exactly one event
+    let mut txn = client.begin_optimistic().await.unwrap();
+    order.save(&mut txn).await.unwrap();
+
+    let mut other = client.begin_optimistic().await.unwrap();
+    assert!(Order::poll_outbox(&mut other, 0)
+        .await
+        .unwrap()
+        .is_empty());
+    other.rollback().await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let events = Order::poll_outbox(&mut txn, 0).await.unwrap();
+    txn.commit().await.unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].operation, OutboxOperation::Save);
+    assert_eq!(
+        events[0].key,
+        serde_json::to_string(&order.id).unwrap()
+    );
+    let saved = Order::from_backup_json(
+        events[0].value.as_deref().unwrap(),
+    )
+    .unwrap();
+    assert_eq!(saved, order);
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    order.delete(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let events = Order::poll_outbox(&mut txn, 0).await.unwrap();
+    assert_eq!(
+        events.iter().map(|e| e.seq).collect::<Vec<_>>(),
+        [1, 2]
+    );
+    assert_eq!(events[1].operation, OutboxOperation::Delete);
+    assert_eq!(events[1].value, None);
+
+    // Polling resumes after a given event
+    let after = Order::poll_outbox(&mut txn, 1).await.unwrap();
+    assert_eq!(after, events[1..]);
+
+    // Acknowledged events are removed, and their numbers not reused
+    assert_eq!(Order::ack_outbox(&mut txn, 1).await.unwrap(), 1);
+    assert_eq!(
+        Order::poll_outbox(&mut txn, 0).await.unwrap(),
+        events[1..]
+    );
+    order.save(&mut txn).await.unwrap();
+    let events = Order::poll_outbox(&mut txn, 0).await.unwrap();
+    assert_eq!(
+        events.iter().map(|e| e.seq).collect::<Vec<_>>(),
+        [2, 3]
+    );
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,70 @@
+use ergokv::{LocalCluster, Store};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(partition_by = "day")]
+struct LogLine {
+    #[key]
+    id: u64,
+    day: String,
+    message: String,
+}
+
+fn line(id: u64, day: &str) -> LogLine {
+    LogLine {
+        id,
+        day: day.to_string(),
+        message: format!("line {id}"),
+    }
+}
+
+async fn partition(
+    day: &str,
+    txn: &mut tikv_client::Transaction,
+) -> Vec<LogLine> {
+    LogLine::all_in_partition(day.to_string(), txn)
+        .map(|line| line.unwrap())
+        .collect()
+        .await
+}
+
+#[tokio::test]
+async fn test_partitions() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let monday = [line(1, "2024-01-01"), line(3, "2024-01-01")];
+    let tuesday = [line(2, "2024-01-02")];
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for line in monday.iter().chain(&tuesday) {
+        line.save(&mut txn).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    // Each partition yields only its own instances, while `all` still sees every one
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(partition("2024-01-01", &mut txn).await, monday);
+    assert_eq!(partition("2024-01-02", &mut txn).await, tuesday);
+    assert!(partition("2024-01-03", &mut txn).await.is_empty());
+    assert_eq!(LogLine::count(&mut txn).await.unwrap(), 3);
+
+    // Instances cannot move to another partition
+    let mut moved = monday[0].clone();
+    moved.day = "2024-01-02".to_string();
+    assert!(moved.save(&mut txn).await.is_err());
+
+    // Deleting an instance takes it out of its partition
+    monday[0].delete(&mut txn).await.unwrap();
+    assert_eq!(
+        partition("2024-01-01", &mut txn).await,
+        [monday[1].clone()]
+    );
+    assert_eq!(partition("2024-01-02", &mut txn).await, tuesday);
+    txn.commit().await.unwrap();
+}
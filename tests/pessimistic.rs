@@ -0,0 +1,70 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, Clone, PartialEq,
+)]
+struct Counter {
+    #[key]
+    name: String,
+    #[index]
+    hits: u64,
+}
+
+async fn increment(
+    client: &tikv_client::TransactionClient,
+    times: u64,
+) {
+    for _ in 0..times {
+        let mut txn = client.begin_pessimistic().await.unwrap();
+        let mut counter =
+            Counter::load(&"page".to_string(), &mut txn)
+                .await
+                .unwrap();
+        counter
+            .lock_and_set_hits(|hits| hits + 1, &mut txn)
+            .await
+            .unwrap();
+
+        // Let the other task run into the lock before committing
+        tokio::task::yield_now().await;
+        txn.commit().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_lock_and_set() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    Counter {
+        name: "page".into(),
+        hits: 0,
+    }
+    .save(&mut txn)
+    .await
+    .unwrap();
+    txn.commit().await.unwrap();
+
+    // Both tasks commit every increment, none of them is lost
+    tokio::join!(increment(&client, 10), increment(&client, 10));
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let counter = Counter::load(&"page".to_string(), &mut txn)
+        .await
+        .unwrap();
+    assert_eq!(counter.hits, 20);
+
+    // The index followed every update
+    assert_eq!(
+        Counter::by_hits(20u64, &mut txn).await.unwrap(),
+        vec![counter]
+    );
+    assert!(!Counter::by_hits_exists(19u64, &mut txn)
+        .await
+        .unwrap());
+    txn.commit().await.unwrap();
+}
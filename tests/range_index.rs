@@ -0,0 +1,113 @@
+#![cfg(feature = "chrono")]
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Event {
+    #[key]
+    id: Uuid,
+    name: String,
+    #[index(range)]
+    created_at: DateTime<Utc>,
+}
+
+#[tokio::test]
+async fn test_range_index_across_epoch() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let epoch = Utc.timestamp_opt(0, 0).unwrap();
+    let offsets = [
+        Duration::days(-365),
+        Duration::seconds(-1),
+        Duration::milliseconds(-1),
+        Duration::zero(),
+        Duration::milliseconds(1),
+        Duration::seconds(1),
+        Duration::days(365),
+    ];
+
+    let events: Vec<Event> = offsets
+        .iter()
+        .enumerate()
+        .map(|(i, offset)| Event {
+            id: Uuid::new_v4(),
+            name: format!("event{i}"),
+            created_at: epoch + *offset,
+        })
+        .collect();
+
+    // Save in reverse to make sure ordering comes from the index
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for event in events.iter().rev() {
+        event.save(&mut txn).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+
+    // Full range, ordered by timestamp
+    let all = Event::by_created_at_range(
+        epoch - Duration::days(1000),
+        epoch + Duration::days(1000),
+        &mut txn,
+    )
+    .await
+    .unwrap();
+    assert_eq!(all, events);
+
+    // Range spanning the epoch, end is exclusive
+    let around_epoch = Event::by_created_at_range(
+        epoch - Duration::seconds(1),
+        epoch + Duration::seconds(1),
+        &mut txn,
+    )
+    .await
+    .unwrap();
+    assert_eq!(around_epoch, events[1..5]);
+
+    // Pre-epoch only
+    let before_epoch = Event::by_created_at_range(
+        epoch - Duration::days(1000),
+        epoch,
+        &mut txn,
+    )
+    .await
+    .unwrap();
+    assert_eq!(before_epoch, events[..3]);
+
+    // Moving an event updates the index
+    let mut moved = events[0].clone();
+    moved
+        .set_created_at(epoch + Duration::days(730), &mut txn)
+        .await
+        .unwrap();
+    let before_epoch = Event::by_created_at_range(
+        epoch - Duration::days(1000),
+        epoch,
+        &mut txn,
+    )
+    .await
+    .unwrap();
+    assert_eq!(before_epoch, events[1..3]);
+
+    // Deleting removes the index entry
+    events[6].delete(&mut txn).await.unwrap();
+    let after_epoch = Event::by_created_at_range(
+        epoch + Duration::days(1),
+        epoch + Duration::days(1000),
+        &mut txn,
+    )
+    .await
+    .unwrap();
+    assert_eq!(after_epoch, vec![moved]);
+
+    txn.commit().await.unwrap();
+}
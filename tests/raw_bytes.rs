@@ -0,0 +1,68 @@
+use bytes::Bytes;
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Attachment {
+    #[key]
+    id: Uuid,
+    name: String,
+    #[store(raw_bytes)]
+    data: Vec<u8>,
+    #[store(raw_bytes)]
+    thumbnail: Bytes,
+}
+
+#[tokio::test]
+async fn test_raw_bytes() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut attachment = Attachment {
+        id: Uuid::new_v4(),
+        name: "logo.png".into(),
+        data: (0..=255).collect(),
+        thumbnail: Bytes::from_static(b"\x89PNG\r\n"),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    attachment.save(&mut txn).await.unwrap();
+
+    let id = attachment.id;
+    let field_key = |field: &str| {
+        format!("ergokv:Attachment:\"{id}\":{field}")
+    };
+    assert_eq!(
+        txn.get(field_key("data")).await.unwrap().unwrap(),
+        attachment.data
+    );
+    assert_eq!(
+        txn.get(field_key("thumbnail")).await.unwrap().unwrap(),
+        attachment.thumbnail.to_vec()
+    );
+    assert_eq!(
+        Attachment::load(&attachment.id, &mut txn)
+            .await
+            .unwrap(),
+        attachment
+    );
+
+    // Setters and empty values take the same path
+    attachment.set_data(Vec::new(), &mut txn).await.unwrap();
+    assert_eq!(
+        txn.get(field_key("data")).await.unwrap().unwrap(),
+        Vec::<u8>::new()
+    );
+    assert_eq!(
+        Attachment::load(&attachment.id, &mut txn)
+            .await
+            .unwrap(),
+        attachment
+    );
+    txn.commit().await.unwrap();
+}
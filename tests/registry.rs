@@ -0,0 +1,141 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, Clone, PartialEq,
+)]
+struct Author {
+    #[key]
+    id: Uuid,
+    name: String,
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, Clone, PartialEq,
+)]
+struct Book {
+    #[key]
+    id: Uuid,
+    #[index]
+    author: Uuid,
+    title: String,
+}
+
+// Without the trie there is no backup, so the model is not registered
+#[derive(
+    Store, Serialize, Deserialize, Debug, Clone, PartialEq,
+)]
+#[store(no_trie)]
+struct Session {
+    #[key]
+    id: Uuid,
+}
+
+mod v1 {
+    use super::*;
+
+    #[derive(
+        Store, Serialize, Deserialize, Debug, Clone, PartialEq,
+    )]
+    #[model_name = "Shelf"]
+    pub struct Shelf {
+        #[key]
+        pub id: Uuid,
+    }
+}
+
+mod v2 {
+    use super::*;
+
+    #[derive(
+        Store, Serialize, Deserialize, Debug, Clone, PartialEq,
+    )]
+    #[model_name = "Shelf"]
+    #[migrate_from(v1::Shelf)]
+    pub struct Shelf {
+        #[key]
+        pub id: Uuid,
+        pub label: String,
+    }
+
+    impl ShelfToShelf for Shelf {
+        fn from_shelf(
+            prev: &v1::Shelf,
+        ) -> Result<Self, tikv_client::Error> {
+            Ok(Self {
+                id: prev.id,
+                label: String::new(),
+            })
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_backup_all() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    // Only the current version of a migrated model is registered
+    let names: Vec<&str> = ergokv::registered_models()
+        .unwrap()
+        .into_iter()
+        .map(|model| model.name())
+        .collect();
+    assert_eq!(names, ["Author", "Book", "Shelf"]);
+
+    let author = Author {
+        id: Uuid::new_v4(),
+        name: "Ursula".into(),
+    };
+    let books = [
+        Book {
+            id: Uuid::new_v4(),
+            author: author.id,
+            title: "The Dispossessed".into(),
+        },
+        Book {
+            id: Uuid::new_v4(),
+            author: author.id,
+            title: "The Lathe of Heaven".into(),
+        },
+    ];
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    author.save(&mut txn).await.unwrap();
+    Session { id: Uuid::new_v4() }.save(&mut txn).await.unwrap();
+    for book in &books {
+        book.save(&mut txn).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    let paths =
+        ergokv::backup_all(&client, tmp.path()).await.unwrap();
+    assert_eq!(paths.len(), 3);
+
+    let file_name = |i: usize| {
+        paths[i]
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    assert!(file_name(0).starts_with("Author_"));
+    assert!(file_name(1).starts_with("Book_"));
+    assert!(file_name(2).starts_with("Shelf_"));
+
+    assert_eq!(
+        Author::from_backup_file(&paths[0]).unwrap(),
+        [author]
+    );
+    let mut backed_up =
+        Book::from_backup_file(&paths[1]).unwrap();
+    backed_up.sort_by(|a, b| a.title.cmp(&b.title));
+    assert_eq!(backed_up, books);
+    assert!(v2::Shelf::from_backup_file(&paths[2])
+        .unwrap()
+        .is_empty());
+}
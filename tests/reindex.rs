@@ -0,0 +1,309 @@
+use ergokv::{
+    IndexPointer, IntegrityReport, LocalCluster, PrefixTrie,
+    ReindexReport, Store,
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct User {
+    #[key]
+    id: Uuid,
+    #[unique_index]
+    username: String,
+    #[index]
+    department: String,
+}
+
+#[tokio::test]
+async fn test_reindex_all() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let users: Vec<User> = ["ann", "ben", "cid"]
+        .iter()
+        .map(|name| User {
+            id: Uuid::new_v4(),
+            username: name.to_string(),
+            department: "Sales".to_string(),
+        })
+        .collect();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for user in &users {
+        user.save(&mut txn).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    // Lose a record from the trie and one of its index entries,
+    // and leave entries behind for a record that no longer exists
+    let trie = PrefixTrie::new("ergokv:__trie");
+    let ghost = Uuid::new_v4();
+    let mut txn = client.begin_optimistic().await.unwrap();
+    trie.remove(&mut txn, &format!("User:\"{}\"", users[0].id))
+        .await
+        .unwrap();
+    trie.insert(&mut txn, &format!("User:\"{ghost}\""))
+        .await
+        .unwrap();
+    txn.delete(
+        "ergokv:User:unique_index:username:\"ann\"".to_string(),
+    )
+    .await
+    .unwrap();
+    let mut ghost_key = Vec::new();
+    ciborium::ser::into_writer(&ghost, &mut ghost_key).unwrap();
+    txn.put(
+        "ergokv:User:unique_index:username:\"ghost\""
+            .to_string(),
+        ghost_key.clone(),
+    )
+    .await
+    .unwrap();
+    let mut ghost_list = Vec::new();
+    ciborium::ser::into_writer(&vec![ghost], &mut ghost_list)
+        .unwrap();
+    txn.put(
+        "ergokv:User:index:department:\"Support\"".to_string(),
+        ghost_list,
+    )
+    .await
+    .unwrap();
+    txn.commit().await.unwrap();
+
+    let report = User::reindex_all(&client).await.unwrap();
+    assert_eq!(
+        report,
+        ReindexReport {
+            records: 3,
+            // Three usernames and one department list
+            index_entries: 4,
+            orphaned_index_entries: 2,
+            orphaned_trie_entries: 1,
+            missing_trie_entries: 1,
+        }
+    );
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let mut found = Vec::new();
+    {
+        let stream = User::all(&mut txn);
+        futures::pin_mut!(stream);
+        while let Some(user) = stream.next().await {
+            found.push(user.unwrap());
+        }
+    }
+    found.sort_by(|a, b| a.username.cmp(&b.username));
+    assert_eq!(found, users);
+
+    assert_eq!(
+        User::by_username("ann", &mut txn).await.unwrap(),
+        Some(users[0].clone())
+    );
+    assert!(User::by_username("ghost", &mut txn)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(!User::by_department_exists("Support", &mut txn)
+        .await
+        .unwrap());
+    assert_eq!(
+        User::by_department("Sales", &mut txn)
+            .await
+            .unwrap()
+            .len(),
+        3
+    );
+    txn.commit().await.unwrap();
+
+    // A second run finds nothing to repair
+    let report = User::reindex_all(&client).await.unwrap();
+    assert_eq!(report.orphaned_index_entries, 0);
+    assert_eq!(report.orphaned_trie_entries, 0);
+    assert_eq!(report.missing_trie_entries, 0);
+}
+
+#[tokio::test]
+async fn test_verify_integrity() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let users: Vec<User> = ["ann", "ben", "cid"]
+        .iter()
+        .map(|name| User {
+            id: Uuid::new_v4(),
+            username: name.to_string(),
+            department: "Sales".to_string(),
+        })
+        .collect();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for user in &users {
+        user.save(&mut txn).await.unwrap();
+    }
+    let report = User::verify_integrity(&mut txn).await.unwrap();
+    assert!(report.is_consistent());
+    assert_eq!(report.records, 3);
+    txn.commit().await.unwrap();
+
+    let [ann, ben, _] = [0, 1, 2].map(|i| users[i].id);
+    let trie = PrefixTrie::new("ergokv:__trie");
+    let ghost = Uuid::new_v4();
+    let mut txn = client.begin_optimistic().await.unwrap();
+
+    // A trie entry without a record, and a record without a trie entry
+    trie.insert(&mut txn, &format!("User:\"{ghost}\""))
+        .await
+        .unwrap();
+    trie.remove(&mut txn, &format!("User:\"{ann}\""))
+        .await
+        .unwrap();
+
+    // An index entry pointing nowhere, and a record missing its entry
+    let mut ghost_key = Vec::new();
+    ciborium::ser::into_writer(&ghost, &mut ghost_key).unwrap();
+    txn.put(
+        "ergokv:User:unique_index:username:\"ghost\""
+            .to_string(),
+        ghost_key,
+    )
+    .await
+    .unwrap();
+    let ann_index =
+        "ergokv:User:unique_index:username:\"ann\"".to_string();
+    txn.delete(ann_index.clone()).await.unwrap();
+
+    // A record that lost a field, so its index entries point to nothing loadable
+    txn.delete(format!("ergokv:User:\"{ben}\":department"))
+        .await
+        .unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let report = User::verify_integrity(&mut txn).await.unwrap();
+    let pointer = |index_key: &str, key: Uuid| IndexPointer {
+        index_key: index_key.to_string(),
+        key: format!("\"{key}\""),
+    };
+    assert_eq!(
+        report,
+        IntegrityReport {
+            records: 3,
+            orphaned_trie_entries: vec![format!("User:\"{ghost}\"")],
+            missing_trie_entries: vec![format!("User:\"{ann}\"")],
+            unloadable_records: vec![format!("\"{ben}\"")],
+            dangling_index_entries: vec![
+                pointer("ergokv:User:index:department:\"Sales\"", ben),
+                pointer("ergokv:User:unique_index:username:\"ben\"", ben),
+                pointer(
+                    "ergokv:User:unique_index:username:\"ghost\"",
+                    ghost
+                ),
+            ],
+            missing_index_entries: vec![pointer(&ann_index, ann)],
+        }
+    );
+    assert!(!report.is_consistent());
+
+    // Nothing was repaired
+    assert!(txn.get(ann_index).await.unwrap().is_none());
+    assert_eq!(
+        User::verify_integrity(&mut txn).await.unwrap(),
+        report
+    );
+    txn.commit().await.unwrap();
+}
+
+fn encode<T: Serialize>(value: &T) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    ciborium::ser::into_writer(value, &mut bytes).unwrap();
+    bytes
+}
+
+#[tokio::test]
+async fn test_prune_indexes() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let user = User {
+        id: Uuid::new_v4(),
+        username: "dee".to_string(),
+        department: "Sales".to_string(),
+    };
+    let mut txn = client.begin_optimistic().await.unwrap();
+    user.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // A unique entry and a list entry for a deleted record, a list
+    // mixing it with a live one, and an empty list
+    let ghost = Uuid::new_v4();
+    let mut txn = client.begin_optimistic().await.unwrap();
+    txn.put(
+        "ergokv:User:unique_index:username:\"ghost\""
+            .to_string(),
+        encode(&ghost),
+    )
+    .await
+    .unwrap();
+    txn.put(
+        "ergokv:User:index:department:\"Support\"".to_string(),
+        encode(&vec![ghost]),
+    )
+    .await
+    .unwrap();
+    txn.put(
+        "ergokv:User:index:department:\"Sales\"".to_string(),
+        encode(&vec![ghost, user.id]),
+    )
+    .await
+    .unwrap();
+    txn.put(
+        "ergokv:User:index:department:\"Legal\"".to_string(),
+        encode(&Vec::<Uuid>::new()),
+    )
+    .await
+    .unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(User::prune_indexes(&mut txn).await.unwrap(), 4);
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for index_key in [
+        "ergokv:User:unique_index:username:\"ghost\"",
+        "ergokv:User:index:department:\"Support\"",
+        "ergokv:User:index:department:\"Legal\"",
+    ] {
+        assert!(txn
+            .get(index_key.to_string())
+            .await
+            .unwrap()
+            .is_none());
+    }
+    let sales = txn
+        .get(
+            "ergokv:User:index:department:\"Sales\"".to_string(),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    let sales: Vec<Uuid> =
+        ciborium::de::from_reader(sales.as_slice()).unwrap();
+    assert_eq!(sales, [user.id]);
+
+    // Live entries are left alone
+    assert_eq!(User::prune_indexes(&mut txn).await.unwrap(), 0);
+    assert_eq!(
+        User::by_username("dee", &mut txn).await.unwrap(),
+        Some(user)
+    );
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,111 @@
+use ergokv::{LocalCluster, Store};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Invoice {
+    #[key]
+    number: u64,
+    #[index]
+    customer: String,
+    amount: u64,
+}
+
+async fn all_invoices(
+    txn: &mut tikv_client::Transaction,
+) -> Vec<Invoice> {
+    Invoice::all(txn).map(|i| i.unwrap()).collect().await
+}
+
+#[tokio::test]
+async fn test_reserve_and_complete() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let existing = Invoice {
+        number: 1,
+        customer: "acme".to_string(),
+        amount: 10,
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    existing.save(&mut txn).await.unwrap();
+    Invoice::reserve_key(&2, &mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+
+    // Stored and reserved keys cannot be reserved again
+    assert!(Invoice::reserve_key(&1, &mut txn).await.is_err());
+    assert!(Invoice::reserve_key(&2, &mut txn).await.is_err());
+
+    // The reserved key is pending, which `load` tells apart from a missing key
+    assert!(Invoice::is_pending(&2, &mut txn).await.unwrap());
+    assert!(!Invoice::is_pending(&1, &mut txn).await.unwrap());
+    let pending = Invoice::load(&2, &mut txn).await.unwrap_err();
+    assert!(pending.to_string().contains("reserved"));
+    let missing = Invoice::load(&3, &mut txn).await.unwrap_err();
+    assert!(!missing.to_string().contains("reserved"));
+
+    // `all` skips it, and it is no integrity problem
+    assert_eq!(
+        all_invoices(&mut txn).await,
+        std::slice::from_ref(&existing)
+    );
+    assert!(Invoice::verify_integrity(&mut txn)
+        .await
+        .unwrap()
+        .is_consistent());
+
+    // Only reserved keys can be completed
+    let unreserved = Invoice {
+        number: 3,
+        customer: "globex".to_string(),
+        amount: 30,
+    };
+    assert!(unreserved.complete(&mut txn).await.is_err());
+
+    let completed = Invoice {
+        number: 2,
+        customer: "initech".to_string(),
+        amount: 20,
+    };
+    completed.complete(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert!(!Invoice::is_pending(&2, &mut txn).await.unwrap());
+    assert_eq!(
+        Invoice::load(&2, &mut txn).await.unwrap(),
+        completed
+    );
+    assert_eq!(
+        Invoice::by_customer("initech", &mut txn).await.unwrap(),
+        std::slice::from_ref(&completed)
+    );
+    assert_eq!(
+        all_invoices(&mut txn).await,
+        [existing, completed.clone()]
+    );
+    assert!(completed.complete(&mut txn).await.is_err());
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_concurrent_reservations_conflict() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut first = client.begin_optimistic().await.unwrap();
+    let mut second = client.begin_optimistic().await.unwrap();
+    Invoice::reserve_key(&7, &mut first).await.unwrap();
+    Invoice::reserve_key(&7, &mut second).await.unwrap();
+
+    first.commit().await.unwrap();
+    assert!(second.commit().await.is_err());
+}
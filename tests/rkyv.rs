@@ -0,0 +1,177 @@
+#![cfg(feature = "rkyv")]
+
+use ergokv::{rkyv, ArchivedValue, LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+#[derive(
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    Debug,
+    PartialEq,
+    Clone,
+)]
+#[rkyv(crate = ergokv::rkyv)]
+struct Point {
+    x: u32,
+    y: u32,
+    label: String,
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct CborScene {
+    #[key]
+    id: u64,
+    points: Vec<Point>,
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(format = "rkyv")]
+struct RkyvScene {
+    #[key]
+    id: u64,
+    points: Vec<Point>,
+}
+
+fn points(n: u32) -> Vec<Point> {
+    (0..n)
+        .map(|i| Point {
+            x: i,
+            y: i * 2,
+            label: format!("point {i}"),
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_rkyv_reads() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let points = points(50_000);
+    let cbor = CborScene {
+        id: 1,
+        points: points.clone(),
+    };
+    let rkyv = RkyvScene {
+        id: 1,
+        points: points.clone(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    cbor.save(&mut txn).await.unwrap();
+    rkyv.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        RkyvScene::load(&1, &mut txn).await.unwrap(),
+        rkyv
+    );
+
+    // The archived value is read in place
+    let archived =
+        RkyvScene::load_field_points_archived(&1, &mut txn)
+            .await
+            .unwrap();
+    assert_eq!(archived.len(), 50_000);
+    assert_eq!(archived[123].y.to_native(), 246);
+    assert_eq!(archived[123].label.as_str(), "point 123");
+    assert_eq!(archived.deserialize().unwrap(), points);
+
+    // The stored bytes are the archive itself behind its tag, read
+    // without decoding them the way the CBOR ones are
+    let cbor_bytes = txn
+        .get("ergokv:CborScene:1:points".to_string())
+        .await
+        .unwrap()
+        .unwrap();
+    let rkyv_bytes = txn
+        .get("ergokv:RkyvScene:1:points".to_string())
+        .await
+        .unwrap()
+        .unwrap();
+
+    let decoded: Vec<Point> =
+        ergokv::ciborium::de::from_reader(cbor_bytes.as_slice())
+            .unwrap();
+    let (format, archive) =
+        ergokv::split_format_tag(&rkyv_bytes);
+    assert_eq!(format, ergokv::StoredFormat::Rkyv);
+    let archived =
+        ArchivedValue::<Vec<Point>>::new(archive).unwrap();
+    assert_eq!(
+        archived.iter().map(|p| p.x.to_native()).sum::<u32>(),
+        decoded.iter().map(|p| p.x).sum::<u32>()
+    );
+    txn.commit().await.unwrap();
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[model_name = "Scene"]
+struct Scene {
+    #[key]
+    id: u64,
+    points: Vec<Point>,
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[model_name = "Scene"]
+struct ArchivedScene {
+    #[key]
+    id: u64,
+    #[store(format = "rkyv")]
+    points: Vec<Point>,
+}
+
+#[tokio::test]
+async fn test_cbor_values() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let scene = Scene {
+        id: 1,
+        points: points(3),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    scene.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // Values stored as CBOR still load, but cannot be read in place
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        ArchivedScene::load(&1, &mut txn).await.unwrap().points,
+        scene.points
+    );
+    assert!(ArchivedScene::load_field_points_archived(
+        &1, &mut txn
+    )
+    .await
+    .is_err());
+
+    // Until they are written again in rkyv
+    assert_eq!(
+        ArchivedScene::rewrite_all(&mut txn).await.unwrap(),
+        1
+    );
+    let archived =
+        ArchivedScene::load_field_points_archived(&1, &mut txn)
+            .await
+            .unwrap();
+    assert_eq!(archived.deserialize().unwrap(), scene.points);
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,136 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, Clone, PartialEq,
+)]
+struct Counter {
+    #[key]
+    name: String,
+    value: u64,
+}
+
+#[tokio::test]
+async fn test_run_txn_retries_conflicts() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    Counter {
+        name: "hits".into(),
+        value: 0,
+    }
+    .save(&mut txn)
+    .await
+    .unwrap();
+    txn.commit().await.unwrap();
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let value = ergokv::run_txn(&client, 3, |txn| {
+        let (client, calls) = (client.clone(), calls.clone());
+        Box::pin(async move {
+            let attempt = calls.fetch_add(1, Ordering::SeqCst);
+            let mut counter =
+                Counter::load(&"hits".to_string(), txn).await?;
+
+            // Another writer sneaks in during the first attempt only
+            if attempt == 0 {
+                let mut other =
+                    client.begin_optimistic().await?;
+                let mut theirs = Counter::load(
+                    &"hits".to_string(),
+                    &mut other,
+                )
+                .await?;
+                theirs.set_value(100, &mut other).await?;
+                other.commit().await?;
+            }
+
+            counter.set_value(counter.value + 1, txn).await?;
+            Ok(counter.value)
+        })
+    })
+    .await
+    .unwrap();
+
+    // The second attempt saw the other write and built on top of it
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert_eq!(value, 101);
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        Counter::load(&"hits".to_string(), &mut txn)
+            .await
+            .unwrap()
+            .value,
+        101
+    );
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_run_txn_fatal_errors() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let err = ergokv::run_txn(&client, 3, |txn| {
+        let calls = calls.clone();
+        Box::pin(async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Counter {
+                name: "lost".into(),
+                value: 1,
+            }
+            .save(txn)
+            .await?;
+            Err::<(), _>(tikv_client::Error::StringError(
+                "boom".into(),
+            ))
+        })
+    })
+    .await
+    .unwrap_err();
+
+    // Fatal errors are not retried and the work is rolled back
+    assert_eq!(err.to_string(), "boom");
+    assert!(!ergokv::is_retryable(&err));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert!(Counter::load(&"lost".to_string(), &mut txn)
+        .await
+        .is_err());
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_run_txn_gives_up() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let err = ergokv::run_txn(&client, 2, |txn| {
+        let (client, calls) = (client.clone(), calls.clone());
+        Box::pin(async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            txn.put("contended".to_string(), vec![1]).await?;
+
+            let mut other = client.begin_optimistic().await?;
+            other.put("contended".to_string(), vec![2]).await?;
+            other.commit().await?;
+            Ok(())
+        })
+    })
+    .await
+    .unwrap_err();
+
+    assert!(ergokv::is_retryable(&err));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
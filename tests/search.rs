@@ -0,0 +1,89 @@
+use ergokv::{LocalCluster, Store};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Account {
+    #[key]
+    id: Uuid,
+    #[index(search)]
+    username: String,
+}
+
+async fn search(
+    prefix: &str,
+    txn: &mut tikv_client::Transaction,
+) -> Vec<String> {
+    Account::search_username(prefix, txn)
+        .map_ok(|account| account.username)
+        .try_collect()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_search_by_prefix() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut accounts = Vec::new();
+    for username in ["alice", "alfred", "albert", "bob", "alice"]
+    {
+        accounts.push(Account {
+            id: Uuid::new_v4(),
+            username: username.to_string(),
+        });
+    }
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for account in &accounts {
+        account.save(&mut txn).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        search("al", &mut txn).await,
+        vec!["albert", "alfred", "alice", "alice"]
+    );
+    assert_eq!(
+        search("ali", &mut txn).await,
+        vec!["alice", "alice"]
+    );
+    assert_eq!(search("bob", &mut txn).await, vec!["bob"]);
+    assert!(search("carol", &mut txn).await.is_empty());
+
+    // A value stays searchable while any instance still has it
+    accounts[0].delete(&mut txn).await.unwrap();
+    assert_eq!(search("ali", &mut txn).await, vec!["alice"]);
+    Account::delete_many(&[accounts[4].id], &mut txn)
+        .await
+        .unwrap();
+    assert!(search("ali", &mut txn).await.is_empty());
+
+    // Renaming moves the instance to its new value
+    accounts[3]
+        .set_username("alfonso".to_string(), &mut txn)
+        .await
+        .unwrap();
+    assert!(search("b", &mut txn).await.is_empty());
+    assert_eq!(
+        search("alf", &mut txn).await,
+        vec!["alfonso", "alfred"]
+    );
+    txn.commit().await.unwrap();
+
+    // Rebuilding the indexes keeps the search trie intact
+    Account::reindex_all(&client).await.unwrap();
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        search("", &mut txn).await,
+        vec!["albert", "alfonso", "alfred"]
+    );
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,121 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+/// Stores a `Vec<u8>` as a hex string.
+mod hex {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        bytes: &[u8],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let hex: String =
+            bytes.iter().map(|b| format!("{b:02x}")).collect();
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<_, _>>()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Attachment {
+    #[key]
+    id: Uuid,
+    #[serde(rename = "fileName")]
+    name: String,
+    #[serde(with = "hex")]
+    checksum: Vec<u8>,
+}
+
+#[tokio::test]
+async fn test_serde_with_field() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let attachment = Attachment {
+        id: Uuid::new_v4(),
+        name: "report.pdf".to_string(),
+        checksum: vec![0xde, 0xad, 0xbe, 0xef],
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    attachment.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // Storage goes through the field's serde(with)
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let raw = txn
+        .get(format!(
+            "ergokv:Attachment:{}:checksum",
+            serde_json::to_string(&attachment.id).unwrap()
+        ))
+        .await
+        .unwrap()
+        .unwrap();
+    let stored: String =
+        ergokv::ciborium::de::from_reader(raw.as_slice())
+            .unwrap();
+    assert_eq!(stored, "deadbeef");
+    assert_eq!(
+        Attachment::load(&attachment.id, &mut txn)
+            .await
+            .unwrap(),
+        attachment
+    );
+
+    // ...and so does the backup format
+    let json = attachment.to_backup_json().unwrap();
+    assert!(json.contains(r#""checksum":"deadbeef""#));
+    assert!(json.contains(r#""fileName":"report.pdf""#));
+    assert_eq!(
+        Attachment::from_backup_json(&json).unwrap(),
+        attachment
+    );
+
+    let backup_path =
+        Attachment::backup(&mut txn, tmp.path()).await.unwrap();
+    attachment.delete(&mut txn).await.unwrap();
+    Attachment::restore(&mut txn, backup_path).await.unwrap();
+    assert_eq!(
+        Attachment::load(&attachment.id, &mut txn)
+            .await
+            .unwrap(),
+        attachment
+    );
+    txn.commit().await.unwrap();
+}
+
+#[test]
+fn test_as_map() {
+    let attachment = Attachment {
+        id: Uuid::new_v4(),
+        name: "report.pdf".to_string(),
+        checksum: vec![0xca, 0xfe],
+    };
+
+    let map = attachment.as_map();
+    let mut keys: Vec<_> =
+        map.keys().map(String::as_str).collect();
+    keys.sort();
+    assert_eq!(keys, ["checksum", "fileName", "id"]);
+    assert_eq!(map["fileName"], serde_json::json!("report.pdf"));
+    assert_eq!(map["checksum"], serde_json::json!("cafe"));
+    assert_eq!(
+        map["id"],
+        serde_json::json!(attachment.id.to_string())
+    );
+}
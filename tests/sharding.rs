@@ -0,0 +1,89 @@
+use ergokv::{LocalCluster, Store};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(key_prefix_shards = 4)]
+struct Reading {
+    #[key]
+    id: Uuid,
+    #[index]
+    sensor: String,
+    value: i64,
+}
+
+#[tokio::test]
+async fn test_sharded_keys() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut readings: Vec<Reading> = (0..32)
+        .map(|i| Reading {
+            id: Uuid::new_v4(),
+            sensor: format!("sensor{}", i % 2),
+            value: i,
+        })
+        .collect();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for reading in &readings {
+        reading.save(&mut txn).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+
+    // Records land in more than one shard, and only under shard prefixes
+    let mut used_shards = 0;
+    let mut sharded_records = 0;
+    for shard in 0..4 {
+        let prefix = format!("ergokv:Reading:shard{shard}:");
+        let end = format!("ergokv:Reading:shard{shard};");
+        let records = txn
+            .scan(prefix..end, u32::MAX)
+            .await
+            .unwrap()
+            .filter(|pair| {
+                let key: &[u8] = pair.key().into();
+                key.ends_with(b":id")
+            })
+            .count();
+        if records > 0 {
+            used_shards += 1;
+        }
+        sharded_records += records;
+    }
+    assert!(used_shards > 1);
+    assert_eq!(sharded_records, 32);
+
+    let mut found = Vec::new();
+    {
+        let stream = Reading::all(&mut txn);
+        futures::pin_mut!(stream);
+        while let Some(reading) = stream.next().await {
+            found.push(reading.unwrap());
+        }
+    }
+    found.sort_by_key(|r| r.value);
+    assert_eq!(found, readings);
+    assert_eq!(Reading::count(&mut txn).await.unwrap(), 32);
+    assert_eq!(
+        Reading::by_sensor("sensor1", &mut txn)
+            .await
+            .unwrap()
+            .len(),
+        16
+    );
+
+    // Deleting removes the record from its shard
+    let removed = readings.remove(0);
+    removed.delete(&mut txn).await.unwrap();
+    assert_eq!(Reading::count(&mut txn).await.unwrap(), 31);
+    assert!(Reading::load(&removed.id, &mut txn).await.is_err());
+    txn.commit().await.unwrap();
+}
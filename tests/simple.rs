@@ -1,4 +1,4 @@
-use ergokv::{LocalCluster, Store};
+use ergokv::{LocalCluster, SaveOutcome, Store};
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use tempfile::TempDir;
@@ -18,6 +18,19 @@ struct User {
     department: String,
 }
 
+#[test]
+fn test_field_names() {
+    assert_eq!(
+        User::FIELD_NAMES,
+        ["id", "username", "email", "department"]
+    );
+    assert_eq!(
+        User::INDEX_FIELDS,
+        ["username", "email", "department"]
+    );
+    assert_eq!(User::UNIQUE_INDEX_FIELDS, ["username"]);
+}
+
 #[tokio::test]
 async fn test_user_store() {
     let tmp = TempDir::new().expect("Failed to create temp dir");
@@ -164,3 +177,646 @@ async fn test_user_all() {
     assert_eq!(users, found_users);
     txn.commit().await.unwrap();
 }
+
+#[tokio::test]
+async fn test_all_dropped_early() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let users: Vec<User> = ["alice", "bob", "charlie"]
+        .iter()
+        .map(|name| User {
+            id: Uuid::new_v4(),
+            username: name.to_string(),
+            email: format!("{name}@example.com"),
+            department: "Engineering".to_string(),
+        })
+        .collect();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for user in &users {
+        user.save(&mut txn).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+
+    // Dropped after its first instance
+    let first = {
+        let mut stream = Box::pin(User::all(&mut txn));
+        stream.next().await.unwrap().unwrap()
+    };
+    assert!(users.contains(&first));
+
+    // Dropped after a single poll, in the middle of its reads
+    {
+        let mut stream = Box::pin(User::all(&mut txn));
+        let _ = futures::FutureExt::now_or_never(stream.next());
+    }
+
+    // The same transaction keeps working, reading and writing
+    let dave = User {
+        id: Uuid::new_v4(),
+        username: "dave".to_string(),
+        email: "dave@example.com".to_string(),
+        department: "Marketing".to_string(),
+    };
+    dave.save(&mut txn).await.unwrap();
+    assert_eq!(User::count(&mut txn).await.unwrap(), 4);
+    let all: Vec<User> = User::all(&mut txn)
+        .map(|user| user.unwrap())
+        .collect()
+        .await;
+    assert_eq!(all.len(), 4);
+    txn.commit().await.unwrap();
+
+    // Nothing but the new instance was written
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for user in users.iter().chain([&dave]) {
+        assert_eq!(
+            &User::load(&user.id, &mut txn).await.unwrap(),
+            user
+        );
+    }
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_group_by() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let users: Vec<User> = [
+        ("alice", "Engineering"),
+        ("bob", "Engineering"),
+        ("charlie", "Marketing"),
+        ("dave", "Sales"),
+        ("erin", "Engineering"),
+    ]
+    .iter()
+    .map(|(name, department)| User {
+        id: Uuid::new_v4(),
+        username: name.to_string(),
+        email: format!("{name}@example.com"),
+        department: department.to_string(),
+    })
+    .collect();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for user in &users {
+        user.save(&mut txn).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    users[3].delete(&mut txn).await.unwrap();
+
+    let groups =
+        User::group_by_department(&mut txn).await.unwrap();
+    let mut names: Vec<(String, Vec<String>)> = groups
+        .into_iter()
+        .map(|(department, members)| {
+            let mut members: Vec<String> = members
+                .into_iter()
+                .map(|u| u.username)
+                .collect();
+            members.sort();
+            (department, members)
+        })
+        .collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            (
+                "Engineering".to_string(),
+                vec![
+                    "alice".to_string(),
+                    "bob".to_string(),
+                    "erin".to_string()
+                ]
+            ),
+            (
+                "Marketing".to_string(),
+                vec!["charlie".to_string()]
+            ),
+        ]
+    );
+
+    // Unique indexes give one instance per value
+    let by_username =
+        User::group_by_username(&mut txn).await.unwrap();
+    assert_eq!(by_username.len(), 4);
+    assert_eq!(by_username["bob"], [users[1].clone()]);
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_auto_transactions() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let user = User {
+        id: Uuid::new_v4(),
+        username: "autouser".to_string(),
+        email: "auto@example.com".to_string(),
+        department: "Operations".to_string(),
+    };
+
+    user.save_auto(&client).await.unwrap();
+    assert_eq!(
+        User::load_auto(&user.id, &client).await.unwrap(),
+        user
+    );
+
+    user.delete_auto(&client).await.unwrap();
+    assert!(User::load_auto(&user.id, &client).await.is_err());
+}
+
+#[tokio::test]
+async fn test_delete_many() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let users: Vec<User> = ["ann", "ben", "cid", "dot"]
+        .iter()
+        .map(|name| User {
+            id: Uuid::new_v4(),
+            username: name.to_string(),
+            email: format!("{name}@example.com"),
+            department: "Support".to_string(),
+        })
+        .collect();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for user in &users {
+        user.save(&mut txn).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    User::delete_many(
+        &[users[0].id, users[1].id, users[2].id],
+        &mut txn,
+    )
+    .await
+    .unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let mut remaining = Vec::new();
+    {
+        let stream = User::all(&mut txn);
+        futures::pin_mut!(stream);
+        while let Some(user) = stream.next().await {
+            remaining.push(user.unwrap());
+        }
+    }
+    assert_eq!(remaining, vec![users[3].clone()]);
+
+    assert_eq!(
+        User::by_department("Support", &mut txn).await.unwrap(),
+        vec![users[3].clone()]
+    );
+    for user in &users[..3] {
+        assert!(User::by_username(
+            user.username.clone(),
+            &mut txn
+        )
+        .await
+        .unwrap()
+        .is_none());
+        assert!(User::by_email(user.email.clone(), &mut txn)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_drain() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut users: Vec<User> = ["ann", "ben", "cid", "dot"]
+        .iter()
+        .map(|name| User {
+            id: Uuid::new_v4(),
+            username: name.to_string(),
+            email: format!("{name}@example.com"),
+            department: "Support".to_string(),
+        })
+        .collect();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for user in &users {
+        user.save(&mut txn).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    // Stopping early leaves the rest in place
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let first = {
+        let stream = User::drain(&mut txn);
+        futures::pin_mut!(stream);
+        stream.next().await.unwrap().unwrap()
+    };
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(User::count(&mut txn).await.unwrap(), 3);
+    let mut drained: Vec<User> = User::drain(&mut txn)
+        .map(|user| user.unwrap())
+        .collect()
+        .await;
+    txn.commit().await.unwrap();
+
+    drained.push(first);
+    drained.sort_by_key(|user| user.id);
+    users.sort_by_key(|user| user.id);
+    assert_eq!(drained, users);
+
+    // Nothing is left, including index entries
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(User::count(&mut txn).await.unwrap(), 0);
+    assert!(User::by_department("Support", &mut txn)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(User::by_username("ann", &mut txn)
+        .await
+        .unwrap()
+        .is_none());
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_index_exists() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let user = User {
+        id: Uuid::new_v4(),
+        username: "exists".to_string(),
+        email: "exists@example.com".to_string(),
+        department: "Research".to_string(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    user.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert!(User::by_username_exists("exists", &mut txn)
+        .await
+        .unwrap());
+    assert!(User::by_department_exists("Research", &mut txn)
+        .await
+        .unwrap());
+    assert!(!User::by_username_exists("missing", &mut txn)
+        .await
+        .unwrap());
+    assert!(!User::by_department_exists("Legal", &mut txn)
+        .await
+        .unwrap());
+
+    // Emptied index lists count as absent
+    user.delete(&mut txn).await.unwrap();
+    assert!(!User::by_username_exists("exists", &mut txn)
+        .await
+        .unwrap());
+    assert!(!User::by_department_exists("Research", &mut txn)
+        .await
+        .unwrap());
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_index_one() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let users: Vec<User> = (0..3)
+        .map(|i| User {
+            id: Uuid::new_v4(),
+            username: format!("one{i}"),
+            email: format!("one{i}@example.com"),
+            department: "Legal".to_string(),
+        })
+        .collect();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for user in &users {
+        user.save(&mut txn).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let one = User::by_department_one("Legal", &mut txn)
+        .await
+        .unwrap()
+        .expect("Expected a Legal user");
+    assert!(users.contains(&one));
+    assert_eq!(
+        User::by_department_one("Marketing", &mut txn)
+            .await
+            .unwrap(),
+        None
+    );
+
+    // Once the listed instances are gone, there is nothing to return
+    for user in &users {
+        user.delete(&mut txn).await.unwrap();
+    }
+    assert_eq!(
+        User::by_department_one("Legal", &mut txn)
+            .await
+            .unwrap(),
+        None
+    );
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_index_in() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let users: Vec<User> = [
+        ("ann", "Sales"),
+        ("ben", "Legal"),
+        ("cid", "Sales"),
+        ("dot", "Support"),
+    ]
+    .iter()
+    .map(|(name, department)| User {
+        id: Uuid::new_v4(),
+        username: name.to_string(),
+        email: format!("{name}@example.com"),
+        department: department.to_string(),
+    })
+    .collect();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for user in &users {
+        user.save(&mut txn).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+
+    // Repeated and unknown values add nothing
+    let departments = ["Sales", "Legal", "Sales", "Marketing"]
+        .map(String::from);
+    let mut found =
+        User::by_department_in(&departments, &mut txn)
+            .await
+            .unwrap();
+    assert_eq!(found.len(), 3);
+    found.sort_by(|a, b| a.username.cmp(&b.username));
+    assert_eq!(found, users[..3]);
+
+    let usernames =
+        ["dot", "ann", "dot", "eve"].map(String::from);
+    assert_eq!(
+        User::by_username_in(&usernames, &mut txn)
+            .await
+            .unwrap(),
+        [users[3].clone(), users[0].clone()]
+    );
+    assert!(User::by_department_in(&[], &mut txn)
+        .await
+        .unwrap()
+        .is_empty());
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_load_field() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let user = User {
+        id: Uuid::new_v4(),
+        username: "projected".to_string(),
+        email: "projected@example.com".to_string(),
+        department: "Finance".to_string(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    user.save(&mut txn).await.unwrap();
+    assert_eq!(
+        User::load_field_email(&user.id, &mut txn)
+            .await
+            .unwrap(),
+        user.email
+    );
+
+    // Only the email key is read, so the other fields are not needed
+    for field in ["id", "username", "department"] {
+        txn.delete(format!(
+            "ergokv:User:\"{}\":{field}",
+            user.id
+        ))
+        .await
+        .unwrap();
+    }
+    assert!(User::load(&user.id, &mut txn).await.is_err());
+    assert_eq!(
+        User::load_field_email(&user.id, &mut txn)
+            .await
+            .unwrap(),
+        user.email
+    );
+    assert!(User::load_field_username(&user.id, &mut txn)
+        .await
+        .is_err());
+    txn.rollback().await.unwrap();
+}
+
+#[derive(
+    Store,
+    Serialize,
+    Deserialize,
+    Debug,
+    PartialEq,
+    Clone,
+    Default,
+)]
+#[store(load_or_default)]
+struct Preferences {
+    #[key]
+    user: Uuid,
+    theme: String,
+    page_size: u32,
+}
+
+#[tokio::test]
+async fn test_load_or_default() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let user = Uuid::new_v4();
+    let mut txn = client.begin_optimistic().await.unwrap();
+
+    // Nothing stored yet, so a default with the right key comes back
+    let defaults = Preferences::load_or_default(&user, &mut txn)
+        .await
+        .unwrap();
+    assert_eq!(
+        defaults,
+        Preferences {
+            user,
+            ..Default::default()
+        }
+    );
+    assert!(Preferences::load(&user, &mut txn).await.is_err());
+
+    let stored = Preferences {
+        user,
+        theme: "dark".to_string(),
+        page_size: 50,
+    };
+    stored.save(&mut txn).await.unwrap();
+    assert_eq!(
+        Preferences::load_or_default(&user, &mut txn)
+            .await
+            .unwrap(),
+        stored
+    );
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_decode_error_names_model_and_field() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let user = User {
+        id: Uuid::new_v4(),
+        username: "corrupted".to_string(),
+        email: "corrupted@example.com".to_string(),
+        department: "Engineering".to_string(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    user.save(&mut txn).await.unwrap();
+
+    // Overwrite the stored email with bytes that are not valid CBOR
+    let field_key = format!(
+        "ergokv:User:{}:email",
+        serde_json::to_string(&user.id).unwrap()
+    );
+    txn.put(field_key.clone(), vec![0xff, 0xff]).await.unwrap();
+
+    let err = User::load(&user.id, &mut txn).await.unwrap_err();
+    let message = err.to_string();
+    assert!(message.contains("User.email"), "{}", message);
+    assert!(message.contains(&field_key), "{}", message);
+    txn.rollback().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_save_outcome() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut user = User {
+        id: Uuid::new_v4(),
+        username: "outcome".to_string(),
+        email: "outcome@example.com".to_string(),
+        department: "Engineering".to_string(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        user.save(&mut txn).await.unwrap(),
+        SaveOutcome::Created
+    );
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    user.department = "Sales".to_string();
+    assert_eq!(
+        user.save(&mut txn).await.unwrap(),
+        SaveOutcome::Updated
+    );
+    txn.commit().await.unwrap();
+
+    assert_eq!(
+        user.save_auto(&client).await.unwrap(),
+        SaveOutcome::Updated
+    );
+}
+
+#[tokio::test]
+async fn test_dangling_index_entry_is_pruned() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let users: Vec<User> = ["ann", "ben", "cid"]
+        .iter()
+        .map(|name| User {
+            id: Uuid::new_v4(),
+            username: name.to_string(),
+            email: format!("{}@example.com", name),
+            department: "Support".to_string(),
+        })
+        .collect();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for user in &users {
+        user.save(&mut txn).await.unwrap();
+    }
+
+    // Remove the fields of one user, as a partially failed delete would
+    let record_path = format!(
+        "ergokv:User:{}",
+        serde_json::to_string(&users[1].id).unwrap()
+    );
+    for field in ["id", "username", "email", "department"] {
+        txn.delete(format!("{}:{}", record_path, field))
+            .await
+            .unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let found =
+        User::by_department("Support", &mut txn).await.unwrap();
+    assert_eq!(found, vec![users[0].clone(), users[2].clone()]);
+
+    let listed = txn
+        .get(
+            "ergokv:User:index:department:\"Support\""
+                .to_string(),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    let listed: Vec<Uuid> =
+        ergokv::ciborium::de::from_reader(listed.as_slice())
+            .unwrap();
+    assert_eq!(listed, vec![users[0].id, users[2].id]);
+    txn.commit().await.unwrap();
+
+    let found =
+        User::by_department_concurrent("Support", &client, 2)
+            .await
+            .unwrap();
+    assert_eq!(found.len(), 2);
+}
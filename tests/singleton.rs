@@ -0,0 +1,67 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(singleton)]
+struct Settings {
+    theme: String,
+    max_upload_mb: u32,
+    #[store(format = "bincode")]
+    motd: Option<String>,
+}
+
+#[tokio::test]
+async fn test_singleton() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(Settings::get(&mut txn).await.unwrap(), None);
+
+    let first = Settings {
+        theme: "dark".to_string(),
+        max_upload_mb: 10,
+        motd: Some("Welcome".to_string()),
+    };
+    Settings::set(&first, &mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        Settings::get(&mut txn).await.unwrap(),
+        Some(first)
+    );
+
+    // A second save replaces the one instance
+    let second = Settings {
+        theme: "light".to_string(),
+        max_upload_mb: 25,
+        motd: None,
+    };
+    second.set(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        Settings::get(&mut txn).await.unwrap(),
+        Some(second)
+    );
+    let keys: Vec<_> = txn
+        .scan_keys(
+            "ergokv:Settings:".to_string()
+                .."ergokv:Settings;".to_string(),
+            u32::MAX,
+        )
+        .await
+        .unwrap()
+        .collect();
+    assert_eq!(keys.len(), 4);
+
+    Settings::clear(&mut txn).await.unwrap();
+    assert_eq!(Settings::get(&mut txn).await.unwrap(), None);
+    txn.commit().await.unwrap();
+}
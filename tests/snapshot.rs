@@ -0,0 +1,122 @@
+use ergokv::{LocalCluster, Store};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Product {
+    #[key]
+    id: Uuid,
+    #[index]
+    name: String,
+    price: u64,
+}
+
+#[tokio::test]
+async fn test_snapshot_reads() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut product = Product {
+        id: Uuid::new_v4(),
+        name: "lamp".into(),
+        price: 40,
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    product.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let before = client.current_timestamp().await.unwrap();
+
+    // Newer writes after the snapshot timestamp
+    let added = Product {
+        id: Uuid::new_v4(),
+        name: "desk".into(),
+        price: 120,
+    };
+    let mut txn = client.begin_optimistic().await.unwrap();
+    product.set_price(55, &mut txn).await.unwrap();
+    added.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let old =
+        Product::load_at(&product.id, &client, before.clone())
+            .await
+            .unwrap();
+    assert_eq!(old.price, 40);
+    assert!(Product::load_at(
+        &added.id,
+        &client,
+        before.clone()
+    )
+    .await
+    .is_err());
+
+    let mut found = Vec::new();
+    {
+        let stream = Product::all_at(&client, before);
+        futures::pin_mut!(stream);
+        while let Some(item) = stream.next().await {
+            found.push(item.unwrap());
+        }
+    }
+    assert_eq!(found, vec![old]);
+
+    // A fresh timestamp sees the newer state
+    let now = client.current_timestamp().await.unwrap();
+    assert_eq!(
+        Product::load_at(&product.id, &client, now.clone())
+            .await
+            .unwrap(),
+        product
+    );
+    let stream = Product::all_at(&client, now);
+    futures::pin_mut!(stream);
+    let mut count = 0;
+    while let Some(item) = stream.next().await {
+        item.unwrap();
+        count += 1;
+    }
+    assert_eq!(count, 2);
+}
+
+#[tokio::test]
+async fn test_load_snapshot() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let product = Product {
+        id: Uuid::new_v4(),
+        name: "chair".into(),
+        price: 70,
+    };
+    let mut txn = client.begin_optimistic().await.unwrap();
+    product.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // One snapshot serves several reads, without a commit
+    let timestamp = client.current_timestamp().await.unwrap();
+    let mut snapshot = client.snapshot(
+        timestamp,
+        tikv_client::TransactionOptions::new_optimistic()
+            .read_only(),
+    );
+    assert_eq!(
+        Product::load_snapshot(&product.id, &mut snapshot)
+            .await
+            .unwrap(),
+        product
+    );
+    assert!(Product::load_snapshot(
+        &Uuid::new_v4(),
+        &mut snapshot
+    )
+    .await
+    .is_err());
+}
@@ -0,0 +1,278 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Person {
+    #[key]
+    id: Uuid,
+    #[index(sparse)]
+    middle_name: String,
+    #[index(sparse)]
+    nickname: Option<String>,
+}
+
+#[tokio::test]
+async fn test_sparse_index() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let plain = Person {
+        id: Uuid::new_v4(),
+        middle_name: String::new(),
+        nickname: None,
+    };
+    let named = Person {
+        id: Uuid::new_v4(),
+        middle_name: "Amadeus".to_string(),
+        nickname: Some("Wolfie".to_string()),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    plain.save(&mut txn).await.unwrap();
+    named.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+
+    // Default values are not indexed at all
+    assert!(txn
+        .get("ergokv:Person:index:middle_name:\"\"".to_string())
+        .await
+        .unwrap()
+        .is_none());
+    assert!(txn
+        .get("ergokv:Person:index:nickname:null".to_string())
+        .await
+        .unwrap()
+        .is_none());
+    assert!(Person::by_middle_name("", &mut txn)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Non-default values are
+    assert_eq!(
+        Person::by_middle_name("Amadeus", &mut txn)
+            .await
+            .unwrap(),
+        vec![named.clone()]
+    );
+    assert_eq!(
+        Person::by_nickname("Wolfie", &mut txn).await.unwrap(),
+        vec![named.clone()]
+    );
+
+    // Clearing a value removes its entry without adding a default one
+    let mut named = named;
+    named.set_nickname(None, &mut txn).await.unwrap();
+    assert!(Person::by_nickname("Wolfie", &mut txn)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(txn
+        .get("ergokv:Person:index:nickname:null".to_string())
+        .await
+        .unwrap()
+        .is_none());
+
+    plain.delete(&mut txn).await.unwrap();
+    named.delete(&mut txn).await.unwrap();
+    assert!(Person::by_middle_name("Amadeus", &mut txn)
+        .await
+        .unwrap()
+        .is_empty());
+    txn.commit().await.unwrap();
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Article {
+    #[key]
+    id: Uuid,
+    #[unique_index]
+    slug: Option<String>,
+}
+
+#[tokio::test]
+async fn test_optional_unique_index() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let drafts: Vec<Article> = (0..3)
+        .map(|_| Article {
+            id: Uuid::new_v4(),
+            slug: None,
+        })
+        .collect();
+    let published = Article {
+        id: Uuid::new_v4(),
+        slug: Some("hello-world".to_string()),
+    };
+
+    // Any number of instances can lack a slug
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for draft in &drafts {
+        draft.save(&mut txn).await.unwrap();
+    }
+    published.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert!(txn
+        .get("ergokv:Article:unique_index:slug:null".to_string())
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        Article::by_slug("hello-world", &mut txn).await.unwrap(),
+        Some(published.clone())
+    );
+    for draft in &drafts {
+        assert_eq!(
+            Article::load(&draft.id, &mut txn).await.unwrap(),
+            *draft
+        );
+    }
+
+    // A slug that is already taken is rejected
+    let duplicate = Article {
+        id: Uuid::new_v4(),
+        slug: Some("hello-world".to_string()),
+    };
+    let err = duplicate.save(&mut txn).await.unwrap_err();
+    assert!(err.to_string().contains("already taken"));
+    assert!(err.to_string().contains("Article.slug"));
+    assert!(err.to_string().contains(&published.id.to_string()));
+    let mut draft = drafts[0].clone();
+    assert!(draft
+        .set_slug(Some("hello-world".to_string()), &mut txn)
+        .await
+        .is_err());
+    txn.rollback().await.unwrap();
+
+    // Once its holder lets go of it, the slug is free again
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let mut published = published;
+    published.set_slug(None, &mut txn).await.unwrap();
+    draft
+        .set_slug(Some("hello-world".to_string()), &mut txn)
+        .await
+        .unwrap();
+    assert_eq!(
+        Article::by_slug("hello-world", &mut txn).await.unwrap(),
+        Some(draft.clone())
+    );
+    txn.commit().await.unwrap();
+
+    // A slug changed through `save` is freed as well
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let renamed = Article {
+        slug: Some("hello-again".to_string()),
+        ..draft
+    };
+    renamed.save(&mut txn).await.unwrap();
+    assert_eq!(
+        Article::by_slug("hello-world", &mut txn).await.unwrap(),
+        None
+    );
+    let reused = Article {
+        id: Uuid::new_v4(),
+        slug: Some("hello-world".to_string()),
+    };
+    reused.save(&mut txn).await.unwrap();
+    assert_eq!(
+        Article::by_slug("hello-world", &mut txn).await.unwrap(),
+        Some(reused)
+    );
+    assert_eq!(
+        Article::by_slug("hello-again", &mut txn).await.unwrap(),
+        Some(renamed)
+    );
+    txn.commit().await.unwrap();
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Subscriber {
+    #[key]
+    id: u64,
+    #[index]
+    email: Option<String>,
+}
+
+#[tokio::test]
+async fn test_optional_index_by_inner_value() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let with_email = Subscriber {
+        id: 1,
+        email: Some("ada@example.com".to_string()),
+    };
+    let without_email = Subscriber { id: 2, email: None };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    with_email.save(&mut txn).await.unwrap();
+    without_email.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // Lookups take the value inside the `Option`, as a `&str` or a
+    // `String`
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        Subscriber::by_email("ada@example.com", &mut txn)
+            .await
+            .unwrap(),
+        vec![with_email.clone()]
+    );
+    assert_eq!(
+        Subscriber::by_email_one(
+            "ada@example.com".to_string(),
+            &mut txn
+        )
+        .await
+        .unwrap(),
+        Some(with_email.clone())
+    );
+    assert!(Subscriber::by_email_exists(
+        "ada@example.com",
+        &mut txn
+    )
+    .await
+    .unwrap());
+    assert!(Subscriber::by_email("bob@example.com", &mut txn)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        Subscriber::by_email_in(
+            &[
+                "ada@example.com".to_string(),
+                "bob@example.com".to_string()
+            ],
+            &mut txn
+        )
+        .await
+        .unwrap(),
+        vec![with_email.clone()]
+    );
+
+    // `None` is still indexed, but only found through the index's
+    // JSON form
+    assert_eq!(
+        Subscriber::by_email_ref(&None::<String>, &mut txn)
+            .await
+            .unwrap(),
+        vec![without_email]
+    );
+    txn.commit().await.unwrap();
+}
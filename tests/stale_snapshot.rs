@@ -0,0 +1,97 @@
+use ergokv::{LocalCluster, Store};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tempfile::TempDir;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(stale_snapshot(max_staleness = "1s"))]
+struct Price {
+    #[key]
+    symbol: String,
+    cents: u64,
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(stale_snapshot(
+    max_staleness = "18446744073709551615ms"
+))]
+struct Archive {
+    #[key]
+    id: u64,
+}
+
+#[tokio::test]
+async fn test_stale_snapshot_reads() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut price = Price {
+        symbol: "ACME".to_string(),
+        cents: 1000,
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    price.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // Too recent for a stale read
+    assert!(Price::load_stale(&price.symbol, &client)
+        .await
+        .is_err());
+
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let old = price.clone();
+
+    // The change is only seen by stale reads once it is old enough
+    let mut txn = client.begin_optimistic().await.unwrap();
+    price.set_cents(1200, &mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    assert_eq!(
+        Price::load_stale(&price.symbol, &client).await.unwrap(),
+        old
+    );
+    assert_eq!(
+        Price::load_many_stale(
+            &[price.symbol.clone()],
+            &client,
+            2
+        )
+        .await
+        .unwrap(),
+        [old]
+    );
+
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(
+        Price::load_stale(&price.symbol, &client).await.unwrap(),
+        price
+    );
+    let all: Vec<Price> =
+        Price::all_stale(&client).try_collect().await.unwrap();
+    assert_eq!(all, [price]);
+}
+
+#[tokio::test]
+async fn test_stale_timestamp_saturates() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    Archive { id: 1 }.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // A staleness further back than TiKV time goes reads at its start,
+    // where nothing is stored yet
+    let timestamp =
+        Archive::stale_timestamp(&client).await.unwrap();
+    assert_eq!(timestamp.physical, 0);
+    assert!(Archive::load_stale(&1, &client).await.is_err());
+}
@@ -0,0 +1,57 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(Store, Serialize, Deserialize, Debug, Clone)]
+struct User {
+    #[key]
+    id: Uuid,
+    name: String,
+}
+
+#[derive(Store, Serialize, Deserialize, Debug, Clone)]
+struct UserGroup {
+    #[key]
+    id: Uuid,
+    name: String,
+}
+
+#[tokio::test]
+async fn test_model_stats() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    for name in ["alice", "bob", "carol"] {
+        User {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+        }
+        .save(&mut txn)
+        .await
+        .unwrap();
+    }
+    let group = UserGroup {
+        id: Uuid::new_v4(),
+        name: "admins".to_string(),
+    };
+    group.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let stats = ergokv::model_stats(&mut txn, "ergokv:__trie")
+        .await
+        .unwrap();
+    assert_eq!(stats.len(), 2);
+    assert_eq!(stats["User"], 3);
+    assert_eq!(stats["UserGroup"], 1);
+
+    group.delete(&mut txn).await.unwrap();
+    let stats = ergokv::model_stats(&mut txn, "ergokv:__trie")
+        .await
+        .unwrap();
+    assert_eq!(stats.get("UserGroup"), None);
+    txn.commit().await.unwrap();
+}
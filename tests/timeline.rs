@@ -0,0 +1,82 @@
+use ergokv::{recent_activity, LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(Store, Serialize, Deserialize, Debug, PartialEq)]
+#[store(timeline)]
+struct Post {
+    #[key]
+    id: Uuid,
+    title: String,
+}
+
+#[derive(Store, Serialize, Deserialize, Debug, PartialEq)]
+#[store(timeline)]
+struct Comment {
+    #[key]
+    id: u64,
+    body: String,
+}
+
+#[derive(Store, Serialize, Deserialize, Debug, PartialEq)]
+struct Draft {
+    #[key]
+    id: u64,
+    body: String,
+}
+
+#[tokio::test]
+async fn test_recent_activity_across_models() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let post = Post {
+        id: Uuid::new_v4(),
+        title: "Hello".to_string(),
+    };
+    let comment = Comment {
+        id: 7,
+        body: "First!".to_string(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    post.save(&mut txn).await.unwrap();
+    comment.save(&mut txn).await.unwrap();
+    // Models without #[store(timeline)] stay out of the feed
+    Draft {
+        id: 1,
+        body: "unpublished".to_string(),
+    }
+    .save(&mut txn)
+    .await
+    .unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let activity = recent_activity(&mut txn, 10).await.unwrap();
+    let feed: Vec<(&str, &str)> = activity
+        .iter()
+        .map(|entry| (entry.model.as_str(), entry.key.as_str()))
+        .collect();
+    let post_key = serde_json::to_string(&post.id).unwrap();
+    assert_eq!(
+        feed,
+        vec![("Comment", "7"), ("Post", post_key.as_str())]
+    );
+    assert!(activity[0].timestamp >= activity[1].timestamp);
+
+    // The limit keeps the most recent entries
+    let latest = recent_activity(&mut txn, 1).await.unwrap();
+    assert_eq!(latest[0].model, "Comment");
+
+    let loaded = Post::load(
+        &serde_json::from_str(&activity[1].key).unwrap(),
+        &mut txn,
+    )
+    .await
+    .unwrap();
+    assert_eq!(loaded, post);
+    txn.commit().await.unwrap();
+}
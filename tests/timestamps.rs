@@ -0,0 +1,117 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[store(timestamps)]
+struct Article {
+    #[key]
+    id: Uuid,
+    title: String,
+    #[index(range)]
+    updated_at: SystemTime,
+    created_at: SystemTime,
+}
+
+#[tokio::test]
+async fn test_managed_timestamps() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let started = SystemTime::now();
+    let article = Article {
+        id: Uuid::new_v4(),
+        title: "Draft".into(),
+        updated_at: UNIX_EPOCH,
+        created_at: UNIX_EPOCH,
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    article.save(&mut txn).await.unwrap();
+    let first =
+        Article::load(&article.id, &mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // Both are set on the first save, whatever the instance carried
+    assert!(first.created_at >= started);
+    assert!(first.updated_at >= started);
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    // Later saves keep created_at, even when given another one
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let edited = Article {
+        title: "Final".into(),
+        created_at: UNIX_EPOCH,
+        ..first.clone()
+    };
+    edited.save(&mut txn).await.unwrap();
+    let second =
+        Article::load(&article.id, &mut txn).await.unwrap();
+    assert_eq!(second.title, "Final");
+    assert_eq!(second.created_at, first.created_at);
+    assert!(second.updated_at > first.updated_at);
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    // Setters bump updated_at too, in the instance and in storage
+    let mut third = second.clone();
+    third.set_title("Published".into(), &mut txn).await.unwrap();
+    assert!(third.updated_at > second.updated_at);
+    assert_eq!(
+        Article::load(&article.id, &mut txn).await.unwrap(),
+        third
+    );
+
+    // Only the current updated_at is indexed
+    let indexed = Article::by_updated_at_range(
+        UNIX_EPOCH,
+        SystemTime::now() + Duration::from_secs(60),
+        &mut txn,
+    )
+    .await
+    .unwrap();
+    assert_eq!(indexed, vec![third]);
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_rekey_keeps_timestamps() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let article = Article {
+        id: Uuid::new_v4(),
+        title: "Draft".into(),
+        updated_at: UNIX_EPOCH,
+        created_at: UNIX_EPOCH,
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    article.save(&mut txn).await.unwrap();
+    let mut stored =
+        Article::load(&article.id, &mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    // The moved instance is the stored one, timestamps included
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let before = stored.clone();
+    let new_id = Uuid::new_v4();
+    stored.rekey(new_id, &mut txn).await.unwrap();
+    assert_eq!(stored.created_at, before.created_at);
+    assert_eq!(stored.updated_at, before.updated_at);
+    assert_eq!(
+        Article::load(&new_id, &mut txn).await.unwrap(),
+        stored
+    );
+    assert!(Article::load(&article.id, &mut txn).await.is_err());
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,14 @@
+use ergokv::Store;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Sku(String);
+
+#[derive(Store, Serialize, Deserialize)]
+struct Product {
+    #[key]
+    sku: Sku,
+    name: String,
+}
+
+fn main() {}
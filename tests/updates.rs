@@ -0,0 +1,442 @@
+use ergokv::{LocalCluster, Store};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Employee {
+    #[key]
+    id: Uuid,
+    #[unique_index]
+    username: String,
+    #[index]
+    department: String,
+    title: String,
+}
+
+#[tokio::test]
+async fn test_apply_patch() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let employee = Employee {
+        id: Uuid::new_v4(),
+        username: "alice".to_string(),
+        department: "Engineering".to_string(),
+        title: "Engineer".to_string(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    employee.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // Change one indexed and one plain field
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let patched = Employee::apply_patch(
+        &employee.id,
+        EmployeePatch {
+            department: Some("Management".to_string()),
+            title: Some("Manager".to_string()),
+            ..Default::default()
+        },
+        &mut txn,
+    )
+    .await
+    .unwrap();
+    txn.commit().await.unwrap();
+
+    assert_eq!(patched.username, "alice");
+    assert_eq!(patched.department, "Management");
+    assert_eq!(patched.title, "Manager");
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        Employee::load(&employee.id, &mut txn).await.unwrap(),
+        patched
+    );
+    assert!(Employee::by_department("Engineering", &mut txn)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        Employee::by_department("Management", &mut txn)
+            .await
+            .unwrap(),
+        vec![patched.clone()]
+    );
+
+    // Patching a missing record fails, upserting creates it
+    let missing = Uuid::new_v4();
+    assert!(Employee::apply_patch(
+        &missing,
+        EmployeePatch::default(),
+        &mut txn
+    )
+    .await
+    .is_err());
+    assert!(Employee::upsert_patch(
+        &missing,
+        EmployeePatch {
+            title: Some("Intern".to_string()),
+            ..Default::default()
+        },
+        &mut txn
+    )
+    .await
+    .is_err());
+
+    let created = Employee::upsert_patch(
+        &missing,
+        EmployeePatch {
+            username: Some("bob".to_string()),
+            department: Some("Engineering".to_string()),
+            title: Some("Intern".to_string()),
+        },
+        &mut txn,
+    )
+    .await
+    .unwrap();
+    assert_eq!(created.id, missing);
+    assert_eq!(
+        Employee::by_username("bob", &mut txn).await.unwrap(),
+        Some(created)
+    );
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_set_unchanged() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let employee = Employee {
+        id: Uuid::new_v4(),
+        username: "carol".to_string(),
+        department: "Engineering".to_string(),
+        title: "Engineer".to_string(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    employee.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // Setting the current values writes nothing, so the transaction does not
+    // conflict with another one changing the same fields meanwhile
+    let mut unchanged = employee.clone();
+    let mut txn = client.begin_optimistic().await.unwrap();
+    unchanged
+        .set_department("Engineering".to_string(), &mut txn)
+        .await
+        .unwrap();
+    unchanged
+        .set_title("Engineer".to_string(), &mut txn)
+        .await
+        .unwrap();
+
+    let mut other = employee.clone();
+    let mut other_txn = client.begin_optimistic().await.unwrap();
+    other
+        .set_department("Research".to_string(), &mut other_txn)
+        .await
+        .unwrap();
+    other
+        .set_title("Researcher".to_string(), &mut other_txn)
+        .await
+        .unwrap();
+    other_txn.commit().await.unwrap();
+
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        Employee::load(&employee.id, &mut txn).await.unwrap(),
+        other
+    );
+    assert_eq!(
+        Employee::by_department("Research", &mut txn)
+            .await
+            .unwrap(),
+        [other.clone()]
+    );
+    assert!(Employee::by_department("Engineering", &mut txn)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // A changed value is still written
+    let mut changed = other.clone();
+    changed
+        .set_title("Lead".to_string(), &mut txn)
+        .await
+        .unwrap();
+    assert_eq!(
+        Employee::load(&employee.id, &mut txn).await.unwrap(),
+        changed
+    );
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_update_builder() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut employee = Employee {
+        id: Uuid::new_v4(),
+        username: "carol".to_string(),
+        department: "Sales".to_string(),
+        title: "Associate".to_string(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    employee.save(&mut txn).await.unwrap();
+    employee
+        .update()
+        .set_username("caroline".to_string())
+        .set_department("Marketing".to_string())
+        .set_title("Lead".to_string())
+        .apply(&mut txn)
+        .await
+        .unwrap();
+    txn.commit().await.unwrap();
+
+    // The instance itself is updated along with the stored fields
+    assert_eq!(employee.username, "caroline");
+    assert_eq!(employee.department, "Marketing");
+    assert_eq!(employee.title, "Lead");
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        Employee::load(&employee.id, &mut txn).await.unwrap(),
+        employee
+    );
+    assert_eq!(
+        Employee::by_username("caroline", &mut txn)
+            .await
+            .unwrap(),
+        Some(employee.clone())
+    );
+    assert_eq!(
+        Employee::by_username("carol", &mut txn).await.unwrap(),
+        None
+    );
+    assert_eq!(
+        Employee::by_department("Marketing", &mut txn)
+            .await
+            .unwrap(),
+        vec![employee.clone()]
+    );
+    assert!(Employee::by_department("Sales", &mut txn)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Fields left out of the update keep their value
+    employee
+        .update()
+        .set_title("Director".to_string())
+        .apply(&mut txn)
+        .await
+        .unwrap();
+    let reloaded =
+        Employee::load(&employee.id, &mut txn).await.unwrap();
+    assert_eq!(reloaded.username, "caroline");
+    assert_eq!(reloaded.title, "Director");
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_with_builder() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let template = Employee {
+        id: Uuid::nil(),
+        username: String::new(),
+        department: "Support".to_string(),
+        title: "Agent".to_string(),
+    };
+    let employee = template
+        .clone()
+        .with_id(Uuid::new_v4())
+        .with_username("dave".to_string())
+        .with_title("Senior Agent".to_string());
+
+    // Only the instance in memory has changed
+    assert_eq!(employee.username, "dave");
+    assert_eq!(employee.department, "Support");
+    assert_eq!(employee.title, "Senior Agent");
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert!(Employee::load(&employee.id, &mut txn)
+        .await
+        .is_err());
+    assert_eq!(Employee::count(&mut txn).await.unwrap(), 0);
+
+    employee.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        Employee::load(&employee.id, &mut txn).await.unwrap(),
+        employee
+    );
+    assert_eq!(
+        Employee::by_username("dave", &mut txn).await.unwrap(),
+        Some(employee.clone())
+    );
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_rekey() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut employee = Employee {
+        id: Uuid::new_v4(),
+        username: "carol".to_string(),
+        department: "Sales".to_string(),
+        title: "Lead".to_string(),
+    };
+    let other = Employee {
+        id: Uuid::new_v4(),
+        username: "dave".to_string(),
+        department: "Sales".to_string(),
+        title: "Rep".to_string(),
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    employee.save(&mut txn).await.unwrap();
+    other.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let old_id = employee.id;
+    let new_id = Uuid::new_v4();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    // Taken keys are rejected
+    assert!(employee.rekey(other.id, &mut txn).await.is_err());
+    assert_eq!(employee.id, old_id);
+
+    employee.rekey(new_id, &mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+    assert_eq!(employee.id, new_id);
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert!(Employee::load(&old_id, &mut txn).await.is_err());
+    assert_eq!(
+        Employee::load(&new_id, &mut txn).await.unwrap(),
+        employee
+    );
+    assert_eq!(
+        Employee::by_username("carol", &mut txn).await.unwrap(),
+        Some(employee.clone())
+    );
+
+    let mut in_sales: Vec<_> =
+        Employee::by_department("Sales", &mut txn)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| e.id)
+            .collect();
+    in_sales.sort();
+    let mut expected = vec![new_id, other.id];
+    expected.sort();
+    assert_eq!(in_sales, expected);
+
+    // The stored values are moved, not those of a stale instance
+    let mut stale = other.clone();
+    stale.title = "Manager".to_string();
+    let moved_id = Uuid::new_v4();
+    stale.rekey(moved_id, &mut txn).await.unwrap();
+    assert_eq!(
+        stale,
+        Employee {
+            id: moved_id,
+            ..other
+        }
+    );
+    assert_eq!(
+        Employee::load(&moved_id, &mut txn).await.unwrap(),
+        stale
+    );
+    txn.commit().await.unwrap();
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+enum Status {
+    Pending,
+    Running,
+    Done,
+}
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Job {
+    #[key]
+    id: Uuid,
+    #[index]
+    status: Status,
+}
+
+#[tokio::test]
+async fn test_compare_and_swap() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut job = Job {
+        id: Uuid::new_v4(),
+        status: Status::Running,
+    };
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    job.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    // Someone else finishes the job behind our back
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let mut other = Job::load(&job.id, &mut txn).await.unwrap();
+    assert!(other
+        .cas_status(Status::Running, Status::Done, &mut txn)
+        .await
+        .unwrap());
+    txn.commit().await.unwrap();
+
+    // Our copy is stale, so the swap fails and the copy is refreshed
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert!(!job
+        .cas_status(Status::Running, Status::Pending, &mut txn)
+        .await
+        .unwrap());
+    assert_eq!(job.status, Status::Done);
+    assert_eq!(
+        Job::load(&job.id, &mut txn).await.unwrap().status,
+        Status::Done
+    );
+
+    // The index follows successful swaps only
+    assert!(Job::by_status(Status::Running, &mut txn)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        Job::by_status(Status::Done, &mut txn).await.unwrap(),
+        vec![job.clone()]
+    );
+    assert!(Job::by_status(Status::Pending, &mut txn)
+        .await
+        .unwrap()
+        .is_empty());
+    txn.commit().await.unwrap();
+}
@@ -0,0 +1,124 @@
+// Models with migration checks get no `set_<field>_buffered`
+#![cfg(not(feature = "strict-migrations"))]
+
+use ergokv::{
+    is_size_limit_exceeded, LocalCluster, Store, WriteBuffer,
+};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+#[derive(
+    Store, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+struct Contact {
+    #[key]
+    id: u64,
+    email: String,
+    phone: String,
+}
+
+#[tokio::test]
+async fn test_buffered_sets() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut contact = Contact {
+        id: 1,
+        email: "old@example.com".to_string(),
+        phone: "555-0100".to_string(),
+    };
+    let mut txn = client.begin_optimistic().await.unwrap();
+    contact.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut buffer = WriteBuffer::new();
+    contact
+        .set_email_buffered(
+            "first@example.com".to_string(),
+            &mut buffer,
+        )
+        .unwrap();
+    contact
+        .set_email_buffered(
+            "second@example.com".to_string(),
+            &mut buffer,
+        )
+        .unwrap();
+    assert_eq!(buffer.len(), 1);
+
+    // Nothing is written before the flush
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        Contact::load(&1, &mut txn).await.unwrap().email,
+        "old@example.com"
+    );
+
+    // Both sets end up as a single put of the last value
+    assert_eq!(buffer.flush(&mut txn).await.unwrap(), 1);
+    assert!(buffer.is_empty());
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(
+        Contact::load(&1, &mut txn).await.unwrap(),
+        contact
+    );
+    assert_eq!(contact.email, "second@example.com");
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_soft_limit() {
+    let tmp = TempDir::new().expect("Failed to create temp dir");
+    let tikv_instance = LocalCluster::start(tmp.path()).unwrap();
+    let client = tikv_instance.spawn_client().await.unwrap();
+
+    let mut contact = Contact {
+        id: 2,
+        email: "old@example.com".to_string(),
+        phone: "555-0100".to_string(),
+    };
+    let mut txn = client.begin_optimistic().await.unwrap();
+    contact.save(&mut txn).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut buffer = WriteBuffer::new().with_soft_limit(256);
+    contact
+        .set_email_buffered(
+            "a@example.com".to_string(),
+            &mut buffer,
+        )
+        .unwrap();
+    let size = buffer.estimated_txn_size();
+    assert!(size > 0);
+
+    // Replacing a queued write counts only the new one
+    contact
+        .set_email_buffered(
+            "b@example.com".to_string(),
+            &mut buffer,
+        )
+        .unwrap();
+    assert_eq!(buffer.estimated_txn_size(), size);
+
+    // A write over the limit is refused with its own error, and the
+    // buffer keeps what it had
+    let err = contact
+        .set_phone_buffered("5".repeat(1024), &mut buffer)
+        .unwrap_err();
+    assert!(is_size_limit_exceeded(&err));
+    assert_eq!(buffer.len(), 1);
+    assert_eq!(buffer.estimated_txn_size(), size);
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    assert_eq!(buffer.flush(&mut txn).await.unwrap(), 1);
+    assert_eq!(buffer.estimated_txn_size(), 0);
+    txn.commit().await.unwrap();
+
+    let mut txn = client.begin_optimistic().await.unwrap();
+    let stored = Contact::load(&2, &mut txn).await.unwrap();
+    assert_eq!(stored.email, "b@example.com");
+    assert_eq!(stored.phone, "555-0100");
+    txn.commit().await.unwrap();
+}